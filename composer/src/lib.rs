@@ -1320,7 +1320,7 @@ impl ComposeTest {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use rpc::mayastor::Null;
+    use rpc::mayastor::{ListNexusRequest, Null};
 
     #[tokio::test]
     async fn compose() {
@@ -1346,7 +1346,10 @@ mod tests {
             .unwrap();
 
         let mut hdl = test.grpc_handle("mayastor").await.unwrap();
-        hdl.mayastor.list_nexus(Null {}).await.expect("list nexus");
+        hdl.mayastor
+            .list_nexus(ListNexusRequest::default())
+            .await
+            .expect("list nexus");
 
         // run with --nocapture to get the logs
         test.logs_all().await.unwrap();
@@ -1,6 +1,10 @@
 //! Functions for CSI publish and unpublish block mode volumes.
 
-use std::path::Path;
+use std::{
+    fs::{self, File},
+    os::unix::{fs::FileTypeExt, io::AsRawFd},
+    path::Path,
+};
 
 use tonic::{Code, Status};
 
@@ -152,3 +156,59 @@ pub fn unpublish_block_volume(
     info!("Volume {} unpublished from {}", volume_id, target_path);
     Ok(())
 }
+
+// BLKGETSIZE64, size of a block device in bytes
+nix::ioctl_read!(blk_get_size64, 0x12, 114, u64);
+
+/// Report the capacity of a published block volume. There is no filesystem
+/// on top of the device, so the used space is unknown and reported as 0.
+pub fn block_volume_stats(
+    msg: &NodeGetVolumeStatsRequest,
+) -> Result<NodeGetVolumeStatsResponse, Status> {
+    let volume_path = &msg.volume_path;
+    let volume_id = &msg.volume_id;
+
+    match fs::metadata(volume_path) {
+        Ok(metadata) if metadata.file_type().is_block_device() => {}
+        _ => {
+            return Err(failure!(
+                Code::NotFound,
+                "Failed to get stats of volume {}: no block device at {}",
+                volume_id,
+                volume_path
+            ))
+        }
+    }
+
+    let device = File::open(volume_path).map_err(|error| {
+        failure!(
+            Code::Internal,
+            "Failed to get stats of volume {}: failed to open {}: {}",
+            volume_id,
+            volume_path,
+            error
+        )
+    })?;
+
+    let mut size: u64 = 0;
+    unsafe { blk_get_size64(device.as_raw_fd(), &mut size) }.map_err(
+        |error| {
+            failure!(
+                Code::Internal,
+                "Failed to get stats of volume {}: failed to get size of {}: {}",
+                volume_id,
+                volume_path,
+                error
+            )
+        },
+    )?;
+
+    Ok(NodeGetVolumeStatsResponse {
+        usage: vec![VolumeUsage {
+            total: size as i64,
+            unit: volume_usage::Unit::Bytes as i32,
+            available: size as i64,
+            used: 0,
+        }],
+    })
+}
@@ -22,13 +22,23 @@
 //! Detaching a device is performed via:
 //! ```ignore
 //!     let uuid = Uuid::parse_str(&volume_id)?;
-//!     if let Some(device) = Device::lookup(&uuid).await? {
+//!     if let Some(device) = Device::lookup_for_detach(&uuid).await? {
 //!         device.detach().await?;
 //!     }
 //! ```
 
-use std::{collections::HashMap, convert::TryFrom, time::Duration};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    mem,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
+use futures::future;
+use glob::glob;
+use nvmeadm::nvmf_subsystem::NvmeSubsystems;
 use tokio::time::sleep;
 use udev::Enumerator;
 use url::Url;
@@ -40,26 +50,80 @@ pub(crate) mod nvmf;
 mod util;
 
 const NVME_NQN_PREFIX: &str = "nqn.2019-05.io.openebs";
+const ATTACH_BACKOFF_INITIAL: Duration = Duration::from_millis(100);
+const ATTACH_BACKOFF_MAX: Duration = Duration::from_millis(1600);
 
-pub use crate::error::DeviceError;
+pub use crate::error::{DeviceError, DeviceErrorKind};
 use crate::match_dev;
 
 pub type DeviceName = String;
 
+/// Transport used by this node to attach volumes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Transport {
+    Nvmf,
+    Nbd,
+}
+
+impl FromStr for Transport {
+    type Err = DeviceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nvmf" => Ok(Self::Nvmf),
+            "nbd" => Ok(Self::Nbd),
+            transport => Err(DeviceError::from(format!(
+                "unsupported transport: {}",
+                transport
+            ))),
+        }
+    }
+}
+
+/// Identity a node presents to the NVMe-oF targets it connects to, which
+/// targets enforcing host ACLs check.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NvmfHost {
+    pub nqn: String,
+    pub id: String,
+}
+
+impl NvmfHost {
+    /// Host NQN derived from the name of the node.
+    pub fn default_nqn(node_name: &str) -> String {
+        format!("{}.mayastor:{}", NVME_NQN_PREFIX, node_name)
+    }
+}
+
 #[tonic::async_trait]
 pub trait Attach: Sync + Send {
     async fn parse_parameters(
         &mut self,
         context: &HashMap<String, String>,
     ) -> Result<(), DeviceError>;
+    /// Set the identity of the host, for devices attached over NVMe-oF.
+    fn set_nvmf_host(&mut self, _host: &NvmfHost) {}
     async fn attach(&self) -> Result<(), DeviceError>;
     async fn find(&self) -> Result<Option<DeviceName>, DeviceError>;
+    /// Find the device made by attach through this URI in particular, for
+    /// volumes reachable through several URIs each with a device of its own.
+    async fn find_path(&self) -> Result<Option<DeviceName>, DeviceError> {
+        self.find().await
+    }
     /// Fixup parameters which cannot be set during attach, eg IO timeout
     async fn fixup(&self) -> Result<(), DeviceError>;
+    /// Disconnect the connection made by attach, leaving other connections
+    /// to the same volume alone. Only needed by devices making one
+    /// connection per URI.
+    async fn disconnect(&self) -> Result<(), DeviceError> {
+        Ok(())
+    }
 }
 
 #[tonic::async_trait]
 pub trait Detach: Sync + Send {
+    /// Detach the device. Detaching a device which is already gone
+    /// succeeds, as an unstage request may be repeated.
     async fn detach(&self) -> Result<(), DeviceError>;
     fn devname(&self) -> DeviceName;
 }
@@ -122,6 +186,80 @@ impl Device {
         Ok(None)
     }
 
+    /// Lookup a device to detach, as `lookup` does. An NVMe-oF controller
+    /// connected to the volume is returned even when its namespace no longer
+    /// shows up as a block device, so that it can still be disconnected. The
+    /// name of such a device is that of the controller, not a block device.
+    pub async fn lookup_for_detach(
+        uuid: &Uuid,
+    ) -> Result<Option<Box<dyn Detach>>, DeviceError> {
+        if let Some(device) = Device::lookup(uuid).await? {
+            return Ok(Some(device));
+        }
+
+        let nqn = format!("{}:nexus-{}", NVME_NQN_PREFIX, uuid.to_string());
+        if let Some(subsystem) = NvmeSubsystems::new()?
+            .flatten()
+            .find(|subsystem| subsystem.nqn == nqn)
+        {
+            warn!(
+                "NVMe controller {} of volume {} has no block device",
+                subsystem.name, uuid
+            );
+            return Ok(Some(Box::new(nvmf::NvmfDetach::new(
+                subsystem.name,
+                nqn,
+            ))));
+        }
+
+        Ok(None)
+    }
+
+    /// Number of NVMe-oF host connections currently active on this node.
+    /// Discovery controllers are not counted.
+    pub fn nvmf_connections() -> Result<usize, DeviceError> {
+        Ok(NvmeSubsystems::new()?.filter(|s| s.is_ok()).count())
+    }
+
+    /// Number of nbd devices available on this node.
+    pub fn nbd_devices() -> usize {
+        glob("/dev/nbd*")
+            .map(|paths| paths.flatten().count())
+            .unwrap_or(0)
+    }
+
+    /// Attach through the first of the given devices, all of which lead to
+    /// the same volume, that becomes available. The attaches, each retried up
+    /// to `attach_retries` times with an exponential backoff, race against
+    /// each other and the remaining ones are cancelled as soon as one of them
+    /// succeeds. Returns the index of the winning device along with the path
+    /// of the device it made.
+    pub async fn find_any(
+        devices: &[Box<dyn Attach>],
+        attach_retries: u32,
+        timeout: Duration,
+        retries: u32,
+    ) -> Result<(usize, DeviceName), DeviceError> {
+        let attempts = devices.iter().enumerate().map(|(index, device)| {
+            Box::pin(async move {
+                attach_with_retry(&**device, attach_retries).await.map_err(
+                    |error| {
+                        DeviceError::with_kind(
+                            error.kind(),
+                            &format!("attach failed: {}", error),
+                        )
+                    },
+                )?;
+                let devname =
+                    Device::wait_for_device(&**device, timeout, retries)
+                        .await?;
+                Ok::<_, DeviceError>((index, devname))
+            })
+        });
+
+        future::select_ok(attempts).await.map(|(found, _)| found)
+    }
+
     /// Wait for a device to show up in udev
     /// once attach() has been called.
     pub async fn wait_for_device(
@@ -130,11 +268,99 @@ impl Device {
         retries: u32,
     ) -> Result<DeviceName, DeviceError> {
         for _ in 0 ..= retries {
-            if let Some(devname) = device.find().await? {
+            if let Some(devname) = device.find_path().await? {
                 return Ok(devname);
             }
             sleep(timeout).await;
         }
-        Err(DeviceError::new("device attach timeout"))
+        Err(DeviceError::with_kind(
+            DeviceErrorKind::Timeout,
+            "device attach timeout",
+        ))
+    }
+}
+
+/// Attach the device, retrying a failed attach up to `retries` times with an
+/// exponential backoff.
+async fn attach_with_retry(
+    device: &dyn Attach,
+    retries: u32,
+) -> Result<(), DeviceError> {
+    let mut backoff = ATTACH_BACKOFF_INITIAL;
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        debug!("Attaching device (attempt {})", attempt);
+        match device.attach().await {
+            Ok(()) => return Ok(()),
+            Err(error) if attempt <= retries => {
+                warn!(
+                    "Attempt {} to attach device failed: {}, retrying in {:?}",
+                    attempt, error, backoff
+                );
+                sleep(backoff).await;
+                backoff = std::cmp::min(backoff * 2, ATTACH_BACKOFF_MAX);
+            }
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+/// Progress of a connect running on the blocking thread pool.
+enum Connect<R, U> {
+    Running,
+    /// the attach waiting for the connect has been given up
+    Abandoned,
+    /// the connect returned, but its result has not been collected yet
+    Finished(R, U),
+    Collected,
+}
+
+/// Give up a connect when the attach waiting for it is dropped, undoing it
+/// right away if it has already returned.
+struct AbandonOnDrop<R, U: FnOnce(&R)>(Arc<Mutex<Connect<R, U>>>);
+
+impl<R, U: FnOnce(&R)> Drop for AbandonOnDrop<R, U> {
+    fn drop(&mut self) {
+        let mut state = self.0.lock().expect("connect state lock poisoned");
+        if let Connect::Finished(result, undo) =
+            mem::replace(&mut *state, Connect::Abandoned)
+        {
+            undo(&result);
+        }
+    }
+}
+
+/// Run a blocking connect on the blocking thread pool, so that an attach
+/// which times out is not held up by it. Should the attach be given up while
+/// the connect is still running, `undo` is called with the result of the
+/// connect once it returns, to tear down any connection it made.
+pub(crate) async fn connect_blocking<R, C, U>(
+    connect: C,
+    undo: U,
+) -> Result<R, DeviceError>
+where
+    R: Send + 'static,
+    C: FnOnce() -> R + Send + 'static,
+    U: FnOnce(&R) + Send + 'static,
+{
+    let state = Arc::new(Mutex::new(Connect::Running));
+    let guard = AbandonOnDrop(Arc::clone(&state));
+
+    tokio::task::spawn_blocking(move || {
+        let result = connect();
+        let mut state = state.lock().expect("connect state lock poisoned");
+        match *state {
+            Connect::Abandoned => undo(&result),
+            _ => *state = Connect::Finished(result, undo),
+        }
+    })
+    .await
+    .map_err(|error| DeviceError::new(&format!("connect failed: {}", error)))?;
+
+    let mut state = guard.0.lock().expect("connect state lock poisoned");
+    match mem::replace(&mut *state, Connect::Collected) {
+        Connect::Finished(result, _) => Ok(result),
+        _ => unreachable!("connect finished without a result"),
     }
 }
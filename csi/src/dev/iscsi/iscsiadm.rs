@@ -8,6 +8,9 @@ use regex::Regex;
 
 use super::DeviceError;
 
+/// Exit status of iscsiadm when no matching session or record was found.
+const ISCSI_ERR_NO_OBJS_FOUND: i32 = 21;
+
 pub(super) struct IscsiAdmin;
 
 impl IscsiAdmin {
@@ -94,7 +97,7 @@ impl IscsiAdmin {
             portal,
             "--logout",
         ];
-        IscsiAdmin::execute(&args)
+        IscsiAdmin::execute_idempotent(&args)
     }
 
     pub(super) fn delete(portal: &str, iqn: &str) -> Result<(), DeviceError> {
@@ -110,7 +113,7 @@ impl IscsiAdmin {
             "--op",
             "delete",
         ];
-        IscsiAdmin::execute(&args)
+        IscsiAdmin::execute_idempotent(&args)
     }
 
     fn execute(args: &[&str]) -> Result<(), DeviceError> {
@@ -127,6 +130,24 @@ impl IscsiAdmin {
         Err(DeviceError::from(String::from_utf8(output.stderr).unwrap()))
     }
 
+    /// Execute a command which removes an object, succeeding if the object
+    /// does not exist (anymore).
+    fn execute_idempotent(args: &[&str]) -> Result<(), DeviceError> {
+        let iscsiadm = IscsiAdmin::get_binary()?;
+
+        trace!("iscsiadm {:?}", args);
+
+        let output = Command::new(iscsiadm).args(args).output()?;
+
+        if output.status.success()
+            || output.status.code() == Some(ISCSI_ERR_NO_OBJS_FOUND)
+        {
+            return Ok(());
+        }
+
+        Err(DeviceError::from(String::from_utf8(output.stderr).unwrap()))
+    }
+
     fn find_target(portal: &str, iqn: &str, data: Vec<u8>) -> bool {
         lazy_static! {
             static ref PATTERN: Regex = Regex::new(r"(?P<portal>[[:digit:]]+(\.[[:digit:]]+){3}:[[:digit:]]+),[[:digit:]]+ +(?P<target>iqn\.[^ ]+)").unwrap();
@@ -7,6 +7,7 @@ use std::{
 use nvmeadm::{
     error::NvmeError,
     nvmf_discovery::{disconnect, ConnectArgsBuilder},
+    nvmf_subsystem::{NvmeSubsystems, Subsystem},
 };
 
 use glob::glob;
@@ -17,7 +18,15 @@ use uuid::Uuid;
 
 use crate::{dev::util::extract_uuid, match_dev::match_nvmf_device};
 
-use super::{Attach, Detach, DeviceError, DeviceName};
+use super::{
+    connect_blocking,
+    Attach,
+    Detach,
+    DeviceError,
+    DeviceErrorKind,
+    DeviceName,
+    NvmfHost,
+};
 
 lazy_static! {
     static ref DEVICE_REGEX: Regex = Regex::new(r"nvme(\d{1,3})n1").unwrap();
@@ -29,6 +38,7 @@ pub(super) struct NvmfAttach {
     uuid: Uuid,
     nqn: String,
     io_timeout: Option<u32>,
+    nvmf_host: Option<NvmfHost>,
 }
 
 impl NvmfAttach {
@@ -39,6 +49,7 @@ impl NvmfAttach {
             uuid,
             nqn,
             io_timeout: None,
+            nvmf_host: None,
         }
     }
 
@@ -120,6 +131,10 @@ impl Attach for NvmfAttach {
         Ok(())
     }
 
+    fn set_nvmf_host(&mut self, host: &NvmfHost) {
+        self.nvmf_host = Some(host.clone());
+    }
+
     async fn attach(&self) -> Result<(), DeviceError> {
         // The default reconnect delay in linux kernel is set to 10s. Use the
         // same default value unless the timeout is less or equal to 10.
@@ -139,8 +154,29 @@ impl Attach for NvmfAttach {
             .nqn(&self.nqn)
             .ctrl_loss_tmo(self.io_timeout)
             .reconnect_delay(reconnect_delay)
+            .hostnqn(self.nvmf_host.as_ref().map(|h| h.nqn.clone()))
+            .hostid(self.nvmf_host.as_ref().map(|h| h.id.clone()))
             .build()?;
-        match ca.connect() {
+        // the connect blocks for as long as the kernel keeps trying to reach
+        // the target, if the attach times out meanwhile the connection made
+        // is torn down once the connect returns
+        let (nqn, host, port) =
+            (self.nqn.clone(), self.host.clone(), self.port);
+        match connect_blocking(
+            move || ca.connect(),
+            move |result| {
+                if result.is_ok() {
+                    if let Err(error) = disconnect_path(&nqn, &host, port) {
+                        warn!(
+                            "Failed to disconnect abandoned attach of {}: {}",
+                            nqn, error
+                        );
+                    }
+                }
+            },
+        )
+        .await?
+        {
             Err(NvmeError::ConnectInProgress) => Ok(()),
             Err(err) => Err(format!("connect failed: {}", err).into()),
             Ok(_) => Ok(()),
@@ -156,11 +192,42 @@ impl Attach for NvmfAttach {
         })
     }
 
+    /// Without native NVMe multipath every controller has a namespace device
+    /// of its own, but all of them have the same WWN which find() matches.
+    /// With multipath the namespace device is shared by all paths.
+    async fn find_path(&self) -> Result<Option<DeviceName>, DeviceError> {
+        for controller in path_controllers(&self.nqn, &self.host, self.port)? {
+            let pattern = format!(
+                "/sys/class/nvme/{}/{}n*",
+                controller.name, controller.name
+            );
+            if let Some(namespace) = glob(&pattern).unwrap().flatten().next() {
+                let devname = format!(
+                    "/dev/{}",
+                    namespace.file_name().unwrap().to_string_lossy()
+                );
+                if Path::new(&devname).exists() {
+                    return Ok(Some(devname));
+                }
+            }
+
+            let pattern =
+                format!("/sys/class/nvme/{}/nvme*c*n*", controller.name);
+            if glob(&pattern).unwrap().flatten().next().is_some() {
+                return self.find().await;
+            }
+        }
+        Ok(None)
+    }
+
     async fn fixup(&self) -> Result<(), DeviceError> {
         if let Some(io_timeout) = self.io_timeout {
-            let device = self
-                .get_device()?
-                .ok_or_else(|| DeviceError::new("NVMe device not found"))?;
+            let device = self.get_device()?.ok_or_else(|| {
+                DeviceError::with_kind(
+                    DeviceErrorKind::NotFound,
+                    "NVMe device not found",
+                )
+            })?;
             let dev_name = device.sysname().to_str().unwrap();
             let major = DEVICE_REGEX
                 .captures(dev_name)
@@ -202,6 +269,46 @@ impl Attach for NvmfAttach {
         }
         Ok(())
     }
+
+    async fn disconnect(&self) -> Result<(), DeviceError> {
+        disconnect_path(&self.nqn, &self.host, self.port)
+    }
+}
+
+/// Return the controllers of the given NQN connected through the given
+/// address.
+fn path_controllers(
+    nqn: &str,
+    host: &str,
+    port: u16,
+) -> Result<Vec<Subsystem>, DeviceError> {
+    let traddr = format!("traddr={}", host);
+    let trsvcid = format!("trsvcid={}", port);
+    Ok(NvmeSubsystems::new()?
+        .flatten()
+        .filter(|s| {
+            s.nqn == nqn
+                && s.address.split(',').any(|a| a == traddr)
+                && s.address.split(',').any(|a| a == trsvcid)
+        })
+        .collect())
+}
+
+/// Disconnect the controllers of the given NQN connected through the given
+/// address, leaving the other paths to the same subsystem alone.
+fn disconnect_path(
+    nqn: &str,
+    host: &str,
+    port: u16,
+) -> Result<(), DeviceError> {
+    for subsystem in path_controllers(nqn, host, port)? {
+        debug!(
+            "Disconnecting {} of {} at {}",
+            subsystem.name, nqn, subsystem.address
+        );
+        subsystem.disconnect()?;
+    }
+    Ok(())
 }
 
 pub(super) struct NvmfDetach {
@@ -221,14 +328,21 @@ impl NvmfDetach {
 #[tonic::async_trait]
 impl Detach for NvmfDetach {
     async fn detach(&self) -> Result<(), DeviceError> {
-        if disconnect(&self.nqn)? == 0 {
-            return Err(DeviceError::from(format!(
-                "nvmf disconnect {} failed: no device found",
-                self.nqn
-            )));
+        match disconnect(&self.nqn) {
+            Ok(0) => {
+                debug!("nvmf device {} is already disconnected", self.nqn);
+                Ok(())
+            }
+            Ok(_) => Ok(()),
+            // the controller went away while it was being deleted
+            Err(NvmeError::FileIoError {
+                source, ..
+            }) if source.kind() == std::io::ErrorKind::NotFound => {
+                debug!("nvmf device {} is already disconnected", self.nqn);
+                Ok(())
+            }
+            Err(error) => Err(error.into()),
         }
-
-        Ok(())
     }
 
     fn devname(&self) -> DeviceName {
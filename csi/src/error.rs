@@ -2,16 +2,52 @@
 use nvmeadm::nvmf_discovery;
 use std::string::FromUtf8Error;
 
+/// Category of a DeviceError, allowing callers to tell failures apart
+/// without having to parse the message.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeviceErrorKind {
+    Timeout,
+    NotFound,
+    Io,
+    Parse,
+    Other,
+}
+
 pub struct DeviceError {
     pub message: String,
+    pub kind: DeviceErrorKind,
+    pub source: Option<Box<dyn std::error::Error + Send + Sync>>,
 }
 
 impl DeviceError {
     pub fn new(message: &str) -> DeviceError {
+        DeviceError::with_kind(DeviceErrorKind::Other, message)
+    }
+
+    pub fn with_kind(kind: DeviceErrorKind, message: &str) -> DeviceError {
         DeviceError {
             message: String::from(message),
+            kind,
+            source: None,
         }
     }
+
+    /// Wrap the source error, the message is taken from the source so that
+    /// the error is displayed the same way as the source.
+    fn from_source<E>(kind: DeviceErrorKind, error: E) -> DeviceError
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        DeviceError {
+            message: format!("{}", error),
+            kind,
+            source: Some(Box::new(error)),
+        }
+    }
+
+    pub fn kind(&self) -> DeviceErrorKind {
+        self.kind
+    }
 }
 
 impl std::fmt::Debug for DeviceError {
@@ -30,45 +66,46 @@ impl std::error::Error for DeviceError {
     fn description(&self) -> &str {
         &self.message
     }
+
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|e| e.as_ref() as &(dyn std::error::Error + 'static))
+    }
 }
 
 impl From<std::io::Error> for DeviceError {
     fn from(error: std::io::Error) -> DeviceError {
-        DeviceError {
-            message: format!("{}", error),
-        }
+        let kind = match error.kind() {
+            std::io::ErrorKind::TimedOut => DeviceErrorKind::Timeout,
+            std::io::ErrorKind::NotFound => DeviceErrorKind::NotFound,
+            _ => DeviceErrorKind::Io,
+        };
+        DeviceError::from_source(kind, error)
     }
 }
 
 impl From<failure::Error> for DeviceError {
     fn from(error: failure::Error) -> DeviceError {
-        DeviceError {
-            message: format!("{}", error),
-        }
+        DeviceError::from_source(DeviceErrorKind::Other, error.compat())
     }
 }
 
 impl From<std::num::ParseIntError> for DeviceError {
     fn from(error: std::num::ParseIntError) -> DeviceError {
-        DeviceError {
-            message: format!("{}", error),
-        }
+        DeviceError::from_source(DeviceErrorKind::Parse, error)
     }
 }
 
 impl From<uuid::Error> for DeviceError {
     fn from(error: uuid::Error) -> DeviceError {
-        DeviceError {
-            message: format!("{}", error),
-        }
+        DeviceError::from_source(DeviceErrorKind::Parse, error)
     }
 }
 
 impl From<nvmf_discovery::ConnectArgsBuilderError> for DeviceError {
     fn from(error: nvmf_discovery::ConnectArgsBuilderError) -> DeviceError {
-        DeviceError {
-            message: format!("{}", error),
-        }
+        DeviceError::from_source(DeviceErrorKind::Other, error)
     }
 }
 
@@ -76,30 +113,76 @@ impl From<String> for DeviceError {
     fn from(message: String) -> DeviceError {
         DeviceError {
             message,
+            kind: DeviceErrorKind::Other,
+            source: None,
         }
     }
 }
 
 impl From<serde_json::error::Error> for DeviceError {
     fn from(error: serde_json::error::Error) -> DeviceError {
-        DeviceError {
-            message: format!("{}", error),
-        }
+        DeviceError::from_source(DeviceErrorKind::Parse, error)
     }
 }
 
 impl From<FromUtf8Error> for DeviceError {
     fn from(error: FromUtf8Error) -> DeviceError {
-        DeviceError {
-            message: format!("{}", error),
-        }
+        DeviceError::from_source(DeviceErrorKind::Parse, error)
     }
 }
 
 impl From<nvmeadm::error::NvmeError> for DeviceError {
     fn from(error: nvmeadm::error::NvmeError) -> DeviceError {
-        DeviceError {
-            message: format!("{}", error),
-        }
+        use nvmeadm::error::NvmeError;
+        let kind = match error {
+            NvmeError::NqnNotFound {
+                ..
+            }
+            | NvmeError::CtlNotFound {
+                ..
+            } => DeviceErrorKind::NotFound,
+            NvmeError::IoError {
+                ..
+            }
+            | NvmeError::FileIoError {
+                ..
+            }
+            | NvmeError::ConnectError {
+                ..
+            } => DeviceErrorKind::Io,
+            NvmeError::ValueParseError {
+                ..
+            }
+            | NvmeError::ParseError {}
+            | NvmeError::UrlError {
+                ..
+            } => DeviceErrorKind::Parse,
+            _ => DeviceErrorKind::Other,
+        };
+        DeviceError::from_source(kind, error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DeviceError, DeviceErrorKind};
+    use std::error::Error;
+
+    #[test]
+    fn device_error_source() {
+        let io = std::io::Error::new(std::io::ErrorKind::TimedOut, "too slow");
+        let error = DeviceError::from(io);
+        assert_eq!(error.kind(), DeviceErrorKind::Timeout);
+        assert_eq!(error.to_string(), "too slow");
+        let source = error.source().expect("source must be preserved");
+        assert!(source.downcast_ref::<std::io::Error>().is_some());
+
+        let error = DeviceError::from("0x".parse::<u32>().unwrap_err());
+        assert_eq!(error.kind(), DeviceErrorKind::Parse);
+        assert!(error.source().is_some());
+
+        let error = DeviceError::new("device attach timeout");
+        assert_eq!(error.kind(), DeviceErrorKind::Other);
+        assert!(error.source().is_none());
     }
 }
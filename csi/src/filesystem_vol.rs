@@ -1,7 +1,8 @@
 //! Functions for CSI stage, unstage, publish and unpublish filesystem volumes.
 
-use std::{fs, io::ErrorKind, path::PathBuf};
+use std::{fs, io::ErrorKind, path::PathBuf, time::Duration};
 
+use devinfo::mountinfo::MountInfo;
 use tonic::{Code, Status};
 
 macro_rules! failure {
@@ -10,16 +11,47 @@ macro_rules! failure {
 }
 
 use crate::{
-    csi::{volume_capability::MountVolume, *},
-    format::prepare_device,
-    mount::{self, subset, ReadOnly},
+    csi::{
+        volume_capability::{access_mode::Mode, MountVolume},
+        *,
+    },
+    format::{
+        grow_filesystem,
+        mkfs_options,
+        prepare_device,
+        PrepareError,
+        Prepared,
+    },
+    mount::{
+        self,
+        conflicting_option,
+        sanitize_options,
+        PublishOptionsPolicy,
+        ReadOnly,
+    },
 };
 
+/// Return true if the capability only allows the volume to be read, in which
+/// case it is staged readonly.
+pub(crate) fn reader_only_access(
+    volume_capability: &Option<VolumeCapability>,
+) -> bool {
+    matches!(
+        volume_capability
+            .as_ref()
+            .and_then(|capability| capability.access_mode.as_ref())
+            .and_then(|access| Mode::from_i32(access.mode)),
+        Some(Mode::SingleNodeReaderOnly) | Some(Mode::MultiNodeReaderOnly)
+    )
+}
+
 pub async fn stage_fs_volume(
     msg: &NodeStageVolumeRequest,
     device_path: String,
     mnt: &MountVolume,
     filesystems: &[String],
+    mkfs_timeout: Duration,
+    check_on_mount: bool,
 ) -> Result<(), Status> {
     let volume_id = &msg.volume_id;
     let fs_staging_path = &msg.staging_target_path;
@@ -55,6 +87,22 @@ pub async fn stage_fs_volume(
         }
     };
 
+    let mut mount_flags =
+        sanitize_options(&mnt.mount_flags, None).map_err(|option| {
+            failure!(
+                Code::InvalidArgument,
+                "Failed to stage volume {}: unsupported mount option: {}",
+                volume_id,
+                option
+            )
+        })?;
+
+    // record the access mode in the staging, so that restaging the volume
+    // for writing can be told apart
+    if reader_only_access(&msg.volume_capability) && !mount_flags.readonly() {
+        mount_flags.push(String::from("ro"));
+    }
+
     if mount::find_mount(Some(&device_path), Some(fs_staging_path)).is_some() {
         debug!(
             "Device {} is already mounted onto {}",
@@ -87,14 +135,54 @@ pub async fn stage_fs_volume(
                 ));
     }
 
-    if let Err(error) = prepare_device(&device_path, &fstype).await {
-        return Err(failure!(
-            Code::Internal,
-            "Failed to stage volume {}: error preparing device {}: {}",
-            volume_id,
-            device_path,
-            error
-        ));
+    match prepare_device(
+        &device_path,
+        &fstype,
+        &mkfs_options(&msg.volume_context),
+        mkfs_timeout,
+        check_on_mount,
+    )
+    .await
+    {
+        Ok(Prepared::Existing(existing)) if existing != fstype => {
+            // mounting it as the requested type would fail obscurely, and
+            // reformatting it would destroy the data on the volume
+            return Err(failure!(
+                Code::AlreadyExists,
+                "Failed to stage volume {}: device {} already has a {} filesystem, requested {}",
+                volume_id,
+                device_path,
+                existing,
+                fstype
+            ));
+        }
+        Ok(_) => {}
+        Err(PrepareError::InvalidOption(error)) => {
+            return Err(failure!(
+                Code::InvalidArgument,
+                "Failed to stage volume {}: invalid filesystem creation option: {}",
+                volume_id,
+                error
+            ));
+        }
+        Err(PrepareError::Corrupted(error)) => {
+            return Err(failure!(
+                Code::DataLoss,
+                "Failed to stage volume {}: filesystem on device {} is corrupted: {}",
+                volume_id,
+                device_path,
+                error
+            ));
+        }
+        Err(error) => {
+            return Err(failure!(
+                Code::Internal,
+                "Failed to stage volume {}: error preparing device {}: {}",
+                volume_id,
+                device_path,
+                error
+            ));
+        }
     }
 
     debug!("Mounting device {} onto {}", device_path, fs_staging_path);
@@ -103,7 +191,7 @@ pub async fn stage_fs_volume(
         &device_path,
         fs_staging_path,
         &fstype,
-        &mnt.mount_flags,
+        &mount_flags,
     ) {
         return Err(failure!(
             Code::Internal,
@@ -120,6 +208,52 @@ pub async fn stage_fs_volume(
     Ok(())
 }
 
+/// Expand a staged filesystem volume to fill the underlying device
+pub async fn expand_fs_volume(
+    msg: &NodeExpandVolumeRequest,
+    device_path: &str,
+    filesystems: &[String],
+) -> Result<(), Status> {
+    let volume_id = &msg.volume_id;
+
+    let mount =
+        mount::find_mount(Some(device_path), None).ok_or_else(|| {
+            failure!(
+                Code::FailedPrecondition,
+                "Failed to expand volume {}: device {} is not mounted",
+                volume_id,
+                device_path
+            )
+        })?;
+
+    if !filesystems.iter().any(|entry| entry == &mount.fstype) {
+        return Err(failure!(
+            Code::InvalidArgument,
+            "Failed to expand volume {}: unsupported filesystem type: {}",
+            volume_id,
+            mount.fstype
+        ));
+    }
+
+    let mountpoint = mount.dest.to_string_lossy();
+
+    if let Err(error) =
+        grow_filesystem(device_path, &mountpoint, &mount.fstype).await
+    {
+        return Err(failure!(
+            Code::Internal,
+            "Failed to expand volume {}: error growing filesystem on device {}: {}",
+            volume_id,
+            device_path,
+            error
+        ));
+    }
+
+    info!("Volume {} expanded", volume_id);
+
+    Ok(())
+}
+
 /// Unstage a filesystem volume
 pub async fn unstage_fs_volume(
     msg: &NodeUnstageVolumeRequest,
@@ -148,10 +282,12 @@ pub async fn unstage_fs_volume(
 }
 
 /// Publish a filesystem volume
-pub fn publish_fs_volume(
+pub async fn publish_fs_volume(
     msg: &NodePublishVolumeRequest,
     mnt: &MountVolume,
     filesystems: &[String],
+    policy: PublishOptionsPolicy,
+    bind_mount_retries: u32,
 ) -> Result<(), Status> {
     let target_path = &msg.target_path;
     let volume_id = &msg.volume_id;
@@ -162,6 +298,16 @@ pub fn publish_fs_volume(
         volume_id, fs_staging_path, target_path
     );
 
+    let mount_flags = sanitize_options(&mnt.mount_flags, Some(msg.readonly))
+        .map_err(|option| {
+            failure!(
+                Code::InvalidArgument,
+                "Failed to publish volume {}: unsupported mount option: {}",
+                volume_id,
+                option
+            )
+        })?;
+
     let staged =
         mount::find_mount(None, Some(fs_staging_path)).ok_or_else(|| {
             failure!(
@@ -196,15 +342,40 @@ pub fn publish_fs_volume(
 
     let readonly = staged.options.readonly();
 
+    // The access mode has been checked to allow writes, so promote a volume
+    // staged as "ro", e.g. a reader-only volume made writable after failover.
     if readonly && !msg.readonly {
-        return Err(failure!(
-                Code::InvalidArgument,
-                "Failed to publish volume {}: volume is staged as \"ro\" but publish requires \"rw\"",
-                volume_id
-            ));
+        promote_staged_volume(volume_id, fs_staging_path, &staged)?;
     }
 
-    if let Some(mount) = mount::find_mount(None, Some(target_path)) {
+    let existing = match mount::find_mount(None, Some(target_path)) {
+        Some(mount)
+            if mount.source != staged.source
+                && mount::is_stale_mount(&mount) =>
+        {
+            warn!(
+                "Volume {}: removing stale mount of {} on {}",
+                volume_id,
+                mount.source.display(),
+                target_path
+            );
+
+            if let Err(error) = mount::bind_unmount(target_path) {
+                return Err(failure!(
+                    Code::Internal,
+                    "Failed to publish volume {}: failed to unmount stale mount on {}: {}",
+                    volume_id,
+                    target_path,
+                    error
+                ));
+            }
+
+            None
+        }
+        existing => existing,
+    };
+
+    if let Some(mount) = existing {
         if mount.source != staged.source {
             return Err(failure!(
                 Code::AlreadyExists,
@@ -214,9 +385,7 @@ pub fn publish_fs_volume(
             ));
         }
 
-        if !subset(&mnt.mount_flags, &mount.options)
-            || msg.readonly != mount.options.readonly()
-        {
+        if msg.readonly != mount.options.readonly() {
             return Err(failure!(
                     Code::AlreadyExists,
                     "Failed to publish volume {}: directory {} is already mounted but with incompatible flags",
@@ -225,6 +394,18 @@ pub fn publish_fs_volume(
                 ));
         }
 
+        if let Some(option) =
+            conflicting_option(&mount_flags, &mount.options, policy)
+        {
+            return Err(failure!(
+                    Code::AlreadyExists,
+                    "Failed to publish volume {}: directory {} is already mounted but with incompatible flag \"{}\"",
+                    volume_id,
+                    target_path,
+                    option
+                ));
+        }
+
         info!(
             "Volume {} is already published to {}",
             volume_id, target_path
@@ -249,7 +430,11 @@ pub fn publish_fs_volume(
 
     debug!("Mounting {} to {}", fs_staging_path, target_path);
 
-    if let Err(error) = mount::bind_mount(fs_staging_path, target_path, false) {
+    if let Err(error) = mount::retry_transient(bind_mount_retries, || {
+        mount::bind_mount(fs_staging_path, target_path, false)
+    })
+    .await
+    {
         return Err(failure!(
             Code::Internal,
             "Failed to publish volume {}: failed to mount {} to {}: {}",
@@ -261,7 +446,7 @@ pub fn publish_fs_volume(
     }
 
     if msg.readonly && !readonly {
-        let mut options = mnt.mount_flags.clone();
+        let mut options = mount_flags.clone();
         options.push(String::from("ro"));
 
         debug!("Remounting {} as readonly", target_path);
@@ -292,6 +477,52 @@ pub fn publish_fs_volume(
     Ok(())
 }
 
+/// Remount the filesystem staged readonly as read-write, making sure that its
+/// existing readonly publications stay readonly.
+fn promote_staged_volume(
+    volume_id: &str,
+    fs_staging_path: &str,
+    staged: &MountInfo,
+) -> Result<(), Status> {
+    let ro = vec![String::from("ro")];
+
+    for mount in mount::find_mounts_of(&staged.source.to_string_lossy()) {
+        let target = mount.dest.to_string_lossy();
+        if target == fs_staging_path || !mount.options.readonly() {
+            continue;
+        }
+
+        debug!("Pinning {} as readonly", target);
+
+        if let Err(error) = mount::bind_remount(&target, &ro) {
+            return Err(failure!(
+                Code::Internal,
+                "Failed to publish volume {}: failed to keep {} readonly: {}",
+                volume_id,
+                target,
+                error
+            ));
+        }
+    }
+
+    if let Err(error) = mount::filesystem_remount(fs_staging_path, &[]) {
+        return Err(failure!(
+            Code::FailedPrecondition,
+            "Failed to publish volume {}: failed to remount {} as \"rw\": {}",
+            volume_id,
+            fs_staging_path,
+            error
+        ));
+    }
+
+    info!(
+        "Volume {} staged as \"ro\" promoted to \"rw\" on {}",
+        volume_id, fs_staging_path
+    );
+
+    Ok(())
+}
+
 pub fn unpublish_fs_volume(
     msg: &NodeUnpublishVolumeRequest,
 ) -> Result<(), Status> {
@@ -1,32 +1,343 @@
-//! Utility function for formatting a device with filesystem
+//! Utility functions for formatting a device with filesystem and growing an
+//! existing filesystem
 
-use std::process::Command;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    process::{Command, Output},
+    time::Duration,
+};
 
 use devinfo::blkid::probe::Probe;
 
+/// State of a device after it has been prepared for mounting
+#[derive(Debug, PartialEq)]
+pub(crate) enum Prepared {
+    /// a new filesystem has been created on the device
+    Formatted,
+    /// the device already had a filesystem of the given type
+    Existing(String),
+}
+
+/// Error preparing a device for mounting
+#[derive(Debug, PartialEq)]
+pub(crate) enum PrepareError {
+    /// the device could not be probed, formatted or checked
+    Failed(String),
+    /// the existing filesystem is corrupted beyond automatic repair
+    Corrupted(String),
+    /// a filesystem creation option is invalid for the filesystem type
+    InvalidOption(String),
+}
+
+impl fmt::Display for PrepareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Failed(error) => write!(f, "{}", error),
+            Self::Corrupted(error) => {
+                write!(f, "filesystem is corrupted: {}", error)
+            }
+            Self::InvalidOption(error) => {
+                write!(f, "invalid filesystem creation option: {}", error)
+            }
+        }
+    }
+}
+
+impl From<String> for PrepareError {
+    fn from(error: String) -> Self {
+        Self::Failed(error)
+    }
+}
+
+/// Prefix of the volume context entries holding filesystem creation options.
+const MKFS_OPTION_PREFIX: &str = "mkfs.";
+
+/// Return the filesystem creation options given by the "mkfs.<option>"
+/// entries of the volume context, without the prefix.
+pub(crate) fn mkfs_options(
+    context: &HashMap<String, String>,
+) -> HashMap<String, String> {
+    context
+        .iter()
+        .filter_map(|(key, value)| {
+            key.strip_prefix(MKFS_OPTION_PREFIX)
+                .map(|option| (option.to_string(), value.clone()))
+        })
+        .collect()
+}
+
+/// Translate filesystem creation options into arguments of mkfs for the
+/// given filesystem type, rejecting options which do not apply to it.
+fn mkfs_args(
+    fstype: &str,
+    options: &HashMap<String, String>,
+) -> Result<Vec<String>, String> {
+    let invalid =
+        |option: &str, value: &str| format!("{}=\"{}\"", option, value);
+    let integer = |option: &str, value: &str| {
+        value
+            .parse::<u64>()
+            .ok()
+            .filter(|value| *value > 0)
+            .ok_or_else(|| invalid(option, value))
+    };
+    // a size in bytes, optionally with a k, m or g suffix
+    let size = |option: &str, value: &str| {
+        let digits = value.trim_end_matches(&['k', 'm', 'g'][..]);
+        if value.len() - digits.len() > 1 {
+            return Err(invalid(option, value));
+        }
+        integer(option, digits).map(|_| value.to_string())
+    };
+
+    let mut args = Vec::new();
+    let mut data = Vec::new();
+
+    // sorted, to pass the arguments in a stable order
+    for (option, value) in options.iter().collect::<BTreeMap<_, _>>() {
+        let (option, value) = (option.as_str(), value.as_str());
+        match (fstype, option) {
+            ("ext4", "blockSize") => {
+                args.push("-b".into());
+                args.push(integer(option, value)?.to_string());
+            }
+            ("ext4", "inodeRatio") => {
+                args.push("-i".into());
+                args.push(integer(option, value)?.to_string());
+            }
+            ("ext4", "reservedBlocksPercentage") => {
+                match value.parse::<f64>() {
+                    Ok(percentage) if (0.0 ..= 50.0).contains(&percentage) => {
+                        args.push("-m".into());
+                        args.push(value.to_string());
+                    }
+                    _ => return Err(invalid(option, value)),
+                }
+            }
+            ("xfs", "blockSize") => {
+                args.push("-b".into());
+                args.push(format!("size={}", size(option, value)?));
+            }
+            ("xfs", "noDiscard") => match value {
+                "true" => args.push("-K".into()),
+                "false" => {}
+                _ => return Err(invalid(option, value)),
+            },
+            ("xfs", "stripeUnit") => {
+                data.push(format!("su={}", size(option, value)?))
+            }
+            ("xfs", "stripeWidth") => {
+                data.push(format!("sw={}", integer(option, value)?))
+            }
+            _ => {
+                return Err(format!(
+                    "{} is not supported for {}",
+                    option, fstype
+                ))
+            }
+        }
+    }
+
+    // the stripe unit and width of xfs must be given together
+    match data.len() {
+        0 => {}
+        2 => {
+            args.push("-d".into());
+            args.push(data.join(","));
+        }
+        _ => {
+            return Err(
+                "stripeUnit and stripeWidth must be given together".into()
+            )
+        }
+    }
+
+    Ok(args)
+}
+
+/// Create a filesystem of the given type on the device, unless it already
+/// has a filesystem, which may be of a different type. An existing
+/// filesystem of the given type is checked and repaired first if requested.
+/// The filesystem creation options are validated against the filesystem
+/// type, but only used when a new filesystem is created.
 pub(crate) async fn prepare_device(
     device: &str,
     fstype: &str,
-) -> Result<(), String> {
+    options: &HashMap<String, String>,
+    timeout: Duration,
+    check: bool,
+) -> Result<Prepared, PrepareError> {
+    let args =
+        mkfs_args(fstype, options).map_err(PrepareError::InvalidOption)?;
+
     debug!("Probing device {}", device);
 
     let probe = Probe::new_from_filename(device)
         .map_err(|error| format!("probe setup failed: {}", error))?;
 
     if let Err(error) = probe.do_probe() {
-        return Err(format!("probe failed: {}", error));
+        return Err(format!("probe failed: {}", error).into());
     }
 
     if let Ok(fs) = probe.lookup_value("TYPE") {
         debug!("Found existing filesystem ({}) on device {}", fs, device);
-        return Ok(());
+        if check && fs == fstype {
+            check_filesystem(device, &fs, timeout).await?;
+        }
+        return Ok(Prepared::Existing(fs));
     }
 
     debug!("Creating new filesystem ({}) on device {}", fstype, device);
 
     let binary = format!("mkfs.{}", fstype);
-    let output = Command::new(&binary)
+    let result = run_mkfs(&binary, &args, device, timeout).await;
+
+    if result.is_err() {
+        wipe_device(device, timeout).await;
+    }
+
+    result
+        .map(|_| Prepared::Formatted)
+        .map_err(PrepareError::from)
+}
+
+/// Check the filesystem on the device, repairing it where this can be done
+/// without user interaction, as it may have been left dirty by an unclean
+/// shutdown of the node.
+async fn check_filesystem(
+    device: &str,
+    fstype: &str,
+    timeout: Duration,
+) -> Result<(), PrepareError> {
+    // exit codes of the check, see e2fsck(8) and xfs_repair(8)
+    let (binary, args): (&str, &[&str]) = match fstype {
+        "ext4" => ("e2fsck", &["-p"]),
+        "xfs" => ("xfs_repair", &[]),
+        fstype => {
+            debug!("Not checking unsupported filesystem type {}", fstype);
+            return Ok(());
+        }
+    };
+
+    debug!("Checking {} filesystem on device {}", fstype, device);
+
+    let output = run_command(binary, args, device, timeout).await?;
+    let code = output.status.code();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    match (fstype, code) {
+        ("ext4", Some(0)) | ("xfs", Some(0)) => Ok(()),
+        ("ext4", Some(1)) | ("ext4", Some(2)) => {
+            warn!("Repaired {} filesystem on device {}", fstype, device);
+            Ok(())
+        }
+        // the log is replayed when the filesystem is mounted
+        ("xfs", Some(2)) => {
+            debug!("Filesystem on device {} has a dirty log", device);
+            Ok(())
+        }
+        ("ext4", Some(4)) | ("xfs", Some(1)) => Err(PrepareError::Corrupted(
+            format!("{} command failed: {}", binary, stderr),
+        )),
+        _ => Err(PrepareError::Failed(format!(
+            "{} command failed ({:?}): {}",
+            binary, code, stderr
+        ))),
+    }
+}
+
+/// Run mkfs as a child process which is killed when it does not complete
+/// within the timeout, or when the returned future is dropped because the
+/// staging request has been cancelled.
+async fn run_mkfs(
+    binary: &str,
+    args: &[String],
+    device: &str,
+    timeout: Duration,
+) -> Result<(), String> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let output = run_command(binary, &args, device, timeout).await?;
+
+    if output.status.success() {
+        return Ok(());
+    }
+
+    Err(format!(
+        "{} command failed: {}",
+        binary,
+        String::from_utf8_lossy(&output.stderr)
+    ))
+}
+
+/// Run a command on the device as a child process which is killed when it
+/// does not complete within the timeout, or when the returned future is
+/// dropped.
+async fn run_command(
+    binary: &str,
+    args: &[&str],
+    device: &str,
+    timeout: Duration,
+) -> Result<Output, String> {
+    let child = tokio::process::Command::new(binary)
+        .args(args)
         .arg(device)
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|error| format!("failed to execute {}: {}", binary, error))?;
+
+    let output = tokio::time::timeout(timeout, child.wait_with_output())
+        .await
+        .map_err(|_| {
+            format!("{} command timed out after {:?}", binary, timeout)
+        })?
+        .map_err(|error| format!("failed to execute {}: {}", binary, error))?;
+
+    trace!(
+        "Output from {} command: {}",
+        binary,
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    Ok(output)
+}
+
+/// Remove any (partial) filesystem signature left behind by a failed mkfs.
+async fn wipe_device(device: &str, timeout: Duration) {
+    match run_command("wipefs", &["--all"], device, timeout).await {
+        Ok(output) if output.status.success() => {
+            debug!("Wiped partial filesystem from device {}", device)
+        }
+        Ok(output) => warn!(
+            "Failed to wipe device {}: {}",
+            device,
+            String::from_utf8_lossy(&output.stderr)
+        ),
+        Err(error) => warn!("Failed to wipe device {}: {}", device, error),
+    }
+}
+
+/// Grow the filesystem mounted at the given mountpoint to fill the device.
+pub(crate) async fn grow_filesystem(
+    device: &str,
+    mountpoint: &str,
+    fstype: &str,
+) -> Result<(), String> {
+    // xfs is grown through its mountpoint whereas ext4 takes the device
+    let (binary, target) = match fstype {
+        "xfs" => ("xfs_growfs", mountpoint),
+        "ext4" => ("resize2fs", device),
+        fstype => {
+            return Err(format!("unsupported filesystem type: {}", fstype))
+        }
+    };
+
+    debug!("Growing {} filesystem on device {}", fstype, device);
+
+    let output = Command::new(binary)
+        .arg(target)
         .output()
         .map_err(|error| format!("failed to execute {}: {}", binary, error))?;
 
@@ -46,3 +357,207 @@ pub(crate) async fn prepare_device(
         String::from_utf8(output.stderr).unwrap()
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        mkfs_args,
+        mkfs_options,
+        prepare_device,
+        run_mkfs,
+        PrepareError,
+        Prepared,
+    };
+    use std::{
+        collections::HashMap,
+        fs,
+        os::unix::fs::PermissionsExt,
+        path::Path,
+        process::Command,
+        time::Duration,
+    };
+
+    #[tokio::test]
+    async fn existing_filesystem() {
+        let image = std::env::temp_dir()
+            .join(format!("csi-prepare-{}.img", uuid::Uuid::new_v4()));
+        let file = fs::File::create(&image).unwrap();
+        // the smallest xfs filesystem of xfsprogs 5.19 and later is 300MiB,
+        // the image is sparse
+        file.set_len(320 * 1024 * 1024).unwrap();
+        drop(file);
+
+        let output = Command::new("mkfs.xfs").arg(&image).output().unwrap();
+        assert!(output.status.success());
+
+        // an existing filesystem is left alone whatever type was requested
+        let prepared = prepare_device(
+            image.to_str().unwrap(),
+            "ext4",
+            &HashMap::new(),
+            Duration::from_secs(30),
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(prepared, Prepared::Existing("xfs".into()));
+
+        fs::remove_file(&image).unwrap();
+    }
+
+    #[tokio::test]
+    async fn check_existing_filesystem() {
+        let image = std::env::temp_dir()
+            .join(format!("csi-check-{}.img", uuid::Uuid::new_v4()));
+        let file = fs::File::create(&image).unwrap();
+        file.set_len(64 * 1024 * 1024).unwrap();
+        drop(file);
+
+        let output = Command::new("mkfs.ext4")
+            .arg("-q")
+            .arg(&image)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+
+        // a clean filesystem passes the check
+        let prepared = prepare_device(
+            image.to_str().unwrap(),
+            "ext4",
+            &HashMap::new(),
+            Duration::from_secs(30),
+            true,
+        )
+        .await
+        .unwrap();
+        assert_eq!(prepared, Prepared::Existing("ext4".into()));
+
+        // clobber the root directory inode, which e2fsck -p refuses to fix,
+        // of a filesystem which was not cleanly unmounted
+        for request in &["clri <2>", "ssv state 0"] {
+            let output = Command::new("debugfs")
+                .arg("-w")
+                .arg("-R")
+                .arg(request)
+                .arg(&image)
+                .output()
+                .unwrap();
+            assert!(output.status.success());
+        }
+
+        let error = prepare_device(
+            image.to_str().unwrap(),
+            "ext4",
+            &HashMap::new(),
+            Duration::from_secs(30),
+            true,
+        )
+        .await
+        .expect_err("a corrupted filesystem must not be mounted");
+        assert!(matches!(error, PrepareError::Corrupted(_)));
+
+        fs::remove_file(&image).unwrap();
+    }
+
+    #[test]
+    fn mkfs_option_args() {
+        let options = |entries: &[(&str, &str)]| {
+            let context: HashMap<String, String> = entries
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect();
+            mkfs_options(&context)
+        };
+
+        assert_eq!(
+            mkfs_args(
+                "ext4",
+                &options(&[
+                    ("mkfs.inodeRatio", "65536"),
+                    ("mkfs.reservedBlocksPercentage", "0.5"),
+                    ("attachTimeout", "10"),
+                ])
+            )
+            .unwrap(),
+            vec!["-i", "65536", "-m", "0.5"]
+        );
+        assert_eq!(
+            mkfs_args(
+                "xfs",
+                &options(&[
+                    ("mkfs.noDiscard", "true"),
+                    ("mkfs.stripeUnit", "64k"),
+                    ("mkfs.stripeWidth", "4"),
+                ])
+            )
+            .unwrap(),
+            vec!["-K", "-d", "su=64k,sw=4"]
+        );
+
+        // options must match the filesystem type and be well formed
+        assert!(
+            mkfs_args("xfs", &options(&[("mkfs.inodeRatio", "4096")])).is_err()
+        );
+        assert!(mkfs_args(
+            "ext4",
+            &options(&[("mkfs.reservedBlocksPercentage", "80")])
+        )
+        .is_err());
+        assert!(
+            mkfs_args("xfs", &options(&[("mkfs.stripeUnit", "64k")])).is_err()
+        );
+        assert!(
+            mkfs_args("xfs", &options(&[("mkfs.blockSize", "4kk")])).is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn mkfs_timeout() {
+        let dir = std::env::temp_dir()
+            .join(format!("csi-mkfs-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let pidfile = dir.join("pid");
+
+        // a slow mkfs which records its pid
+        let binary = dir.join("mkfs.slow");
+        fs::write(
+            &binary,
+            format!(
+                "#!/bin/sh\necho $$ > {}\nexec sleep 60\n",
+                pidfile.display()
+            ),
+        )
+        .unwrap();
+        let mut perms = fs::metadata(&binary).unwrap().permissions();
+        perms.set_mode(0o755);
+        fs::set_permissions(&binary, perms).unwrap();
+
+        let error = run_mkfs(
+            binary.to_str().unwrap(),
+            &[],
+            "/dev/null",
+            Duration::from_millis(500),
+        )
+        .await
+        .expect_err("mkfs must time out");
+        assert!(error.contains("timed out"));
+
+        // the process must have been killed
+        let pid = fs::read_to_string(&pidfile).unwrap();
+        let proc = format!("/proc/{}", pid.trim());
+        let mut killed = false;
+        for _ in 0 .. 50 {
+            let state = fs::read_to_string(Path::new(&proc).join("stat"))
+                .unwrap_or_default();
+            // a killed process which is not reaped yet is a zombie
+            if state.is_empty() || state.contains(") Z ") {
+                killed = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert!(killed, "mkfs process {} still running", pid.trim());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,222 @@
+//! Health probe endpoint of the node plugin, meant for the liveness and
+//! readiness probes of the kubelet.
+//!
+//! `/healthz` succeeds for as long as the plugin is serving requests.
+//! `/readyz` additionally checks that the mount table can be read and holds
+//! no stale mounts of volumes of this plugin, and that the node is able to
+//! attach volumes using the configured transport. The number of volumes
+//! currently attached to the node is reported in the body of the response.
+//! Both return 200 on success and 503 otherwise.
+
+use std::{collections::HashSet, fs, net::SocketAddr, path::Path};
+
+use devinfo::mountinfo::{MountInfo, MountIter};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::{
+    dev::{Device, Transport},
+    identity::PLUGIN_NAME,
+    mount::is_stale_mount,
+};
+
+const NVME_FABRICS_PATH: &str = "/dev/nvme-fabrics";
+
+/// The kubelet records the CSI driver of a staged or published volume in
+/// this file, next to the directory the volume is mounted on.
+const VOL_DATA_FILE: &str = "vol_data.json";
+
+pub struct HealthServer {}
+
+impl HealthServer {
+    pub async fn run(
+        endpoint: SocketAddr,
+        transport: Transport,
+    ) -> Result<(), ()> {
+        info!("Health probe endpoint configured at address {:?}", endpoint);
+        let listener = TcpListener::bind(endpoint).await.map_err(|error| {
+            error!("Failed to bind health probe endpoint: {}", error);
+        })?;
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(serve(stream, transport));
+                }
+                Err(error) => {
+                    warn!("Failed to accept health probe: {}", error);
+                }
+            }
+        }
+    }
+}
+
+/// Answer a single probe and close the connection.
+async fn serve(mut stream: TcpStream, transport: Transport) {
+    let mut buf = [0u8; 1024];
+    let len = match stream.read(&mut buf).await {
+        Ok(len) => len,
+        Err(error) => {
+            debug!("Failed to read health probe: {}", error);
+            return;
+        }
+    };
+
+    let (status, body) =
+        match request_path(&String::from_utf8_lossy(&buf[.. len])) {
+            Some("/healthz") => ("200 OK", "ok\n".to_string()),
+            Some("/readyz") => {
+                // reading the mount table and sysfs may block
+                tokio::task::spawn_blocking(move || readiness(transport))
+                    .await
+                    .unwrap_or_else(|error| {
+                        error!("Readiness check failed: {}", error);
+                        (
+                            "500 Internal Server Error",
+                            "readiness check failed\n".to_string(),
+                        )
+                    })
+            }
+            Some(_) => ("404 Not Found", "not found\n".to_string()),
+            None => ("400 Bad Request", "bad request\n".to_string()),
+        };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    if let Err(error) = stream.write_all(response.as_bytes()).await {
+        debug!("Failed to answer health probe: {}", error);
+    }
+}
+
+/// Return the path of a GET request, ignoring any query string.
+fn request_path(request: &str) -> Option<&str> {
+    let mut parts = request.lines().next()?.split_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some("GET"), Some(target)) => target.split('?').next(),
+        _ => None,
+    }
+}
+
+/// Check whether the node is ready to stage volumes, and report the number
+/// of volumes currently attached.
+fn readiness(transport: Transport) -> (&'static str, String) {
+    let mut problems = Vec::new();
+
+    let mounts: Vec<MountInfo> = match MountIter::new() {
+        Ok(iter) => iter.flatten().map(MountInfo::from).collect(),
+        Err(error) => {
+            problems.push(format!("cannot read mount table: {}", error));
+            Vec::new()
+        }
+    };
+    let stale = mounts
+        .iter()
+        .filter(|mount| is_stale_mount(mount) && mounted_by_plugin(mount))
+        .count();
+    if stale > 0 {
+        problems.push(format!("{} stale mount(s)", stale));
+    }
+
+    let attached = match transport {
+        Transport::Nvmf => {
+            if !Path::new(NVME_FABRICS_PATH).exists() {
+                problems.push(format!(
+                    "{} not found, is the nvme_tcp module loaded?",
+                    NVME_FABRICS_PATH
+                ));
+            }
+            Device::nvmf_connections().unwrap_or_else(|error| {
+                problems
+                    .push(format!("cannot list NVMe subsystems: {}", error));
+                0
+            })
+        }
+        Transport::Nbd => mounts
+            .iter()
+            .map(|mount| mount.source.to_string_lossy().to_string())
+            .filter(|source| source.starts_with("/dev/nbd"))
+            .collect::<HashSet<_>>()
+            .len(),
+    };
+
+    let mut body = format!("attached volumes: {}\n", attached);
+    for problem in &problems {
+        body.push_str(problem);
+        body.push('\n');
+    }
+
+    if problems.is_empty() {
+        ("200 OK", body)
+    } else {
+        warn!("Node plugin not ready: {}", problems.join(", "));
+        ("503 Service Unavailable", body)
+    }
+}
+
+/// Return true if the mount belongs to a volume of this plugin, as recorded
+/// by the kubelet, so that the stale mounts of other drivers are ignored.
+fn mounted_by_plugin(mount: &MountInfo) -> bool {
+    mount
+        .dest
+        .parent()
+        .and_then(|dir| fs::read_to_string(dir.join(VOL_DATA_FILE)).ok())
+        .and_then(|data| serde_json::from_str::<serde_json::Value>(&data).ok())
+        .map_or(false, |data| data["driverName"] == PLUGIN_NAME)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn probe_request_path() {
+        assert_eq!(
+            request_path("GET /readyz HTTP/1.1\r\nHost: localhost\r\n\r\n"),
+            Some("/readyz")
+        );
+        assert_eq!(
+            request_path("GET /healthz?verbose HTTP/1.1\r\n\r\n"),
+            Some("/healthz")
+        );
+        assert_eq!(request_path("POST /healthz HTTP/1.1\r\n\r\n"), None);
+        assert_eq!(request_path(""), None);
+    }
+
+    #[test]
+    fn mounts_of_other_drivers() {
+        let dir = std::env::temp_dir()
+            .join(format!("csi-health-{}", std::process::id()));
+        let mount = |name: &str| MountInfo {
+            source: PathBuf::from("/dev/nvme0n1"),
+            dest: dir.join(name).join("globalmount"),
+            fstype: "ext4".to_string(),
+            options: Vec::new(),
+        };
+
+        for (name, driver) in
+            &[("ours", PLUGIN_NAME), ("theirs", "other.csi.example.com")]
+        {
+            fs::create_dir_all(dir.join(name)).unwrap();
+            fs::write(
+                dir.join(name).join(VOL_DATA_FILE),
+                format!(
+                    r#"{{"driverName":"{}","volumeHandle":"{}"}}"#,
+                    driver, name
+                ),
+            )
+            .unwrap();
+        }
+
+        assert!(mounted_by_plugin(&mount("ours")));
+        assert!(!mounted_by_plugin(&mount("theirs")));
+        assert!(!mounted_by_plugin(&mount("missing")));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
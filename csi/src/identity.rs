@@ -4,7 +4,7 @@ use super::csi::*;
 use std::{boxed::Box, collections::HashMap};
 use tonic::{Request, Response, Status};
 
-const PLUGIN_NAME: &str = "io.openebs.csi-mayastor";
+pub(crate) const PLUGIN_NAME: &str = "io.openebs.csi-mayastor";
 // TODO: can we generate version with commit SHA dynamically?
 const PLUGIN_VERSION: &str = "0.2";
 
@@ -1,8 +1,9 @@
 //! Utility functions for mounting and unmounting filesystems.
 
-use std::{collections::HashSet, io::Error};
+use std::{collections::HashSet, io::Error, time::Duration};
 
 use devinfo::mountinfo::{MountInfo, MountIter};
+use nix::errno::Errno;
 use sys_mount::{unmount, FilesystemType, Mount, MountFlags, UnmountFlags};
 
 // Simple trait for checking if the readonly (ro) option
@@ -54,22 +55,205 @@ pub fn find_mount(
     found.map(MountInfo::from)
 }
 
-/// Check if options in "first" are also present in "second",
-/// but exclude values "ro" and "rw" from the comparison.
-pub(super) fn subset(first: &[String], second: &[String]) -> bool {
-    let set: HashSet<&String> = second.iter().collect();
-    for entry in first {
-        if entry == "ro" {
-            continue;
+/// Return all mounts of the given source.
+pub fn find_mounts_of(source: &str) -> Vec<MountInfo> {
+    MountIter::new()
+        .unwrap()
+        .flatten()
+        .filter(|mount| mount.source.to_string_lossy() == source)
+        .map(MountInfo::from)
+        .collect()
+}
+
+/// Return true if the source device of a mount no longer exists, as is the
+/// case for a bind mount left behind by a plugin that was restarted after
+/// the volume was detached.
+pub fn is_stale_mount(mount: &MountInfo) -> bool {
+    let source = mount.source.to_string_lossy();
+    source.ends_with("(deleted)")
+        || (source.starts_with("/dev/") && !mount.source.exists())
+}
+
+/// Mount options accepted from the CO, as passed to mount(2).
+const SUPPORTED_OPTIONS: [&str; 20] = [
+    "ro",
+    "rw",
+    "atime",
+    "noatime",
+    "diratime",
+    "nodiratime",
+    "relatime",
+    "norelatime",
+    "strictatime",
+    "lazytime",
+    "nolazytime",
+    "sync",
+    "async",
+    "dirsync",
+    "exec",
+    "noexec",
+    "nosuid",
+    "nodev",
+    "discard",
+    "nodiscard",
+];
+
+/// Filesystem specific mount options taking a value (xfs and ext4).
+const SUPPORTED_VALUE_OPTIONS: [&str; 6] = [
+    "allocsize",
+    "logbsize",
+    "logbufs",
+    "commit",
+    "data",
+    "errors",
+];
+
+/// CSI-style aliases and their mount(2) equivalent, if any.
+const OPTION_ALIASES: [(&str, Option<&str>); 3] = [
+    ("defaults", None),
+    ("readonly", Some("ro")),
+    ("read-only", Some("ro")),
+];
+
+/// Validate and normalize the mount options requested by the CO.
+/// Aliases are mapped to their mount(2) equivalent and options conflicting
+/// with the requested access mode (if known) are dropped.
+/// Returns the offending option if it is not supported.
+pub(super) fn sanitize_options(
+    options: &[String],
+    readonly: Option<bool>,
+) -> Result<Vec<String>, String> {
+    let mut list: Vec<String> = Vec::new();
+
+    for entry in options {
+        let entry = match OPTION_ALIASES.iter().find(|(a, _)| a == entry) {
+            Some((_, Some(option))) => *option,
+            Some((_, None)) => continue,
+            None => entry.as_str(),
+        };
+
+        let supported = SUPPORTED_OPTIONS.contains(&entry)
+            || entry.split_once('=').map_or(false, |(key, value)| {
+                !value.is_empty() && SUPPORTED_VALUE_OPTIONS.contains(&key)
+            });
+
+        if !supported {
+            return Err(entry.to_string());
         }
-        if entry == "rw" {
+
+        match (entry, readonly) {
+            ("rw", Some(true)) | ("ro", Some(false)) => {
+                debug!(
+                    "Dropping mount option {} conflicting with access mode",
+                    entry
+                );
+                continue;
+            }
+            _ => {}
+        }
+
+        if !list.iter().any(|e| e == entry) {
+            list.push(entry.to_string());
+        }
+    }
+
+    Ok(list)
+}
+
+/// Policy applied when republishing a volume onto a target that is already
+/// mounted with different options.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PublishOptionsPolicy {
+    /// Reject any option missing from the existing mount.
+    Strict,
+    /// Allow additive options that do not change the behaviour of the
+    /// filesystem and only reject genuine conflicts.
+    Relaxed,
+}
+
+/// Options which may differ between publishes of the same volume without
+/// changing the behaviour of the filesystem in a way that matters to users.
+const ADDITIVE_OPTIONS: [&str; 4] =
+    ["noatime", "nodiratime", "relatime", "lazytime"];
+
+/// Pairs of options that negate each other.
+const OPPOSITE_OPTIONS: [(&str, &str); 6] = [
+    ("atime", "noatime"),
+    ("diratime", "nodiratime"),
+    ("exec", "noexec"),
+    ("suid", "nosuid"),
+    ("dev", "nodev"),
+    ("sync", "async"),
+];
+
+/// Options selecting how access times are updated, only one of which applies.
+const ATIME_OPTIONS: [&str; 4] =
+    ["atime", "noatime", "relatime", "strictatime"];
+
+/// Merge default mount options into the options requested by the CO, which
+/// take precedence: a default is dropped when the CO requested the same
+/// option, possibly with another value, or one negating it.
+pub(super) fn merge_options(
+    requested: &[String],
+    defaults: &[String],
+) -> Vec<String> {
+    fn key(option: &str) -> &str {
+        option.split_once('=').map_or(option, |(key, _)| key)
+    }
+
+    fn overrides(option: &str, default: &str) -> bool {
+        key(option) == key(default)
+            || opposite(option) == Some(default)
+            || (ATIME_OPTIONS.contains(&option)
+                && ATIME_OPTIONS.contains(&default))
+            || (matches!(option, "ro" | "rw" | "readonly" | "read-only")
+                && matches!(default, "ro" | "rw"))
+    }
+
+    let mut list = requested.to_vec();
+    list.extend(
+        defaults
+            .iter()
+            .filter(|d| !requested.iter().any(|o| overrides(o, d)))
+            .cloned(),
+    );
+    list
+}
+
+/// Return the option negating the given one, if any.
+fn opposite(option: &str) -> Option<&'static str> {
+    OPPOSITE_OPTIONS.iter().find_map(|(a, b)| {
+        if *a == option {
+            Some(*b)
+        } else if *b == option {
+            Some(*a)
+        } else {
+            None
+        }
+    })
+}
+
+/// Check whether the options in "requested" are compatible with the
+/// "existing" options, according to the given policy.
+/// Values "ro" and "rw" are excluded from the comparison.
+/// Returns the first conflicting option, if any.
+pub(super) fn conflicting_option(
+    requested: &[String],
+    existing: &[String],
+    policy: PublishOptionsPolicy,
+) -> Option<String> {
+    let set: HashSet<&str> = existing.iter().map(String::as_str).collect();
+    for entry in requested {
+        if entry == "ro" || entry == "rw" || set.contains(entry.as_str()) {
             continue;
         }
-        if set.get(entry).is_none() {
-            return false;
+        let additive = ADDITIVE_OPTIONS.contains(&entry.as_str())
+            && !opposite(entry).map_or(false, |o| set.contains(o));
+        if policy == PublishOptionsPolicy::Strict || !additive {
+            return Some(entry.clone());
         }
     }
-    true
+    None
 }
 
 /// Return supported filesystems.
@@ -158,6 +342,41 @@ pub fn filesystem_mount(
     Ok(mount)
 }
 
+/// Remount a mounted filesystem to modify its mount options, such as
+/// switching it from readonly to read-write. Unlike a bind remount this
+/// applies to the filesystem itself and so to all of its bind mounts which
+/// are not readonly on their own.
+pub fn filesystem_remount(
+    target: &str,
+    options: &[String],
+) -> Result<Mount, Error> {
+    let mut flags = MountFlags::empty();
+
+    let (readonly, value) = parse(options);
+
+    if readonly {
+        flags.insert(MountFlags::RDONLY);
+    }
+
+    flags.insert(MountFlags::REMOUNT);
+
+    let mount = Mount::new(
+        "none",
+        target,
+        FilesystemType::Manual("none"),
+        flags,
+        option(&value),
+    )?;
+
+    debug!(
+        "Filesystem on target {} remounted (options: {})",
+        target,
+        show(options)
+    );
+
+    Ok(mount)
+}
+
 /// Unmount a device from a directory (mountpoint)
 /// Should not be used for removing bind mounts.
 pub fn filesystem_unmount(target: &str) -> Result<(), Error> {
@@ -200,6 +419,38 @@ pub fn bind_mount(
     Ok(mount)
 }
 
+/// delay between attempts of an operation failing with a transient error
+const TRANSIENT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Return true if the error may go away when retrying, as happens when the
+/// source of a bind mount is briefly unavailable while being remounted.
+fn is_transient(error: &Error) -> bool {
+    error.kind() == std::io::ErrorKind::NotFound
+        || error.raw_os_error() == Some(Errno::ESTALE as i32)
+}
+
+/// Retry an operation up to `retries` times, as long as it fails with a
+/// transient error. Permanent errors are returned right away.
+pub async fn retry_transient<T, F>(retries: u32, mut op: F) -> Result<T, Error>
+where
+    F: FnMut() -> Result<T, Error>,
+{
+    let mut attempt = 0;
+    loop {
+        match op() {
+            Err(error) if attempt < retries && is_transient(&error) => {
+                attempt += 1;
+                warn!(
+                    "Transient error, retrying ({} of {}): {}",
+                    attempt, retries, error
+                );
+                tokio::time::sleep(TRANSIENT_RETRY_DELAY).await;
+            }
+            result => return result,
+        }
+    }
+}
+
 /// Bind remount a path to modify mount options.
 /// Assumes that target has already been bind mounted.
 pub fn bind_remount(target: &str, options: &[String]) -> Result<Mount, Error> {
@@ -287,3 +538,157 @@ pub fn blockdevice_unmount(target: &str) -> Result<(), Error> {
     info!("block device at {} has been unmounted", target);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn merge_default_options() {
+        let defaults = options(&["noatime", "nodiratime", "commit=30"]);
+
+        assert_eq!(
+            merge_options(&options(&["nodev"]), &defaults),
+            options(&["nodev", "noatime", "nodiratime", "commit=30"])
+        );
+        // options requested by the CO take precedence
+        assert_eq!(
+            merge_options(
+                &options(&["relatime", "diratime", "commit=5"]),
+                &defaults
+            ),
+            options(&["relatime", "diratime", "commit=5"])
+        );
+        assert_eq!(
+            merge_options(&options(&["readonly"]), &options(&["rw"])),
+            options(&["readonly"])
+        );
+        assert_eq!(merge_options(&[], &[]), Vec::<String>::new());
+    }
+
+    #[test]
+    fn sanitize_mount_options() {
+        assert_eq!(
+            sanitize_options(
+                &options(&["defaults", "readonly", "noatime", "data=ordered"]),
+                None
+            ),
+            Ok(options(&["ro", "noatime", "data=ordered"]))
+        );
+        assert_eq!(
+            sanitize_options(&options(&["rw", "nodev"]), Some(true)),
+            Ok(options(&["nodev"]))
+        );
+        assert_eq!(
+            sanitize_options(&options(&["noatime", "suid"]), None),
+            Err(String::from("suid"))
+        );
+        assert_eq!(
+            sanitize_options(&options(&["errors="]), None),
+            Err(String::from("errors="))
+        );
+    }
+
+    #[tokio::test]
+    async fn transient_retry() {
+        let mut attempts = 0;
+        let result = retry_transient(3, || {
+            attempts += 1;
+            if attempts == 1 {
+                Err(Error::from_raw_os_error(2))
+            } else {
+                Ok(attempts)
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 2);
+
+        // a permanent error is not retried
+        let mut attempts = 0;
+        let result: Result<(), Error> = retry_transient(3, || {
+            attempts += 1;
+            Err(Error::from_raw_os_error(1))
+        })
+        .await;
+        assert_eq!(result.unwrap_err().raw_os_error(), Some(1));
+        assert_eq!(attempts, 1);
+
+        // the number of retries is bounded
+        let mut attempts = 0;
+        let result: Result<(), Error> = retry_transient(2, || {
+            attempts += 1;
+            Err(Error::from_raw_os_error(Errno::ESTALE as i32))
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn stale_mount() {
+        let mount = |source: &str| MountInfo {
+            source: source.into(),
+            dest: "/var/lib/kubelet/pods/volume".into(),
+            fstype: "ext4".into(),
+            options: options(&["rw"]),
+        };
+
+        assert!(is_stale_mount(&mount("/dev/csi-test-missing-device")));
+        assert!(is_stale_mount(&mount("/dev/nvme0n1\\040(deleted)")));
+        assert!(!is_stale_mount(&mount("/dev/null")));
+        assert!(!is_stale_mount(&mount("tmpfs")));
+    }
+
+    #[test]
+    fn publish_options_additive() {
+        let existing = options(&["rw", "relatime"]);
+        let requested = options(&["rw", "noatime"]);
+
+        assert_eq!(
+            conflicting_option(
+                &requested,
+                &existing,
+                PublishOptionsPolicy::Relaxed
+            ),
+            None
+        );
+        assert_eq!(
+            conflicting_option(
+                &requested,
+                &existing,
+                PublishOptionsPolicy::Strict
+            ),
+            Some(String::from("noatime"))
+        );
+    }
+
+    #[test]
+    fn publish_options_conflicting() {
+        let existing = options(&["rw", "atime", "exec"]);
+
+        for policy in
+            &[PublishOptionsPolicy::Strict, PublishOptionsPolicy::Relaxed]
+        {
+            assert_eq!(
+                conflicting_option(&options(&["noatime"]), &existing, *policy),
+                Some(String::from("noatime"))
+            );
+            assert_eq!(
+                conflicting_option(&options(&["noexec"]), &existing, *policy),
+                Some(String::from("noexec"))
+            );
+            assert_eq!(
+                conflicting_option(
+                    &options(&["ro", "exec"]),
+                    &existing,
+                    *policy
+                ),
+                None
+            );
+        }
+    }
+}
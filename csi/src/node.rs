@@ -1,6 +1,7 @@
 use std::{
     boxed::Box,
-    collections::HashMap,
+    collections::{HashMap, HashSet},
+    fs,
     path::Path,
     time::Duration,
     vec::Vec,
@@ -13,31 +14,82 @@ macro_rules! failure {
     (Code::$code:ident, $fmt:literal $(,$args:expr)+) => {{ let message = format!($fmt $(,$args)+); error!("{}", message); Status::new(Code::$code, message) }};
 }
 
+use devinfo::mountinfo::MountInfo;
+use rpc::validate::parse_uuid;
 use uuid::Uuid;
 
 use crate::{
-    block_vol::{publish_block_volume, unpublish_block_volume},
+    block_vol::{
+        block_volume_stats,
+        publish_block_volume,
+        unpublish_block_volume,
+    },
     csi::{
         volume_capability::{access_mode::Mode, AccessType},
         *,
     },
-    dev::Device,
+    dev::{
+        Attach,
+        Device,
+        DeviceError,
+        DeviceErrorKind,
+        DeviceName,
+        NvmfHost,
+        Transport,
+    },
     filesystem_vol::{
+        expand_fs_volume,
         publish_fs_volume,
+        reader_only_access,
         stage_fs_volume,
         unpublish_fs_volume,
         unstage_fs_volume,
     },
+    mount::{
+        self,
+        merge_options,
+        sanitize_options,
+        PublishOptionsPolicy,
+        ReadOnly,
+    },
 };
 
 #[derive(Clone, Debug)]
 pub struct Node {
     pub node_name: String,
     pub filesystems: Vec<String>,
+    /// maximum number of active NVMe-oF connections, if limited
+    pub max_nvmf_connections: Option<usize>,
+    /// transport used to attach volumes on this node
+    pub transport: Transport,
+    /// overrides the transport derived maximum number of volumes
+    pub max_volumes_per_node: Option<i64>,
+    /// how to treat option differences when republishing a volume
+    pub publish_options_policy: PublishOptionsPolicy,
+    /// remove a regular file found at the target path when unpublishing,
+    /// instead of failing the request
+    pub remove_stale_target_files: bool,
+    /// mount options of filesystem volumes unless overridden by the CO
+    pub default_mount_flags: Vec<String>,
+    /// time allowed for creating or checking a filesystem when staging a
+    /// volume
+    pub mkfs_timeout: Duration,
+    /// check and repair an existing filesystem before mounting it
+    pub check_on_mount: bool,
+    /// number of times a failed device attach is retried
+    pub attach_retries: u32,
+    /// number of times a bind mount failing with a transient error is retried
+    pub bind_mount_retries: u32,
+    /// NQN this node presents to NVMe-oF targets
+    pub host_nqn: String,
+    /// host ID (a UUID) this node presents to NVMe-oF targets
+    pub host_id: String,
 }
 
 const ATTACH_TIMEOUT_INTERVAL: Duration = Duration::from_millis(100);
 const ATTACH_RETRIES: u32 = 100;
+/// time allowed for attaching a volume unless given in the publish context
+const ATTACH_TIMEOUT_DEFAULT: Duration = Duration::from_secs(60);
 
 // Determine if given access mode in conjunction with ro mount flag makes
 // sense or not. If access mode is not supported or the combination does
@@ -88,6 +140,177 @@ fn check_access_mode(
     }
 }
 
+/// Check that attaching another NVMe-oF volume would not exceed the
+/// configured maximum number of active connections.
+fn check_nvmf_connection_limit(
+    volume_id: &str,
+    active: usize,
+    max: usize,
+) -> Result<(), Status> {
+    if active >= max {
+        return Err(failure!(
+            Code::ResourceExhausted,
+            "Failed to stage volume {}: maximum number of NVMe-oF connections reached ({}/{})",
+            volume_id,
+            active,
+            max
+        ));
+    }
+    Ok(())
+}
+
+/// Handle a regular file found at the target path of a volume being
+/// unpublished, where a directory or a block special file was expected.
+/// Such a file is typically left over from a failed publish.
+fn unpublish_target_file(
+    volume_id: &str,
+    target_path: &Path,
+    remove: bool,
+) -> Result<(), Status> {
+    if !remove {
+        return Err(failure!(
+            Code::Internal,
+            "Failed to unpublish volume {}: {} is a regular file, expected a directory or a block device node",
+            volume_id,
+            target_path.display()
+        ));
+    }
+
+    fs::remove_file(target_path).map_err(|error| {
+        failure!(
+            Code::Internal,
+            "Failed to unpublish volume {}: failed to remove stale file {}: {}",
+            volume_id,
+            target_path.display(),
+            error
+        )
+    })?;
+
+    info!(
+        "Removed stale file {} while unpublishing volume {}",
+        target_path.display(),
+        volume_id
+    );
+    Ok(())
+}
+
+/// The time allowed for attaching a volume, given in seconds by the
+/// "attachTimeout" entry of the publish context.
+fn attach_timeout(
+    volume_id: &str,
+    context: &HashMap<String, String>,
+) -> Result<Duration, Status> {
+    match context.get("attachTimeout") {
+        Some(value) => match value.parse::<u64>() {
+            Ok(secs) if secs > 0 => Ok(Duration::from_secs(secs)),
+            _ => Err(failure!(
+                Code::InvalidArgument,
+                "Failed to stage volume {}: invalid attachTimeout value: \"{}\"",
+                volume_id,
+                value
+            )),
+        },
+        None => Ok(ATTACH_TIMEOUT_DEFAULT),
+    }
+}
+
+/// Find the first of the given devices, all of which lead to the same volume,
+/// that is attached already.
+async fn find_attached(
+    devices: &[Box<dyn Attach>],
+    volume_id: &str,
+) -> Result<Option<(usize, DeviceName)>, Status> {
+    for (index, device) in devices.iter().enumerate() {
+        if let Some(devpath) = device.find().await.map_err(|error| {
+            failure!(
+                Code::Internal,
+                "Failed to stage volume {}: error locating device: {}",
+                volume_id,
+                error
+            )
+        })? {
+            return Ok(Some((index, devpath)));
+        }
+    }
+    Ok(None)
+}
+
+/// Attach a volume through the first of the given devices that becomes
+/// available, all of which lead to the same volume, see `Device::find_any`.
+/// The connections made by the losing devices are disconnected. The whole
+/// attach is given up once the timeout expires. A connect which stalls
+/// runs on until it returns, after which the device undoes it, see
+/// `connect_blocking`.
+async fn attach_any(
+    mut devices: Vec<Box<dyn Attach>>,
+    uuid: &Uuid,
+    volume_id: &str,
+    retries: u32,
+    timeout: Duration,
+) -> Result<(Box<dyn Attach>, DeviceName), Status> {
+    debug!("Attaching volume {}", volume_id);
+    let attempts = Device::find_any(
+        &devices,
+        retries,
+        ATTACH_TIMEOUT_INTERVAL,
+        ATTACH_RETRIES,
+    );
+
+    let result = match tokio::time::timeout(timeout, attempts).await {
+        Ok(Ok(found)) => Ok(found),
+        Ok(Err(error)) => {
+            // COs retry on unavailable, so only report an error which
+            // retrying cannot fix otherwise
+            if error.kind() == DeviceErrorKind::Parse {
+                Err(failure!(
+                    Code::Internal,
+                    "Failed to stage volume {}: {}",
+                    volume_id,
+                    error
+                ))
+            } else {
+                Err(failure!(
+                    Code::Unavailable,
+                    "Failed to stage volume {}: {}",
+                    volume_id,
+                    error
+                ))
+            }
+        }
+        Err(_) => {
+            if let Err(error) = detach(
+                uuid,
+                format!("Failed to clean up volume {}:", volume_id),
+            )
+            .await
+            {
+                warn!("{}", error.message());
+            }
+            return Err(failure!(
+                Code::DeadlineExceeded,
+                "Failed to stage volume {}: attach timed out after {:?}",
+                volume_id,
+                timeout
+            ));
+        }
+    };
+
+    let winner = result.as_ref().ok().map(|(index, _)| *index);
+    for (index, device) in devices.iter().enumerate() {
+        if Some(index) != winner {
+            if let Err(error) = device.disconnect().await {
+                warn!(
+                    "Failed to disconnect an unused path of volume {}: {}",
+                    volume_id, error
+                );
+            }
+        }
+    }
+
+    let (index, devpath) = result?;
+    Ok((devices.swap_remove(index), devpath))
+}
+
 /// Retrieve the AccessType from VolumeCapability
 fn get_access_type(
     volume_capability: &Option<VolumeCapability>,
@@ -101,17 +324,81 @@ fn get_access_type(
     }
 }
 
+/// Collect the URIs of a volume from the publish context. Besides the
+/// mandatory "uri" entry, alternative addresses of the same volume may be
+/// given as "uri1", "uri2" and so on.
+fn publish_uris(context: &HashMap<String, String>) -> Vec<String> {
+    context
+        .get("uri")
+        .into_iter()
+        .chain(
+            (1 ..)
+                .map(|index| context.get(&format!("uri{}", index)))
+                .take_while(Option::is_some)
+                .flatten(),
+        )
+        .cloned()
+        .collect()
+}
+
+/// Check that a volume staged again onto a path with an existing mount
+/// requests the same capability it was staged with, including whether its
+/// access mode only allows reading. An identical request is left to the
+/// regular, idempotent, staging path.
+fn check_restage(
+    volume_id: &str,
+    access_type: &AccessType,
+    reader_only: bool,
+    staged: &MountInfo,
+) -> Result<(), Status> {
+    let mnt = match access_type {
+        AccessType::Mount(mnt) => mnt,
+        AccessType::Block(_) => {
+            return Err(failure!(
+                Code::AlreadyExists,
+                "Failed to stage volume {}: already staged as a filesystem volume, not as a block volume",
+                volume_id
+            ));
+        }
+    };
+
+    if !mnt.fs_type.is_empty() && mnt.fs_type != staged.fstype {
+        return Err(failure!(
+            Code::AlreadyExists,
+            "Failed to stage volume {}: already staged with filesystem type {}, not {}",
+            volume_id,
+            staged.fstype,
+            mnt.fs_type
+        ));
+    }
+
+    // unsupported options are reported by the staging itself
+    if let Ok(flags) = sanitize_options(&mnt.mount_flags, None) {
+        if (flags.readonly() || reader_only) != staged.options.readonly() {
+            return Err(failure!(
+                Code::AlreadyExists,
+                "Failed to stage volume {}: already staged with incompatible flags",
+                volume_id
+            ));
+        }
+    }
+
+    Ok(())
+}
+
 /// Detach the nexus device from the system, either at volume unstage,
 /// or after failed filesystem mount at volume stage.
 async fn detach(uuid: &Uuid, errheader: String) -> Result<(), Status> {
-    if let Some(device) = Device::lookup(uuid).await.map_err(|error| {
-        failure!(
-            Code::Internal,
-            "{} error locating device: {}",
-            &errheader,
-            error
-        )
-    })? {
+    if let Some(device) =
+        Device::lookup_for_detach(uuid).await.map_err(|error| {
+            failure!(
+                Code::Internal,
+                "{} error locating device: {}",
+                &errheader,
+                error
+            )
+        })?
+    {
         let device_path = device.devname();
         debug!("Detaching device {}", device_path);
         if let Err(error) = device.detach().await {
@@ -127,7 +414,42 @@ async fn detach(uuid: &Uuid, errheader: String) -> Result<(), Status> {
     Ok(())
 }
 
-impl Node {}
+impl Node {
+    /// Identity this node presents to NVMe-oF targets.
+    fn nvmf_host(&self) -> NvmfHost {
+        NvmfHost {
+            nqn: self.host_nqn.clone(),
+            id: self.host_id.clone(),
+        }
+    }
+
+    /// Maximum number of volumes that can be attached to this node,
+    /// 0 meaning there is no limit.
+    fn max_volumes_per_node(&self) -> i64 {
+        if let Some(max) = self.max_volumes_per_node {
+            return max;
+        }
+        match self.transport {
+            // each volume takes up one NVMe-oF controller
+            Transport::Nvmf => self.max_nvmf_connections.unwrap_or(0) as i64,
+            Transport::Nbd => Device::nbd_devices() as i64,
+        }
+    }
+
+    /// Retrieve the AccessType from VolumeCapability, merging the default
+    /// mount flags of the node into those of a filesystem volume.
+    fn access_type(
+        &self,
+        volume_capability: &Option<VolumeCapability>,
+    ) -> Result<AccessType, String> {
+        let mut access_type = get_access_type(volume_capability)?.clone();
+        if let AccessType::Mount(mnt) = &mut access_type {
+            mnt.mount_flags =
+                merge_options(&mnt.mount_flags, &self.default_mount_flags);
+        }
+        Ok(access_type)
+    }
+}
 #[tonic::async_trait]
 impl node_server::Node for Node {
     async fn node_get_info(
@@ -145,7 +467,7 @@ impl node_server::Node for Node {
 
         Ok(Response::new(NodeGetInfoResponse {
             node_id,
-            max_volumes_per_node: 0,
+            max_volumes_per_node: self.max_volumes_per_node(),
             accessible_topology: Some(Topology {
                 segments,
             }),
@@ -156,11 +478,13 @@ impl node_server::Node for Node {
         &self,
         _request: Request<NodeGetCapabilitiesRequest>,
     ) -> Result<Response<NodeGetCapabilitiesResponse>, Status> {
-        let caps = vec![node_service_capability::rpc::Type::StageUnstageVolume];
+        let caps = vec![
+            node_service_capability::rpc::Type::StageUnstageVolume,
+            node_service_capability::rpc::Type::ExpandVolume,
+        ];
 
         debug!("NodeGetCapabilities request: {:?}", caps);
 
-        // We don't support stage/unstage and expand volume rpcs
         Ok(Response::new(NodeGetCapabilitiesResponse {
             capabilities: caps
                 .into_iter()
@@ -249,7 +573,7 @@ impl node_server::Node for Node {
             ));
         }
 
-        match get_access_type(&msg.volume_capability).map_err(|error| {
+        match self.access_type(&msg.volume_capability).map_err(|error| {
             failure!(
                 Code::InvalidArgument,
                 "Failed to publish volume {}: {}",
@@ -258,7 +582,14 @@ impl node_server::Node for Node {
             )
         })? {
             AccessType::Mount(mnt) => {
-                publish_fs_volume(&msg, mnt, &self.filesystems)?;
+                publish_fs_volume(
+                    &msg,
+                    &mnt,
+                    &self.filesystems,
+                    self.publish_options_policy,
+                    self.bind_mount_retries,
+                )
+                .await?;
             }
             AccessType::Block(_) => {
                 publish_block_volume(&msg).await?;
@@ -309,17 +640,13 @@ impl node_server::Node for Node {
         if target_path.exists() {
             if target_path.is_dir() {
                 unpublish_fs_volume(&msg)?;
+            } else if target_path.is_file() {
+                unpublish_target_file(
+                    &msg.volume_id,
+                    target_path,
+                    self.remove_stale_target_files,
+                )?;
             } else {
-                if target_path.is_file() {
-                    return Err(Status::new(
-                        Code::Unknown,
-                        format!(
-                            "Failed to unpublish volume {}: {} is a file.",
-                            &msg.volume_id, &msg.target_path
-                        ),
-                    ));
-                }
-
                 unpublish_block_volume(&msg)?;
             }
         }
@@ -338,27 +665,100 @@ impl node_server::Node for Node {
         let msg = request.into_inner();
         trace!("node_get_volume_stats {:?}", msg);
 
-        /*
-        Ok(Response::new(NodeGetVolumeStatsResponse {
-            usage: vec![VolumeUsage {
-                total: 0 as i64,
-                unit: volume_usage::Unit::Bytes as i32,
-                available: 0,
-                used: 0,
-            }],
-        }))
-        */
-        error!("Unimplemented {:?}", msg);
-        Err(Status::new(Code::Unimplemented, "Method not implemented"))
+        if msg.volume_id.is_empty() {
+            return Err(failure!(
+                Code::InvalidArgument,
+                "Failed to get volume stats: missing volume id"
+            ));
+        }
+
+        if msg.volume_path.is_empty() {
+            return Err(failure!(
+                Code::InvalidArgument,
+                "Failed to get stats of volume {}: missing volume path",
+                &msg.volume_id
+            ));
+        }
+
+        // filesystem volumes are published on a directory
+        if Path::new(&msg.volume_path).is_dir() {
+            error!("Unimplemented {:?}", msg);
+            return Err(Status::new(
+                Code::Unimplemented,
+                "Stats of filesystem volumes are not implemented",
+            ));
+        }
+
+        block_volume_stats(&msg).map(Response::new)
     }
 
+    /// Grow the filesystem of a staged volume after the underlying nexus
+    /// has been expanded. Only filesystem volumes can be expanded, block
+    /// volumes are handled entirely by the consumer of the block device.
     async fn node_expand_volume(
         &self,
         request: Request<NodeExpandVolumeRequest>,
     ) -> Result<Response<NodeExpandVolumeResponse>, Status> {
         let msg = request.into_inner();
-        error!("Unimplemented {:?}", msg);
-        Err(Status::new(Code::Unimplemented, "Method not implemented"))
+
+        trace!("node_expand_volume {:?}", msg);
+
+        if msg.volume_id.is_empty() {
+            return Err(failure!(
+                Code::InvalidArgument,
+                "Failed to expand volume: missing volume id"
+            ));
+        }
+
+        if msg.volume_path.is_empty() {
+            return Err(failure!(
+                Code::InvalidArgument,
+                "Failed to expand volume {}: missing volume path",
+                &msg.volume_id
+            ));
+        }
+
+        let uuid = parse_uuid(&msg.volume_id).map_err(|error| {
+            failure!(
+                Code::InvalidArgument,
+                "Failed to expand volume: {}",
+                error
+            )
+        })?;
+
+        let device = Device::lookup(&uuid)
+            .await
+            .map_err(|error| {
+                failure!(
+                    Code::Internal,
+                    "Failed to expand volume {}: error locating device: {}",
+                    &msg.volume_id,
+                    error
+                )
+            })?
+            .ok_or_else(|| {
+                failure!(
+                    Code::FailedPrecondition,
+                    "Failed to expand volume {}: volume is not staged",
+                    &msg.volume_id
+                )
+            })?;
+
+        // block volumes are published as a block special file
+        if !Path::new(&msg.volume_path).is_dir() {
+            return Err(failure!(
+                Code::InvalidArgument,
+                "Failed to expand volume {}: {} is not a filesystem volume",
+                &msg.volume_id,
+                &msg.volume_path
+            ));
+        }
+
+        expand_fs_volume(&msg, &device.devname(), &self.filesystems).await?;
+
+        Ok(Response::new(NodeExpandVolumeResponse {
+            capacity_bytes: 0,
+        }))
     }
 
     async fn node_stage_volume(
@@ -397,7 +797,7 @@ impl node_server::Node for Node {
             ));
         };
 
-        let access_type = match get_access_type(&msg.volume_capability) {
+        let access_type = match self.access_type(&msg.volume_capability) {
             Ok(accesstype) => accesstype,
             Err(error) => {
                 return Err(failure!(
@@ -408,6 +808,18 @@ impl node_server::Node for Node {
                 ));
             }
         };
+        let access_type = &access_type;
+
+        if let Some(staged) =
+            mount::find_mount(None, Some(&msg.staging_target_path))
+        {
+            check_restage(
+                &msg.volume_id,
+                access_type,
+                reader_only_access(&msg.volume_capability),
+                &staged,
+            )?;
+        }
 
         let uri = &msg.publish_context.get("uri").ok_or_else(|| {
             failure!(
@@ -417,13 +829,8 @@ impl node_server::Node for Node {
             )
         })?;
 
-        let uuid = Uuid::parse_str(&msg.volume_id).map_err(|error| {
-            failure!(
-                Code::Internal,
-                "Failed to stage volume {}: not a valid UUID: {}",
-                &msg.volume_id,
-                error
-            )
+        let uuid = parse_uuid(&msg.volume_id).map_err(|error| {
+            failure!(Code::InvalidArgument, "Failed to stage volume: {}", error)
         })?;
 
         // Note checking existence of staging_target_path, is delegated to
@@ -432,64 +839,80 @@ impl node_server::Node for Node {
         // All checks complete, now attach, if not attached already.
         debug!("Volume {} has URI {}", &msg.volume_id, uri);
 
-        let mut device = Device::parse(uri).map_err(|error| {
-            failure!(
-                Code::Internal,
-                "Failed to stage volume {}: error parsing URI {}: {}",
+        let mut uris = publish_uris(&msg.publish_context);
+        // a duplicate would disconnect the path it shares with the winner
+        let mut seen = HashSet::new();
+        uris.retain(|uri| seen.insert(uri.clone()));
+        if uris.len() > 1 {
+            debug!(
+                "Volume {} has alternative URIs {:?}",
                 &msg.volume_id,
-                uri,
-                error
-            )
-        })?;
-        device
-            .parse_parameters(&msg.publish_context)
-            .await
-            .map_err(|error| {
+                &uris[1 ..]
+            );
+        }
+
+        let mut devices = Vec::with_capacity(uris.len());
+        for uri in &uris {
+            let mut device = Device::parse(uri).map_err(|error| {
                 failure!(
-            Code::InvalidArgument,
-            "Failed to parse storage class parameters for volume {}: {}",
-            &msg.volume_id,
-            error
-        )
+                    Code::Internal,
+                    "Failed to stage volume {}: error parsing URI {}: {}",
+                    &msg.volume_id,
+                    uri,
+                    error
+                )
             })?;
+            device
+                .parse_parameters(&msg.publish_context)
+                .await
+                .map_err(|error| {
+                    failure!(
+                Code::InvalidArgument,
+                "Failed to parse storage class parameters for volume {}: {}",
+                &msg.volume_id,
+                error
+            )
+                })?;
+            device.set_nvmf_host(&self.nvmf_host());
+            devices.push(device);
+        }
 
-        let device_path = match device.find().await.map_err(|error| {
-            failure!(
-            Code::Internal,
-            "Failed to stage volume {}: error locating device for URI {}: {}",
-            &msg.volume_id,
-            uri,
-            error
-        )
-        })? {
-            Some(devpath) => devpath,
+        let device_path = match find_attached(&devices, &msg.volume_id).await? {
+            Some((_, devpath)) => devpath,
             None => {
-                debug!("Attaching volume {}", &msg.volume_id);
-                // device.attach is idempotent, so does not restart the attach
-                // process
-                if let Err(error) = device.attach().await {
-                    return Err(failure!(
-                        Code::Internal,
-                        "Failed to stage volume {}: attach failed: {}",
-                        &msg.volume_id,
-                        error
-                    ));
+                if let Some(max) = self.max_nvmf_connections {
+                    if uris.iter().any(|uri| uri.starts_with("nvmf://")) {
+                        let active =
+                            Device::nvmf_connections().map_err(|error| {
+                                failure!(
+                                    Code::Internal,
+                                    "Failed to stage volume {}: error counting NVMe-oF connections: {}",
+                                    &msg.volume_id,
+                                    error
+                                )
+                            })?;
+                        debug!(
+                            "{} of {} NVMe-oF connections active",
+                            active, max
+                        );
+                        check_nvmf_connection_limit(
+                            &msg.volume_id,
+                            active,
+                            max,
+                        )?;
+                    }
                 }
 
-                let devpath = Device::wait_for_device(
-                    &*device,
-                    ATTACH_TIMEOUT_INTERVAL,
-                    ATTACH_RETRIES,
+                // device.attach is idempotent, so does not restart the attach
+                // process
+                let (device, devpath) = attach_any(
+                    devices,
+                    &uuid,
+                    &msg.volume_id,
+                    self.attach_retries,
+                    attach_timeout(&msg.volume_id, &msg.publish_context)?,
                 )
-                .await
-                .map_err(|error| {
-                    failure!(
-                        Code::Unavailable,
-                        "Failed to stage volume {}: {}",
-                        &msg.volume_id,
-                        error
-                    )
-                })?;
+                .await?;
 
                 device.fixup().await.map_err(|error| {
                     failure!(
@@ -507,9 +930,15 @@ impl node_server::Node for Node {
         // Attach successful, now stage mount if required.
         match access_type {
             AccessType::Mount(mnt) => {
-                if let Err(fsmount_error) =
-                    stage_fs_volume(&msg, device_path, mnt, &self.filesystems)
-                        .await
+                if let Err(fsmount_error) = stage_fs_volume(
+                    &msg,
+                    device_path,
+                    mnt,
+                    &self.filesystems,
+                    self.mkfs_timeout,
+                    self.check_on_mount,
+                )
+                .await
                 {
                     detach(
                         &uuid,
@@ -552,11 +981,10 @@ impl node_server::Node for Node {
 
         debug!("Unstaging volume {}", &msg.volume_id);
 
-        let uuid = Uuid::parse_str(&msg.volume_id).map_err(|error| {
+        let uuid = parse_uuid(&msg.volume_id).map_err(|error| {
             failure!(
-                Code::Internal,
-                "Failed to unstage volume {}: not a valid UUID: {}",
-                &msg.volume_id,
+                Code::InvalidArgument,
+                "Failed to unstage volume: {}",
                 error
             )
         })?;
@@ -569,7 +997,9 @@ impl node_server::Node for Node {
         unstage_fs_volume(&msg).await?;
 
         // unmounts (if any) are complete.
-        // If the device is attached, detach the device.
+        // If the device is attached, detach the device, even if nothing
+        // was mounted, so that a controller left behind by an out-of-band
+        // unmount is not leaked.
         // Device::lookup will return None for nbd devices,
         // this is correct, as the attach for nbd is a no-op.
         detach(
@@ -581,3 +1011,477 @@ impl node_server::Node for Node {
         Ok(Response::new(NodeUnstageVolumeResponse {}))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{csi::node_server::Node as _, dev::connect_blocking};
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    #[test]
+    fn nvmf_connection_limit() {
+        let volume_id = "11111111-0000-0000-0000-000000000000";
+        let max = 4;
+
+        for active in 0 .. max {
+            assert!(check_nvmf_connection_limit(volume_id, active, max).is_ok());
+        }
+
+        let status = check_nvmf_connection_limit(volume_id, max, max)
+            .expect_err("limit must be enforced");
+        assert_eq!(status.code(), Code::ResourceExhausted);
+        assert!(status.message().contains("maximum number of NVMe-oF"));
+    }
+
+    #[test]
+    fn publish_context_attach_timeout() {
+        let volume_id = "11111111-0000-0000-0000-000000000000";
+        let context = |value: &str| {
+            let mut context = HashMap::new();
+            context.insert("attachTimeout".to_string(), value.to_string());
+            context
+        };
+
+        assert_eq!(
+            attach_timeout(volume_id, &HashMap::new()).unwrap(),
+            ATTACH_TIMEOUT_DEFAULT
+        );
+        assert_eq!(
+            attach_timeout(volume_id, &context("5")).unwrap(),
+            Duration::from_secs(5)
+        );
+        for value in &["0", "-1", "5s"] {
+            let status = attach_timeout(volume_id, &context(value))
+                .expect_err("invalid timeout must be rejected");
+            assert_eq!(status.code(), Code::InvalidArgument);
+        }
+    }
+
+    /// a device whose connect blocks its thread for a while, and which
+    /// records the undo of that connect
+    struct StalledAttach {
+        stall: Duration,
+        undone: Arc<AtomicBool>,
+    }
+
+    #[tonic::async_trait]
+    impl Attach for StalledAttach {
+        async fn parse_parameters(
+            &mut self,
+            _context: &HashMap<String, String>,
+        ) -> Result<(), DeviceError> {
+            Ok(())
+        }
+        async fn attach(&self) -> Result<(), DeviceError> {
+            let stall = self.stall;
+            let undone = Arc::clone(&self.undone);
+            connect_blocking(
+                move || std::thread::sleep(stall),
+                move |_| undone.store(true, Ordering::SeqCst),
+            )
+            .await
+        }
+        async fn find(&self) -> Result<Option<String>, DeviceError> {
+            Ok(None)
+        }
+        async fn fixup(&self) -> Result<(), DeviceError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn attach_timeout_expires() {
+        let uuid = Uuid::new_v4();
+        let undone = Arc::new(AtomicBool::default());
+        let device = StalledAttach {
+            stall: Duration::from_secs(2),
+            undone: Arc::clone(&undone),
+        };
+
+        let started = std::time::Instant::now();
+        let status = attach_any(
+            vec![Box::new(device)],
+            &uuid,
+            &uuid.to_string(),
+            0,
+            Duration::from_millis(100),
+        )
+        .await
+        .expect_err("a stalled attach must time out");
+        assert_eq!(status.code(), Code::DeadlineExceeded);
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        // the connect is only undone once it has returned
+        assert!(!undone.load(Ordering::SeqCst));
+        tokio::time::sleep(Duration::from_secs(3)).await;
+        assert!(undone.load(Ordering::SeqCst));
+    }
+
+    /// a device which shows up once attached, and records its disconnect,
+    /// every path of which finds the same device by WWN
+    struct TrackedAttach {
+        name: &'static str,
+        fail: bool,
+        attached: AtomicBool,
+        disconnected: Arc<AtomicBool>,
+    }
+
+    impl TrackedAttach {
+        fn new(name: &'static str, fail: bool) -> (Self, Arc<AtomicBool>) {
+            let disconnected = Arc::new(Default::default());
+            let device = Self {
+                name,
+                fail,
+                attached: Default::default(),
+                disconnected: Arc::clone(&disconnected),
+            };
+            (device, disconnected)
+        }
+    }
+
+    #[tonic::async_trait]
+    impl Attach for TrackedAttach {
+        async fn parse_parameters(
+            &mut self,
+            _context: &HashMap<String, String>,
+        ) -> Result<(), DeviceError> {
+            Ok(())
+        }
+        async fn attach(&self) -> Result<(), DeviceError> {
+            if self.fail {
+                return Err(DeviceError::new("connect failed"));
+            }
+            self.attached.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+        async fn find(&self) -> Result<Option<String>, DeviceError> {
+            Ok(self
+                .attached
+                .load(Ordering::SeqCst)
+                .then(|| "/dev/by-wwn".to_string()))
+        }
+        async fn find_path(&self) -> Result<Option<String>, DeviceError> {
+            Ok(self
+                .attached
+                .load(Ordering::SeqCst)
+                .then(|| self.name.to_string()))
+        }
+        async fn fixup(&self) -> Result<(), DeviceError> {
+            Ok(())
+        }
+        async fn disconnect(&self) -> Result<(), DeviceError> {
+            self.disconnected.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn attach_any_disconnects_losers() {
+        let uuid = Uuid::new_v4();
+        let (failing, failing_disconnected) =
+            TrackedAttach::new("/dev/failing", true);
+        let (winner, winner_disconnected) =
+            TrackedAttach::new("/dev/winner", false);
+
+        let stalled = StalledAttach {
+            stall: Duration::from_millis(500),
+            undone: Default::default(),
+        };
+
+        let (_, devpath) = attach_any(
+            vec![Box::new(failing), Box::new(winner), Box::new(stalled)],
+            &uuid,
+            &uuid.to_string(),
+            0,
+            Duration::from_secs(5),
+        )
+        .await
+        .unwrap();
+        // the device of the winning path, not the one found by WWN
+        assert_eq!(devpath, "/dev/winner");
+        assert!(failing_disconnected.load(Ordering::SeqCst));
+        assert!(!winner_disconnected.load(Ordering::SeqCst));
+
+        // an attach failing on every path keeps its error code
+        let (failing, failing_disconnected) =
+            TrackedAttach::new("/dev/failing", true);
+        let status = attach_any(
+            vec![Box::new(failing)],
+            &uuid,
+            &uuid.to_string(),
+            0,
+            Duration::from_secs(5),
+        )
+        .await
+        .expect_err("no path can be attached");
+        assert_eq!(status.code(), Code::Unavailable);
+        assert!(failing_disconnected.load(Ordering::SeqCst));
+    }
+
+    fn staged_mount(fstype: &str, options: &[&str]) -> MountInfo {
+        MountInfo {
+            source: "/dev/nvme0n1".into(),
+            dest: "/var/lib/kubelet/plugins/staging".into(),
+            fstype: fstype.into(),
+            options: options.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    fn mount_access(fs_type: &str, mount_flags: &[&str]) -> AccessType {
+        AccessType::Mount(volume_capability::MountVolume {
+            fs_type: fs_type.into(),
+            mount_flags: mount_flags.iter().map(|s| s.to_string()).collect(),
+        })
+    }
+
+    #[test]
+    fn publish_context_uris() {
+        let mut context = HashMap::new();
+        context.insert("uri".to_string(), "nvmf://10.0.0.1:8420/nqn".into());
+        assert_eq!(publish_uris(&context), vec!["nvmf://10.0.0.1:8420/nqn"]);
+
+        context.insert("uri1".to_string(), "nvmf://10.0.0.2:8420/nqn".into());
+        context.insert("uri3".to_string(), "nvmf://10.0.0.4:8420/nqn".into());
+        assert_eq!(
+            publish_uris(&context),
+            vec!["nvmf://10.0.0.1:8420/nqn", "nvmf://10.0.0.2:8420/nqn"]
+        );
+    }
+
+    #[test]
+    fn restage_identical() {
+        let volume_id = "11111111-0000-0000-0000-000000000000";
+        let staged = staged_mount("ext4", &["rw", "relatime"]);
+
+        assert!(check_restage(
+            volume_id,
+            &mount_access("ext4", &[]),
+            false,
+            &staged
+        )
+        .is_ok());
+        // an unspecified filesystem type matches whatever was staged
+        assert!(check_restage(
+            volume_id,
+            &mount_access("", &["noatime"]),
+            false,
+            &staged
+        )
+        .is_ok());
+
+        // a reader only volume is staged readonly
+        let staged = staged_mount("ext4", &["ro", "relatime"]);
+        assert!(check_restage(
+            volume_id,
+            &mount_access("ext4", &[]),
+            true,
+            &staged
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn restage_conflicting() {
+        let volume_id = "11111111-0000-0000-0000-000000000000";
+        let staged = staged_mount("ext4", &["rw", "relatime"]);
+
+        for access in &[
+            mount_access("xfs", &[]),
+            mount_access("ext4", &["ro"]),
+            AccessType::Block(volume_capability::BlockVolume {}),
+        ] {
+            let status = check_restage(volume_id, access, false, &staged)
+                .expect_err("conflicting capability must be rejected");
+            assert_eq!(status.code(), Code::AlreadyExists);
+        }
+
+        // a volume staged for reading only cannot be restaged for writing,
+        // nor the other way round
+        let status =
+            check_restage(volume_id, &mount_access("ext4", &[]), true, &staged)
+                .expect_err("reader only access must be rejected");
+        assert_eq!(status.code(), Code::AlreadyExists);
+
+        let staged = staged_mount("ext4", &["ro", "relatime"]);
+        let status = check_restage(
+            volume_id,
+            &mount_access("ext4", &[]),
+            false,
+            &staged,
+        )
+        .expect_err("writer access must be rejected");
+        assert_eq!(status.code(), Code::AlreadyExists);
+    }
+
+    #[test]
+    fn rw_publish_access_mode() {
+        let capability = |mode: Mode| {
+            Some(VolumeCapability {
+                access_mode: Some(volume_capability::AccessMode {
+                    mode: mode as i32,
+                }),
+                access_type: Some(mount_access("ext4", &[])),
+            })
+        };
+
+        // a volume staged as "ro" may only be promoted for writer modes
+        for mode in &[Mode::SingleNodeWriter, Mode::MultiNodeSingleWriter] {
+            assert!(check_access_mode(&capability(*mode), false).is_ok());
+        }
+        for mode in &[Mode::SingleNodeReaderOnly, Mode::MultiNodeReaderOnly] {
+            assert!(check_access_mode(&capability(*mode), true).is_ok());
+            assert!(check_access_mode(&capability(*mode), false).is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn rw_publish_promotes_staging() {
+        let volume_id = "11111111-0000-0000-0000-000000000000";
+        let dir = std::env::temp_dir()
+            .join(format!("csi-promote-{}", Uuid::new_v4()));
+        let staging = dir.join("staging");
+        let reader = dir.join("reader");
+        let writer = dir.join("writer");
+        fs::create_dir_all(&staging).unwrap();
+
+        // a source of its own keeps the mounts of the host out of the way
+        let source = format!("csi-promote-{}", Uuid::new_v4());
+        mount::filesystem_mount(
+            &source,
+            staging.to_str().unwrap(),
+            "tmpfs",
+            &["ro".to_string()],
+        )
+        .unwrap();
+
+        let mut node = node(false);
+        node.filesystems = vec!["tmpfs".into()];
+        let request = |mode: Mode, target: &Path, readonly: bool| {
+            Request::new(NodePublishVolumeRequest {
+                volume_id: volume_id.into(),
+                staging_target_path: staging.to_str().unwrap().into(),
+                target_path: target.to_str().unwrap().into(),
+                volume_capability: Some(VolumeCapability {
+                    access_mode: Some(volume_capability::AccessMode {
+                        mode: mode as i32,
+                    }),
+                    access_type: Some(mount_access("", &[])),
+                }),
+                readonly,
+                ..Default::default()
+            })
+        };
+        let readonly = |path: &Path| {
+            mount::find_mount(None, Some(path.to_str().unwrap()))
+                .unwrap()
+                .options
+                .readonly()
+        };
+
+        // a reader leaves the staging readonly
+        let status = node
+            .node_publish_volume(request(
+                Mode::SingleNodeReaderOnly,
+                &reader,
+                false,
+            ))
+            .await
+            .expect_err("a reader cannot publish read-write");
+        assert_eq!(status.code(), Code::InvalidArgument);
+        node.node_publish_volume(request(
+            Mode::SingleNodeReaderOnly,
+            &reader,
+            true,
+        ))
+        .await
+        .unwrap();
+        assert!(readonly(&staging));
+        assert!(readonly(&reader));
+
+        // a writer promotes the staging, the reader stays readonly
+        node.node_publish_volume(request(
+            Mode::SingleNodeWriter,
+            &writer,
+            false,
+        ))
+        .await
+        .unwrap();
+        assert!(!readonly(&staging));
+        assert!(!readonly(&writer));
+        assert!(readonly(&reader));
+
+        for path in &[&writer, &reader] {
+            mount::bind_unmount(path.to_str().unwrap()).unwrap();
+        }
+        mount::filesystem_unmount(staging.to_str().unwrap()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn node(remove_stale_target_files: bool) -> Node {
+        Node {
+            node_name: "node".into(),
+            filesystems: vec![],
+            max_nvmf_connections: None,
+            transport: Transport::Nvmf,
+            max_volumes_per_node: None,
+            publish_options_policy: PublishOptionsPolicy::Strict,
+            remove_stale_target_files,
+            default_mount_flags: vec![],
+            mkfs_timeout: Duration::from_secs(1),
+            check_on_mount: false,
+            attach_retries: 0,
+            bind_mount_retries: 0,
+            host_nqn: NvmfHost::default_nqn("node"),
+            host_id: Uuid::new_v4().to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn unpublish_regular_file() {
+        let volume_id = "11111111-0000-0000-0000-000000000000";
+        let target_path =
+            std::env::temp_dir().join(format!("csi-target-{}", Uuid::new_v4()));
+        fs::write(&target_path, b"leftover").unwrap();
+
+        let request = || {
+            Request::new(NodeUnpublishVolumeRequest {
+                volume_id: volume_id.into(),
+                target_path: target_path.to_str().unwrap().into(),
+            })
+        };
+
+        // the file is left alone and the error explains what was found
+        let status = node(false)
+            .node_unpublish_volume(request())
+            .await
+            .expect_err("a regular file must be rejected");
+        assert_eq!(status.code(), Code::Internal);
+        assert!(status.message().contains("is a regular file"));
+        assert!(target_path.exists());
+
+        // the leftover file is removed
+        node(true).node_unpublish_volume(request()).await.unwrap();
+        assert!(!target_path.exists());
+    }
+
+    #[tokio::test]
+    async fn unstage_invalid_volume_id() {
+        for volume_id in &[
+            "00000000-0000-0000-0000-000000000000",
+            "11111111-0000-0000-0000-00000000000",
+            "11111111-0000-0000-0000-0000000000000",
+        ] {
+            let status = node(false)
+                .node_unstage_volume(Request::new(NodeUnstageVolumeRequest {
+                    volume_id: volume_id.to_string(),
+                    staging_target_path: "/var/lib/kubelet/staging".into(),
+                }))
+                .await
+                .expect_err("an invalid volume id must be rejected");
+            assert_eq!(status.code(), Code::InvalidArgument);
+        }
+    }
+}
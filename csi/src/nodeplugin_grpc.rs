@@ -13,14 +13,18 @@ use mayastor_node_plugin::{
     FindVolumeRequest,
     FreezeFsReply,
     FreezeFsRequest,
+    GetVolumeInfoReply,
+    GetVolumeInfoRequest,
     UnfreezeFsReply,
     UnfreezeFsRequest,
+    VolumeMount,
     VolumeType,
 };
 
 use nodeplugin_svc::{
     find_volume,
     freeze_volume,
+    get_volume_info,
     unfreeze_volume,
     ServiceError,
     TypeOfMount,
@@ -100,6 +104,42 @@ impl MayastorNodePlugin for MayastorNodePluginSvc {
             })),
         }
     }
+
+    async fn get_volume_info(
+        &self,
+        request: Request<GetVolumeInfoRequest>,
+    ) -> Result<Response<GetVolumeInfoReply>, Status> {
+        let volume_id = request.into_inner().volume_id;
+        debug!("get_volume_info({})", volume_id);
+        let info = get_volume_info(&volume_id).await?;
+        let fstype = match (&info.mount_type, &info.staging) {
+            (Some(TypeOfMount::FileSystem), Some(staging)) => {
+                staging.fstype.clone()
+            }
+            _ => String::new(),
+        };
+        Ok(Response::new(GetVolumeInfoReply {
+            device: info.device,
+            mounted: info.mount_type.is_some(),
+            volume_type: match info.mount_type {
+                Some(TypeOfMount::RawBlock) => VolumeType::Rawblock as i32,
+                _ => VolumeType::Filesystem as i32,
+            },
+            fstype,
+            staging: info.staging.map(VolumeMount::from),
+            targets: info.targets.into_iter().map(VolumeMount::from).collect(),
+        }))
+    }
+}
+
+impl From<nodeplugin_svc::VolumeMount> for VolumeMount {
+    fn from(mount: nodeplugin_svc::VolumeMount) -> Self {
+        Self {
+            readonly: mount.readonly(),
+            path: mount.path,
+            options: mount.options,
+        }
+    }
 }
 
 pub struct MayastorNodePluginGrpcServer {}
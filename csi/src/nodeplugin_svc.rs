@@ -4,11 +4,11 @@
 use crate::{
     dev::{Device, DeviceError},
     findmnt,
-    mount,
+    mount::{self, ReadOnly},
 };
+use rpc::validate::{parse_uuid, InvalidUuid};
 use snafu::{ResultExt, Snafu};
 use tokio::process::Command;
-use uuid::Uuid;
 
 #[derive(Debug, Snafu)]
 #[snafu(visibility = "pub(crate)")]
@@ -16,7 +16,7 @@ pub enum ServiceError {
     #[snafu(display("Cannot find volume: volume ID: {}", volid))]
     VolumeNotFound { volid: String },
     #[snafu(display("Invalid volume ID: {}, {}", volid, source))]
-    InvalidVolumeId { source: uuid::Error, volid: String },
+    InvalidVolumeId { source: InvalidUuid, volid: String },
     #[snafu(display("fsfreeze failed: volume ID: {}, {}", volid, error))]
     FsfreezeFailed { volid: String, error: String },
     #[snafu(display("Internal failure: volume ID: {}, {}", volid, source))]
@@ -32,18 +32,77 @@ pub enum ServiceError {
     BlockDeviceMount { volid: String },
 }
 
+#[derive(Debug, PartialEq)]
 pub enum TypeOfMount {
     FileSystem,
     RawBlock,
 }
 
+/// A mount of a volume on this node.
+#[derive(Debug, PartialEq)]
+pub struct VolumeMount {
+    pub path: String,
+    pub fstype: String,
+    pub options: Vec<String>,
+}
+
+impl VolumeMount {
+    pub fn readonly(&self) -> bool {
+        self.options.readonly()
+    }
+}
+
+/// How a volume is attached, staged and published on this node.
+#[derive(Debug, PartialEq)]
+pub struct VolumeInfo {
+    pub device: String,
+    /// None if the volume is not mounted
+    pub mount_type: Option<TypeOfMount>,
+    /// staging mount of a filesystem volume
+    pub staging: Option<VolumeMount>,
+    /// published mounts
+    pub targets: Vec<VolumeMount>,
+}
+
+/// Sort the mounts of a volume into its staging and published mounts.
+/// A filesystem volume is mounted onto its staging path first, and bind
+/// mounted from there onto its target paths, so the first mount is the
+/// staging mount. A raw block volume is not staged.
+fn volume_info(device: String, mut mounts: Vec<VolumeMount>) -> VolumeInfo {
+    if mounts.is_empty() {
+        return VolumeInfo {
+            device,
+            mount_type: None,
+            staging: None,
+            targets: mounts,
+        };
+    }
+
+    if mounts.iter().any(|m| m.fstype == "devtmpfs") {
+        return VolumeInfo {
+            device,
+            mount_type: Some(TypeOfMount::RawBlock),
+            staging: None,
+            targets: mounts,
+        };
+    }
+
+    let staging = mounts.remove(0);
+    VolumeInfo {
+        device,
+        mount_type: Some(TypeOfMount::FileSystem),
+        staging: Some(staging),
+        targets: mounts,
+    }
+}
+
 const FSFREEZE: &str = "fsfreeze";
 
 async fn fsfreeze(
     volume_id: &str,
     freeze_op: &str,
 ) -> Result<(), ServiceError> {
-    let uuid = Uuid::parse_str(volume_id).context(InvalidVolumeId {
+    let uuid = parse_uuid(volume_id).context(InvalidVolumeId {
         volid: volume_id.to_string(),
     })?;
 
@@ -118,7 +177,7 @@ pub async fn unfreeze_volume(volume_id: &str) -> Result<(), ServiceError> {
 }
 
 pub async fn find_volume(volume_id: &str) -> Result<TypeOfMount, ServiceError> {
-    let uuid = Uuid::parse_str(volume_id).context(InvalidVolumeId {
+    let uuid = parse_uuid(volume_id).context(InvalidVolumeId {
         volid: volume_id.to_string(),
     })?;
 
@@ -160,3 +219,96 @@ pub async fn find_volume(volume_id: &str) -> Result<TypeOfMount, ServiceError> {
         volid: volume_id.to_string(),
     })
 }
+
+pub async fn get_volume_info(
+    volume_id: &str,
+) -> Result<VolumeInfo, ServiceError> {
+    let uuid = parse_uuid(volume_id).context(InvalidVolumeId {
+        volid: volume_id.to_string(),
+    })?;
+
+    let device = Device::lookup(&uuid)
+        .await
+        .context(InternalFailure {
+            volid: volume_id.to_string(),
+        })?
+        .ok_or_else(|| ServiceError::VolumeNotFound {
+            volid: volume_id.to_string(),
+        })?;
+
+    let device_path = device.devname();
+    let mounts = findmnt::get_mountpaths(&device_path)
+        .context(InternalFailure {
+            volid: volume_id.to_string(),
+        })?
+        .into_iter()
+        .map(|devmount| VolumeMount {
+            options: mount::find_mount(None, Some(&devmount.mount_path))
+                .map(|m| m.options)
+                .unwrap_or_default(),
+            path: devmount.mount_path,
+            fstype: devmount.fstype,
+        })
+        .collect();
+
+    Ok(volume_info(device_path, mounts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn volume_mount(path: &str, fstype: &str, options: &[&str]) -> VolumeMount {
+        VolumeMount {
+            path: path.into(),
+            fstype: fstype.into(),
+            options: options.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn filesystem_volume_info() {
+        let info = volume_info(
+            "/dev/nvme0n1".into(),
+            vec![
+                volume_mount("/staging", "ext4", &["rw", "noatime"]),
+                volume_mount("/target1", "ext4", &["ro", "noatime"]),
+                volume_mount("/target2", "ext4", &["rw", "noatime"]),
+            ],
+        );
+
+        assert_eq!(info.device, "/dev/nvme0n1");
+        assert_eq!(info.mount_type, Some(TypeOfMount::FileSystem));
+        let staging = info.staging.expect("staging mount");
+        assert_eq!(staging.path, "/staging");
+        assert_eq!(staging.fstype, "ext4");
+        assert!(!staging.readonly());
+        assert_eq!(info.targets.len(), 2);
+        assert_eq!(info.targets[0].path, "/target1");
+        assert!(info.targets[0].readonly());
+        assert!(!info.targets[1].readonly());
+    }
+
+    #[test]
+    fn block_volume_info() {
+        let info = volume_info(
+            "/dev/nvme0n1".into(),
+            vec![volume_mount("/target", "devtmpfs", &["rw"])],
+        );
+
+        assert_eq!(info.mount_type, Some(TypeOfMount::RawBlock));
+        assert!(info.staging.is_none());
+        assert_eq!(
+            info.targets,
+            vec![volume_mount("/target", "devtmpfs", &["rw"])]
+        );
+    }
+
+    #[test]
+    fn unmounted_volume_info() {
+        let info = volume_info("/dev/nvme0n1".into(), vec![]);
+        assert_eq!(info.mount_type, None);
+        assert!(info.staging.is_none());
+        assert!(info.targets.is_empty());
+    }
+}
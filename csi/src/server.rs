@@ -12,19 +12,27 @@ extern crate tracing;
 use std::{
     fs,
     io::{ErrorKind, Write},
+    os::unix::fs::{FileTypeExt, PermissionsExt},
 };
 
-use crate::{identity::Identity, mount::probe_filesystems, node::Node};
+use crate::{
+    dev::{NvmfHost, Transport},
+    identity::Identity,
+    mount::{probe_filesystems, sanitize_options, PublishOptionsPolicy},
+    node::Node,
+};
 use chrono::Local;
 use clap::{App, Arg};
 use csi::{identity_server::IdentityServer, node_server::NodeServer};
 use env_logger::{Builder, Env};
 use futures::TryFutureExt;
+use health::HealthServer;
 use nodeplugin_grpc::MayastorNodePluginGrpcServer;
 use std::{
     path::Path,
     pin::Pin,
     task::{Context, Poll},
+    time::Duration,
 };
 use tokio::{
     io::{AsyncRead, AsyncWrite, ReadBuf},
@@ -48,6 +56,7 @@ mod error;
 mod filesystem_vol;
 mod findmnt;
 mod format;
+mod health;
 mod identity;
 mod match_dev;
 mod mount;
@@ -108,6 +117,13 @@ async fn main() -> Result<(), String> {
                 .help("CSI gRPC listen socket (default /var/tmp/csi.sock)")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("csi-socket-mode")
+                .long("csi-socket-mode")
+                .value_name("MODE")
+                .takes_value(true)
+                .help("File mode of the CSI gRPC listen socket, in octal (e.g. 0660)"),
+        )
         .arg(
             Arg::with_name("log-debug")
                 .short("l")
@@ -131,12 +147,107 @@ async fn main() -> Result<(), String> {
                 .required(true)
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("health-endpoint")
+                .long("health-endpoint")
+                .value_name("ADDRESS")
+                .takes_value(true)
+                .required(false)
+                .help("ip address and port of the HTTP endpoint serving /healthz and /readyz probes"),
+        )
         .arg(
             Arg::with_name("v")
                 .short("v")
                 .multiple(true)
                 .help("Sets the verbosity level"),
         )
+        .arg(
+            Arg::with_name("max-nvmf-connections")
+                .long("max-nvmf-connections")
+                .value_name("NUMBER")
+                .takes_value(true)
+                .required(false)
+                .help("Maximum number of active NVMe-oF connections on this node"),
+        )
+        .arg(
+            Arg::with_name("transport")
+                .long("transport")
+                .value_name("TRANSPORT")
+                .possible_values(&["nvmf", "nbd"])
+                .default_value("nvmf")
+                .help("Transport used to attach volumes on this node"),
+        )
+        .arg(
+            Arg::with_name("max-volumes-per-node")
+                .long("max-volumes-per-node")
+                .value_name("NUMBER")
+                .takes_value(true)
+                .required(false)
+                .help("Maximum number of volumes that can be attached to this node, overriding the transport limit"),
+        )
+        .arg(
+            Arg::with_name("publish-options-policy")
+                .long("publish-options-policy")
+                .value_name("POLICY")
+                .possible_values(&["strict", "relaxed"])
+                .default_value("strict")
+                .help("Whether to allow additive mount options when republishing a volume"),
+        )
+        .arg(
+            Arg::with_name("remove-stale-target-files")
+                .long("remove-stale-target-files")
+                .help("Remove a regular file found at the target path when unpublishing a volume instead of failing"),
+        )
+        .arg(
+            Arg::with_name("default-mount-flags")
+                .long("default-mount-flags")
+                .value_name("OPTIONS")
+                .takes_value(true)
+                .required(false)
+                .help("Comma separated mount options applied to all filesystem volumes, unless overridden by the mount flags of the volume"),
+        )
+        .arg(
+            Arg::with_name("mkfs-timeout")
+                .long("mkfs-timeout")
+                .value_name("SECONDS")
+                .default_value("300")
+                .help("Time allowed for creating or checking a filesystem on a volume before the command is killed"),
+        )
+        .arg(
+            Arg::with_name("check-on-mount")
+                .long("check-on-mount")
+                .help("Check and repair an existing filesystem of a volume before mounting it"),
+        )
+        .arg(
+            Arg::with_name("attach-retries")
+                .long("attach-retries")
+                .value_name("NUMBER")
+                .default_value("5")
+                .help("Number of times a failed volume attach is retried, with an exponential backoff"),
+        )
+        .arg(
+            Arg::with_name("bind-mount-retries")
+                .long("bind-mount-retries")
+                .value_name("NUMBER")
+                .default_value("3")
+                .help("Number of times a bind mount failing with a transient error is retried"),
+        )
+        .arg(
+            Arg::with_name("host-nqn")
+                .long("host-nqn")
+                .value_name("NQN")
+                .takes_value(true)
+                .required(false)
+                .help("NQN presented to NVMe-oF targets, derived from the node name by default"),
+        )
+        .arg(
+            Arg::with_name("host-id")
+                .long("host-id")
+                .value_name("UUID")
+                .takes_value(true)
+                .required(false)
+                .help("Host ID presented to NVMe-oF targets, the machine UUID by default"),
+        )
         .arg(
             Arg::with_name("nvme-core-io-timeout")
                 .long("nvme-core-io-timeout")
@@ -152,6 +263,71 @@ async fn main() -> Result<(), String> {
     let csi_socket = matches
         .value_of("csi-socket")
         .unwrap_or("/var/tmp/csi.sock");
+    let csi_socket_mode = matches.value_of("csi-socket-mode").map(|mode| {
+        u32::from_str_radix(mode.trim_start_matches("0o"), 8)
+            .expect("csi-socket-mode should be an octal file mode")
+    });
+    let max_nvmf_connections =
+        matches.value_of("max-nvmf-connections").map(|max| {
+            max.parse::<usize>()
+                .expect("max-nvmf-connections should be an integer number")
+        });
+    let transport = matches
+        .value_of("transport")
+        .unwrap()
+        .parse::<Transport>()
+        .expect("transport should be one of nvmf or nbd");
+    let max_volumes_per_node =
+        matches.value_of("max-volumes-per-node").map(|max| {
+            max.parse::<i64>()
+                .expect("max-volumes-per-node should be an integer number")
+        });
+    let publish_options_policy =
+        match matches.value_of("publish-options-policy").unwrap() {
+            "relaxed" => PublishOptionsPolicy::Relaxed,
+            _ => PublishOptionsPolicy::Strict,
+        };
+    let remove_stale_target_files =
+        matches.is_present("remove-stale-target-files");
+    let mkfs_timeout = Duration::from_secs(
+        matches
+            .value_of("mkfs-timeout")
+            .unwrap()
+            .parse::<u64>()
+            .expect("mkfs-timeout should be an integer number of seconds"),
+    );
+    let check_on_mount = matches.is_present("check-on-mount");
+    let default_mount_flags: Vec<String> = matches
+        .value_of("default-mount-flags")
+        .map(|flags| flags.split(',').map(String::from).collect())
+        .unwrap_or_default();
+    if let Err(option) = sanitize_options(&default_mount_flags, None) {
+        panic!("Unsupported default mount option: {}", option);
+    }
+    let attach_retries = matches
+        .value_of("attach-retries")
+        .unwrap()
+        .parse::<u32>()
+        .expect("attach-retries should be an integer number");
+    let bind_mount_retries = matches
+        .value_of("bind-mount-retries")
+        .unwrap()
+        .parse::<u32>()
+        .expect("bind-mount-retries should be an integer number");
+    let host_nqn = matches
+        .value_of("host-nqn")
+        .map(String::from)
+        .unwrap_or_else(|| NvmfHost::default_nqn(node_name));
+    let host_id = match matches.value_of("host-id") {
+        Some(id) => rpc::validate::parse_uuid(id)
+            .expect("host-id should be a UUID")
+            .to_string(),
+        None => nvmeadm::nvmf_discovery::default_host_id().to_string(),
+    };
+    let health_endpoint = matches.value_of("health-endpoint").map(|addr| {
+        addr.parse::<std::net::SocketAddr>()
+            .expect("Invalid health probe endpoint")
+    });
     let level = match matches.occurrences_of("v") as usize {
         0 => "info",
         1 => "debug",
@@ -194,18 +370,7 @@ async fn main() -> Result<(), String> {
         }
     }
 
-    // Remove stale CSI socket from previous instance if there is any
-    match fs::remove_file(csi_socket) {
-        Ok(_) => info!("Removed stale CSI socket {}", csi_socket),
-        Err(err) => {
-            if err.kind() != ErrorKind::NotFound {
-                return Err(format!(
-                    "Error removing stale CSI socket {}: {}",
-                    csi_socket, err
-                ));
-            }
-        }
-    }
+    prepare_csi_socket(Path::new(csi_socket))?;
 
     let sock_addr = if endpoint.contains(':') {
         endpoint.to_string()
@@ -214,21 +379,126 @@ async fn main() -> Result<(), String> {
     };
 
     let _ = tokio::join!(
-        CsiServer::run(csi_socket, node_name),
+        CsiServer::run(
+            csi_socket,
+            csi_socket_mode,
+            Node {
+                node_name: node_name.into(),
+                filesystems: probe_filesystems(),
+                max_nvmf_connections,
+                transport,
+                max_volumes_per_node,
+                publish_options_policy,
+                remove_stale_target_files,
+                default_mount_flags,
+                mkfs_timeout,
+                check_on_mount,
+                attach_retries,
+                bind_mount_retries,
+                host_nqn,
+                host_id,
+            }
+        ),
         MayastorNodePluginGrpcServer::run(
             sock_addr.parse().expect("Invalid gRPC endpoint")
         ),
+        async {
+            match health_endpoint {
+                Some(endpoint) => HealthServer::run(endpoint, transport).await,
+                None => Ok(()),
+            }
+        },
     );
 
     Ok(())
 }
 
+/// Make sure the CSI socket can be created: create its directory if needed,
+/// check that the directory is writable and remove a stale socket left over
+/// from a previous instance.
+fn prepare_csi_socket(csi_socket: &Path) -> Result<(), String> {
+    let dir = match csi_socket.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    fs::create_dir_all(dir).map_err(|err| {
+        format!(
+            "Error creating CSI socket directory {}: {}",
+            dir.display(),
+            err
+        )
+    })?;
+
+    let probe = dir.join(format!(".csi-socket-probe-{}", std::process::id()));
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+        .and_then(|_| fs::remove_file(&probe))
+        .map_err(|err| {
+            format!(
+                "CSI socket directory {} is not writable: {}",
+                dir.display(),
+                err
+            )
+        })?;
+
+    // Remove stale CSI socket from previous instance if there is any
+    match fs::symlink_metadata(csi_socket) {
+        Ok(meta) if meta.file_type().is_socket() => {
+            fs::remove_file(csi_socket).map_err(|err| {
+                format!(
+                    "Error removing stale CSI socket {}: {}",
+                    csi_socket.display(),
+                    err
+                )
+            })?;
+            info!("Removed stale CSI socket {}", csi_socket.display());
+        }
+        Ok(_) => {
+            return Err(format!(
+                "CSI socket path {} exists and is not a socket",
+                csi_socket.display()
+            ));
+        }
+        Err(err) if err.kind() == ErrorKind::NotFound => {}
+        Err(err) => {
+            return Err(format!(
+                "Error accessing CSI socket {}: {}",
+                csi_socket.display(),
+                err
+            ));
+        }
+    }
+
+    Ok(())
+}
+
 struct CsiServer {}
 
 impl CsiServer {
-    pub async fn run(csi_socket: &str, node_name: &str) -> Result<(), ()> {
+    pub async fn run(
+        csi_socket: &str,
+        csi_socket_mode: Option<u32>,
+        node: Node,
+    ) -> Result<(), ()> {
         let incoming = {
-            let uds = UnixListener::bind(csi_socket).unwrap();
+            let uds = UnixListener::bind(csi_socket).map_err(|err| {
+                error!("Failed to bind CSI socket {}: {}", csi_socket, err);
+            })?;
+            if let Some(mode) = csi_socket_mode {
+                fs::set_permissions(
+                    csi_socket,
+                    fs::Permissions::from_mode(mode),
+                )
+                .map_err(|err| {
+                    error!(
+                        "Failed to set mode {:o} on CSI socket {}: {}",
+                        mode, csi_socket, err
+                    );
+                })?;
+            }
             info!("CSI plugin bound to {}", csi_socket);
 
             async_stream::stream! {
@@ -239,10 +509,7 @@ impl CsiServer {
         };
 
         if let Err(e) = Server::builder()
-            .add_service(NodeServer::new(Node {
-                node_name: node_name.into(),
-                filesystems: probe_filesystems(),
-            }))
+            .add_service(NodeServer::new(node))
             .add_service(IdentityServer::new(Identity {}))
             .serve_with_incoming(incoming)
             .await
@@ -305,3 +572,86 @@ mod wrapped_stream {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csi::{identity_client::IdentityClient, GetPluginInfoRequest};
+    use std::convert::TryFrom;
+    use tokio::net::UnixStream;
+    use tonic::transport::{Endpoint, Uri};
+    use tower::service_fn;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn custom_csi_socket() {
+        let dir =
+            std::env::temp_dir().join(format!("csi-socket-{}", Uuid::new_v4()));
+        let csi_socket = dir.join("plugin").join("csi.sock");
+
+        // the socket directory is created and a stale socket is removed
+        prepare_csi_socket(&csi_socket).unwrap();
+        std::os::unix::net::UnixListener::bind(&csi_socket).unwrap();
+        prepare_csi_socket(&csi_socket).unwrap();
+        assert!(!csi_socket.exists());
+
+        let node = Node {
+            node_name: "node".into(),
+            filesystems: vec![],
+            max_nvmf_connections: None,
+            transport: Transport::Nvmf,
+            max_volumes_per_node: None,
+            publish_options_policy: PublishOptionsPolicy::Strict,
+            remove_stale_target_files: false,
+            default_mount_flags: vec![],
+            mkfs_timeout: Duration::from_secs(1),
+            check_on_mount: false,
+            attach_retries: 0,
+            bind_mount_retries: 0,
+            host_nqn: NvmfHost::default_nqn("node"),
+            host_id: Uuid::new_v4().to_string(),
+        };
+        let path = csi_socket.to_str().unwrap().to_string();
+        tokio::spawn(
+            async move { CsiServer::run(&path, Some(0o600), node).await },
+        );
+
+        let path = csi_socket.clone();
+        let mut retries = 50;
+        let channel = loop {
+            let path = path.clone();
+            match Endpoint::try_from("http://[::]:50051")
+                .unwrap()
+                .connect_with_connector(service_fn(move |_: Uri| {
+                    UnixStream::connect(path.clone())
+                }))
+                .await
+            {
+                Ok(channel) => break channel,
+                Err(error) if retries == 0 => panic!("{}", error),
+                Err(_) => {
+                    retries -= 1;
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            }
+        };
+
+        let info = IdentityClient::new(channel)
+            .get_plugin_info(GetPluginInfoRequest {})
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(!info.name.is_empty());
+
+        let mode = fs::metadata(&csi_socket).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // a path which is not a socket is never removed
+        let regular = dir.join("regular");
+        fs::write(&regular, b"data").unwrap();
+        assert!(prepare_csi_socket(&regular).is_err());
+        assert!(regular.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
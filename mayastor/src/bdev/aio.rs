@@ -9,7 +9,12 @@ use url::Url;
 use spdk_sys::{bdev_aio_delete, create_aio_bdev};
 
 use crate::{
-    bdev::{dev::reject_unknown_parameters, util::uri, CreateDestroy, GetName},
+    bdev::{
+        dev::reject_unknown_parameters,
+        util::{block_size, uri},
+        CreateDestroy,
+        GetName,
+    },
     core::Bdev,
     ffihelper::{cb_arg, done_errno_cb, ErrnoResult},
     nexus_uri::{self, NexusBdevError},
@@ -47,7 +52,7 @@ impl TryFrom<&Url> for Aio {
                     parameter: String::from("blk_size"),
                 })?
             }
-            None => 512,
+            None => block_size::default_blk_size(url.path()),
         };
 
         let uuid = uri::uuid(parameters.remove("uuid")).context(
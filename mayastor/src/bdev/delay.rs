@@ -0,0 +1,211 @@
+//! A delay bdev wraps an existing bdev and injects a configurable latency
+//! into its read and write I/O. It exists to test the behaviour of the I/O
+//! stack under controlled latency and should not be used in production.
+//!
+//! For example: "delay:///malloc0?avg_read_us=500&avg_write_us=1000"
+//! creates the bdev "malloc0_delay" on top of the existing bdev "malloc0".
+//! The p99 latencies default to the average latencies when not given.
+use std::{collections::HashMap, convert::TryFrom};
+
+use async_trait::async_trait;
+use futures::channel::oneshot;
+use nix::errno::Errno;
+use snafu::ResultExt;
+use url::Url;
+
+use crate::{
+    bdev::{dev::reject_unknown_parameters, util::uri, CreateDestroy, GetName},
+    core::Bdev,
+    ffihelper::{cb_arg, done_errno_cb, ErrnoResult, IntoCString},
+    nexus_uri::{self, NexusBdevError},
+};
+
+#[derive(Debug)]
+pub(super) struct Delay {
+    /// name of the delay bdev
+    name: String,
+    /// alias which can be used to open the bdev
+    alias: String,
+    /// name of the bdev to wrap
+    base: String,
+    /// average and p99 read latency in microseconds
+    avg_read_us: u64,
+    p99_read_us: u64,
+    /// average and p99 write latency in microseconds
+    avg_write_us: u64,
+    p99_write_us: u64,
+    uuid: Option<uuid::Uuid>,
+}
+
+/// Parse an optional latency parameter given in microseconds
+fn latency(
+    url: &Url,
+    parameters: &mut HashMap<String, String>,
+    name: &str,
+) -> Result<Option<u64>, NexusBdevError> {
+    parameters
+        .remove(name)
+        .map(|value| {
+            value.parse().context(nexus_uri::IntParamParseError {
+                uri: url.to_string(),
+                parameter: String::from(name),
+            })
+        })
+        .transpose()
+}
+
+/// Convert a URI to a Delay "object"
+impl TryFrom<&Url> for Delay {
+    type Error = NexusBdevError;
+
+    fn try_from(url: &Url) -> Result<Self, Self::Error> {
+        let segments = uri::segments(url);
+
+        if segments.is_empty() {
+            return Err(NexusBdevError::UriInvalid {
+                uri: url.to_string(),
+                message: String::from("no base bdev specified"),
+            });
+        }
+
+        if segments.len() > 1 {
+            return Err(NexusBdevError::UriInvalid {
+                uri: url.to_string(),
+                message: String::from("too many path segments"),
+            });
+        }
+
+        let mut parameters: HashMap<String, String> =
+            url.query_pairs().into_owned().collect();
+
+        let avg_read_us =
+            latency(url, &mut parameters, "avg_read_us")?.unwrap_or(0);
+        let avg_write_us =
+            latency(url, &mut parameters, "avg_write_us")?.unwrap_or(0);
+        let p99_read_us = latency(url, &mut parameters, "p99_read_us")?
+            .unwrap_or(avg_read_us);
+        let p99_write_us = latency(url, &mut parameters, "p99_write_us")?
+            .unwrap_or(avg_write_us);
+
+        if p99_read_us < avg_read_us || p99_write_us < avg_write_us {
+            return Err(NexusBdevError::UriInvalid {
+                uri: url.to_string(),
+                message: String::from(
+                    "p99 latency must not be smaller than the average latency",
+                ),
+            });
+        }
+
+        let uuid = uri::uuid(parameters.remove("uuid")).context(
+            nexus_uri::UuidParamParseError {
+                uri: url.to_string(),
+            },
+        )?;
+
+        reject_unknown_parameters(url, parameters)?;
+
+        Ok(Delay {
+            name: format!("{}_delay", segments[0]),
+            alias: url.to_string(),
+            base: segments[0].to_string(),
+            avg_read_us,
+            p99_read_us,
+            avg_write_us,
+            p99_write_us,
+            uuid,
+        })
+    }
+}
+
+impl GetName for Delay {
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+}
+
+#[async_trait(?Send)]
+impl CreateDestroy for Delay {
+    type Error = NexusBdevError;
+
+    /// Create a delay bdev on top of the base bdev
+    async fn create(&self) -> Result<String, Self::Error> {
+        if Bdev::lookup_by_name(&self.base).is_none() {
+            return Err(NexusBdevError::BdevNotFound {
+                name: self.base.clone(),
+            });
+        }
+
+        if Bdev::lookup_by_name(&self.name).is_some() {
+            return Err(NexusBdevError::BdevExists {
+                name: self.name.clone(),
+            });
+        }
+
+        let cbase = self.base.clone().into_cstring();
+        let cname = self.name.clone().into_cstring();
+
+        let errno = unsafe {
+            spdk_sys::create_delay_disk(
+                cbase.as_ptr(),
+                cname.as_ptr(),
+                self.avg_read_us,
+                self.p99_read_us,
+                self.avg_write_us,
+                self.p99_write_us,
+            )
+        };
+
+        if errno != 0 {
+            return Err(NexusBdevError::CreateBdev {
+                source: Errno::from_i32(errno.abs()),
+                name: self.name.clone(),
+            });
+        }
+
+        if let Some(mut bdev) = Bdev::lookup_by_name(&self.name) {
+            if let Some(uuid) = self.uuid {
+                bdev.set_uuid(uuid);
+            }
+
+            if !bdev.add_alias(&self.alias) {
+                error!(
+                    "failed to add alias {} to device {}",
+                    self.alias,
+                    self.get_name()
+                );
+            }
+
+            return Ok(self.name.clone());
+        }
+
+        Err(NexusBdevError::BdevNotFound {
+            name: self.name.clone(),
+        })
+    }
+
+    /// Destroy the delay bdev, leaving the base bdev in place
+    async fn destroy(self: Box<Self>) -> Result<(), Self::Error> {
+        if let Some(bdev) = Bdev::lookup_by_name(&self.name) {
+            let (s, r) = oneshot::channel::<ErrnoResult<()>>();
+            unsafe {
+                spdk_sys::delete_delay_disk(
+                    bdev.as_ptr(),
+                    Some(done_errno_cb),
+                    cb_arg(s),
+                )
+            };
+
+            r.await
+                .context(nexus_uri::CancelBdev {
+                    name: self.name.clone(),
+                })?
+                .context(nexus_uri::DestroyBdev {
+                    name: self.name,
+                })
+        } else {
+            Err(NexusBdevError::BdevNotFound {
+                name: self.name,
+            })
+        }
+    }
+}
@@ -26,12 +26,13 @@ use snafu::ResultExt;
 use url::Url;
 
 use crate::{
-    bdev::{BdevCreateDestroy, SpdkBlockDevice, Uri},
+    bdev::{util::uri, BdevCreateDestroy, SpdkBlockDevice, Uri},
     core::{BlockDevice, BlockDeviceDescriptor, CoreError},
     nexus_uri::{self, NexusBdevError},
+    subsys::Config,
 };
 
-use super::{aio, loopback, malloc, null, nvme, nvmx, uring};
+use super::{aio, delay, loopback, malloc, null, nvme, nvmx, uring, util};
 
 impl Uri {
     pub fn parse(
@@ -47,12 +48,16 @@ impl Uri {
         match url.scheme() {
             "aio" => Ok(Box::new(aio::Aio::try_from(&url)?)),
             "bdev" => Ok(Box::new(loopback::Loopback::try_from(&url)?)),
+            "delay" => Ok(Box::new(delay::Delay::try_from(&url)?)),
             "loopback" => Ok(Box::new(loopback::Loopback::try_from(&url)?)),
             "malloc" => Ok(Box::new(malloc::Malloc::try_from(&url)?)),
             "null" => Ok(Box::new(null::Null::try_from(&url)?)),
             "nvmf" => Ok(Box::new(nvmx::NvmfDeviceTemplate::try_from(&url)?)),
             "pcie" => Ok(Box::new(nvme::NVMe::try_from(&url)?)),
-            "uring" => Ok(Box::new(uring::Uring::try_from(&url)?)),
+            "uring" if util::uring::kernel_version_support() => {
+                Ok(Box::new(uring::Uring::try_from(&url)?))
+            }
+            "uring" => Ok(Box::new(aio::Aio::try_from(&url)?)),
 
             scheme => Err(NexusBdevError::UriSchemeUnsupported {
                 scheme: scheme.to_string(),
@@ -61,16 +66,35 @@ impl Uri {
     }
 }
 
+/// Check the query parameters left over after parsing a device URI.
+/// In strict mode these are rejected, otherwise they are ignored with a
+/// warning. Strict mode is selected with the "strict" parameter, which is
+/// accepted by all device types, and defaults to the configured value.
 pub(crate) fn reject_unknown_parameters(
     url: &Url,
-    parameters: HashMap<String, String>,
+    mut parameters: HashMap<String, String>,
 ) -> Result<(), NexusBdevError> {
-    if !parameters.is_empty() {
-        let invalid_parameters = parameters
-            .iter()
-            .map(|(k, v)| format!("{}={}", k, v))
-            .collect::<Vec<_>>()
-            .join(", ");
+    let strict = match parameters.remove("strict") {
+        Some(value) => uri::boolean(&value, true).context(
+            nexus_uri::BoolParamParseError {
+                uri: url.to_string(),
+                parameter: String::from("strict"),
+            },
+        )?,
+        None => Config::get_or_init(Config::default).strict_uri_parameters,
+    };
+
+    if parameters.is_empty() {
+        return Ok(());
+    }
+
+    let invalid_parameters = parameters
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    if strict {
         Err(NexusBdevError::UriInvalid {
             uri: url.to_string(),
             message: format!(
@@ -79,6 +103,10 @@ pub(crate) fn reject_unknown_parameters(
             ),
         })
     } else {
+        warn!(
+            "ignoring unrecognized parameters of {}: {}",
+            url, invalid_parameters
+        );
         Ok(())
     }
 }
@@ -293,6 +293,9 @@ fn alloc_bdev_io_ctx(
 ) -> Result<*mut IoCtx, CoreError> {
     let pool = BDEV_IOCTX_POOL.get().unwrap();
     pool.get(ctx).ok_or_else(|| {
+        warn!(
+            "memory pool [bdev_io_ctx] exhausted, consider raising --bdev-pool-size"
+        );
         io_type_to_err(op, Errno::ENOMEM, offset_blocks, num_blocks)
     })
 }
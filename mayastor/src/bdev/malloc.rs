@@ -3,11 +3,17 @@
 //! that the backing memory is allocated from huge pages and not from the
 //! heap. IOW, you must ensure you do not run out of huge pages while using
 //! this.
+//!
+//! The disk reads back as zeroes unless it is filled at create time, either
+//! with a single byte, eg: `malloc:///m0?size_mb=64&fill=0xa5` or with pseudo
+//! random bytes which are the same for the same seed, eg:
+//! `malloc:///m0?size_mb=64&pattern=random:42`.
 use std::{collections::HashMap, convert::TryFrom};
 
 use async_trait::async_trait;
 use futures::channel::oneshot;
 use nix::errno::Errno;
+use rand::{rngs::StdRng, RngCore, SeedableRng};
 use snafu::ResultExt;
 use url::Url;
 
@@ -15,7 +21,7 @@ use spdk_sys::{create_malloc_disk, delete_malloc_disk};
 
 use crate::{
     bdev::{dev::reject_unknown_parameters, util::uri, CreateDestroy, GetName},
-    core::Bdev,
+    core::{Bdev, CoreError},
     ffihelper::{cb_arg, done_errno_cb, ErrnoResult, IntoCString},
     nexus_uri::{self, NexusBdevError},
 };
@@ -33,6 +39,98 @@ pub struct Malloc {
     blk_size: u32,
     /// uuid of the spdk bdev
     uuid: Option<uuid::Uuid>,
+    /// initial contents of the disk, zeroes if none
+    fill: Option<Fill>,
+}
+
+/// Initial contents of a malloc disk
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Fill {
+    /// every byte is set to the value
+    Byte(u8),
+    /// pseudo random bytes generated from the seed
+    Random(u64),
+}
+
+/// amount of data written at once when filling the disk
+const FILL_CHUNK_SIZE: u64 = 1 << 20;
+
+impl Fill {
+    /// Parses the `fill` and `pattern` parameters of the URI, at most one of
+    /// which may be given
+    fn parse(
+        uri: &Url,
+        fill: Option<String>,
+        pattern: Option<String>,
+    ) -> Result<Option<Self>, NexusBdevError> {
+        let invalid = |message: String| NexusBdevError::UriInvalid {
+            uri: uri.to_string(),
+            message,
+        };
+
+        match (fill, pattern) {
+            (Some(_), Some(_)) => Err(invalid(
+                "conflicting parameters fill and pattern are mutually exclusive"
+                    .to_string(),
+            )),
+            (Some(value), None) => {
+                let hex = value.trim_start_matches("0x");
+                if hex.is_empty() || hex.len() > 2 {
+                    return Err(invalid(format!(
+                        "invalid fill value {}, must be a hex byte",
+                        value
+                    )));
+                }
+                u8::from_str_radix(hex, 16).map(|b| Some(Self::Byte(b))).map_err(
+                    |_| {
+                        invalid(format!(
+                            "invalid fill value {}, must be a hex byte",
+                            value
+                        ))
+                    },
+                )
+            }
+            (None, Some(value)) => value
+                .strip_prefix("random:")
+                .and_then(|seed| seed.parse().ok())
+                .map(|seed| Some(Self::Random(seed)))
+                .ok_or_else(|| {
+                    invalid(format!(
+                        "invalid pattern {}, must be random:<seed>",
+                        value
+                    ))
+                }),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Writes the contents to the whole of the bdev
+    async fn write(self, bdev: &Bdev) -> Result<(), CoreError> {
+        let handle = bdev.open(true)?.into_handle()?;
+        let size = bdev.size_in_bytes();
+        let mut rng = match self {
+            Self::Random(seed) => Some(StdRng::seed_from_u64(seed)),
+            Self::Byte(_) => None,
+        };
+
+        let mut offset = 0;
+        while offset < size {
+            let len = std::cmp::min(FILL_CHUNK_SIZE, size - offset);
+            let mut buf = handle.dma_malloc(len).map_err(|_| {
+                CoreError::DmaAllocationError {
+                    size: len,
+                }
+            })?;
+            if let Some(rng) = rng.as_mut() {
+                rng.fill_bytes(buf.as_mut_slice());
+            } else if let Self::Byte(b) = self {
+                buf.fill(b);
+            }
+            handle.write_at(offset, &buf).await?;
+            offset += len;
+        }
+        Ok(())
+    }
 }
 
 impl TryFrom<&Url> for Malloc {
@@ -101,6 +199,12 @@ impl TryFrom<&Url> for Malloc {
             },
         )?;
 
+        let fill = Fill::parse(
+            uri,
+            parameters.remove("fill"),
+            parameters.remove("pattern"),
+        )?;
+
         reject_unknown_parameters(uri, parameters)?;
 
         Ok(Self {
@@ -113,6 +217,7 @@ impl TryFrom<&Url> for Malloc {
             } as u64,
             blk_size,
             uuid,
+            fill,
         })
     }
 }
@@ -159,6 +264,22 @@ impl CreateDestroy for Malloc {
                 bdev.set_uuid(uuid);
             }
 
+            if let Some(fill) = self.fill {
+                if let Err(source) = fill.write(&bdev).await {
+                    // do not leave a disk with partial contents behind
+                    if let Err(error) = delete_disk(&self.name).await {
+                        error!(
+                            "failed to delete malloc disk {}: {}",
+                            self.name, error
+                        );
+                    }
+                    return Err(NexusBdevError::FillBdev {
+                        source,
+                        name: self.name.clone(),
+                    });
+                }
+            }
+
             if !bdev.add_alias(&self.alias) {
                 error!(
                     "failed to add alias {} to device {}",
@@ -176,28 +297,29 @@ impl CreateDestroy for Malloc {
     }
 
     async fn destroy(self: Box<Self>) -> Result<(), Self::Error> {
-        if let Some(bdev) = Bdev::lookup_by_name(&self.name) {
-            let (s, r) = oneshot::channel::<ErrnoResult<()>>();
-
-            unsafe {
-                delete_malloc_disk(
-                    bdev.as_ptr(),
-                    Some(done_errno_cb),
-                    cb_arg(s),
-                );
-            }
+        delete_disk(&self.name).await
+    }
+}
 
-            r.await
-                .context(nexus_uri::CancelBdev {
-                    name: self.name.clone(),
-                })?
-                .context(nexus_uri::DestroyBdev {
-                    name: self.name,
-                })
-        } else {
-            Err(NexusBdevError::BdevNotFound {
-                name: self.name,
-            })
+/// Deletes the malloc disk with the given name
+async fn delete_disk(name: &str) -> Result<(), NexusBdevError> {
+    if let Some(bdev) = Bdev::lookup_by_name(name) {
+        let (s, r) = oneshot::channel::<ErrnoResult<()>>();
+
+        unsafe {
+            delete_malloc_disk(bdev.as_ptr(), Some(done_errno_cb), cb_arg(s));
         }
+
+        r.await
+            .context(nexus_uri::CancelBdev {
+                name: name.to_string(),
+            })?
+            .context(nexus_uri::DestroyBdev {
+                name: name.to_string(),
+            })
+    } else {
+        Err(NexusBdevError::BdevNotFound {
+            name: name.to_string(),
+        })
     }
 }
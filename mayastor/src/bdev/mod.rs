@@ -5,16 +5,35 @@ pub use device::{bdev_io_ctx_pool_init, SpdkBlockDevice};
 pub use nexus::{
     nexus_bdev::{
         nexus_create,
+        nexus_create_cancellable,
+        nexus_create_dry_run,
         nexus_create_v2,
+        nexus_create_with_block_len,
         nexus_lookup,
         Nexus,
         NexusNvmeParams,
+        NexusReadPolicy,
         NexusState,
         NexusStatus,
+        NexusWritePolicy,
         VerboseError,
     },
-    nexus_child::{lookup_nexus_child, ChildState, Reason},
-    nexus_label::{GptEntry, GptGuid as Guid, GptHeader},
+    nexus_child::{
+        lookup_nexus_child,
+        ChildState,
+        ConfigReason,
+        Reason,
+        RebuildRole,
+        ReservationOp,
+    },
+    nexus_event::{self, ChildEvent, ChildFaultedEvent, ChildUnreachableEvent},
+    nexus_label::{
+        GptEntry,
+        GptGuid as Guid,
+        GptHeader,
+        LabelPolicy,
+        NexusLabelStatus,
+    },
     nexus_metadata::{
         MetaDataChildEntry,
         MetaDataIndex,
@@ -24,13 +43,17 @@ pub use nexus::{
     nexus_persistence::{ChildInfo, NexusInfo},
 };
 pub use nvmx::{
+    discover,
     nvme_io_ctx_pool_init,
+    utils::NvmeHealthLog,
     NvmeController,
     NvmeControllerState,
+    NvmeTransportId,
     NVME_CONTROLLERS,
 };
 
 mod aio;
+mod delay;
 pub(crate) mod dev;
 pub(crate) mod device;
 mod loopback;
@@ -24,8 +24,10 @@ pub mod nexus_bdev;
 pub mod nexus_bdev_children;
 pub mod nexus_bdev_rebuild;
 pub mod nexus_bdev_snapshot;
+pub mod nexus_bdev_verify;
 mod nexus_channel;
 pub(crate) mod nexus_child;
+pub mod nexus_event;
 pub mod nexus_fn_table;
 pub mod nexus_io;
 pub mod nexus_label;
@@ -131,7 +133,11 @@ pub async fn nexus_children_to_destroying_state() {
     info!("setting all nexus children to destroying state...");
     for nexus in instances() {
         for child in nexus.children.iter() {
-            child.set_state(nexus_child::ChildState::Destroying);
+            if let Err(error) =
+                child.set_state(nexus_child::ChildState::Destroying)
+            {
+                error!("{}: {}", nexus.name, error);
+            }
         }
     }
     info!("set all nexus children to destroying state");
@@ -5,6 +5,7 @@
 //! application needs synchronous mirroring may be required.
 
 use std::{
+    collections::VecDeque,
     env,
     fmt::{Display, Formatter},
     os::raw::c_void,
@@ -12,18 +13,23 @@ use std::{
 };
 
 use crossbeam::atomic::AtomicCell;
-use futures::channel::oneshot;
+use futures::{
+    channel::oneshot,
+    future::{self, Either},
+};
 use nix::errno::Errno;
 use serde::Serialize;
 use snafu::{ResultExt, Snafu};
 use tonic::{Code, Status};
 
-use rpc::mayastor::NvmeAnaState;
+use rpc::{mayastor::NvmeAnaState, validate::parse_uuid};
 use spdk_sys::{spdk_bdev, spdk_bdev_register, spdk_bdev_unregister};
 
 use crate::{
     bdev::{
+        device_create,
         device_destroy,
+        device_lookup,
         nexus::{
             self,
             instances,
@@ -48,13 +54,14 @@ use crate::{
         IoType,
         Protocol,
         Reactor,
+        Reactors,
         Share,
         MWQ,
     },
     ffihelper::errno_result_from_i32,
-    nexus_uri::NexusBdevError,
-    rebuild::RebuildError,
-    subsys::{NvmfError, NvmfSubsystem},
+    nexus_uri::{bdev_get_name, NexusBdevError},
+    rebuild::{RebuildError, RebuildRecord},
+    subsys::{Config, NvmfError, NvmfSubsystem},
 };
 
 pub static NVME_MIN_CNTLID: u16 = 1;
@@ -141,7 +148,10 @@ pub enum Error {
     },
     #[snafu(display("Deferring open because nexus {} is incomplete", name))]
     NexusIncomplete { name: String },
-    #[snafu(display("Children of nexus {} have mixed block sizes", name))]
+    #[snafu(display(
+        "Children of nexus {} have mixed block sizes or a block size other than requested",
+        name
+    ))]
     MixedBlockSizes { name: String },
     #[snafu(display(
         "Child {} of nexus {} has incompatible size or block size",
@@ -151,6 +161,17 @@ pub enum Error {
     ChildGeometry { child: String, name: String },
     #[snafu(display("Child {} of nexus {} cannot be found", child, name))]
     ChildMissing { child: String, name: String },
+    #[snafu(display(
+        "Nexus {} has {} healthy children, {} required",
+        name,
+        healthy,
+        required
+    ))]
+    NotEnoughHealthyChildren {
+        name: String,
+        healthy: u32,
+        required: u32,
+    },
     #[snafu(display("Child {} of nexus {} has no error store", child, name))]
     ChildMissingErrStore { child: String, name: String },
     #[snafu(display(
@@ -201,12 +222,50 @@ pub enum Error {
     },
     #[snafu(display("Child {} of nexus {} not found", child, name))]
     ChildNotFound { child: String, name: String },
+    #[snafu(display(
+        "Write policy of nexus {} leaves no child written synchronously",
+        name
+    ))]
+    WritePolicyNoSyncChild { name: String },
+    #[snafu(display(
+        "NVMe reservation operation failed on child {} of nexus {}",
+        child,
+        name
+    ))]
+    ChildReservation {
+        source: ChildError,
+        child: String,
+        name: String,
+    },
+    #[snafu(display(
+        "Failed to set the I/O timeout of child {} of nexus {}",
+        child,
+        name
+    ))]
+    ChildIoTimeout {
+        source: ChildError,
+        child: String,
+        name: String,
+    },
     #[snafu(display("Child {} of nexus {} already exists", child, name))]
     ChildAlreadyExists { child: String, name: String },
     #[snafu(display("Failed to pause child {} of nexus {}", child, name))]
     PauseChild { child: String, name: String },
     #[snafu(display("Suitable rebuild source for nexus {} not found", name))]
     NoRebuildSource { name: String },
+    #[snafu(display(
+        "Rebuild destination {} of nexus {} is too small: {} bytes, {} bytes required",
+        child,
+        name,
+        child_size,
+        required
+    ))]
+    RebuildDestinationTooSmall {
+        child: String,
+        name: String,
+        child_size: u64,
+        required: u64,
+    },
     #[snafu(display(
         "Failed to create rebuild job for child {} of nexus {}",
         child,
@@ -255,6 +314,8 @@ pub enum Error {
     InvalidArguments { name: String, args: String },
     #[snafu(display("Failed to create nexus {}", name))]
     NexusCreate { name: String },
+    #[snafu(display("Creation of nexus {} was cancelled", name))]
+    NexusCreateCancelled { name: String },
     #[snafu(display("Failed to destroy nexus {}", name))]
     NexusDestroy { name: String },
     #[snafu(display(
@@ -274,6 +335,14 @@ pub enum Error {
     FailedCreateSnapshot { name: String, source: CoreError },
     #[snafu(display("NVMf subsystem error: {}", e))]
     SubsysNvmf { e: String },
+    #[snafu(display("Invalid range to verify nexus {}: {}", name, reason))]
+    VerifyInvalidRange { name: String, reason: String },
+    #[snafu(display("Failed to read child {} of nexus {}", child, name))]
+    VerifyIo {
+        source: CoreError,
+        name: String,
+        child: String,
+    },
     #[snafu(display("failed to pause {} current state {:?}", name, state))]
     Pause {
         state: NexusPauseState,
@@ -319,6 +388,13 @@ impl From<Error> for tonic::Status {
             Error::ChildGeometry {
                 ..
             } => Status::invalid_argument(e.to_string()),
+            Error::OpenChild {
+                source:
+                    ChildError::AlreadyClaimed {
+                        ..
+                    },
+                ..
+            } => Status::failed_precondition(e.verbose()),
             Error::OpenChild {
                 ..
             } => Status::invalid_argument(e.to_string()),
@@ -328,6 +404,28 @@ impl From<Error> for tonic::Status {
             Error::ChildNotFound {
                 ..
             } => Status::not_found(e.to_string()),
+            Error::WritePolicyNoSyncChild {
+                ..
+            } => Status::invalid_argument(e.to_string()),
+            Error::ChildIoTimeout {
+                source: ChildError::ChildIoTimeoutUnsupported {},
+                ..
+            } => Status::failed_precondition(e.verbose()),
+            Error::RebuildDestinationTooSmall {
+                ..
+            } => Status::invalid_argument(e.to_string()),
+            Error::NotEnoughHealthyChildren {
+                ..
+            } => Status::failed_precondition(e.to_string()),
+            Error::VerifyInvalidRange {
+                ..
+            } => Status::invalid_argument(e.to_string()),
+            Error::InvalidArguments {
+                ..
+            } => Status::invalid_argument(e.to_string()),
+            Error::NexusCreateCancelled {
+                ..
+            } => Status::cancelled(e.to_string()),
             e => Status::new(Code::Internal, e.to_string()),
         }
     }
@@ -403,6 +501,8 @@ pub struct Nexus {
     pub state: parking_lot::Mutex<NexusState>,
     /// the offset in num blocks where the data partition starts
     pub data_ent_offset: u64,
+    /// opened in label recovery mode, none of the children has a valid label
+    pub(crate) no_label: bool,
     /// the handle to be used when sharing the nexus, this allows for the bdev
     /// to be shared with vbdevs on top
     pub(crate) share_handle: Option<String>,
@@ -410,11 +510,26 @@ pub struct Nexus {
     pub nexus_target: Option<NexusTarget>,
     /// Nexus I/O device.
     pub io_device: Option<IoDevice>,
+    /// reactor core the I/O device was registered on
+    pub(crate) core: u32,
+    /// reactor core of the first I/O channel of the nexus still in use
+    pub(crate) io_core: AtomicCell<Option<u32>>,
     /// Nexus pause counter to allow concurrent pause/resume.
     pause_state: AtomicCell<NexusPauseState>,
     pause_waiters: Vec<oneshot::Sender<i32>>,
     /// information saved to a persistent store
     pub nexus_info: futures::lock::Mutex<NexusInfo>,
+    /// when writes are acknowledged with respect to the children
+    pub(crate) write_policy: NexusWritePolicy,
+    /// which children reads are sent to
+    pub(crate) read_policy: NexusReadPolicy,
+    /// block size requested for the nexus, taken from the children if none
+    pub(crate) required_block_len: Option<u64>,
+    /// the most recently finished rebuilds, oldest first
+    pub(crate) rebuild_history: VecDeque<RebuildRecord>,
+    /// transfer rate limit of the rebuilds in bytes per second, 0 if
+    /// unlimited
+    pub(crate) rebuild_rate_limit: u64,
 }
 
 unsafe impl core::marker::Sync for Nexus {}
@@ -430,6 +545,58 @@ pub enum NexusStatus {
     Online,
 }
 
+#[derive(Debug, Serialize, Clone, PartialEq)]
+/// Determines when a write to the nexus is acknowledged.
+pub enum NexusWritePolicy {
+    /// once all children have committed it
+    WriteThroughAll,
+    /// once all children but the slow ones have committed it. The data is
+    /// copied and written to the slow children in the background, a slow
+    /// child failing such a write is faulted so that it gets rebuilt. Reads
+    /// are not sent to the slow children, overlapping writes reach them in
+    /// the order they were submitted to the nexus and flushes wait for the
+    /// writes in the background. Once the data copied for a slow child
+    /// exceeds `background_write_max_mb`, writes wait for it as well.
+    WriteBack { slow_children: Vec<String> },
+}
+
+impl Default for NexusWritePolicy {
+    fn default() -> Self {
+        Self::WriteThroughAll
+    }
+}
+
+impl NexusWritePolicy {
+    /// true if writes to the child are acknowledged before it commits them
+    pub fn is_slow(&self, child: &str) -> bool {
+        match self {
+            Self::WriteThroughAll => false,
+            Self::WriteBack {
+                slow_children,
+            } => slow_children.iter().any(|c| c == child),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+/// Determines which of the healthy children a read is sent to.
+pub enum NexusReadPolicy {
+    /// rotate between all children
+    RoundRobin,
+    /// rotate between the children local to the nexus, or between all
+    /// children when none is local
+    PreferLocal,
+    /// read from a single child, a local one if any, moving on to the next
+    /// child only once it is removed from the I/O path
+    FailoverOnly,
+}
+
+impl Default for NexusReadPolicy {
+    fn default() -> Self {
+        Self::RoundRobin
+    }
+}
+
 #[derive(Debug, Serialize, Clone, Copy, PartialEq, PartialOrd)]
 pub enum NexusState {
     /// nexus created but no children attached
@@ -524,14 +691,22 @@ impl Nexus {
             state: parking_lot::Mutex::new(NexusState::Init),
             bdev_raw: Box::into_raw(b),
             data_ent_offset: 0,
+            no_label: false,
             share_handle: None,
             size,
             nexus_target: None,
             nvme_params,
             io_device: None,
+            core: Cores::current(),
+            io_core: AtomicCell::new(None),
             pause_state: AtomicCell::new(NexusPauseState::Unpaused),
             pause_waiters: Vec::new(),
             nexus_info: futures::lock::Mutex::new(Default::default()),
+            write_policy: NexusWritePolicy::default(),
+            read_policy: NexusReadPolicy::default(),
+            required_block_len: None,
+            rebuild_history: VecDeque::new(),
+            rebuild_rate_limit: 0,
         });
 
         // set the UUID of the underlying bdev
@@ -553,7 +728,7 @@ impl Nexus {
     /// Generate a new UUID if specified uuid is None (or invalid)
     pub fn set_uuid(&mut self, uuid: Option<&str>) {
         match uuid {
-            Some(s) => match uuid::Uuid::parse_str(s) {
+            Some(s) => match parse_uuid(s) {
                 Ok(u) => {
                     self.bdev.set_uuid(u);
                     info!("UUID set to {} for nexus {}", u, self.name);
@@ -638,6 +813,16 @@ impl Nexus {
             });
         }
 
+        // open without labels if recovery is requested and none are valid,
+        // rather than overwriting them
+        if Config::get().nexus_opts.label_recovery
+            && self.recover_child_labels().await.context(ReadLabel {
+                name: self.name.clone(),
+            })?
+        {
+            return Ok(());
+        }
+
         // update child labels as necessary
         if let Err(error) = self.update_child_labels().await {
             warn!("error updating child labels: {}", error);
@@ -651,6 +836,91 @@ impl Nexus {
         Ok(())
     }
 
+    /// Write fresh labels to the children of a nexus that was opened in
+    /// label recovery mode, after which it is no longer degraded.
+    pub async fn rewrite_labels(&mut self) -> Result<(), Error> {
+        if !self.no_label {
+            return Ok(());
+        }
+
+        info!("{}: rewriting labels of all children", self.name);
+        self.create_child_labels().await.context(WriteLabel {
+            name: self.name.clone(),
+        })?;
+        self.no_label = false;
+
+        Ok(())
+    }
+
+    /// Check if the nexus was opened without valid labels
+    pub fn is_label_recovery(&self) -> bool {
+        self.no_label
+    }
+
+    /// Returns the write policy of the nexus.
+    pub fn write_policy(&self) -> &NexusWritePolicy {
+        &self.write_policy
+    }
+
+    /// Change when writes are acknowledged. At least one child must be left
+    /// to be written synchronously, and to serve reads, it must not have any
+    /// writes in the background left from a previous policy.
+    pub async fn set_write_policy(
+        &mut self,
+        policy: NexusWritePolicy,
+    ) -> Result<(), Error> {
+        if let NexusWritePolicy::WriteBack {
+            slow_children,
+        } = &policy
+        {
+            if let Some(child) = slow_children
+                .iter()
+                .find(|c| !self.children.iter().any(|n| &n.name == *c))
+            {
+                return Err(Error::ChildNotFound {
+                    child: child.clone(),
+                    name: self.name.clone(),
+                });
+            }
+
+            if self
+                .children
+                .iter()
+                .all(|c| policy.is_slow(&c.name) || c.background_io() > 0)
+            {
+                return Err(Error::WritePolicyNoSyncChild {
+                    name: self.name.clone(),
+                });
+            }
+        }
+
+        info!("{}: setting write policy {:?}", self.name, policy);
+        self.write_policy = policy;
+        self.reconfigure(DrEvent::WritePolicy).await;
+
+        Ok(())
+    }
+
+    /// Returns the read policy of the nexus.
+    pub fn read_policy(&self) -> NexusReadPolicy {
+        self.read_policy
+    }
+
+    /// Change which children reads are sent to.
+    pub async fn set_read_policy(&mut self, policy: NexusReadPolicy) {
+        info!("{}: setting read policy {:?}", self.name, policy);
+        self.read_policy = policy;
+        self.reconfigure(DrEvent::ReadPolicy).await;
+    }
+
+    /// Returns the reactor core the nexus is serviced on, which is the core
+    /// its first I/O channel was created on. Without I/O channels this is
+    /// the core that owns the nexus I/O device, where management of the
+    /// device (reconfiguration, child retire, etc.) is serviced.
+    pub fn core(&self) -> u32 {
+        self.io_core.load().unwrap_or(self.core)
+    }
+
     /// close the nexus and any children that are open
     pub(crate) fn destruct(&mut self) -> NexusState {
         // a closed operation might already be in progress calling unregister
@@ -1011,6 +1281,7 @@ impl Nexus {
                 self.persist(PersistOp::Create).await;
                 self.set_state(NexusState::Open);
                 self.io_device = Some(io_device);
+                self.core = Cores::current();
                 Ok(())
             }
             Err(err) => {
@@ -1072,11 +1343,12 @@ impl Nexus {
             NexusState::Init => NexusStatus::Degraded,
             NexusState::Closed => NexusStatus::Faulted,
             NexusState::Open | NexusState::Reconfiguring => {
-                if self
-                    .children
-                    .iter()
-                    // All children are online, so the Nexus is also online
-                    .all(|c| c.state() == ChildState::Open)
+                if !self.no_label
+                    && self
+                        .children
+                        .iter()
+                        // All children are online, so the Nexus is also online
+                        .all(|c| c.state() == ChildState::Open)
                 {
                     NexusStatus::Online
                 } else if self
@@ -1113,7 +1385,62 @@ pub async fn nexus_create(
         size,
         uuid,
         NexusNvmeParams::default(),
+        None,
+        children,
+        None,
+    )
+    .await
+}
+
+/// As nexus_create, with the block size of the nexus given rather than taken
+/// from its children. All children must have the given block size.
+pub async fn nexus_create_with_block_len(
+    name: &str,
+    size: u64,
+    block_len: u64,
+    uuid: Option<&str>,
+    children: &[String],
+) -> Result<(), Error> {
+    check_block_len(name, block_len)?;
+
+    nexus_create_internal(
+        name,
+        size,
+        uuid,
+        NexusNvmeParams::default(),
+        Some(block_len),
+        children,
+        None,
+    )
+    .await
+}
+
+/// As nexus_create_with_block_len, with a block size of None meaning that of
+/// the children, but the creation is abandoned once `cancel` receives a value
+/// or its sender is dropped. The creation of each child is raced against the
+/// cancellation, a child still being created when cancelled is destroyed in
+/// the background once its creation completes. The children created so far
+/// are closed and destroyed, so that none of them is left claimed.
+pub async fn nexus_create_cancellable(
+    name: &str,
+    size: u64,
+    block_len: Option<u64>,
+    uuid: Option<&str>,
+    children: &[String],
+    cancel: oneshot::Receiver<()>,
+) -> Result<(), Error> {
+    if let Some(block_len) = block_len {
+        check_block_len(name, block_len)?;
+    }
+
+    nexus_create_internal(
+        name,
+        size,
+        uuid,
+        NexusNvmeParams::default(),
+        block_len,
         children,
+        Some(cancel),
     )
     .await
 }
@@ -1151,7 +1478,64 @@ pub async fn nexus_create_v2(
         });
     }
 
-    nexus_create_internal(name, size, uuid, nvme_params, children).await
+    nexus_create_internal(name, size, uuid, nvme_params, None, children, None)
+        .await
+}
+
+fn check_block_len(name: &str, block_len: u64) -> Result<(), Error> {
+    if !block_len.is_power_of_two() {
+        let args = format!("invalid block size {}", block_len);
+        error!("failed to create nexus {}: {}", name, args);
+        return Err(Error::InvalidArguments {
+            name: name.to_owned(),
+            args,
+        });
+    }
+    Ok(())
+}
+
+/// A create is cancelled once a value is sent or the sender is dropped.
+fn create_cancelled(cancel: &mut Option<oneshot::Receiver<()>>) -> bool {
+    match cancel {
+        Some(cancel) => !matches!(cancel.try_recv(), Ok(None)),
+        None => false,
+    }
+}
+
+/// Create the device of a child unless the create is cancelled first, in
+/// which case None is returned. The creation of a device cannot be
+/// interrupted, so a device still being created is destroyed once created.
+async fn create_child_device(
+    uri: &str,
+    cancel: &mut Option<oneshot::Receiver<()>>,
+) -> Option<Result<String, NexusBdevError>> {
+    let create = {
+        let uri = uri.to_string();
+        Box::pin(async move { device_create(&uri).await })
+    };
+
+    let cancel = match cancel {
+        Some(cancel) => cancel,
+        None => return Some(create.await),
+    };
+
+    match future::select(create, cancel).await {
+        Either::Left((result, _)) => Some(result),
+        Either::Right((_, create)) => {
+            let uri = uri.to_string();
+            Reactors::master().send_future(async move {
+                if create.await.is_ok() {
+                    if let Err(error) = device_destroy(&uri).await {
+                        error!(
+                            "failed to destroy child {} of a cancelled nexus: {}",
+                            uri, error
+                        );
+                    }
+                }
+            });
+            None
+        }
+    }
 }
 
 async fn nexus_create_internal(
@@ -1159,7 +1543,9 @@ async fn nexus_create_internal(
     size: u64,
     uuid: Option<&str>,
     nvme_params: NexusNvmeParams,
+    block_len: Option<u64>,
     children: &[String],
+    mut cancel: Option<oneshot::Receiver<()>>,
 ) -> Result<(), Error> {
     // global variable defined in the nexus module
     let nexus_list = instances();
@@ -1180,7 +1566,9 @@ async fn nexus_create_internal(
     // closing a child assumes that the nexus to which it belongs will appear
     // in the global list of nexus instances. We must also ensure that the
     // nexus instance gets removed from the global list if an error occurs.
-    nexus_list.push(Nexus::new(name, size, uuid, nvme_params, None));
+    let mut nexus = Nexus::new(name, size, uuid, nvme_params, None);
+    nexus.required_block_len = block_len;
+    nexus_list.push(nexus);
 
     // Obtain a reference to the newly created Nexus object.
     let ni =
@@ -1192,20 +1580,36 @@ async fn nexus_create_internal(
             })?;
 
     for child in children {
-        if let Err(error) = ni.create_and_register(child).await {
-            error!(
-                "failed to create nexus {}: failed to create child {}: {}",
-                name, child, error
-            );
-            ni.close_children().await;
-            nexus_list.retain(|n| n.name != name);
-            return Err(Error::CreateChild {
-                source: error,
-                name: String::from(name),
-            });
+        if create_cancelled(&mut cancel) {
+            break;
+        }
+        match create_child_device(child, &mut cancel).await {
+            Some(Ok(device)) => ni.register_child(child, &device),
+            Some(Err(error)) => {
+                error!(
+                    "failed to create nexus {}: failed to create child {}: {}",
+                    name, child, error
+                );
+                ni.close_children().await;
+                nexus_list.retain(|n| n.name != name);
+                return Err(Error::CreateChild {
+                    source: error,
+                    name: String::from(name),
+                });
+            }
+            None => break,
         }
     }
 
+    if create_cancelled(&mut cancel) {
+        warn!("creation of nexus {} cancelled", name);
+        ni.close_children().await;
+        nexus_list.retain(|n| n.name != name);
+        return Err(Error::NexusCreateCancelled {
+            name: String::from(name),
+        });
+    }
+
     match ni.open().await {
         Err(Error::NexusIncomplete {
             ..
@@ -1239,6 +1643,79 @@ async fn nexus_create_internal(
     }
 }
 
+/// Check that a nexus could be created from the given children without
+/// creating it. Every child URI must resolve to a device at least as large as
+/// the nexus, and all children must have the same block size, which must be
+/// `block_len` if one is given. Devices created for the check are destroyed
+/// again, nothing is claimed.
+pub async fn nexus_create_dry_run(
+    name: &str,
+    size: u64,
+    block_len: Option<u64>,
+    children: &[String],
+) -> Result<(), Error> {
+    if let Some(block_len) = block_len {
+        check_block_len(name, block_len)?;
+    }
+
+    let mut nexus_block_len = block_len;
+
+    for child in children {
+        let device_name = bdev_get_name(child).context(CreateChild {
+            name: name.to_owned(),
+        })?;
+
+        // leave devices which exist already alone, they may be in use
+        let existed = device_lookup(&device_name).is_some();
+        if !existed {
+            device_create(child).await.context(CreateChild {
+                name: name.to_owned(),
+            })?;
+        }
+
+        let geometry = device_lookup(&device_name)
+            .map(|dev| (dev.size_in_bytes(), dev.block_len()));
+
+        if !existed {
+            if let Err(error) = device_destroy(child).await {
+                warn!(
+                    "dry run of nexus {}: failed to destroy child {}: {}",
+                    name, child, error
+                );
+            }
+        }
+
+        let (child_size, block_len) =
+            geometry.ok_or_else(|| Error::OpenChild {
+                source: ChildError::ChildInaccessible {},
+                child: child.clone(),
+                name: name.to_owned(),
+            })?;
+
+        if size > child_size {
+            return Err(Error::OpenChild {
+                source: ChildError::ChildTooSmall {
+                    child_size,
+                    parent_size: size,
+                },
+                child: child.clone(),
+                name: name.to_owned(),
+            });
+        }
+
+        match nexus_block_len {
+            Some(len) if len != block_len => {
+                return Err(Error::MixedBlockSizes {
+                    name: name.to_owned(),
+                });
+            }
+            _ => nexus_block_len = Some(block_len),
+        }
+    }
+
+    Ok(())
+}
+
 /// Lookup a nexus by its name (currently used only by test functions).
 pub fn nexus_lookup(name: &str) -> Option<&mut Nexus> {
     instances()
@@ -36,6 +36,8 @@ use crate::{
         lookup_nexus_child,
         nexus::{
             nexus_bdev::{
+                ChildIoTimeout,
+                ChildReservation,
                 CreateChild,
                 Error,
                 Nexus,
@@ -44,13 +46,18 @@ use crate::{
                 OpenChild,
             },
             nexus_channel::DrEvent,
-            nexus_child::{ChildState, NexusChild},
+            nexus_child::{
+                ChildState,
+                ConfigReason,
+                NexusChild,
+                ReservationOp,
+            },
+            nexus_event::{self, ChildUnreachableEvent},
         },
         Reason,
         VerboseError,
     },
     core::DeviceEventType,
-    nexus_uri::NexusBdevError,
 };
 
 impl Nexus {
@@ -72,22 +79,17 @@ impl Nexus {
             .for_each(drop);
     }
 
-    /// Create and register a single child to nexus, only allowed during the
-    /// nexus init phase
-    pub async fn create_and_register(
-        &mut self,
-        uri: &str,
-    ) -> Result<(), NexusBdevError> {
+    /// Register a single child to nexus whose device has been created, only
+    /// allowed during the nexus init phase
+    pub(crate) fn register_child(&mut self, uri: &str, device: &str) {
         assert_eq!(*self.state.lock(), NexusState::Init);
-        let name = device_create(uri).await?;
         self.children.push(NexusChild::new(
             uri.to_string(),
             self.name.clone(),
-            device_lookup(&name),
+            device_lookup(device),
         ));
 
         self.child_count += 1;
-        Ok(())
     }
 
     /// add a new child to an existing nexus. note that the child is added and
@@ -178,7 +180,8 @@ impl Nexus {
             self.name.clone(),
             Some(child_bdev),
         );
-        let mut child_name = child.open(self.size);
+        let mut child_name =
+            child.open(self.size, u64::from(self.bdev.block_len()));
         if let Ok(ref name) = child_name {
             // we have created the bdev, and created a nexusChild struct. To
             // make use of the device itself the
@@ -306,6 +309,59 @@ impl Nexus {
         Ok(self.status())
     }
 
+    /// Perform an NVMe reservation operation on a child, allowing the control
+    /// plane to fence other hosts from writing to it.
+    pub async fn child_reservation(
+        &self,
+        name: &str,
+        op: ReservationOp,
+    ) -> Result<(), Error> {
+        trace!("{}: reservation {:?} on child {}", self.name, op, name);
+
+        let child = self
+            .children
+            .iter()
+            .find(|c| c.get_name() == name)
+            .ok_or_else(|| Error::ChildNotFound {
+                name: self.name.clone(),
+                child: name.to_owned(),
+            })?;
+
+        child.reservation(op).await.context(ChildReservation {
+            child: name.to_owned(),
+            name: self.name.clone(),
+        })
+    }
+
+    /// Set the I/O timeout of a child, `None` restoring the global default.
+    pub fn set_child_io_timeout(
+        &mut self,
+        name: &str,
+        timeout_us: Option<u64>,
+    ) -> Result<(), Error> {
+        trace!(
+            "{}: set I/O timeout {:?} of child {}",
+            self.name,
+            timeout_us,
+            name
+        );
+
+        let nexus_name = self.name.clone();
+        let child = self
+            .children
+            .iter_mut()
+            .find(|c| c.get_name() == name)
+            .ok_or_else(|| Error::ChildNotFound {
+                name: nexus_name.clone(),
+                child: name.to_owned(),
+            })?;
+
+        child.set_io_timeout(timeout_us).context(ChildIoTimeout {
+            child: name.to_owned(),
+            name: nexus_name,
+        })
+    }
+
     /// fault a child device and reconfigure the IO channels
     pub async fn fault_child(
         &mut self,
@@ -375,7 +431,15 @@ impl Nexus {
         if let Some(child) =
             self.children.iter_mut().find(|c| c.get_name() == name)
         {
-            child.online(self.size).await.context(OpenChild {
+            let block_len = u64::from(self.bdev.block_len());
+            // a faulted child is brought back in place
+            let result = match child.state() {
+                ChildState::Faulted(reason) if reason != Reason::OutOfSync => {
+                    child.reopen(self.size, block_len).await
+                }
+                _ => child.online(self.size, block_len).await,
+            };
+            result.context(OpenChild {
                 child: name.to_owned(),
                 name: self.name.clone(),
             })?;
@@ -419,6 +483,25 @@ impl Nexus {
                     }
                 }
             }
+            DeviceEventType::KeepAliveTimeout => {
+                match lookup_nexus_child(device) {
+                    Some(child) => {
+                        warn!(
+                            "{}: child {} is unreachable, its keep-alive timed out",
+                            child.get_nexus_name(),
+                            child.get_name(),
+                        );
+                        nexus_event::emit(ChildUnreachableEvent {
+                            nexus: child.get_nexus_name().to_string(),
+                            child: child.get_name().to_string(),
+                        });
+                    }
+                    None => warn!(
+                        "No nexus child exists for device {}, ignoring keep-alive timeout",
+                        device
+                    ),
+                }
+            }
             _ => {
                 info!("Ignoring {:?} event for device {}", event, device);
             }
@@ -435,13 +518,24 @@ impl Nexus {
             });
         }
 
-        let blk_size = self.children[0].get_device().unwrap().block_len();
+        let blk_size = self.required_block_len.unwrap_or_else(|| {
+            self.children[0].get_device().unwrap().block_len()
+        });
 
-        if self
+        let mismatched = self
             .children
             .iter()
-            .any(|b| b.get_device().unwrap().block_len() != blk_size)
-        {
+            .filter(|c| c.get_device().unwrap().block_len() != blk_size)
+            .collect::<Vec<_>>();
+
+        if !mismatched.is_empty() {
+            mismatched.iter().for_each(|c| {
+                if let Err(error) = c.set_state(ChildState::ConfigInvalid(
+                    ConfigReason::BlockSizeMismatch,
+                )) {
+                    error!("{}: {}", self.name, error);
+                }
+            });
             return Err(Error::MixedBlockSizes {
                 name: self.name.clone(),
             });
@@ -454,7 +548,7 @@ impl Nexus {
         let (opened, failed): (Vec<usize>, Vec<usize>) = (0 .. self
             .children
             .len())
-            .partition(|&i| match self.children[i].open(size) {
+            .partition(|&i| match self.children[i].open(size, blk_size) {
                 Ok(name) => {
                     info!("{}: opened child {}", self.name, name);
                     self.register_child_event_listener(&self.children[i]);
@@ -549,6 +643,24 @@ impl Nexus {
         }
     }
 
+    /// Check that at least `min` children are healthy, i.e. open and in sync
+    /// with the nexus.
+    pub fn check_healthy_children(&self, min: u32) -> Result<(), Error> {
+        let healthy = self
+            .children
+            .iter()
+            .filter(|c| c.state() == ChildState::Open)
+            .count() as u32;
+        if healthy < min {
+            return Err(Error::NotEnoughHealthyChildren {
+                name: self.name.clone(),
+                healthy,
+                required: min,
+            });
+        }
+        Ok(())
+    }
+
     /// The nexus is allowed to be smaller then the underlying child devices
     /// this function returns the smallest blkcnt of all online children as
     /// they MAY vary in size.
@@ -1,7 +1,11 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use futures::channel::oneshot::Receiver;
 use snafu::ResultExt;
 
 use rpc::mayastor::{
+    RebuildHistoryRecord,
+    RebuildHistoryReply,
     RebuildProgressReply,
     RebuildStateReply,
     RebuildStatsReply,
@@ -29,20 +33,43 @@ use crate::{
         ClientOperations,
         RebuildError,
         RebuildJob,
+        RebuildRecord,
         RebuildState,
         RebuildStats,
     },
 };
 
+/// Number of finished rebuilds kept in the history of a nexus
+pub(crate) const REBUILD_HISTORY_LEN: usize = 16;
+
 impl Nexus {
     /// Starts a rebuild job and returns a receiver channel
     /// which can be used to await the rebuild completion
     pub async fn start_rebuild(
         &mut self,
         name: &str,
+    ) -> Result<Receiver<RebuildState>, Error> {
+        self.start_rebuild_with_verify(name, 0).await
+    }
+
+    /// Starts a rebuild job which, once the copy is done, reads back
+    /// `verify_percent` of the rebuilt segments from both the source and the
+    /// destination and fails the rebuild if they differ. 0 disables the
+    /// verification.
+    pub async fn start_rebuild_with_verify(
+        &mut self,
+        name: &str,
+        verify_percent: u32,
     ) -> Result<Receiver<RebuildState>, Error> {
         trace!("{}: start rebuild request for {}", self.name, name);
 
+        if verify_percent > 100 {
+            return Err(Error::InvalidArguments {
+                name: self.name.clone(),
+                args: format!("invalid verify percentage {}", verify_percent),
+            });
+        }
+
         let src_child_name = match self
             .children
             .iter()
@@ -72,14 +99,36 @@ impl Nexus {
                 }),
             }?;
 
+        let range = std::ops::Range::<u64> {
+            start: self.data_ent_offset,
+            end: self.bdev.num_blocks() + self.data_ent_offset,
+        };
+
+        // fail up front rather than when the rebuild reaches the end of a
+        // destination which cannot hold all of the data of the nexus
+        if let Some(Ok(dev)) = self
+            .children
+            .iter()
+            .find(|c| c.get_name() == name)
+            .map(|c| c.get_device())
+        {
+            let child_size = dev.size_in_bytes();
+            let required = range.end * u64::from(self.bdev.block_len());
+            if child_size < required {
+                return Err(Error::RebuildDestinationTooSmall {
+                    child: name.to_owned(),
+                    name: self.name.clone(),
+                    child_size,
+                    required,
+                });
+            }
+        }
+
         let job = RebuildJob::create(
             &self.name,
             &src_child_name,
             &dst_child_name,
-            std::ops::Range::<u64> {
-                start: self.data_ent_offset,
-                end: self.bdev.num_blocks() + self.data_ent_offset,
-            },
+            range,
             |nexus, job| {
                 Reactors::current().send_future(async move {
                     Nexus::notify_rebuild(nexus, job).await;
@@ -90,6 +139,8 @@ impl Nexus {
             child: name.to_owned(),
             name: self.name.clone(),
         })?;
+        job.set_rate_limit(self.rebuild_rate_limit);
+        job.set_verify_percent(verify_percent);
 
         // We're now rebuilding the `dst_child` which means it HAS to become an
         // active participant in the frontend nexus bdev for Writes.
@@ -156,6 +207,53 @@ impl Nexus {
         })
     }
 
+    /// Limits the transfer rate of the rebuilds of the nexus to
+    /// `bytes_per_sec`, 0 removes the limit. Applies to the running rebuilds
+    /// as well as to the ones started later.
+    pub fn set_rebuild_throttle(&mut self, bytes_per_sec: u64) {
+        info!(
+            "{}: setting rebuild rate limit to {} B/s",
+            self.name, bytes_per_sec
+        );
+        self.rebuild_rate_limit = bytes_per_sec;
+        for job in self.get_rebuild_jobs() {
+            job.set_rate_limit(bytes_per_sec);
+        }
+    }
+
+    /// Pause all rebuild jobs of the nexus which are running or about to.
+    /// Jobs which are paused already are left alone, returns the number of
+    /// jobs which were paused.
+    pub async fn pause_rebuilds(&mut self) -> Result<u32, Error> {
+        let mut paused = 0;
+        for job in self.get_rebuild_jobs() {
+            if job.target_state() == RebuildState::Running {
+                job.as_client().pause().context(RebuildOperation {
+                    job: job.destination.clone(),
+                    name: self.name.clone(),
+                })?;
+                paused += 1;
+            }
+        }
+        Ok(paused)
+    }
+
+    /// Resume all paused rebuild jobs of the nexus, returns the number of
+    /// jobs which were resumed.
+    pub async fn resume_rebuilds(&mut self) -> Result<u32, Error> {
+        let mut resumed = 0;
+        for job in self.get_rebuild_jobs() {
+            if job.target_state() == RebuildState::Paused {
+                job.as_client().resume().context(RebuildOperation {
+                    job: job.destination.clone(),
+                    name: self.name.clone(),
+                })?;
+                resumed += 1;
+            }
+        }
+        Ok(resumed)
+    }
+
     /// Return the state of a rebuild job
     pub async fn get_rebuild_state(
         &mut self,
@@ -182,12 +280,36 @@ impl Nexus {
         name: &str,
     ) -> Result<RebuildProgressReply, Error> {
         let rj = self.get_rebuild_job(name)?;
+        let stats = rj.as_client().stats();
 
         Ok(RebuildProgressReply {
-            progress: rj.as_client().stats().progress as u32,
+            progress: stats.progress as u32,
+            rate_bytes_per_sec: stats.rate_bytes_per_sec,
+            eta_seconds: stats.eta_seconds,
+            rate_limit_bytes_per_sec: rj.rate_limit(),
         })
     }
 
+    /// Returns the most recently finished rebuilds of the nexus, oldest first
+    pub fn get_rebuild_history(&self) -> RebuildHistoryReply {
+        RebuildHistoryReply {
+            records: self
+                .rebuild_history
+                .iter()
+                .map(RebuildHistoryRecord::from)
+                .collect(),
+        }
+    }
+
+    /// Adds a finished rebuild to the history, dropping the oldest record
+    /// once the history is full
+    fn record_rebuild(&mut self, record: RebuildRecord) {
+        if self.rebuild_history.len() == REBUILD_HISTORY_LEN {
+            self.rebuild_history.pop_front();
+        }
+        self.rebuild_history.push_back(record);
+    }
+
     /// Cancels all rebuilds jobs associated with the child.
     /// Returns a list of rebuilding children whose rebuild job was cancelled.
     pub async fn cancel_child_rebuild_jobs(&self, name: &str) -> Vec<String> {
@@ -235,6 +357,15 @@ impl Nexus {
         jobs
     }
 
+    /// Return the rebuild jobs with a child of the nexus as destination
+    fn get_rebuild_jobs(&self) -> Vec<&mut RebuildJob> {
+        self.children
+            .iter()
+            .filter_map(|c| RebuildJob::lookup(&c.name).ok())
+            .filter(|job| job.nexus == self.name)
+            .collect()
+    }
+
     /// Return rebuild job associated with the dest child name.
     /// Return error if no rebuild job associated with it.
     fn get_rebuild_job<'a>(
@@ -260,11 +391,17 @@ impl Nexus {
 
         match job.state() {
             RebuildState::Completed => {
-                recovering_child.set_state(ChildState::Open);
-                info!(
-                    "Child {} has been rebuilt successfully",
-                    recovering_child.get_name()
-                );
+                match recovering_child.set_state(ChildState::Open) {
+                    Ok(()) => info!(
+                        "Child {} has been rebuilt successfully",
+                        recovering_child.get_name()
+                    ),
+                    Err(error) => error!(
+                        "Child {} has been rebuilt but cannot be opened: {}",
+                        recovering_child.get_name(),
+                        error
+                    ),
+                }
             }
             RebuildState::Stopped => {
                 info!(
@@ -315,6 +452,7 @@ impl Nexus {
             return Ok(());
         }
 
+        self.record_rebuild(j.to_record());
         let complete_err = self.on_rebuild_complete_job(j).await;
         let remove_err = RebuildJob::remove(&job)
             .context(RemoveRebuildJob {
@@ -356,3 +494,24 @@ impl From<RebuildStats> for RebuildStatsReply {
         }
     }
 }
+
+/// Milliseconds since the UNIX epoch
+fn epoch_ms(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+impl From<&RebuildRecord> for RebuildHistoryRecord {
+    fn from(record: &RebuildRecord) -> Self {
+        RebuildHistoryRecord {
+            child_uri: record.destination.clone(),
+            src_uri: record.source.clone(),
+            state: record.state.to_string(),
+            error: record.error.clone(),
+            start_time_ms: epoch_ms(record.start_time),
+            end_time_ms: epoch_ms(record.end_time),
+            bytes_recovered: record.bytes_recovered,
+        }
+    }
+}
@@ -0,0 +1,204 @@
+//! Compares the data held by the children of a nexus, to detect children
+//! which diverged from each other, e.g. after a split brain. Every range is
+//! read in chunks from each open child and the checksum of each chunk is
+//! compared against the one of the first open child. Writes to the nexus
+//! while it is being verified may be reported as mismatches.
+
+use crc::crc32::{self, Hasher32};
+use rpc::mayastor::{NexusChildMismatch, NexusRange, VerifyNexusReply};
+use snafu::ResultExt;
+
+use crate::{
+    bdev::nexus::{
+        nexus_bdev::{Error, Nexus, VerifyIo},
+        nexus_child::ChildState,
+    },
+    core::{BlockDeviceHandle, CoreError},
+};
+
+/// size of the reads issued when comparing the children
+const VERIFY_IO_SIZE: u64 = 1 << 20;
+/// largest nexus which can be compared in full
+pub const VERIFY_FULL_SCAN_MAX: u64 = 4 << 30;
+
+impl Nexus {
+    /// Compare the given ranges, or the whole nexus if `full_scan` is set,
+    /// across all open children.
+    pub async fn verify(
+        &self,
+        ranges: Vec<NexusRange>,
+        full_scan: bool,
+    ) -> Result<VerifyNexusReply, Error> {
+        let ranges = self.verify_ranges(ranges, full_scan)?;
+
+        let children = self
+            .children
+            .iter()
+            .filter(|child| child.state() == ChildState::Open)
+            .collect::<Vec<_>>();
+
+        if children.len() < 2 {
+            return Err(Error::NotEnoughHealthyChildren {
+                name: self.name.clone(),
+                healthy: children.len() as u32,
+                required: 2,
+            });
+        }
+
+        let handles = children
+            .iter()
+            .map(|child| {
+                child.get_io_handle().context(VerifyIo {
+                    name: self.name.clone(),
+                    child: child.name.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let data_offset =
+            self.data_ent_offset * u64::from(self.bdev.block_len());
+        let mut mismatches: Vec<Vec<NexusRange>> = vec![vec![]; children.len()];
+        let mut compared = 0;
+
+        for range in &ranges {
+            let end = range.offset + range.length;
+            let mut offset = range.offset;
+
+            while offset < end {
+                let length = VERIFY_IO_SIZE.min(end - offset);
+                let mut reference = None;
+
+                for (i, handle) in handles.iter().enumerate() {
+                    let checksum = chunk_checksum(
+                        handle.as_ref(),
+                        data_offset + offset,
+                        length,
+                    )
+                    .await
+                    .context(VerifyIo {
+                        name: self.name.clone(),
+                        child: children[i].name.clone(),
+                    })?;
+
+                    match reference {
+                        None => reference = Some(checksum),
+                        Some(reference) if reference == checksum => {}
+                        Some(_) => {
+                            add_mismatch(&mut mismatches[i], offset, length)
+                        }
+                    }
+                }
+
+                compared += length;
+                offset += length;
+            }
+        }
+
+        let mismatches = children
+            .iter()
+            .zip(mismatches)
+            .filter(|(_, ranges)| !ranges.is_empty())
+            .map(|(child, ranges)| {
+                warn!(
+                    "{}: child {} differs from {} in {} range(s)",
+                    self.name,
+                    child.name,
+                    children[0].name,
+                    ranges.len()
+                );
+                NexusChildMismatch {
+                    uri: child.name.clone(),
+                    ranges,
+                }
+            })
+            .collect();
+
+        Ok(VerifyNexusReply {
+            reference: children[0].name.clone(),
+            bytes_compared: compared,
+            mismatches,
+        })
+    }
+
+    /// validate the ranges to compare against the nexus
+    fn verify_ranges(
+        &self,
+        ranges: Vec<NexusRange>,
+        full_scan: bool,
+    ) -> Result<Vec<NexusRange>, Error> {
+        let size = self.size();
+        let invalid = |reason: String| Error::VerifyInvalidRange {
+            name: self.name.clone(),
+            reason,
+        };
+
+        if full_scan {
+            if size > VERIFY_FULL_SCAN_MAX {
+                return Err(invalid(format!(
+                    "nexus of {} bytes is too large for a full scan, limit is {}",
+                    size, VERIFY_FULL_SCAN_MAX
+                )));
+            }
+            return Ok(vec![NexusRange {
+                offset: 0,
+                length: size,
+            }]);
+        }
+
+        if ranges.is_empty() {
+            return Err(invalid("no ranges given".into()));
+        }
+
+        let block_len = u64::from(self.bdev.block_len());
+        for range in &ranges {
+            if range.length == 0
+                || range.offset % block_len != 0
+                || range.length % block_len != 0
+                || range
+                    .offset
+                    .checked_add(range.length)
+                    .map_or(true, |end| end > size)
+            {
+                return Err(invalid(format!(
+                    "range {}+{} is empty, not aligned to {} bytes or exceeds the nexus size {}",
+                    range.offset, range.length, block_len, size
+                )));
+            }
+        }
+
+        Ok(ranges)
+    }
+}
+
+/// read a chunk of a block device and return its checksum, also used by the
+/// rebuild to verify the segments it copied
+pub(crate) async fn chunk_checksum(
+    handle: &dyn BlockDeviceHandle,
+    offset: u64,
+    length: u64,
+) -> Result<u32, CoreError> {
+    let mut buf = handle.dma_malloc(length).map_err(|_| {
+        CoreError::DmaAllocationError {
+            size: length,
+        }
+    })?;
+    handle.read_at(offset, &mut buf).await?;
+
+    let mut digest = crc32::Digest::new(crc32::IEEE);
+    digest.write(buf.as_slice());
+    Ok(digest.sum32())
+}
+
+/// record a mismatching chunk, merging it with the previous one if adjacent
+fn add_mismatch(ranges: &mut Vec<NexusRange>, offset: u64, length: u64) {
+    if let Some(last) = ranges.last_mut() {
+        if last.offset + last.length == offset {
+            last.length += length;
+            return;
+        }
+    }
+    ranges.push(NexusRange {
+        offset,
+        length,
+    });
+}
@@ -15,7 +15,13 @@ use spdk_sys::{
 };
 
 use crate::{
-    bdev::{nexus::nexus_child::ChildState, Nexus, Reason},
+    bdev::{
+        nexus::nexus_child::{is_local_device, ChildState, NexusChild},
+        Nexus,
+        NexusReadPolicy,
+        NexusWritePolicy,
+        Reason,
+    },
     core::{BlockDeviceHandle, Cores, Mthread},
 };
 
@@ -29,7 +35,14 @@ pub(crate) struct NexusChannel {
 #[repr(C)]
 pub(crate) struct NexusChannelInner {
     pub(crate) writers: Vec<Box<dyn BlockDeviceHandle>>,
+    /// writers of the slow children, which are not awaited on write
+    pub(crate) async_writers: Vec<Box<dyn BlockDeviceHandle>>,
+    /// readers, the ones of the children local to the nexus first. Children
+    /// with writes in the background are left out as their data may be stale
     pub(crate) readers: Vec<Box<dyn BlockDeviceHandle>>,
+    /// number of readers of local children
+    local_readers: usize,
+    read_policy: NexusReadPolicy,
     pub(crate) previous: usize,
     pub(crate) fail_fast: u32,
     device: *mut c_void,
@@ -39,9 +52,10 @@ impl Debug for NexusChannelInner {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "readers = {}, writers = {}",
+            "readers = {}, writers = {}, async writers = {}",
             self.readers.len(),
-            self.writers.len()
+            self.writers.len(),
+            self.async_writers.len()
         )
     }
 }
@@ -79,6 +93,10 @@ pub enum DrEvent {
     ChildRemove,
     /// Child rebuild event
     ChildRebuild,
+    /// the write policy of the nexus changed
+    WritePolicy,
+    /// the read policy of the nexus changed
+    ReadPolicy,
 }
 
 impl NexusChannelInner {
@@ -87,11 +105,19 @@ impl NexusChannelInner {
     /// not the case but a side effect of using the async. As we poll
     /// threads more often depending on what core we are on etc, we might be
     /// "awaiting' while the thread is already trying to submit IO.
+    /// The read policy of the nexus limits the rotation to the first readers.
     pub(crate) fn child_select(&mut self) -> Option<usize> {
         if self.readers.is_empty() {
             None
         } else {
-            if self.previous < self.readers.len() - 1 {
+            let candidates = match self.read_policy {
+                NexusReadPolicy::PreferLocal if self.local_readers > 0 => {
+                    self.local_readers
+                }
+                NexusReadPolicy::FailoverOnly => 1,
+                _ => self.readers.len(),
+            };
+            if self.previous < candidates - 1 {
                 self.previous += 1;
             } else {
                 self.previous = 0;
@@ -100,6 +126,22 @@ impl NexusChannelInner {
         }
     }
 
+    /// Set the readers, moving the ones of local children first.
+    fn set_readers(
+        &mut self,
+        nexus: &Nexus,
+        readers: Vec<Box<dyn BlockDeviceHandle>>,
+    ) {
+        let (mut local, remote): (Vec<_>, Vec<_>) = readers
+            .into_iter()
+            .partition(|r| is_local_device(r.get_device()));
+
+        self.local_readers = local.len();
+        local.extend(remote);
+        self.readers = local;
+        self.read_policy = nexus.read_policy;
+    }
+
     /// Remove a child from the readers and/or writers
     pub fn remove_child(&mut self, name: &str) -> bool {
         self.previous = 0;
@@ -116,10 +158,16 @@ impl NexusChannelInner {
             self.writers.len(),
             self.readers.len(),
         );
+        self.local_readers -= self.readers[.. self.local_readers]
+            .iter()
+            .filter(|c| c.get_device().device_name() == name)
+            .count();
         self.readers
             .retain(|c| c.get_device().device_name() != name);
         self.writers
             .retain(|c| c.get_device().device_name() != name);
+        self.async_writers
+            .retain(|c| c.get_device().device_name() != name);
 
         trace!(?name,
             "core: {} thread: {}: New number of IO channels write:{} read:{} out of {} children",
@@ -151,14 +199,60 @@ impl NexusChannelInner {
                 }
             })
             .any(|c| {
-                ChildState::Open
+                let faulted = ChildState::Open
                     == c.state.compare_and_swap(
                         ChildState::Open,
                         ChildState::Faulted(Reason::IoError),
-                    )
+                    );
+                if faulted {
+                    c.faulted(Reason::IoError);
+                }
+                faulted
             })
     }
 
+    /// Record an I/O error on the child with the given device name.
+    pub fn child_io_error(&self, name: &str) {
+        let nexus = unsafe { Nexus::from_raw(self.device) };
+        if let Some(child) = nexus.children.iter().find(|c| {
+            matches!(c.get_device(), Ok(device) if device.device_name() == name)
+        }) {
+            child.io_error();
+        }
+    }
+
+    /// A child only serves reads when it is written synchronously and none of
+    /// its writes in the background is left.
+    fn is_reader(policy: &NexusWritePolicy, child: &NexusChild) -> bool {
+        !policy.is_slow(&child.name) && child.background_io() == 0
+    }
+
+    /// Split off the writers of the children which the write policy of the
+    /// nexus does not wait for. When no other writer would be left, all
+    /// writers are waited for.
+    fn partition_writers(
+        nexus: &Nexus,
+        writers: Vec<Box<dyn BlockDeviceHandle>>,
+    ) -> (
+        Vec<Box<dyn BlockDeviceHandle>>,
+        Vec<Box<dyn BlockDeviceHandle>>,
+    ) {
+        let (slow, sync): (Vec<_>, Vec<_>) =
+            writers.into_iter().partition(|w| {
+                let name = w.get_device().device_name();
+                nexus.children.iter().any(|c| {
+                    nexus.write_policy.is_slow(&c.name)
+                        && matches!(c.get_device(), Ok(d) if d.device_name() == name)
+                })
+            });
+
+        if sync.is_empty() {
+            (slow, Vec::new())
+        } else {
+            (sync, slow)
+        }
+    }
+
     /// Refreshing our channels simply means that we either have a child going
     /// online or offline. We don't know which child has gone, or was added, so
     /// we simply put back all the channels, and reopen the bdevs that are in
@@ -190,23 +284,38 @@ impl NexusChannelInner {
 
         let mut writers = Vec::new();
         let mut readers = Vec::new();
+        let policy = nexus.write_policy.clone();
 
         // iterate over all our children which are in the open state
         nexus
             .children
             .iter_mut()
             .filter(|c| c.state() == ChildState::Open)
-            .for_each(|c| match (c.get_io_handle(), c.get_io_handle()) {
-                (Ok(w), Ok(r)) => {
-                    writers.push(w);
-                    readers.push(r);
-                }
-                _ => {
-                    c.set_state(ChildState::Faulted(Reason::CantOpen));
-                    error!("failed to get I/O handle for {}", c.get_name());
+            .for_each(|c| {
+                let reader = if Self::is_reader(&policy, c) {
+                    c.get_io_handle().map(Some)
+                } else {
+                    Ok(None)
+                };
+                match (c.get_io_handle(), reader) {
+                    (Ok(w), Ok(r)) => {
+                        writers.push(w);
+                        readers.extend(r);
+                    }
+                    _ => {
+                        if let Err(error) =
+                            c.set_state(ChildState::Faulted(Reason::CantOpen))
+                        {
+                            error!("{}", error);
+                        }
+                        error!("failed to get I/O handle for {}", c.get_name());
+                    }
                 }
             });
 
+        let (mut writers, async_writers) =
+            Self::partition_writers(nexus, writers);
+
         // then add write-only children
         if !self.readers.is_empty() {
             nexus
@@ -217,17 +326,23 @@ impl NexusChannelInner {
                     if let Ok(hdl) = c.get_io_handle() {
                         writers.push(hdl);
                     } else {
-                        c.set_state(ChildState::Faulted(Reason::CantOpen));
+                        if let Err(error) =
+                            c.set_state(ChildState::Faulted(Reason::CantOpen))
+                        {
+                            error!("{}", error);
+                        }
                         error!("failed to get I/O handle for {}", c.get_name());
                     }
                 });
         }
 
         self.writers.clear();
+        self.async_writers.clear();
         self.readers.clear();
 
         self.writers = writers;
-        self.readers = readers;
+        self.async_writers = async_writers;
+        self.set_readers(nexus, readers);
 
         trace!(
             "{}: New number of IO channels write:{} read:{} out of {} children",
@@ -250,29 +365,55 @@ impl NexusChannel {
         let nexus = unsafe { Nexus::from_raw(device) };
         debug!("{}: Creating IO channels at {:p}", nexus.bdev.name(), ctx);
 
+        let _ = nexus.io_core.compare_exchange(None, Some(Cores::current()));
+
         let ch = NexusChannel::from_raw(ctx);
         let mut channels = Box::new(NexusChannelInner {
             writers: Vec::new(),
+            async_writers: Vec::new(),
             readers: Vec::new(),
+            local_readers: 0,
+            read_policy: nexus.read_policy,
             previous: 0,
             device,
             fail_fast: 0,
         });
 
+        let policy = nexus.write_policy.clone();
         nexus
             .children
             .iter_mut()
             .filter(|c| c.state() == ChildState::Open)
-            .for_each(|c| match (c.get_io_handle(), c.get_io_handle()) {
-                (Ok(w), Ok(r)) => {
-                    channels.writers.push(w);
-                    channels.readers.push(r);
-                }
-                _ => {
-                    c.set_state(ChildState::Faulted(Reason::CantOpen));
-                    error!("Failed to get I/O handle for {}, skipping block device", c.get_name())
+            .for_each(|c| {
+                let reader = if NexusChannelInner::is_reader(&policy, c) {
+                    c.get_io_handle().map(Some)
+                } else {
+                    Ok(None)
+                };
+                match (c.get_io_handle(), reader) {
+                    (Ok(w), Ok(r)) => {
+                        channels.writers.push(w);
+                        channels.readers.extend(r);
+                    }
+                    _ => {
+                        if let Err(error) =
+                            c.set_state(ChildState::Faulted(Reason::CantOpen))
+                        {
+                            error!("{}", error);
+                        }
+                        error!("Failed to get I/O handle for {}, skipping block device", c.get_name())
+                    }
                 }
             });
+
+        let (writers, async_writers) = NexusChannelInner::partition_writers(
+            nexus,
+            std::mem::take(&mut channels.writers),
+        );
+        channels.writers = writers;
+        channels.async_writers = async_writers;
+        let readers = std::mem::take(&mut channels.readers);
+        channels.set_readers(nexus, readers);
         ch.inner = Box::into_raw(channels);
         0
     }
@@ -281,9 +422,12 @@ impl NexusChannel {
     pub(crate) extern "C" fn destroy(device: *mut c_void, ctx: *mut c_void) {
         let nexus = unsafe { Nexus::from_raw(device) };
         debug!("{} Destroying IO channels", nexus.bdev.name());
+        let _ = nexus.io_core.compare_exchange(Some(Cores::current()), None);
         let inner = NexusChannel::from_raw(ctx).inner_mut();
         inner.writers.clear();
+        inner.async_writers.clear();
         inner.readers.clear();
+        inner.local_readers = 0;
     }
 
     /// function called when we receive a Dynamic Reconfigure event (DR)
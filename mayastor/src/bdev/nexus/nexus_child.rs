@@ -1,7 +1,15 @@
-use std::fmt::{Debug, Display, Formatter};
+use std::{
+    convert::TryFrom,
+    fmt::{Debug, Display, Formatter},
+    os::raw::c_void,
+};
 
 use crossbeam::atomic::AtomicCell;
-use futures::{channel::mpsc, SinkExt, StreamExt};
+use futures::{
+    channel::{mpsc, oneshot},
+    SinkExt,
+    StreamExt,
+};
 use nix::errno::Errno;
 use serde::Serialize;
 use snafu::{ResultExt, Snafu};
@@ -15,8 +23,10 @@ use crate::{
             instances,
             nexus_channel::DrEvent,
             nexus_child::ChildState::Faulted,
+            nexus_event::{self, ChildFaultedEvent},
         },
         nexus_lookup,
+        nvmx::nvme_bdev_running_config,
         Guid,
         VerboseError,
     },
@@ -24,12 +34,17 @@ use crate::{
         nvme_reservation_acquire_action,
         nvme_reservation_register_action,
         nvme_reservation_register_cptpl,
+        nvme_reservation_release_action,
         nvme_reservation_type,
+        Bdev,
         BlockDevice,
         BlockDeviceDescriptor,
         BlockDeviceHandle,
         CoreError,
+        DeviceTimeoutAction,
         DmaError,
+        IoCompletionStatus,
+        IoType,
         Reactor,
         Reactors,
     },
@@ -49,20 +64,44 @@ pub enum ChildError {
     ChildNotOffline {},
     #[snafu(display("Child is not closed"))]
     ChildNotClosed {},
+    #[snafu(display("Child is not faulted"))]
+    ChildNotFaulted {},
     #[snafu(display("Child is faulted, it cannot be reopened"))]
     ChildFaulted {},
     #[snafu(display("Child is being destroyed"))]
     ChildBeingDestroyed {},
+    #[snafu(display(
+        "Child {} cannot change state from {} to {}",
+        child,
+        from,
+        to
+    ))]
+    ChildStateChange {
+        child: String,
+        from: ChildState,
+        to: ChildState,
+    },
     #[snafu(display(
         "Child is smaller than parent {} vs {}",
         child_size,
         parent_size
     ))]
     ChildTooSmall { child_size: u64, parent_size: u64 },
+    #[snafu(display(
+        "Child block size differs from parent {} vs {}",
+        child_block_len,
+        parent_block_len
+    ))]
+    ChildBlockSizeMismatch {
+        child_block_len: u64,
+        parent_block_len: u64,
+    },
     #[snafu(display("Open child"))]
     OpenChild { source: CoreError },
     #[snafu(display("Claim child"))]
     ClaimChild { source: Errno },
+    #[snafu(display("Child is already claimed by {}", by))]
+    AlreadyClaimed { by: String },
     #[snafu(display("Child is inaccessible"))]
     ChildInaccessible {},
     #[snafu(display("Invalid state of child"))]
@@ -84,6 +123,8 @@ pub enum ChildError {
         source
     ))]
     ResvReport { source: CoreError },
+    #[snafu(display("Failed to release reservation for child: {}", source))]
+    ResvRelease { source: CoreError },
     #[snafu(display("Failed to get NVMe host ID: {}", source))]
     NvmeHostId { source: CoreError },
     #[snafu(display("Failed to create a BlockDevice for child {}", child))]
@@ -91,6 +132,29 @@ pub enum ChildError {
         child: String,
         source: NexusBdevError,
     },
+    #[snafu(display("I/O to child failed: {}", source))]
+    ChildIoError { source: CoreError },
+    #[snafu(display(
+        "Reading the {} of the child label timed out after {} ms",
+        part,
+        timeout_ms
+    ))]
+    LabelReadTimeout { part: String, timeout_ms: u64 },
+    #[snafu(display("Failed to destroy the BlockDevice of child {}", child))]
+    ChildBdevDestroy {
+        child: String,
+        source: NexusBdevError,
+    },
+    #[snafu(display("Child device does not support I/O timeouts"))]
+    ChildIoTimeoutUnsupported {},
+    #[snafu(display("Failed to set I/O timeout of child: {}", source))]
+    ChildIoTimeout { source: CoreError },
+}
+
+/// Determine if a block device is local to the nexus (i.e. on the same node).
+pub(crate) fn is_local_device(dev: &dyn BlockDevice) -> bool {
+    // A local device is not exported over nvme or iscsi.
+    dev.driver_name() != "nvme" && dev.driver_name() != "iscsi"
 }
 
 #[derive(Debug, Serialize, PartialEq, Deserialize, Eq, Copy, Clone)]
@@ -129,13 +193,36 @@ impl Display for Reason {
     }
 }
 
+/// Reason the configuration of a child is incompatible with its nexus
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+pub enum ConfigReason {
+    /// the child is smaller than the nexus
+    TooSmall,
+    /// the block size of the child differs from the nexus
+    BlockSizeMismatch,
+    /// the child cannot be written to, only reported for open children
+    ReadOnly,
+}
+
+impl Display for ConfigReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooSmall => write!(f, "The child is smaller than the nexus"),
+            Self::BlockSizeMismatch => {
+                write!(f, "The block size of the child differs from the nexus")
+            }
+            Self::ReadOnly => write!(f, "The child cannot be written to"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
 pub enum ChildState {
     /// child has not been opened, but we are in the process of opening it
     Init,
     /// cannot add this block device to the parent as its incompatible property
     /// wise
-    ConfigInvalid,
+    ConfigInvalid(ConfigReason),
     /// the child is open for RW
     Open,
     /// the child is being destroyed
@@ -151,7 +238,9 @@ impl Display for ChildState {
         match self {
             Self::Faulted(r) => write!(f, "Faulted with reason {}", r),
             Self::Init => write!(f, "Init"),
-            Self::ConfigInvalid => write!(f, "Config parameters are invalid"),
+            Self::ConfigInvalid(r) => {
+                write!(f, "Config parameters are invalid: {}", r)
+            }
             Self::Open => write!(f, "Child is open"),
             Self::Destroying => write!(f, "Child is being destroyed"),
             Self::Closed => write!(f, "Closed"),
@@ -159,17 +248,70 @@ impl Display for ChildState {
     }
 }
 
+impl ChildState {
+    /// Whether a child may change from this state to the given one.
+    fn can_transition_to(self, to: ChildState) -> bool {
+        use ChildState::*;
+        match (self, to) {
+            (from, to) if from == to => true,
+            // a child being destroyed is either closed or, when only its
+            // device is removed, returns to its former state but it never
+            // comes back to life
+            (Destroying, Open) | (Destroying, Faulted(Reason::OutOfSync)) => {
+                false
+            }
+            (Destroying, _) => true,
+            (_, Init) => false,
+            // only a rebuilt child becomes healthy after being faulted
+            (Faulted(Reason::OutOfSync), Open) => true,
+            (Faulted(_), Open) => false,
+            _ => true,
+        }
+    }
+}
+
+/// Role of a child in an ongoing rebuild of its nexus
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+pub enum RebuildRole {
+    /// the child is not involved in any rebuild
+    None,
+    /// the child is being read from to rebuild another child
+    Source,
+    /// the child is being rebuilt
+    Destination,
+}
+
+/// NVMe reservation operation to perform on a child, used by the control
+/// plane to fence hosts which should no longer write to a shared replica
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ReservationOp {
+    /// register the key of this host
+    Register { key: u64 },
+    /// unregister the key of this host
+    Unregister { key: u64 },
+    /// acquire a reservation of the given type
+    Acquire { key: u64, resv_type: u8 },
+    /// release a reservation of the given type held by this host
+    Release { key: u64, resv_type: u8 },
+    /// take over the reservation of the host registered with preempt_key
+    Preempt {
+        key: u64,
+        preempt_key: u64,
+        resv_type: u8,
+    },
+}
+
 #[derive(Serialize)]
 pub struct NexusChild {
     /// name of the parent this child belongs too
     parent: String,
 
-    /// current state of the child
+    /// current state of the child, only changed through set_state()
     #[serde(skip_serializing)]
-    pub state: AtomicCell<ChildState>,
+    state: AtomicCell<ChildState>,
     /// previous state of the child
     #[serde(skip_serializing)]
-    pub prev_state: AtomicCell<ChildState>,
+    prev_state: AtomicCell<ChildState>,
     #[serde(skip_serializing)]
     remove_channel: (mpsc::Sender<()>, mpsc::Receiver<()>),
     pub guid: Guid,
@@ -182,6 +324,17 @@ pub struct NexusChild {
     device: Option<Box<dyn BlockDevice>>,
     #[serde(skip_serializing)]
     device_descriptor: Option<Box<dyn BlockDeviceDescriptor>>,
+    /// number of I/O errors seen on the child
+    #[serde(skip_serializing)]
+    io_errors: AtomicCell<u64>,
+    /// I/O timeout of the child in microseconds, overriding the global
+    /// default of NVMe controllers
+    #[serde(skip_serializing)]
+    io_timeout_us: Option<u64>,
+    /// number of I/Os submitted to the child in the background, as a slow
+    /// child of the write policy, which have not completed yet
+    #[serde(skip_serializing)]
+    background_io: AtomicCell<u64>,
 }
 
 impl Debug for NexusChild {
@@ -200,16 +353,75 @@ impl Display for NexusChild {
 }
 
 impl NexusChild {
-    pub(crate) fn set_state(&self, state: ChildState) {
-        let prev_state = self.state.swap(state);
+    /// Change the state of the child. A change which is not allowed, like
+    /// opening a child which is being destroyed, is refused with an error and
+    /// the child keeps its current state.
+    pub(crate) fn set_state(
+        &self,
+        state: ChildState,
+    ) -> Result<(), ChildError> {
+        let prev_state = self.state.load();
+        if !prev_state.can_transition_to(state) {
+            return Err(ChildError::ChildStateChange {
+                child: self.name.clone(),
+                from: prev_state,
+                to: state,
+            });
+        }
+        self.state.store(state);
         self.prev_state.store(prev_state);
-        trace!(
+        debug!(
             "{}: child {}: state change from {} to {}",
-            self.parent,
-            self.name,
-            prev_state.to_string(),
-            state.to_string(),
+            self.parent, self.name, prev_state, state,
         );
+        if let Faulted(reason) = state {
+            if prev_state != state {
+                self.faulted(reason);
+            }
+        }
+        Ok(())
+    }
+
+    /// Raise the event for a child which became faulted. An out-of-sync child
+    /// is merely degraded as it is about to be rebuilt.
+    pub(crate) fn faulted(&self, reason: Reason) {
+        if reason == Reason::OutOfSync {
+            return;
+        }
+        nexus_event::emit(ChildFaultedEvent {
+            nexus: self.parent.clone(),
+            child: self.name.clone(),
+            reason,
+            io_errors: self.io_errors(),
+        });
+    }
+
+    /// Record an I/O error seen on the child.
+    pub(crate) fn io_error(&self) {
+        self.io_errors.fetch_add(1);
+    }
+
+    /// Number of I/O errors seen on the child.
+    pub fn io_errors(&self) -> u64 {
+        self.io_errors.load()
+    }
+
+    /// Number of I/Os submitted to the child in the background which have
+    /// not completed yet. The child does not serve reads until there is none
+    /// left, as it may not hold the latest data before.
+    pub fn background_io(&self) -> u64 {
+        self.background_io.load()
+    }
+
+    /// Record an I/O submitted to the child in the background.
+    pub(crate) fn background_io_submitted(&self) {
+        self.background_io.fetch_add(1);
+    }
+
+    /// Record the completion of an I/O submitted to the child in the
+    /// background, returning true if it was the last one.
+    pub(crate) fn background_io_completed(&self) -> bool {
+        self.background_io.fetch_sub(1) == 1
     }
 
     /// Open the child in RW mode and claim the device to be ours. If the child
@@ -225,6 +437,7 @@ impl NexusChild {
     pub(crate) fn open(
         &mut self,
         parent_size: u64,
+        parent_block_len: u64,
     ) -> Result<String, ChildError> {
         trace!("{}: Opening child device {}", self.parent, self.name);
 
@@ -263,22 +476,69 @@ impl NexusChild {
                 self.parent, self.name, parent_size, child_size
             );
 
-            self.set_state(ChildState::ConfigInvalid);
+            self.set_state(ChildState::ConfigInvalid(ConfigReason::TooSmall))?;
             return Err(ChildError::ChildTooSmall {
                 parent_size,
                 child_size,
             });
         }
 
-        let desc = dev.open(true).map_err(|source| {
-            self.set_state(Faulted(Reason::CantOpen));
-            ChildError::OpenChild {
-                source,
+        let child_block_len = dev.block_len();
+        if parent_block_len != child_block_len {
+            error!(
+                "{}: child {} block size {} differs from parent block size {}",
+                self.parent, self.name, child_block_len, parent_block_len
+            );
+
+            self.set_state(ChildState::ConfigInvalid(
+                ConfigReason::BlockSizeMismatch,
+            ))?;
+            return Err(ChildError::ChildBlockSizeMismatch {
+                child_block_len,
+                parent_block_len,
+            });
+        }
+
+        if !dev.io_type_supported(IoType::Write) {
+            warn!("{}: child {} is read-only", self.parent, self.name);
+        }
+
+        let desc = match dev.open(true) {
+            Ok(desc) => desc,
+            Err(source) => {
+                self.set_state(Faulted(Reason::CantOpen))?;
+                // a claimed bdev cannot be opened for writing, report who
+                // holds the claim, typically another nexus, rather than the
+                // bare errno
+                return Err(match (&source, dev.claimed_by()) {
+                    (
+                        CoreError::OpenBdev {
+                            source: Errno::EPERM,
+                        },
+                        Some(by),
+                    ) => ChildError::AlreadyClaimed {
+                        by,
+                    },
+                    _ => ChildError::OpenChild {
+                        source,
+                    },
+                });
             }
-        })?;
+        };
         self.device_descriptor = Some(desc);
 
-        self.set_state(ChildState::Open);
+        // a device created when onlining the child starts off with the
+        // global defaults
+        if self.io_timeout_us.is_some() {
+            if let Err(error) = self.apply_io_timeout() {
+                warn!(
+                    "{}: failed to apply the I/O timeout of child {}: {}",
+                    self.parent, self.name, error
+                );
+            }
+        }
+
+        self.set_state(ChildState::Open)?;
 
         debug!("{}: child {} opened successfully", self.parent, self.name);
         Ok(self.name.clone())
@@ -341,6 +601,27 @@ impl NexusChild {
         Ok(())
     }
 
+    /// Release an NVMe reservation
+    async fn resv_release(
+        &self,
+        hdl: &dyn BlockDeviceHandle,
+        current_key: u64,
+        resv_type: u8,
+    ) -> Result<(), ChildError> {
+        hdl.nvme_resv_release(
+            current_key,
+            resv_type,
+            nvme_reservation_release_action::RELEASE,
+        )
+        .await
+        .context(ResvRelease {})?;
+        info!(
+            "{}: released reservation type {:x}h, current key {:0x}h on child {}",
+            self.parent, resv_type, current_key, self.name
+        );
+        Ok(())
+    }
+
     /// Get NVMe reservation report
     /// Returns: (key, host id) of write exclusive reservation holder
     async fn resv_report(
@@ -473,26 +754,81 @@ impl NexusChild {
         Ok(())
     }
 
+    /// Perform an NVMe reservation operation on the child.
+    pub(crate) async fn reservation(
+        &self,
+        op: ReservationOp,
+    ) -> Result<(), ChildError> {
+        let hdl = self.get_io_handle().context(HandleOpen {})?;
+        match op {
+            ReservationOp::Register {
+                key,
+            } => self
+                .resv_register(&*hdl, key)
+                .await
+                .context(ResvRegisterKey {}),
+            ReservationOp::Unregister {
+                key,
+            } => hdl
+                .nvme_resv_register(
+                    key,
+                    0,
+                    nvme_reservation_register_action::UNREGISTER_KEY,
+                    nvme_reservation_register_cptpl::NO_CHANGES,
+                )
+                .await
+                .context(ResvRegisterKey {}),
+            ReservationOp::Acquire {
+                key,
+                resv_type,
+            } => {
+                self.resv_acquire(
+                    &*hdl,
+                    key,
+                    0,
+                    nvme_reservation_acquire_action::ACQUIRE,
+                    resv_type,
+                )
+                .await
+            }
+            ReservationOp::Release {
+                key,
+                resv_type,
+            } => self.resv_release(&*hdl, key, resv_type).await,
+            ReservationOp::Preempt {
+                key,
+                preempt_key,
+                resv_type,
+            } => {
+                self.resv_acquire(
+                    &*hdl,
+                    key,
+                    preempt_key,
+                    nvme_reservation_acquire_action::PREEMPT,
+                    resv_type,
+                )
+                .await
+            }
+        }
+    }
+
     /// Fault the child with a specific reason.
     /// We do not close the child if it is out-of-sync because it will
     /// subsequently be rebuilt.
     pub(crate) async fn fault(&mut self, reason: Reason) {
-        match reason {
-            Reason::OutOfSync => {
-                self.set_state(ChildState::Faulted(reason));
-            }
-            _ => {
-                if let Err(e) = self.close().await {
-                    error!(
-                        "{}: child {} failed to close with error {}",
-                        self.parent,
-                        self.name,
-                        e.verbose()
-                    );
-                }
-                self.set_state(ChildState::Faulted(reason));
+        if reason != Reason::OutOfSync {
+            if let Err(e) = self.close().await {
+                error!(
+                    "{}: child {} failed to close with error {}",
+                    self.parent,
+                    self.name,
+                    e.verbose()
+                );
             }
         }
+        if let Err(error) = self.set_state(ChildState::Faulted(reason)) {
+            error!("{}: failed to fault child: {}", self.parent, error);
+        }
     }
 
     /// Set the child as temporarily offline
@@ -523,6 +859,7 @@ impl NexusChild {
     pub(crate) async fn online(
         &mut self,
         parent_size: u64,
+        parent_block_len: u64,
     ) -> Result<String, ChildError> {
         // Only online a child if it was previously set offline. Check for a
         // "Closed" state as that is what offlining a child will set it to.
@@ -542,16 +879,117 @@ impl NexusChild {
                         "{}: failed to lookup device after successful creation",
                         self.name,
                     );
+                    return Err(ChildError::ChildInaccessible {});
                 }
             }
             _ => return Err(ChildError::ChildNotClosed {}),
         }
 
-        let result = self.open(parent_size);
-        self.set_state(ChildState::Faulted(Reason::OutOfSync));
+        // a child which failed to open keeps the state telling why
+        let result = self.open(parent_size, parent_block_len);
+        if result.is_ok() {
+            self.set_state(ChildState::Faulted(Reason::OutOfSync))?;
+        }
         result
     }
 
+    /// Reopen a faulted child once its device has recovered, e.g. after a
+    /// transient failure of the disk. The block device, if it still exists,
+    /// is destroyed and the child is onlined again, out-of-sync so that it
+    /// gets rebuilt.
+    pub(crate) async fn reopen(
+        &mut self,
+        parent_size: u64,
+        parent_block_len: u64,
+    ) -> Result<String, ChildError> {
+        match self.state() {
+            // an out-of-sync child is still open and about to be rebuilt
+            ChildState::Faulted(reason) if reason != Reason::OutOfSync => {
+                info!(
+                    "{}: reopening child {} faulted with reason {}",
+                    self.parent, self.name, reason
+                );
+            }
+            _ => return Err(ChildError::ChildNotFaulted {}),
+        }
+
+        let exists = self.device.as_ref().map_or(false, |device| {
+            Bdev::lookup_by_name(&device.device_name()).is_some()
+        });
+        if exists {
+            self.close().await.context(ChildBdevDestroy {
+                child: self.name.clone(),
+            })?;
+        } else {
+            // the device went away together with its descriptor
+            self.device_descriptor.take();
+            self.device = None;
+        }
+
+        self.set_state(ChildState::Closed)?;
+        self.online(parent_size, parent_block_len).await
+    }
+
+    /// Get the I/O timeout of the child, if it overrides the global default.
+    pub fn io_timeout_us(&self) -> Option<u64> {
+        self.io_timeout_us
+    }
+
+    /// Set the I/O timeout of the child, `None` restoring the global default.
+    /// An I/O exceeding the timeout resets the controller of this child only,
+    /// so a slow child can tolerate more latency than the other children.
+    /// The timeout is kept when the device of the child is recreated.
+    pub(crate) fn set_io_timeout(
+        &mut self,
+        timeout_us: Option<u64>,
+    ) -> Result<(), ChildError> {
+        let previous = std::mem::replace(&mut self.io_timeout_us, timeout_us);
+        if self.device.is_none() {
+            return Ok(());
+        }
+
+        self.apply_io_timeout().map_err(|error| {
+            self.io_timeout_us = previous;
+            error
+        })
+    }
+
+    /// Apply the I/O timeout of the child to the controller of its device.
+    fn apply_io_timeout(&self) -> Result<(), ChildError> {
+        let mut controller = self
+            .device
+            .as_ref()
+            .and_then(|device| device.get_io_controller())
+            .ok_or(ChildError::ChildIoTimeoutUnsupported {})?;
+
+        let (action, timeout_us) = match self.io_timeout_us {
+            Some(timeout_us) => (DeviceTimeoutAction::Reset, timeout_us),
+            None => {
+                let defaults = nvme_bdev_running_config();
+                let action =
+                    DeviceTimeoutAction::try_from(defaults.action_on_timeout)
+                        .unwrap_or(DeviceTimeoutAction::Ignore);
+                (action, defaults.timeout_us)
+            }
+        };
+
+        controller
+            .set_timeout_action(action)
+            .context(ChildIoTimeout {})?;
+        controller
+            .set_timeout_us(timeout_us)
+            .context(ChildIoTimeout {})?;
+
+        info!(
+            "{}: child {} I/O timeout set to {} us, action {}",
+            self.parent,
+            self.name,
+            timeout_us,
+            action.to_string()
+        );
+        Ok(())
+    }
+
     /// Extract a UUID from a URI.
     pub(crate) fn uuid(uri: &str) -> Option<String> {
         let url = Url::parse(uri).expect("Failed to parse URI");
@@ -624,23 +1062,22 @@ impl NexusChild {
 
             state = self.prev_state.load();
         }
-        match state {
+        let changed = match state {
             ChildState::Open | Faulted(Reason::OutOfSync) => {
                 // Change the state of the child to ensure it is taken out of
                 // the I/O path when the nexus is reconfigured.
                 self.set_state(ChildState::Closed)
             }
             // leave the state into whatever we found it as
-            _ => {
-                if destroying {
-                    // Restore the previous state
-                    info!(
-                        "Restoring previous child state {}",
-                        state.to_string()
-                    );
-                    self.set_state(state);
-                }
+            _ if destroying => {
+                // Restore the previous state
+                info!("Restoring previous child state {}", state.to_string());
+                self.set_state(state)
             }
+            _ => Ok(()),
+        };
+        if let Err(error) = changed {
+            warn!("{}: {}", self.parent, error);
         }
 
         // Remove the child from the I/O path. If we had an IO error the block
@@ -704,13 +1141,18 @@ impl NexusChild {
             remove_channel: mpsc::channel(0),
             guid: Guid::from(uuid::Uuid::nil()),
             metadata_index_lba: 0,
+            io_errors: AtomicCell::new(0),
+            io_timeout_us: None,
+            background_io: AtomicCell::new(0),
         }
     }
 
     /// destroy the child device
     pub async fn destroy(&self) -> Result<(), NexusBdevError> {
         if self.device.is_some() {
-            self.set_state(ChildState::Destroying);
+            if let Err(error) = self.set_state(ChildState::Destroying) {
+                error!("{}: {}", self.parent, error);
+            }
             info!("{}: destroying underlying block device", self.name);
             device_destroy(&self.name).await?;
             info!("{}: underlying block device destroyed", self.name);
@@ -730,6 +1172,12 @@ impl NexusChild {
         }
     }
 
+    /// Check if the underlying device of the child cannot be written to.
+    pub fn is_read_only(&self) -> bool {
+        self.get_device()
+            .map_or(false, |dev| !dev.io_type_supported(IoType::Write))
+    }
+
     /// Return the rebuild job which is rebuilding this child, if rebuilding.
     fn get_rebuild_job(&self) -> Option<&mut RebuildJob> {
         let job = RebuildJob::lookup(&self.name).ok()?;
@@ -744,16 +1192,143 @@ impl NexusChild {
             .unwrap_or_else(|| -1)
     }
 
+    /// Return the role of this child in the rebuilds of its nexus.
+    pub fn rebuild_role(&self) -> RebuildRole {
+        if self.get_rebuild_job().is_some() {
+            RebuildRole::Destination
+        } else if RebuildJob::lookup_src(&self.name)
+            .iter()
+            .any(|j| j.nexus == self.parent)
+        {
+            RebuildRole::Source
+        } else {
+            RebuildRole::None
+        }
+    }
+
     /// Determine if a child is local to the nexus (i.e. on the same node).
     pub fn is_local(&self) -> Option<bool> {
-        match &self.device {
-            Some(dev) => {
-                // A local child is not exported over nvme or iscsi.
-                let local =
-                    dev.driver_name() != "nvme" && dev.driver_name() != "iscsi";
-                Some(local)
-            }
-            None => None,
+        self.device
+            .as_ref()
+            .map(|dev| is_local_device(dev.as_ref()))
+    }
+
+    /// Check whether the block device of the child supports the given I/O
+    /// type, eg: whether it can be unmapped.
+    pub fn io_type_supported(&self, io_type: IoType) -> bool {
+        self.device
+            .as_ref()
+            .map_or(false, |d| d.io_type_supported(io_type))
+    }
+
+    /// Unmap `len` bytes at `offset` of the child, both in bytes and aligned
+    /// to the block size. Whether the child supports unmap can be checked with
+    /// `io_type_supported(IoType::Unmap)`.
+    pub async fn unmap_at(
+        &self,
+        offset: u64,
+        len: u64,
+    ) -> Result<(), ChildError> {
+        self.zero_blocks(IoType::Unmap, offset, len).await
+    }
+
+    /// Write zeroes over `len` bytes at `offset` of the child, both in bytes
+    /// and aligned to the block size. Whether the child supports it can be
+    /// checked with `io_type_supported(IoType::WriteZeros)`.
+    pub async fn write_zeroes_at(
+        &self,
+        offset: u64,
+        len: u64,
+    ) -> Result<(), ChildError> {
+        self.zero_blocks(IoType::WriteZeros, offset, len).await
+    }
+
+    /// Completion callback of the I/O issued by `zero_blocks`.
+    fn zero_blocks_done(
+        _device: &dyn BlockDevice,
+        status: IoCompletionStatus,
+        ctx: *mut c_void,
+    ) {
+        let sender = unsafe {
+            Box::from_raw(ctx as *mut oneshot::Sender<IoCompletionStatus>)
+        };
+        sender.send(status).ok();
+    }
+
+    /// Issue an unmap or write zeroes I/O to the child and wait for it to
+    /// complete.
+    async fn zero_blocks(
+        &self,
+        io_type: IoType,
+        offset: u64,
+        len: u64,
+    ) -> Result<(), ChildError> {
+        let hdl = self.get_io_handle().context(HandleOpen {})?;
+        let device = hdl.get_device();
+        let block_len = device.block_len();
+
+        if !device.io_type_supported(io_type) {
+            return Err(ChildError::ChildIoError {
+                source: CoreError::NotSupported {
+                    source: Errno::EOPNOTSUPP,
+                },
+            });
+        }
+        if offset % block_len != 0
+            || len % block_len != 0
+            || offset + len > device.size_in_bytes()
+        {
+            return Err(ChildError::ChildIoError {
+                source: CoreError::InvalidOffset {
+                    offset,
+                },
+            });
+        }
+
+        let (s, r) = oneshot::channel::<IoCompletionStatus>();
+        let ctx = Box::into_raw(Box::new(s)) as *mut c_void;
+        let (offset_blocks, num_blocks) = (offset / block_len, len / block_len);
+
+        let submitted = if io_type == IoType::Unmap {
+            hdl.unmap_blocks(
+                offset_blocks,
+                num_blocks,
+                Self::zero_blocks_done,
+                ctx,
+            )
+        } else {
+            hdl.write_zeroes(
+                offset_blocks,
+                num_blocks,
+                Self::zero_blocks_done,
+                ctx,
+            )
+        };
+
+        if let Err(source) = submitted {
+            // the callback is not invoked when the I/O is not submitted
+            drop(unsafe {
+                Box::from_raw(ctx as *mut oneshot::Sender<IoCompletionStatus>)
+            });
+            return Err(ChildError::ChildIoError {
+                source,
+            });
+        }
+
+        match r.await {
+            Ok(IoCompletionStatus::Success) => Ok(()),
+            _ if io_type == IoType::Unmap => Err(ChildError::ChildIoError {
+                source: CoreError::UnmapFailed {
+                    offset,
+                    len,
+                },
+            }),
+            _ => Err(ChildError::ChildIoError {
+                source: CoreError::WriteZeroesFailed {
+                    offset,
+                    len,
+                },
+            }),
         }
     }
 
@@ -785,3 +1360,26 @@ pub fn lookup_nexus_child(bdev_name: &str) -> Option<&mut NexusChild> {
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{ChildState::*, ConfigReason, Reason};
+
+    #[test]
+    fn child_state_transitions() {
+        assert!(Init.can_transition_to(Open));
+        assert!(Closed.can_transition_to(Open));
+        assert!(Open.can_transition_to(Faulted(Reason::IoError)));
+        assert!(Faulted(Reason::OutOfSync).can_transition_to(Open));
+        assert!(Destroying.can_transition_to(Closed));
+        assert!(Destroying.can_transition_to(Faulted(Reason::IoError)));
+        assert!(
+            ConfigInvalid(ConfigReason::TooSmall).can_transition_to(Destroying)
+        );
+
+        assert!(!Destroying.can_transition_to(Open));
+        assert!(!Destroying.can_transition_to(Faulted(Reason::OutOfSync)));
+        assert!(!Faulted(Reason::IoError).can_transition_to(Open));
+        assert!(!Open.can_transition_to(Init));
+    }
+}
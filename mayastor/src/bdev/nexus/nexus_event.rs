@@ -0,0 +1,70 @@
+//! Events raised by a nexus which clients can subscribe to, rather than
+//! having to poll the state of the nexus.
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::bdev::nexus::nexus_child::Reason;
+
+/// number of events kept for subscribers which fall behind
+const EVENT_CAPACITY: usize = 256;
+
+static CHILD_EVENTS: Lazy<broadcast::Sender<ChildEvent>> =
+    Lazy::new(|| broadcast::channel(EVENT_CAPACITY).0);
+
+/// Events raised for the children of a nexus.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum ChildEvent {
+    Faulted(ChildFaultedEvent),
+    Unreachable(ChildUnreachableEvent),
+}
+
+/// Raised when a child of a nexus transitions to the faulted state.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChildFaultedEvent {
+    /// name of the nexus the child belongs to
+    pub nexus: String,
+    /// URI of the child
+    pub child: String,
+    /// reason the child was faulted for
+    pub reason: Reason,
+    /// number of I/O errors seen on the child
+    pub io_errors: u64,
+}
+
+impl From<ChildFaultedEvent> for ChildEvent {
+    fn from(event: ChildFaultedEvent) -> Self {
+        Self::Faulted(event)
+    }
+}
+
+/// Raised when the keep-alive of the controller of a child times out. The
+/// child is not faulted by this alone, its controller is reset to reconnect.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChildUnreachableEvent {
+    /// name of the nexus the child belongs to
+    pub nexus: String,
+    /// URI of the child
+    pub child: String,
+}
+
+impl From<ChildUnreachableEvent> for ChildEvent {
+    fn from(event: ChildUnreachableEvent) -> Self {
+        Self::Unreachable(event)
+    }
+}
+
+/// Subscribe to the child events of all nexus instances. Only events raised
+/// after subscribing are received.
+pub fn subscribe() -> broadcast::Receiver<ChildEvent> {
+    CHILD_EVENTS.subscribe()
+}
+
+/// Deliver the event to all current subscribers.
+pub(crate) fn emit(event: impl Into<ChildEvent>) {
+    let event = event.into();
+    debug!(?event, "child event");
+    // an error only means that nobody is subscribed
+    let _ = CHILD_EVENTS.send(event);
+}
@@ -1,19 +1,30 @@
 use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
     fmt::Debug,
-    ops::{Deref, DerefMut},
+    ops::{Deref, DerefMut, Range},
     ptr::NonNull,
+    sync::Mutex,
 };
 
 use libc::c_void;
 use nix::errno::Errno;
+use once_cell::sync::Lazy;
 
-use spdk_sys::{spdk_bdev_io, spdk_bdev_io_get_buf, spdk_io_channel};
+use spdk_sys::{
+    iovec,
+    spdk_bdev_io,
+    spdk_bdev_io_get_buf,
+    spdk_get_io_channel,
+    spdk_io_channel,
+    spdk_io_channel_get_io_device,
+    spdk_put_io_channel,
+};
 
 use crate::{
     bdev::{
         nexus::{
             nexus_bdev::NEXUS_PRODUCT_ID,
-            nexus_channel::{NexusChannel, NexusChannelInner},
+            nexus_channel::{DrEvent, NexusChannel, NexusChannelInner},
         },
         nexus_lookup,
         Nexus,
@@ -25,6 +36,7 @@ use crate::{
         BlockDeviceHandle,
         CoreError,
         Cores,
+        DmaBuf,
         GenericStatusCode,
         IoCompletionStatus,
         IoStatus,
@@ -33,6 +45,7 @@ use crate::{
         NvmeCommandStatus,
         Reactors,
     },
+    subsys::Config,
 };
 
 #[allow(unused_macros)]
@@ -99,6 +112,342 @@ pub struct NioCtx {
     must_fail: bool,
 }
 
+/// Writes in the background of each slow child, keyed by the name of its
+/// device. They are shared by all cores, as overlapping writes have to reach
+/// the child in the order they were submitted to the nexus, whichever core
+/// they were submitted on.
+static BACKGROUND_WRITES: Lazy<Mutex<HashMap<String, BackgroundWrites>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Default)]
+struct BackgroundWrites {
+    /// sequence number of the next IO
+    next_seq: u64,
+    /// IOs submitted to the child by sequence number, with their blocks
+    inflight: BTreeMap<u64, Option<Range<u64>>>,
+    /// IOs held back until the overlapping writes before them completed
+    deferred: VecDeque<Box<AsyncIoCtx>>,
+    /// bytes of data copied for the IOs in flight and held back
+    bytes: u64,
+    /// flushes waiting for the IOs before the sequence number to complete
+    flushes: Vec<(u64, BackgroundWaiter)>,
+}
+
+impl BackgroundWrites {
+    /// Reserve room for a copy of the data of a write to the device, fails
+    /// once the data copied for the device would exceed the limit.
+    fn reserve(device: &str, size: u64) -> bool {
+        let max = Config::get().nexus_opts.background_write_max_mb << 20;
+        let mut all = BACKGROUND_WRITES.lock().unwrap();
+        let writes = all.entry(device.to_string()).or_default();
+        if writes.bytes + size > max {
+            return false;
+        }
+        writes.bytes += size;
+        true
+    }
+
+    /// release the room reserved for a copy of the data of a write
+    fn unreserve(device: &str, size: u64) {
+        let mut all = BACKGROUND_WRITES.lock().unwrap();
+        if let Some(writes) = all.get_mut(device) {
+            writes.bytes -= size;
+            if writes.is_idle() {
+                all.remove(device);
+            }
+        }
+    }
+
+    /// true once nothing is left to track for the device
+    fn is_idle(&self) -> bool {
+        self.inflight.is_empty()
+            && self.deferred.is_empty()
+            && self.flushes.is_empty()
+            && self.bytes == 0
+    }
+
+    /// Move the IOs held back which do not overlap any write in flight or
+    /// held back before them to the IOs in flight, returning them.
+    fn dispatch(&mut self) -> Vec<Box<AsyncIoCtx>> {
+        let mut ready = Vec::new();
+        let mut held = Vec::new();
+        let mut i = 0;
+
+        while i < self.deferred.len() {
+            let blocked = self.deferred[i].blocks().map_or(false, |blocks| {
+                self.inflight
+                    .values()
+                    .flatten()
+                    .chain(held.iter())
+                    .any(|b| b.start < blocks.end && blocks.start < b.end)
+            });
+
+            if blocked {
+                held.extend(self.deferred[i].blocks());
+                i += 1;
+            } else {
+                let io = self.deferred.remove(i).unwrap();
+                self.inflight.insert(io.seq, io.blocks());
+                ready.push(io);
+            }
+        }
+
+        ready
+    }
+
+    /// take the flushes no longer waiting for any IO
+    fn flushed(&mut self) -> Vec<BackgroundWaiter> {
+        let oldest = self
+            .inflight
+            .keys()
+            .next()
+            .copied()
+            .into_iter()
+            .chain(self.deferred.iter().map(|io| io.seq))
+            .min()
+            .unwrap_or(u64::MAX);
+
+        let (done, waiting): (Vec<_>, Vec<_>) =
+            std::mem::take(&mut self.flushes)
+                .into_iter()
+                .partition(|(seq, _)| *seq <= oldest);
+        self.flushes = waiting;
+        done.into_iter().map(|(_, waiter)| waiter).collect()
+    }
+}
+
+/// A nexus IO waiting for IOs in the background, on the thread it was
+/// submitted on.
+struct BackgroundWaiter {
+    io: NonNull<spdk_bdev_io>,
+    thread: Mthread,
+}
+
+unsafe impl Send for BackgroundWaiter {}
+
+impl BackgroundWaiter {
+    /// Let the nexus IO know that one of the IOs it waits for is done. This
+    /// always goes through a message, as the nexus IO may not have accounted
+    /// for all the IOs it waits for yet.
+    fn done(self) {
+        extern "C" fn waited(io: *mut c_void) {
+            let mut io = NexusBio::from(io);
+            io.ctx_as_mut().in_flight -= 1;
+            io.ok_checked();
+        }
+
+        self.thread.send_msg(waited, self.io.as_ptr().cast());
+    }
+}
+
+/// Context of an IO submitted to a slow child, which outlives the nexus IO
+/// unless the nexus IO waits for it.
+pub(crate) struct AsyncIoCtx {
+    /// name of the nexus the IO was submitted for
+    nexus: String,
+    /// name of the device of the child the IO is submitted to
+    device: String,
+    cmd: IoType,
+    /// first block of the IO on the child
+    offset: u64,
+    num_blocks: u64,
+    /// position of the IO among the IOs to the child
+    seq: u64,
+    /// the nexus channel the IO was submitted on, referenced until the IO
+    /// completes
+    channel: NonNull<spdk_io_channel>,
+    /// thread of the nexus channel
+    thread: Mthread,
+    /// copy of the data to write, as the buffers of the nexus IO are gone
+    /// once it has completed
+    buf: Option<DmaBuf>,
+    /// describes buf
+    iov: iovec,
+    /// the nexus IO, when it waits for this one and its data is not copied
+    parent: Option<NonNull<spdk_bdev_io>>,
+}
+
+/// The context is only used on the thread of its channel, other threads only
+/// hold it while it is queued.
+unsafe impl Send for AsyncIoCtx {}
+
+impl AsyncIoCtx {
+    /// blocks of the child written by the IO, none for a reset
+    fn blocks(&self) -> Option<Range<u64>> {
+        match self.cmd {
+            IoType::Reset => None,
+            _ => Some(self.offset .. self.offset + self.num_blocks),
+        }
+    }
+
+    /// Queue the IO behind the overlapping writes to the same child which
+    /// were submitted before it on any core, as the child may otherwise
+    /// complete them out of order.
+    fn queue(mut self: Box<Self>) {
+        let ready = {
+            let mut all = BACKGROUND_WRITES.lock().unwrap();
+            let writes = all.entry(self.device.clone()).or_default();
+            self.seq = writes.next_seq;
+            writes.next_seq += 1;
+            writes.deferred.push_back(self);
+            writes.dispatch()
+        };
+
+        ready.into_iter().for_each(|io| io.submit_on_thread());
+    }
+
+    /// submit the IO on the thread of its channel
+    fn submit_on_thread(self: Box<Self>) {
+        extern "C" fn submit(ctx: *mut c_void) {
+            unsafe { Box::from_raw(ctx as *mut AsyncIoCtx) }.submit_local();
+        }
+
+        if Mthread::current() == Some(self.thread) {
+            self.submit_local();
+        } else {
+            let thread = self.thread;
+            thread.send_msg(submit, Box::into_raw(self).cast());
+        }
+    }
+
+    /// Submit the IO to its child, a child failing the submission is retired
+    /// right away. The IO is dropped if the child left the I/O path, it gets
+    /// rebuilt.
+    fn submit_local(self: Box<Self>) {
+        let inner = NexusChannel::inner_from_channel(self.channel.as_ptr());
+        let hdl = match inner
+            .async_writers
+            .iter()
+            .chain(inner.writers.iter())
+            .find(|h| h.get_device().device_name() == self.device)
+        {
+            Some(hdl) => hdl,
+            None => return self.done(),
+        };
+
+        if let Err((ctx, error)) = self.submit(hdl.as_ref()) {
+            error!(
+                "{}: async IO submission failed with error {:?}",
+                ctx.device, error
+            );
+            inner.child_io_error(&ctx.device);
+            if inner.remove_child(&ctx.device) {
+                Reactors::master().send_future(NexusBio::child_retire(
+                    ctx.nexus.clone(),
+                    ctx.device.clone(),
+                ));
+            }
+            ctx.done();
+        }
+    }
+
+    /// Submit the IO to the child, handing the context back on failure.
+    fn submit(
+        self: Box<Self>,
+        hdl: &dyn BlockDeviceHandle,
+    ) -> Result<(), (Box<Self>, CoreError)> {
+        let ctx = Box::into_raw(self);
+        let io = unsafe { &mut *ctx };
+
+        let result = match io.cmd {
+            IoType::Write => {
+                let (iovs, iovcnt) = match io.parent {
+                    Some(parent) => {
+                        let parent = Bio::from(parent.as_ptr());
+                        (parent.iovs(), parent.iov_count())
+                    }
+                    None => (&mut io.iov as *mut iovec, 1),
+                };
+                hdl.writev_blocks(
+                    iovs,
+                    iovcnt,
+                    io.offset,
+                    io.num_blocks,
+                    NexusBio::async_completion,
+                    ctx.cast(),
+                )
+            }
+            IoType::Unmap => hdl.unmap_blocks(
+                io.offset,
+                io.num_blocks,
+                NexusBio::async_completion,
+                ctx.cast(),
+            ),
+            IoType::WriteZeros => hdl.write_zeroes(
+                io.offset,
+                io.num_blocks,
+                NexusBio::async_completion,
+                ctx.cast(),
+            ),
+            IoType::Reset => hdl.reset(NexusBio::async_completion, ctx.cast()),
+            // we should never reach here, if we do it is a bug.
+            _ => unreachable!(),
+        };
+
+        result.map_err(|error| (unsafe { Box::from_raw(ctx) }, error))
+    }
+
+    /// Called once the IO is done with: its blocks are no longer in flight,
+    /// so the IOs held back which do not overlap any other write are
+    /// submitted, and the flushes waiting for it are completed.
+    fn done(self: Box<Self>) {
+        let (ready, flushed) = {
+            let mut all = BACKGROUND_WRITES.lock().unwrap();
+            match all.get_mut(&self.device) {
+                Some(writes) => {
+                    writes.inflight.remove(&self.seq);
+                    writes.bytes -=
+                        self.buf.as_ref().map_or(0, |buf| buf.len());
+                    let ready = writes.dispatch();
+                    let flushed = writes.flushed();
+                    if writes.is_idle() {
+                        all.remove(&self.device);
+                    }
+                    (ready, flushed)
+                }
+                None => (Vec::new(), Vec::new()),
+            }
+        };
+
+        ready.into_iter().for_each(|io| io.submit_on_thread());
+        flushed.into_iter().for_each(BackgroundWaiter::done);
+
+        if let Some(io) = self.parent {
+            BackgroundWaiter {
+                io,
+                thread: self.thread,
+            }
+            .done();
+        }
+
+        self.release();
+    }
+
+    /// Record that the IO is done with and drop the reference to the
+    /// channel. A child which is written synchronously again is added back
+    /// to the readers once its last IO in the background is done with.
+    fn release(self: Box<Self>) {
+        if let Some(nexus) = nexus_lookup(&self.nexus) {
+            if let Some(child) = nexus.children.iter().find(|c| {
+                matches!(c.get_device(), Ok(d) if d.device_name() == self.device)
+            }) {
+                if child.background_io_completed()
+                    && !nexus.write_policy.is_slow(&child.name)
+                {
+                    let name = self.nexus.clone();
+                    Reactors::master().send_future(async move {
+                        if let Some(nexus) = nexus_lookup(&name) {
+                            nexus.reconfigure(DrEvent::WritePolicy).await;
+                        }
+                    });
+                }
+            }
+        }
+
+        unsafe { spdk_put_io_channel(self.channel.as_ptr()) };
+    }
+}
+
 pub(crate) fn nexus_submit_io(mut io: NexusBio) {
     if let Err(_e) = match io.cmd() {
         IoType::Read => io.readv(),
@@ -107,7 +456,7 @@ pub(crate) fn nexus_submit_io(mut io: NexusBio) {
             io.submit_all()
         }
         IoType::Flush => {
-            io.ok();
+            io.flush_background();
             Ok(())
         }
         IoType::NvmeAdmin => {
@@ -296,6 +645,7 @@ impl NexusBio {
                 trace!(
                     "(core: {} thread: {}): read IO to {} submission failed with error {:?}",
                     Cores::current(), Mthread::current().unwrap().name(), device, r);
+                inner.child_io_error(&device);
                 let must_retire = inner.fault_child(&device);
                 if must_retire {
                     self.do_retire(device);
@@ -433,6 +783,10 @@ impl NexusBio {
                 })
         });
 
+        if result.is_ok() {
+            inflight += self.submit_async();
+        }
+
         // Submission errors can also trigger device retire.
         // Such a situation can happen when there is no active I/O in the
         // queues, but error on qpair is observed due to network
@@ -446,6 +800,7 @@ impl NexusBio {
             let device = failed_device.unwrap();
             // set the IO as failed in the submission stage.
             self.ctx_as_mut().must_fail = true;
+            self.inner_channel().child_io_error(&device);
             if self.inner_channel().remove_child(&device) {
                 self.do_retire(device);
             }
@@ -466,6 +821,195 @@ impl NexusBio {
         result
     }
 
+    /// Submit the IO to the slow children, which the nexus IO does not wait
+    /// for unless the data copied for a child exceeds the limit. Returns the
+    /// number of children the nexus IO waits for.
+    fn submit_async(&self) -> u8 {
+        let devices = self
+            .inner_channel()
+            .async_writers
+            .iter()
+            .map(|h| h.get_device().device_name())
+            .collect::<Vec<_>>();
+
+        devices
+            .into_iter()
+            .filter(|device| self.submit_async_child(device))
+            .count() as u8
+    }
+
+    /// Submit the IO to a single slow child with a context of its own,
+    /// returning true if the nexus IO waits for it.
+    fn submit_async_child(&self, device: &str) -> bool {
+        let nexus = self.nexus_as_ref();
+        let child = match nexus.children.iter().find(
+            |c| matches!(c.get_device(), Ok(d) if d.device_name() == device),
+        ) {
+            Some(child) => child,
+            None => return false,
+        };
+
+        let size = match self.cmd() {
+            IoType::Write => self.num_blocks() * self.block_len(),
+            _ => 0,
+        };
+        let buf = if size > 0 && BackgroundWrites::reserve(device, size) {
+            let hdl = self
+                .inner_channel()
+                .async_writers
+                .iter()
+                .find(|h| h.get_device().device_name() == device)
+                .expect("slow child left the channel");
+            match self.copy_data(hdl.as_ref()) {
+                Ok(buf) => Some(buf),
+                Err(error) => {
+                    warn!("{}: failed to copy write: {}", device, error);
+                    BackgroundWrites::unreserve(device, size);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        // the data is left in place when it cannot be copied, in which case
+        // the nexus IO waits for the child
+        let parent = if buf.is_none() && size > 0 {
+            NonNull::new(self.as_ptr())
+        } else {
+            None
+        };
+        let iov = match &buf {
+            Some(buf) => iovec {
+                iov_base: **buf,
+                iov_len: buf.len(),
+            },
+            None => iovec {
+                iov_base: std::ptr::null_mut(),
+                iov_len: 0,
+            },
+        };
+
+        let channel = unsafe {
+            spdk_get_io_channel(spdk_io_channel_get_io_device(
+                self.ctx().channel.as_ptr(),
+            ))
+        };
+        child.background_io_submitted();
+        let waits = parent.is_some();
+        Box::new(AsyncIoCtx {
+            nexus: nexus.name.clone(),
+            device: device.to_string(),
+            cmd: self.cmd(),
+            offset: self.offset() + self.data_ent_offset(),
+            num_blocks: self.num_blocks(),
+            seq: 0,
+            channel: NonNull::new(channel).expect("no nexus IO channel"),
+            thread: Mthread::current().expect("not on an SPDK thread"),
+            buf,
+            iov,
+            parent,
+        })
+        .queue();
+
+        waits
+    }
+
+    /// Complete a flush once the writes in the background to the slow
+    /// children of the nexus which were submitted before it completed.
+    fn flush_background(&mut self) {
+        let nexus = self.nexus_as_ref();
+        let thread = Mthread::current().expect("not on an SPDK thread");
+        let mut waiting = 0;
+
+        {
+            let mut all = BACKGROUND_WRITES.lock().unwrap();
+            for child in nexus.children.iter() {
+                let device = match child.get_device() {
+                    Ok(device) => device.device_name(),
+                    Err(_) => continue,
+                };
+                if let Some(writes) = all.get_mut(&device) {
+                    if writes.inflight.is_empty() && writes.deferred.is_empty()
+                    {
+                        continue;
+                    }
+                    writes.flushes.push((
+                        writes.next_seq,
+                        BackgroundWaiter {
+                            io: NonNull::new(self.as_ptr()).unwrap(),
+                            thread,
+                        },
+                    ));
+                    waiting += 1;
+                }
+            }
+            self.ctx_as_mut().in_flight = waiting;
+        }
+
+        self.ok_checked();
+    }
+
+    /// copy the data of the nexus IO into a buffer of its own
+    fn copy_data(
+        &self,
+        hdl: &dyn BlockDeviceHandle,
+    ) -> Result<DmaBuf, CoreError> {
+        let size = self.num_blocks() * self.block_len();
+        let mut buf = hdl.dma_malloc(size).map_err(|_| {
+            CoreError::DmaAllocationError {
+                size,
+            }
+        })?;
+
+        let iovs = unsafe {
+            std::slice::from_raw_parts(self.iovs(), self.iov_count() as usize)
+        };
+        let data = buf.as_mut_slice();
+        let mut pos = 0;
+        for iov in iovs {
+            let len = (iov.iov_len as usize).min(data.len() - pos);
+            let src = unsafe {
+                std::slice::from_raw_parts(iov.iov_base as *const u8, len)
+            };
+            data[pos .. pos + len].copy_from_slice(src);
+            pos += len;
+        }
+
+        Ok(buf)
+    }
+
+    /// invoked when an IO to a slow child completes. The nexus IO has most
+    /// likely completed already, so a failure can only be dealt with by
+    /// retiring the child, which then has to be rebuilt.
+    fn async_completion(
+        device: &dyn BlockDevice,
+        status: IoCompletionStatus,
+        ctx: *mut c_void,
+    ) {
+        let ctx = unsafe { Box::from_raw(ctx as *mut AsyncIoCtx) };
+
+        if status != IoCompletionStatus::Success {
+            let device = device.device_name();
+            error!(
+                "{}: async IO completion failed: {:?}, retiring child",
+                device, status
+            );
+
+            if let Some(nexus) = nexus_lookup(&ctx.nexus) {
+                if let Some(child) = nexus.children.iter().find(|c| {
+                    matches!(c.get_device(), Ok(d) if d.device_name() == device)
+                }) {
+                    child.io_error();
+                }
+            }
+
+            Reactors::master()
+                .send_future(Self::child_retire(ctx.nexus.clone(), device));
+        }
+
+        ctx.done();
+    }
+
     fn do_retire(&self, child: String) {
         Reactors::master().send_future(Self::child_retire(
             self.nexus_as_ref().name.clone(),
@@ -515,6 +1059,7 @@ impl NexusBio {
         );
 
         let child = child.device_name();
+        self.inner_channel().child_io_error(&child);
         // check if this child needs to be retired
         let needs_retire = self.inner_channel().fault_child(&child);
         // The child state was not faulted yet, so this is the first IO
@@ -58,10 +58,12 @@ use std::{
     fmt,
     io::{Cursor, Seek, SeekFrom},
     str::FromStr,
+    time::Duration,
 };
 
 use bincode::{deserialize_from, serialize, serialize_into, Error};
 use crc::{crc32, Hasher32};
+use futures::future::{self, Either};
 use serde::{
     de::{Deserializer, SeqAccess, Unexpected, Visitor},
     ser::{SerializeTuple, Serializer},
@@ -74,12 +76,33 @@ use uuid::{self, Uuid};
 use crate::{
     bdev::nexus::{
         nexus_bdev::Nexus,
-        nexus_child::NexusChild,
+        nexus_child::{ChildError, ChildState, NexusChild, Reason},
         nexus_metadata::{MetaDataError, NexusMetaData},
     },
     core::{BlockDeviceHandle, CoreError, DmaBuf, DmaError},
+    sleep::mayastor_sleep,
+    subsys::Config,
 };
 
+/// Policy for children whose labels disagree when a nexus is opened.
+/// With either policy the child with the highest metadata generation is
+/// authoritative and children with a lower generation are marked for resync.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LabelPolicy {
+    /// refuse to open the nexus if any child has an invalid label
+    Strict,
+    /// mark children with an invalid label for resync, as long as at least
+    /// one child has a valid label
+    BestEffort,
+}
+
+impl Default for LabelPolicy {
+    fn default() -> Self {
+        Self::Strict
+    }
+}
+
 #[derive(Debug, Snafu)]
 pub enum LabelError {
     #[snafu(display("Serialization error: {}", source))]
@@ -98,6 +121,8 @@ pub enum LabelError {
     WriteAlloc { source: DmaError, name: String },
     #[snafu(display("Error reading from child {}: {}", name, source))]
     ReadError { source: CoreError, name: String },
+    #[snafu(display("Child {} stopped responding: {}", name, source))]
+    ReadTimeout { source: ChildError, name: String },
     #[snafu(display("Error writing to child {}: {}", name, source))]
     WriteError { source: CoreError, name: String },
     #[snafu(display("Label is invalid: {}", source))]
@@ -124,6 +149,8 @@ pub enum LabelError {
         source
     ))]
     IndexAddress { source: MetaDataError, name: String },
+    #[snafu(display("No child of nexus {} has a valid label", name))]
+    NoValidLabel { name: String },
 }
 
 #[derive(Debug, Snafu)]
@@ -315,15 +342,24 @@ impl GptHeader {
             return Err(ProbeError::GptRevision {});
         }
 
-        let checksum = header.self_checksum;
-
-        if checksum != header.checksum().context(ChecksumSerializeError {})? {
+        if !header
+            .verify_checksum()
+            .context(ChecksumSerializeError {})?
+        {
             return Err(ProbeError::GptChecksum {});
         }
 
         Ok(header)
     }
 
+    /// verify that the stored header checksum matches the header contents,
+    /// which catches a corrupted header even when its partition table is
+    /// still intact
+    pub fn verify_checksum(&self) -> Result<bool, Error> {
+        let mut header = *self;
+        Ok(header.checksum()? == self.self_checksum)
+    }
+
     /// checksum the header with the checksum field itself set to 0
     pub fn checksum(&mut self) -> Result<u32, Error> {
         self.self_checksum = 0;
@@ -1033,6 +1069,88 @@ impl NexusLabel {
 }
 
 impl NexusChild {
+    /// Read part of the label into a new buffer, giving up once the label
+    /// read timeout expires, as a child whose device silently died would
+    /// otherwise hang the nexus. Such a child is faulted. Its read may still
+    /// complete later, so the buffer is leaked rather than freed.
+    async fn read_label_blocks(
+        &self,
+        handle: &dyn BlockDeviceHandle,
+        offset: u64,
+        len: u64,
+        name: &str,
+    ) -> Result<DmaBuf, LabelError> {
+        let mut buf = handle.dma_malloc(len).context(ReadAlloc {
+            name: String::from(name),
+        })?;
+
+        let timeout_ms = Config::get().nexus_opts.label_read_timeout_ms;
+        if timeout_ms == 0 {
+            handle.read_at(offset, &mut buf).await.context(ReadError {
+                name: String::from(name),
+            })?;
+            return Ok(buf);
+        }
+
+        let read = handle.read_at(offset, &mut buf);
+        let timeout = mayastor_sleep(Duration::from_millis(timeout_ms));
+        match future::select(read, timeout).await {
+            Either::Left((result, _)) => {
+                result.context(ReadError {
+                    name: String::from(name),
+                })?;
+                Ok(buf)
+            }
+            Either::Right((_, read)) => {
+                std::mem::forget(read);
+                std::mem::forget(buf);
+                error!(
+                    "{}: reading the {} of child {} timed out after {} ms",
+                    self.parent, name, self.name, timeout_ms
+                );
+                if let Err(error) =
+                    self.set_state(ChildState::Faulted(Reason::IoError))
+                {
+                    error!("{}: {}", self.parent, error);
+                }
+                Err(ChildError::LabelReadTimeout {
+                    part: String::from(name),
+                    timeout_ms,
+                })
+                .context(ReadTimeout {
+                    name: self.name.clone(),
+                })
+            }
+        }
+    }
+
+    /// Check whether either GPT header of this child still carries the GPT
+    /// signature, that is whether the child was labelled before, as opposed
+    /// to a blank device.
+    async fn has_label_header(&self) -> Result<bool, LabelError> {
+        let handle = self.get_io_handle().context(HandleError {
+            name: self.name.clone(),
+        })?;
+
+        let bdev = handle.get_device();
+        let block_size = bdev.block_len();
+        let headers = [
+            (block_size, "primary GPT header"),
+            ((bdev.num_blocks() - 1) * block_size, "secondary GPT header"),
+        ];
+
+        for (offset, name) in headers.iter() {
+            let buf = self
+                .read_label_blocks(&*handle, *offset, block_size, name)
+                .await?;
+            if buf.as_slice()[.. 8] == GptHeader::HEADER_SIGNATURE {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
     /// Read and validate this child's label.
     pub async fn probe_label(&self) -> Result<NexusLabel, LabelError> {
         let handle = self.get_io_handle().context(HandleError {
@@ -1044,12 +1162,9 @@ impl NexusChild {
         let num_blocks = bdev.num_blocks();
 
         // Protective MBR
-        let mut buf = handle.dma_malloc(block_size).context(ReadAlloc {
-            name: String::from("header"),
-        })?;
-        handle.read_at(0, &mut buf).await.context(ReadError {
-            name: String::from("MBR"),
-        })?;
+        let buf = self
+            .read_label_blocks(&*handle, 0, block_size, "MBR")
+            .await?;
         let mbr = NexusLabel::read_mbr(&buf).context(InvalidLabel {})?;
 
         // GPT headers
@@ -1060,21 +1175,28 @@ impl NexusChild {
         let active: &GptHeader;
 
         // Get primary GPT header.
-        handle
-            .read_at(block_size, &mut buf)
-            .await
-            .context(ReadError {
-                name: String::from("primary GPT header"),
-            })?;
+        let buf = self
+            .read_label_blocks(
+                &*handle,
+                block_size,
+                block_size,
+                "primary GPT header",
+            )
+            .await?;
         match NexusLabel::read_primary_header(&buf, block_size, num_blocks) {
             Ok(header) => {
                 primary = header;
                 active = &primary;
                 // Get secondary GPT header.
                 let offset = (num_blocks - 1) * block_size;
-                handle.read_at(offset, &mut buf).await.context(ReadError {
-                    name: String::from("secondary GPT header"),
-                })?;
+                let buf = self
+                    .read_label_blocks(
+                        &*handle,
+                        offset,
+                        block_size,
+                        "secondary GPT header",
+                    )
+                    .await?;
                 match NexusLabel::read_secondary_header(
                     &buf, block_size, num_blocks,
                 ) {
@@ -1100,10 +1222,21 @@ impl NexusChild {
             Err(error) => {
                 // Primary GPT header is either not present or invalid.
                 // See if we can obtain a valid secondary GPT header.
+                if let ProbeError::GptChecksum {} = error {
+                    warn!(
+                        "{}: primary GPT header checksum mismatch, falling back to the backup header",
+                        self.name
+                    );
+                }
                 let offset = (num_blocks - 1) * block_size;
-                handle.read_at(offset, &mut buf).await.context(ReadError {
-                    name: String::from("secondary GPT header"),
-                })?;
+                let buf = self
+                    .read_label_blocks(
+                        &*handle,
+                        offset,
+                        block_size,
+                        "secondary GPT header",
+                    )
+                    .await?;
                 match NexusLabel::read_secondary_header(
                     &buf, block_size, num_blocks,
                 ) {
@@ -1142,14 +1275,15 @@ impl NexusChild {
             u64::from(active.entry_size * active.num_entries),
             block_size,
         );
-        let mut buf =
-            handle.dma_malloc(blocks * block_size).context(ReadAlloc {
-                name: String::from("partition table"),
-            })?;
         let offset = active.lba_table * block_size;
-        handle.read_at(offset, &mut buf).await.context(ReadError {
-            name: String::from("partition table"),
-        })?;
+        let buf = self
+            .read_label_blocks(
+                &*handle,
+                offset,
+                blocks * block_size,
+                "partition table",
+            )
+            .await?;
         let mut partitions = NexusLabel::read_partitions(&buf, active)
             .context(InvalidLabel {})?;
 
@@ -1338,13 +1472,33 @@ impl Nexus {
             });
         }
 
+        let policy = Config::get().nexus_opts.label_policy;
         let block_size = u64::from(self.bdev.block_len());
 
         let mut offsets: Vec<u64> = Vec::new();
         let mut size = self.size;
 
         for child in self.children.iter_mut().filter(|c| c.is_open()) {
-            let label = child.validate_label().await?;
+            let label = match child.validate_label().await {
+                Ok(label) => label,
+                // the child is faulted, carry on with the others
+                Err(LabelError::ReadTimeout {
+                    ..
+                }) => continue,
+                Err(error) if policy == LabelPolicy::BestEffort => {
+                    warn!(
+                        "{}: child {} has no valid label, marking it for resync: {}",
+                        self.name, child.name, error
+                    );
+                    if let Err(error) =
+                        child.set_state(ChildState::Faulted(Reason::OutOfSync))
+                    {
+                        error!("{}: {}", self.name, error);
+                    }
+                    continue;
+                }
+                Err(error) => return Err(error),
+            };
 
             if child.metadata_index_lba == 0 {
                 // Set the address of the MetaDataIndex
@@ -1361,6 +1515,12 @@ impl Nexus {
             size = min(size, label.partition_size("MayaData")?);
         }
 
+        if offsets.is_empty() {
+            return Err(LabelError::NoValidLabel {
+                name: self.name.clone(),
+            });
+        }
+
         // Ensure Data partitions offsets are identical for all children.
         offsets.dedup();
 
@@ -1376,9 +1536,147 @@ impl Nexus {
         // Set the nexus size
         self.bdev.set_block_count(size / block_size);
 
+        self.reconcile_child_generations().await;
+
         Ok(())
     }
 
+    /// Use the child with the highest metadata generation as authoritative
+    /// and mark all children with a lower generation for resync.
+    /// Children without a metadata index are at generation 0.
+    async fn reconcile_child_generations(&mut self) {
+        let mut generations = Vec::new();
+
+        for child in self
+            .children
+            .iter()
+            .filter(|c| c.state() == ChildState::Open)
+        {
+            let generation = match NexusMetaData::get_index(child).await {
+                Ok(index) => index.map_or(0, |index| index.generation),
+                Err(error) => {
+                    warn!(
+                        "{}: failed to read metadata index of child {}: {}",
+                        self.name, child.name, error
+                    );
+                    0
+                }
+            };
+            generations.push(generation);
+        }
+
+        let authoritative = match generations.iter().max() {
+            Some(generation) => *generation,
+            None => return,
+        };
+
+        for (child, generation) in self
+            .children
+            .iter()
+            .filter(|c| c.state() == ChildState::Open)
+            .zip(generations)
+        {
+            if generation < authoritative {
+                info!(
+                    "{}: child {} is at generation {}, behind generation {}, marking it for resync",
+                    self.name, child.name, generation, authoritative
+                );
+                if let Err(error) =
+                    child.set_state(ChildState::Faulted(Reason::OutOfSync))
+                {
+                    error!("{}: {}", self.name, error);
+                }
+            }
+        }
+    }
+
+    /// Prepare the nexus for I/O without labels, when none of the children
+    /// has a valid label but each of them still has a damaged one, and all of
+    /// them are readable and of the same size. The data partition is placed
+    /// where a freshly generated label would put it. Returns false if the
+    /// children are not eligible for label recovery, like blank devices which
+    /// are labelled as usual.
+    pub(crate) async fn recover_child_labels(
+        &mut self,
+    ) -> Result<bool, LabelError> {
+        let mut geometry = None;
+
+        for child in self.children.iter().filter(|c| c.is_open()) {
+            match child.probe_label().await {
+                // an existing label, valid or not ours, must not be ignored
+                Ok(_) => return Ok(false),
+                Err(LabelError::InvalidLabel {
+                    ..
+                }) => {}
+                // the child is faulted, carry on with the others
+                Err(LabelError::ReadTimeout {
+                    ..
+                }) => continue,
+                Err(error) => return Err(error),
+            }
+
+            // neither header is there at all, the child has never been
+            // labelled and there is nothing to recover
+            match child.has_label_header().await {
+                Ok(true) => {}
+                Ok(false) => return Ok(false),
+                Err(LabelError::ReadTimeout {
+                    ..
+                }) => continue,
+                Err(error) => return Err(error),
+            }
+
+            let handle = child.get_io_handle().context(HandleError {
+                name: child.name.clone(),
+            })?;
+            let device = handle.get_device();
+            let (block_len, num_blocks) =
+                (device.block_len(), device.num_blocks());
+
+            match geometry {
+                None => geometry = Some((block_len, num_blocks, device.uuid())),
+                Some((bl, nb, _)) if bl == block_len && nb == num_blocks => {}
+                Some(_) => {
+                    warn!(
+                        "{}: children differ in size, cannot recover labels",
+                        self.name
+                    );
+                    return Ok(false);
+                }
+            }
+        }
+
+        let (block_len, num_blocks, uuid) = match geometry {
+            Some(geometry) => geometry,
+            None => {
+                return Err(LabelError::MissingChildren {
+                    name: self.name.clone(),
+                })
+            }
+        };
+
+        let label = NexusLabel::generate_label(
+            GptGuid::from(uuid),
+            block_len,
+            num_blocks,
+            self.size,
+        )?;
+
+        let block_size = u64::from(self.bdev.block_len());
+        let size = min(self.size, label.partition_size("MayaData")?);
+
+        self.data_ent_offset = label.partition_offset("MayaData")? / block_size;
+        self.bdev.set_block_count(size / block_size);
+        self.no_label = true;
+
+        warn!(
+            "{}: no child has a valid label, opened in label recovery mode",
+            self.name
+        );
+
+        Ok(true)
+    }
+
     /// Create or Update label on each child device as and when necessary.
     pub(crate) async fn update_child_labels(
         &mut self,
@@ -1390,7 +1688,13 @@ impl Nexus {
         }
 
         for child in self.children.iter_mut().filter(|c| c.is_open()) {
-            child.update_label(self.size).await?;
+            match child.update_label(self.size).await {
+                // the child is faulted, carry on with the others
+                Err(LabelError::ReadTimeout {
+                    ..
+                }) => continue,
+                result => result?,
+            };
         }
 
         Ok(())
@@ -19,7 +19,7 @@ use spdk_sys::{
 };
 
 use crate::{
-    bdev::{CreateDestroy, GetName},
+    bdev::{dev::reject_unknown_parameters, CreateDestroy, GetName},
     core::Bdev,
     ffihelper::{cb_arg, errno_result_from_i32, ErrnoResult, IntoCString},
     nexus_uri::{self, NexusBdevError},
@@ -38,6 +38,11 @@ impl TryFrom<&Url> for NVMe {
     type Error = NexusBdevError;
 
     fn try_from(url: &Url) -> Result<Self, Self::Error> {
+        reject_unknown_parameters(
+            url,
+            url.query_pairs().into_owned().collect(),
+        )?;
+
         Ok(Self {
             name: url.path()[1 ..].into(),
             url: url.clone(),
@@ -301,16 +301,16 @@ impl NvmeIoChannelInner<'_> {
     }
 
     /// Shutdown I/O channel and make it completely unusable for I/O.
+    /// Pending I/O is aborted when the qpair is dropped. The channel is
+    /// marked as shut down beforehand, so that I/O which completes as
+    /// aborted, as well as any new I/O, fails with ChannelShutdown.
     pub fn shutdown(&mut self) -> i32 {
         if self.is_shutdown {
             return 0;
         }
 
-        let rc = self.reset();
-        if rc == 0 {
-            self.is_shutdown = true;
-        }
-        rc
+        self.is_shutdown = true;
+        self.reset()
     }
 
     /// Account active I/O for channel.
@@ -500,7 +500,17 @@ impl NvmeControllerIoChannel {
 
         let (cname, controller, block_size) = {
             let controller = carc.lock();
-            // Make sure controller is available.
+            // Make sure controller is available. A faulted controller only
+            // becomes available again once it has been successfully reset.
+            if let NvmeControllerState::Faulted(reason) = controller.get_state()
+            {
+                error!(
+                    "{} controller is faulted ({:?}), I/O channel creation not possible until it is reset",
+                    controller.get_name(),
+                    reason
+                );
+                return 1;
+            }
             if controller.get_state() != NvmeControllerState::Running {
                 error!(
                     "{} controller is in {:?} state, I/O channel creation not possible",
@@ -515,10 +525,16 @@ impl NvmeControllerIoChannel {
             // the reference to the controller instance (carc) which
             // guarantees that the controller exists during I/O channel
             // creation.
-            let block_size = controller
-                .namespace()
-                .expect("No namespaces in active controller")
-                .block_len();
+            let block_size = match controller.namespace() {
+                Some(ns) => ns.block_len(),
+                None => {
+                    error!(
+                        "{} controller has no active namespace, I/O channel creation not possible",
+                        controller.get_name()
+                    );
+                    return 1;
+                }
+            };
             (
                 controller.get_name(),
                 controller.controller().unwrap(),
@@ -591,6 +607,7 @@ impl NvmeControllerIoChannel {
         });
 
         nvme_channel.inner = Box::into_raw(inner);
+        carc.lock().channel_created();
         debug!(?cname, ?ctx, "I/O channel successfully initialized");
         0
     }
@@ -617,6 +634,13 @@ impl NvmeControllerIoChannel {
             }
         }
 
+        // The controller is already gone when the channel outlives it.
+        if let Some(c) =
+            NVME_CONTROLLERS.lookup_by_name((device as u64).to_string())
+        {
+            c.lock().channel_destroyed();
+        }
+
         debug!(
             "IO channel for controller ID 0x{:X} successfully destroyed",
             device as u64
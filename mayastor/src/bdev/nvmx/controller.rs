@@ -6,9 +6,15 @@ use std::{
     fmt,
     os::raw::c_void,
     ptr::NonNull,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+        Mutex,
+    },
+    time::{Duration, Instant},
 };
 
+use crossbeam::atomic::AtomicCell;
 use futures::channel::oneshot;
 use merge::Merge;
 use nix::errno::Errno;
@@ -17,9 +23,13 @@ use spdk_sys::{
     spdk_nvme_async_event_completion,
     spdk_nvme_cpl,
     spdk_nvme_ctrlr,
+    spdk_nvme_ctrlr_cmd_get_log_page,
     spdk_nvme_ctrlr_fail,
     spdk_nvme_ctrlr_get_ns,
+    spdk_nvme_ctrlr_get_opts,
     spdk_nvme_ctrlr_is_active_ns,
+    spdk_nvme_ctrlr_is_log_page_supported,
+    spdk_nvme_ctrlr_process_admin_completions,
     spdk_nvme_ctrlr_register_aer_callback,
     spdk_nvme_ctrlr_reset,
     spdk_nvme_detach,
@@ -28,6 +38,7 @@ use spdk_sys::{
 use crate::{
     bdev::nvmx::{
         channel::{NvmeControllerIoChannel, NvmeIoChannel, NvmeIoChannelInner},
+        controller::transport::NvmeTransportId,
         controller_inner::{SpdkNvmeController, TimeoutConfig},
         controller_state::{
             ControllerFailureReason,
@@ -41,6 +52,9 @@ use crate::{
             NvmeAerInfoNotice,
             NvmeAerInfoNvmCommandSet,
             NvmeAerType,
+            NvmeHealthLog,
+            NVME_HEALTH_LOG_SIZE,
+            NVME_LOG_HEALTH_INFORMATION,
         },
         NvmeControllerState,
         NvmeControllerState::*,
@@ -48,19 +62,27 @@ use crate::{
         NVME_CONTROLLERS,
     },
     core::{
+        nvme_admin_opc,
         poller,
         BlockDeviceIoStats,
         CoreError,
+        Cores,
         DeviceEventListener,
         DeviceEventType,
+        DmaBuf,
         IoDevice,
         OpCompletionCallback,
         OpCompletionCallbackArg,
+        Reactors,
     },
     ffihelper::{cb_arg, done_cb},
     nexus_uri::NexusBdevError,
+    sleep::mayastor_sleep,
 };
 
+/// Context of a controller reset. It is allocated for each reset rather than
+/// taken from a fixed size pool, so concurrent resets of any number of
+/// controllers cannot fail for lack of contexts.
 #[derive(Debug)]
 struct ResetCtx {
     name: String,
@@ -98,9 +120,32 @@ impl<'a> NvmeControllerInner<'a> {
             .with_poll_fn(move || nvme_poll_adminq(cfg.as_ptr().cast()))
             .build();
 
+        // Idle controllers are only detached when a timeout is configured.
+        let idle_timeout_ms = nvme_bdev_running_config().idle_detach_timeout_ms;
+        let idle_poller = if idle_timeout_ms > 0 {
+            let timeout = Duration::from_millis(idle_timeout_ms);
+            Some(
+                poller::Builder::new()
+                    .with_name("nvme_idle_detach")
+                    .with_interval(IDLE_POLL_PERIOD_US)
+                    .with_poll_fn(move || {
+                        // the nexus children are looked up on the master
+                        let name = name.clone();
+                        Reactors::master().send_future(async move {
+                            detach_if_idle(&name, timeout);
+                        });
+                        0
+                    })
+                    .build(),
+            )
+        } else {
+            None
+        };
+
         Self {
             ctrlr,
             adminq_poller,
+            idle_poller,
             namespaces: Vec::new(),
             io_device,
         }
@@ -111,11 +156,48 @@ pub struct NvmeControllerInner<'a> {
     namespaces: Vec<Arc<NvmeNamespace>>,
     ctrlr: SpdkNvmeController,
     adminq_poller: poller::Poller<'a>,
+    idle_poller: Option<poller::Poller<'a>>,
     io_device: Arc<IoDevice>,
 }
 
 type EventCallbackList = Vec<DeviceEventListener>;
 
+/// Descriptors opened on a controller. Shared with the descriptors, which
+/// account for themselves when dropped without taking the controller lock.
+#[derive(Debug)]
+pub(crate) struct OpenDescriptors {
+    count: AtomicUsize,
+    last_closed: AtomicCell<Instant>,
+}
+
+impl OpenDescriptors {
+    fn new() -> Self {
+        Self {
+            count: AtomicUsize::new(0),
+            last_closed: AtomicCell::new(Instant::now()),
+        }
+    }
+
+    fn opened(&self) {
+        self.count.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Account for a closed descriptor.
+    pub(crate) fn closed(&self) {
+        self.last_closed.store(Instant::now());
+        self.count.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    fn count(&self) -> usize {
+        self.count.load(Ordering::SeqCst)
+    }
+
+    /// Time elapsed since the last descriptor was closed.
+    fn elapsed(&self) -> Duration {
+        self.last_closed.load().elapsed()
+    }
+}
+
 /*
  * NVME controller implementation.
  */
@@ -126,6 +208,13 @@ pub struct NvmeController<'a> {
     inner: Option<NvmeControllerInner<'a>>,
     state_machine: ControllerStateMachine,
     event_listeners: Mutex<EventCallbackList>,
+    /// number of I/O channels, at most one per core, of the controller
+    num_channels: usize,
+    /// when the controller was last opened or had its last I/O channel
+    /// destroyed
+    last_used: Instant,
+    /// descriptors currently opened on the controller
+    descriptors: Arc<OpenDescriptors>,
     /// Timeout config is accessed by SPDK-driven timeout callback handlers,
     /// so it needs to be a raw pointer. Mutable members are made atomic to
     /// eliminate lock contention between API path and callback path.
@@ -155,6 +244,9 @@ impl<'a> NvmeController<'a> {
             state_machine: ControllerStateMachine::new(name),
             inner: None,
             event_listeners: Mutex::new(Vec::<fn(DeviceEventType, &str)>::new()),
+            num_channels: 0,
+            last_used: Instant::now(),
+            descriptors: Arc::new(OpenDescriptors::new()),
             timeout_config: NonNull::new(Box::into_raw(Box::new(
                 TimeoutConfig::new(name),
             )))
@@ -210,6 +302,29 @@ impl<'a> NvmeController<'a> {
         self.inner.as_ref().map(|c| c.ctrlr)
     }
 
+    /// Returns the model number reported by the controller, if attached.
+    pub fn model(&self) -> Option<String> {
+        self.controller().map(|c| c.model())
+    }
+
+    /// Returns the serial number reported by the controller, if attached.
+    pub fn serial(&self) -> Option<String> {
+        self.controller().map(|c| c.serial())
+    }
+
+    /// Returns the transport id of the controller, if attached.
+    pub fn transport_id(&self) -> Option<NvmeTransportId> {
+        self.controller().map(|c| c.transport_id())
+    }
+
+    /// Returns the number of I/O queues negotiated with the target, 0 if the
+    /// controller is not attached yet.
+    pub fn num_io_queues(&self) -> u32 {
+        self.inner.as_ref().map_or(0, |c| unsafe {
+            (*spdk_nvme_ctrlr_get_opts(c.ctrlr.as_ptr())).num_io_queues
+        })
+    }
+
     /// we should try to avoid this
     pub fn ctrlr_as_ptr(&self) -> *mut spdk_nvme_ctrlr {
         self.inner.as_ref().map_or(std::ptr::null_mut(), |c| {
@@ -280,6 +395,45 @@ impl<'a> NvmeController<'a> {
         self.state_machine.current_state()
     }
 
+    /// Account for a newly created I/O channel.
+    pub(crate) fn channel_created(&mut self) {
+        self.num_channels += 1;
+    }
+
+    /// Account for a destroyed I/O channel. The controller becomes idle once
+    /// its last I/O channel is gone.
+    pub(crate) fn channel_destroyed(&mut self) {
+        self.num_channels = self.num_channels.saturating_sub(1);
+        if self.num_channels == 0 {
+            self.last_used = Instant::now();
+        }
+    }
+
+    /// Mark the controller as used, which postpones detaching it when idle.
+    pub(crate) fn touch(&mut self) {
+        self.last_used = Instant::now();
+    }
+
+    /// Account for a newly opened descriptor, which releases the returned
+    /// counter when it is closed.
+    pub(crate) fn descriptor_opened(&self) -> Arc<OpenDescriptors> {
+        self.descriptors.opened();
+        Arc::clone(&self.descriptors)
+    }
+
+    /// Whether the controller is running without any I/O channels, open
+    /// descriptors or nexus child referencing it, and has been so for at
+    /// least the given time. Must be called on the master reactor, which
+    /// owns the nexus instances.
+    fn is_idle(&self, timeout: Duration) -> bool {
+        self.get_state() == Running
+            && self.num_channels == 0
+            && self.descriptors.count() == 0
+            && self.last_used.elapsed() >= timeout
+            && self.descriptors.elapsed() >= timeout
+            && crate::bdev::lookup_nexus_child(&self.name).is_none()
+    }
+
     /// Reset the controller.
     /// Upon reset all pending I/O operations are cancelled and all I/O handles
     /// are reinitialized.
@@ -484,6 +638,108 @@ impl<'a> NvmeController<'a> {
         Ok(())
     }
 
+    /// Get the SMART / Health Information log page of the controller.
+    /// The log page is retrieved through the admin queue and the callback is
+    /// invoked once the command completes.
+    pub fn get_health_log<T: 'static + Sized, F>(
+        &self,
+        cb: F,
+        cb_arg: T,
+    ) -> Result<(), CoreError>
+    where
+        F: Fn(Result<NvmeHealthLog, CoreError>, T) + 'static,
+    {
+        struct HealthLogCtx<V: 'static + Sized> {
+            cb: Box<dyn Fn(Result<NvmeHealthLog, CoreError>, V) + 'static>,
+            cb_arg: V,
+            buf: DmaBuf,
+        }
+
+        extern "C" fn health_log_done<V>(
+            ctx: *mut c_void,
+            cpl: *const spdk_nvme_cpl,
+        ) {
+            let ctx = unsafe { Box::from_raw(ctx as *mut HealthLogCtx<V>) };
+
+            let log = if nvme_cpl_succeeded(cpl) {
+                NvmeHealthLog::parse(ctx.buf.as_slice()).ok_or(
+                    CoreError::NvmeAdminFailed {
+                        opcode: nvme_admin_opc::GET_LOG_PAGE as u16,
+                    },
+                )
+            } else {
+                Err(CoreError::NvmeAdminFailed {
+                    opcode: nvme_admin_opc::GET_LOG_PAGE as u16,
+                })
+            };
+
+            (ctx.cb)(log, ctx.cb_arg)
+        }
+
+        if self.state_machine.current_state() != Running {
+            error!(
+                "{} Controller is in '{:?}' state, cannot get health log",
+                self.name,
+                self.state_machine.current_state()
+            );
+            return Err(CoreError::NvmeAdminDispatch {
+                source: Errno::EAGAIN,
+                opcode: nvme_admin_opc::GET_LOG_PAGE as u16,
+            });
+        }
+
+        let ctrlr = self.ctrlr_as_ptr();
+
+        if !unsafe {
+            spdk_nvme_ctrlr_is_log_page_supported(
+                ctrlr,
+                NVME_LOG_HEALTH_INFORMATION,
+            )
+        } {
+            return Err(CoreError::NvmeLogPageUnsupported {
+                name: self.get_name(),
+                log_page: NVME_LOG_HEALTH_INFORMATION,
+            });
+        }
+
+        let buf = DmaBuf::new(NVME_HEALTH_LOG_SIZE, 8).map_err(|_| {
+            CoreError::DmaAllocationError {
+                size: NVME_HEALTH_LOG_SIZE,
+            }
+        })?;
+
+        let ctx = Box::into_raw(Box::new(HealthLogCtx {
+            cb: Box::new(cb),
+            cb_arg,
+            buf,
+        }));
+
+        let rc = unsafe {
+            spdk_nvme_ctrlr_cmd_get_log_page(
+                ctrlr,
+                NVME_LOG_HEALTH_INFORMATION,
+                0xffffffff,
+                *(*ctx).buf,
+                NVME_HEALTH_LOG_SIZE as u32,
+                0,
+                Some(health_log_done::<T>),
+                ctx.cast(),
+            )
+        };
+
+        if rc != 0 {
+            // The completion callback is never invoked for a command that
+            // failed to be submitted, so reclaim the context here.
+            drop(unsafe { Box::from_raw(ctx) });
+            return Err(CoreError::NvmeAdminDispatch {
+                source: Errno::from_i32(-rc),
+                opcode: nvme_admin_opc::GET_LOG_PAGE as u16,
+            });
+        }
+
+        Ok(())
+    }
+
     /// Shutdown the controller and all its resources.
     /// This function deallocates all controller's resources (I/O queues, I/O
     /// channels and pollers), aborts all active I/O operations and
@@ -546,6 +802,12 @@ impl<'a> NvmeController<'a> {
                     Running,
                     Faulted(ControllerFailureReason::Reset),
                 );
+            } else if let Faulted(reason) = controller.get_state() {
+                // A successful reset recovers a controller whose previous
+                // reset failed, otherwise it would remain faulted forever.
+                let _ = controller
+                    .state_machine
+                    .transition_checked(Faulted(reason), Running);
             }
 
             // Unlock the controller before calling the callback to avoid
@@ -634,6 +896,12 @@ impl<'a> NvmeController<'a> {
                 "{}: controller shutdown detected, skipping reset",
                 reset_ctx.name
             );
+            // Still complete the reset, so that the reset flag is cleared
+            // and the caller learns that the reset did not happen.
+            NvmeController::_complete_reset(
+                reset_ctx,
+                -(Errno::ECANCELED as i32),
+            );
             return;
         }
 
@@ -694,7 +962,7 @@ impl<'a> NvmeController<'a> {
         NvmeController::_complete_reset(reset_ctx, status);
     }
 
-    fn notify_event(&self, event: DeviceEventType) -> usize {
+    pub(crate) fn notify_event(&self, event: DeviceEventType) -> usize {
         // Keep a separate copy of all registered listeners in order to not
         // invoke them with the lock held.
         let listeners = {
@@ -727,6 +995,14 @@ impl<'a> NvmeController<'a> {
     }
 }
 
+/// number of times detaching a controller is retried when dropping it
+const DETACH_RETRIES: u32 = 25;
+/// interval between attempts to detach a dropped controller
+const DETACH_RETRY_INTERVAL: Duration = Duration::from_millis(200);
+
+/// how often controllers are checked for being idle
+const IDLE_POLL_PERIOD_US: u64 = 1_000_000;
+
 impl<'a> Drop for NvmeController<'a> {
     fn drop(&mut self) {
         let curr_state = self.get_state();
@@ -751,6 +1027,9 @@ impl<'a> Drop for NvmeController<'a> {
             );
 
             inner.adminq_poller.stop();
+            if let Some(poller) = inner.idle_poller {
+                poller.stop();
+            }
 
             drop(inner.io_device);
 
@@ -759,8 +1038,24 @@ impl<'a> Drop for NvmeController<'a> {
                 "detaching NVMe controller"
             );
             let rc = unsafe { spdk_nvme_detach(inner.ctrlr.as_ptr()) };
-
-            assert_eq!(rc, 0, "Failed to detach NVMe controller");
+            if rc != 0 {
+                // Detach may fail, i.e. while a reset is in progress.
+                // Panicking within drop aborts, and the controller still
+                // refers to the timeout config, so hand both over to
+                // retries that give the reset time to complete.
+                warn!(
+                    ?self.name,
+                    rc,
+                    "failed to detach NVMe controller, retrying"
+                );
+                let pending = PendingDetach {
+                    name: self.name.clone(),
+                    ctrlr: inner.ctrlr,
+                    timeout_config: self.timeout_config,
+                };
+                Reactors::master().send_future(pending.retry());
+                return;
+            }
             info!(
                 ?self.name,
                 "NVMe controller successfully detached"
@@ -773,6 +1068,42 @@ impl<'a> Drop for NvmeController<'a> {
     }
 }
 
+/// A dropped controller which failed to detach, along with the timeout config
+/// it still refers to.
+struct PendingDetach {
+    name: String,
+    ctrlr: SpdkNvmeController,
+    timeout_config: NonNull<TimeoutConfig>,
+}
+
+impl PendingDetach {
+    /// Retry the detach, processing the admin queue and waiting in between
+    /// for a reset in progress to complete. The timeout config is only freed
+    /// once the controller is detached and leaked if that never happens.
+    async fn retry(self) {
+        let name = self.name;
+        for attempt in 1 ..= DETACH_RETRIES {
+            unsafe {
+                spdk_nvme_ctrlr_process_admin_completions(self.ctrlr.as_ptr())
+            };
+            if mayastor_sleep(DETACH_RETRY_INTERVAL).await.is_err() {
+                break;
+            }
+
+            let rc = unsafe { spdk_nvme_detach(self.ctrlr.as_ptr()) };
+            if rc == 0 {
+                info!(?name, "NVMe controller successfully detached");
+                unsafe {
+                    Box::from_raw(self.timeout_config.as_ptr());
+                }
+                return;
+            }
+            warn!(?name, rc, attempt, "failed to detach NVMe controller");
+        }
+        error!(?name, "failed to detach NVMe controller, giving up");
+    }
+}
+
 extern "C" fn aer_cb(ctx: *mut c_void, cpl: *const spdk_nvme_cpl) {
     let mut event = spdk_nvme_async_event_completion::default();
 
@@ -821,13 +1152,18 @@ extern "C" fn aer_cb(ctx: *mut c_void, cpl: *const spdk_nvme_cpl) {
     }
 }
 
-/// return number of completions processed (maybe 0) or the negated on error,
-/// which is one of:
+/// Poll the admin queue, returning 1 when completions were processed and 0
+/// otherwise, as expected of a poller. Processing the admin queue fails with
+/// the negated errno, which is one of:
 ///
 /// ENXIO: the qpair is not conected  or when the controller is
 /// marked as failed.
 ///
 /// EGAIN: returned whenever the controller is being reset.
+///
+/// A failed admin queue does not recover by itself, so the controller is
+/// reset when ENXIO is returned. A controller which stops completing admin
+/// commands altogether is reset once its keep-alive timeout elapses.
 pub extern "C" fn nvme_poll_adminq(ctx: *mut c_void) -> i32 {
     let mut context = NonNull::<TimeoutConfig>::new(ctx.cast())
         .expect("ctx pointer may never be null");
@@ -836,13 +1172,17 @@ pub extern "C" fn nvme_poll_adminq(ctx: *mut c_void) -> i32 {
     let result = context.process_adminq();
 
     if result < 0 {
-        //error!("{}: {}", context.name, Errno::from_i32(result.abs()));
-        return 1;
+        if Errno::from_i32(result.abs()) == Errno::ENXIO {
+            context.admin_queue_failed();
+        }
+        return 0;
     }
 
     if result == 0 {
+        context.check_keep_alive();
         0
     } else {
+        context.admin_completed();
         1
     }
 }
@@ -857,22 +1197,82 @@ pub(crate) async fn destroy_device(name: String) -> Result<(), NexusBdevError> {
 
     // 1. Initiate controller shutdown, which shuts down all I/O resources
     // of the controller.
-    let (s, r) = oneshot::channel::<bool>();
-    {
+    let r = {
         let mut controller = carc.lock();
+        shutdown_controller(&mut controller)?
+    };
+
+    remove_device(name, carc, r).await
+}
+
+/// Detach the controller if it has been idle for the given time. The check
+/// and the start of the shutdown happen under the controller lock, so an I/O
+/// channel created concurrently either keeps the controller attached or fails
+/// as the controller is no longer running. Runs on the master reactor.
+fn detach_if_idle(name: &str, timeout: Duration) {
+    let carc = match NVME_CONTROLLERS.lookup_by_name(name) {
+        Some(c) => c,
+        None => return,
+    };
+
+    // Do not wait for the lock, the controller is in use when it is held.
+    let r = {
+        let mut controller = match carc.try_lock() {
+            Some(c) => c,
+            None => return,
+        };
 
-        fn _shutdown_callback(success: bool, ctx: *mut c_void) {
-            done_cb(ctx, success);
+        if !controller.is_idle(timeout) {
+            return;
         }
 
-        controller
-            .shutdown(_shutdown_callback, cb_arg(s))
-            .map_err(|_| NexusBdevError::DestroyBdev {
-                name: String::from(&name),
-                source: Errno::EAGAIN,
-            })?
+        info!(?name, ?timeout, "detaching unused NVMe controller");
+
+        match shutdown_controller(&mut controller) {
+            Ok(r) => r,
+            Err(error) => {
+                warn!(?name, ?error, "failed to detach idle controller");
+                return;
+            }
+        }
+    };
+
+    let name = name.to_string();
+    Reactors::master().send_future(async move {
+        if let Err(error) = remove_device(name.clone(), carc, r).await {
+            error!(?name, ?error, "failed to detach idle controller");
+        }
+    });
+}
+
+/// Start the shutdown of the controller, the receiver yields whether it
+/// succeeded.
+fn shutdown_controller(
+    controller: &mut NvmeController,
+) -> Result<oneshot::Receiver<bool>, NexusBdevError> {
+    let (s, r) = oneshot::channel::<bool>();
+
+    fn _shutdown_callback(success: bool, ctx: *mut c_void) {
+        done_cb(ctx, success);
     }
 
+    controller
+        .shutdown(_shutdown_callback, cb_arg(s))
+        .map_err(|_| NexusBdevError::DestroyBdev {
+            name: controller.get_name(),
+            source: Errno::EAGAIN,
+        })?;
+
+    Ok(r)
+}
+
+/// Wait for the shutdown of the controller to complete, then remove it from
+/// the list and notify all listeners about device removal.
+async fn remove_device(
+    name: String,
+    carc: Arc<parking_lot::Mutex<NvmeController<'_>>>,
+    r: oneshot::Receiver<bool>,
+) -> Result<(), NexusBdevError> {
     if !r.await.expect("Failed awaiting at shutdown()") {
         error!(?name, "failed to shutdown controller");
         return Err(NexusBdevError::DestroyBdev {
@@ -936,7 +1336,7 @@ pub(crate) fn connected_attached_cb(
         controller.timeout_config,
     ));
 
-    controller.configure_timeout();
+    controller.configure_timeout(ctx.timeout_action());
 
     if !controller.populate_namespaces() {
         error!("{}: failed to populate namespaces", ctx.name());
@@ -949,12 +1349,26 @@ pub(crate) fn connected_attached_cb(
     // Register callbacks.
     controller.register_callbacks();
 
+    // A qpair is allocated per core, so having less I/O queues than cores
+    // limits the parallelism of the controller.
+    let num_io_queues = controller.num_io_queues();
+    let num_cores = Cores::count().id();
+    if num_io_queues < num_cores {
+        warn!(
+            "{}: target offers {} I/O queues for {} cores, I/O parallelism is limited",
+            ctx.name(),
+            num_io_queues,
+            num_cores
+        );
+    }
+
     NVME_CONTROLLERS.insert_controller(cid.to_string(), ctl);
 
     controller
         .state_machine
         .transition(Running)
         .expect("Failed to transition controller into Running state");
+    controller.touch();
 
     // Wake up the waiter and complete controller registration.
     ctx.sender()
@@ -1007,6 +1421,7 @@ pub(crate) mod options {
         host_nqn: Option<String>,
         keep_alive_timeout_ms: Option<u32>,
         transport_retry_count: Option<u8>,
+        num_io_queues: Option<u32>,
     }
 
     #[allow(dead_code)]
@@ -1049,6 +1464,13 @@ pub(crate) mod options {
             self
         }
 
+        /// Number of I/O queues to request, the target may grant fewer
+        /// during negotiation.
+        pub fn with_num_io_queues(mut self, count: u32) -> Self {
+            self.num_io_queues = Some(count);
+            self
+        }
+
         /// Builder to override default values
         pub fn build(self) -> NvmeControllerOpts {
             let mut opts = NvmeControllerOpts::default();
@@ -1069,6 +1491,11 @@ pub(crate) mod options {
                 opts.0.keep_alive_timeout_ms = timeout_ms;
             }
 
+            // clamp to the maximum SPDK supports, which is the default
+            if let Some(count) = self.num_io_queues {
+                opts.0.num_io_queues = count.max(1).min(opts.0.num_io_queues);
+            }
+
             if let Some(ext_host_id) = self.ext_host_id {
                 opts.0.extended_host_id = ext_host_id;
             }
@@ -1102,6 +1529,21 @@ pub(crate) mod options {
             assert_eq!(opts.0.fabrics_connect_timeout_us, 1);
             assert_eq!(opts.0.transport_retry_count, 1);
         }
+
+        #[test]
+        fn nvme_num_io_queues_clamped() {
+            let max = options::NvmeControllerOpts::default().0.num_io_queues;
+
+            let opts = options::Builder::new().with_num_io_queues(2).build();
+            assert_eq!(opts.0.num_io_queues, 2);
+
+            let opts = options::Builder::new().with_num_io_queues(0).build();
+            assert_eq!(opts.0.num_io_queues, 1);
+
+            let opts =
+                options::Builder::new().with_num_io_queues(u32::MAX).build();
+            assert_eq!(opts.0.num_io_queues, max);
+        }
     }
 }
 
@@ -1112,6 +1554,7 @@ pub(crate) mod transport {
 
     use spdk_sys::spdk_nvme_transport_id;
 
+    #[derive(Clone)]
     pub struct NvmeTransportId(spdk_nvme_transport_id);
 
     impl Debug for NvmeTransportId {
@@ -1127,6 +1570,12 @@ pub(crate) mod transport {
         }
     }
 
+    impl From<&spdk_nvme_transport_id> for NvmeTransportId {
+        fn from(trid: &spdk_nvme_transport_id) -> Self {
+            Self(*trid)
+        }
+    }
+
     impl NvmeTransportId {
         pub fn trtype(&self) -> String {
             unsafe {
@@ -1162,6 +1611,16 @@ pub(crate) mod transport {
         pub fn as_ptr(&self) -> *const spdk_nvme_transport_id {
             &self.0
         }
+
+        /// URI which attaches to the subsystem of the transport ID
+        pub fn uri(&self) -> String {
+            format!(
+                "nvmf://{}:{}/{}",
+                self.traddr(),
+                self.svcid(),
+                self.subnqn()
+            )
+        }
     }
 
     #[derive(Debug)]
@@ -1286,6 +1745,10 @@ pub(crate) mod transport {
             assert_eq!(transport.traddr(), "127.0.0.1");
             assert_eq!(transport.subnqn(), "nqn.2021-01-01:test.nqn");
             assert_eq!(transport.svcid(), "4420");
+            assert_eq!(
+                transport.uri(),
+                "nvmf://127.0.0.1:4420/nqn.2021-01-01:test.nqn"
+            );
         }
     }
 }
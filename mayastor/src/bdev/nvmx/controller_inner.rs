@@ -14,7 +14,9 @@ use spdk_sys::{
     spdk_nvme_ctrlr,
     spdk_nvme_ctrlr_cmd_abort,
     spdk_nvme_ctrlr_fail,
+    spdk_nvme_ctrlr_get_data,
     spdk_nvme_ctrlr_get_regs_csts,
+    spdk_nvme_ctrlr_get_transport_id,
     spdk_nvme_ctrlr_process_admin_completions,
     spdk_nvme_ctrlr_register_timeout_callback,
     spdk_nvme_qpair,
@@ -25,12 +27,18 @@ use spdk_sys::{
 
 use crate::{
     bdev::nvmx::{
+        controller::transport::NvmeTransportId,
         nvme_bdev_running_config,
         utils::nvme_cpl_succeeded,
         NvmeController,
         NVME_CONTROLLERS,
     },
-    core::{CoreError, DeviceIoController, DeviceTimeoutAction},
+    core::{
+        CoreError,
+        DeviceEventType,
+        DeviceIoController,
+        DeviceTimeoutAction,
+    },
 };
 
 impl TryFrom<u32> for DeviceTimeoutAction {
@@ -70,10 +78,14 @@ const RESET_COOLDOWN_INTERVAL: Duration = Duration::from_secs(3);
 pub(crate) struct TimeoutConfig {
     pub name: String,
     timeout_action: AtomicCell<DeviceTimeoutAction>,
+    timeout_us: AtomicCell<u64>,
     reset_in_progress: AtomicCell<bool>,
     ctrlr: SpdkNvmeController,
     reset_attempts: u32,
     next_reset_time: Instant,
+    keep_alive_timeout: Duration,
+    last_admin_completion: Instant,
+    keep_alive_expired: bool,
 }
 
 impl Drop for TimeoutConfig {
@@ -89,10 +101,14 @@ impl TimeoutConfig {
         Self {
             name: String::from(ctrlr),
             timeout_action: AtomicCell::new(DeviceTimeoutAction::Ignore),
+            timeout_us: AtomicCell::new(0),
             reset_in_progress: AtomicCell::new(false),
             ctrlr: SpdkNvmeController(NonNull::dangling()),
             reset_attempts: MAX_RESET_ATTEMPTS,
             next_reset_time: Instant::now(),
+            keep_alive_timeout: Duration::default(),
+            last_admin_completion: Instant::now(),
+            keep_alive_expired: false,
         }
     }
 
@@ -102,6 +118,9 @@ impl TimeoutConfig {
 
     pub fn set_controller(&mut self, ctrlr: SpdkNvmeController) {
         self.ctrlr = ctrlr;
+        self.keep_alive_timeout =
+            Duration::from_millis(ctrlr.opts.keep_alive_timeout_ms as u64);
+        self.last_admin_completion = Instant::now();
     }
 
     pub fn process_adminq(&self) -> i32 {
@@ -121,6 +140,7 @@ impl TimeoutConfig {
             // In case of successful reset, also reset the allowed number of
             // reset attempts.
             timeout_ctx.reset_attempts = MAX_RESET_ATTEMPTS;
+            timeout_ctx.admin_completed();
         } else {
             error!(
                 "{} failed to reset controller in response to I/O timeout",
@@ -228,6 +248,71 @@ impl TimeoutConfig {
         );
     }
 
+    /// Reset the controller after its admin queue failed at the transport
+    /// level, unless a reset is already in progress or resets are cooling
+    /// down after too many failed attempts.
+    pub(crate) fn admin_queue_failed(&mut self) {
+        if self.reset_in_progress.load()
+            || (self.reset_attempts == 0
+                && Instant::now() < self.next_reset_time)
+        {
+            return;
+        }
+
+        error!("{}: admin queue failed, resetting controller", self.name);
+        self.reset_controller();
+    }
+
+    /// Record that the controller completed admin commands, which includes
+    /// the keep-alive commands the controller is sent periodically.
+    pub(crate) fn admin_completed(&mut self) {
+        self.last_admin_completion = Instant::now();
+        if self.keep_alive_expired {
+            info!("{}: controller is reachable again", self.name);
+            self.keep_alive_expired = false;
+        }
+    }
+
+    /// Check whether a keep-alive command failed to complete within the
+    /// keep-alive timeout. Keep-alive commands are sent at half the timeout,
+    /// so the next one is sent at most half the timeout after the last admin
+    /// completion, and then has the full timeout to complete however slow
+    /// the round trip. Once elapsed, listeners are notified and the
+    /// configured timeout action is taken, where aborting falls back to a
+    /// reset as for any admin command. The event is raised once until the
+    /// controller responds again.
+    pub(crate) fn check_keep_alive(&mut self) {
+        if self.keep_alive_expired
+            || self.keep_alive_timeout == Duration::default()
+            || self.reset_in_progress.load()
+            || self.last_admin_completion.elapsed()
+                < self.keep_alive_timeout + self.keep_alive_timeout / 2
+        {
+            return;
+        }
+
+        self.keep_alive_expired = true;
+        error!(
+            "{}: keep-alive timeout of {} ms elapsed, controller unreachable",
+            self.name,
+            self.keep_alive_timeout.as_millis()
+        );
+
+        if let Some(c) = NVME_CONTROLLERS.lookup_by_name(&self.name) {
+            c.lock().notify_event(DeviceEventType::KeepAliveTimeout);
+        }
+
+        match self.timeout_action.load() {
+            DeviceTimeoutAction::Abort | DeviceTimeoutAction::Reset => {
+                self.reset_controller()
+            }
+            DeviceTimeoutAction::HotRemove => self.hot_remove(),
+            DeviceTimeoutAction::Ignore => {
+                info!("{}: no timeout action defined, ignored", self.name)
+            }
+        }
+    }
+
     /// Set new I/O timeout action.
     pub fn set_timeout_action(&mut self, action: DeviceTimeoutAction) {
         self.timeout_action.store(action);
@@ -238,6 +323,16 @@ impl TimeoutConfig {
         self.timeout_action.load()
     }
 
+    /// Set new I/O timeout (in microseconds).
+    pub fn set_timeout_us(&mut self, timeout_us: u64) {
+        self.timeout_us.store(timeout_us);
+    }
+
+    /// Get current I/O timeout (in microseconds).
+    pub fn get_timeout_us(&self) -> u64 {
+        self.timeout_us.load()
+    }
+
     pub fn from_ptr(ptr: *mut TimeoutConfig) -> &'static mut TimeoutConfig {
         unsafe { &mut *(ptr as *mut TimeoutConfig) }
     }
@@ -299,6 +394,33 @@ impl SpdkNvmeController {
     pub fn ext_host_id(&self) -> &[u8; 16] {
         unsafe { &(*self.as_ptr()).opts.extended_host_id }
     }
+
+    /// Returns the model number reported by the controller
+    pub fn model(&self) -> String {
+        let data = unsafe { &*spdk_nvme_ctrlr_get_data(self.0.as_ptr()) };
+        identify_string(&data.mn)
+    }
+
+    /// Returns the serial number reported by the controller
+    pub fn serial(&self) -> String {
+        let data = unsafe { &*spdk_nvme_ctrlr_get_data(self.0.as_ptr()) };
+        identify_string(&data.sn)
+    }
+
+    /// Returns the transport id the controller is connected with
+    pub fn transport_id(&self) -> NvmeTransportId {
+        unsafe { &*spdk_nvme_ctrlr_get_transport_id(self.0.as_ptr()) }.into()
+    }
+}
+
+/// Convert a space padded string of the identify data into a String.
+fn identify_string<T: Copy + Into<i16>>(field: &[T]) -> String {
+    let bytes = field
+        .iter()
+        .map(|c| (*c).into() as u8)
+        .take_while(|c| *c != 0)
+        .collect::<Vec<_>>();
+    String::from_utf8_lossy(&bytes).trim_end().to_string()
 }
 
 impl From<*mut spdk_nvme_ctrlr> for SpdkNvmeController {
@@ -326,6 +448,19 @@ impl<'a> DeviceIoController for NvmeController<'a> {
         info!("{} timeout action set to {:?}", self.name, action);
         Ok(())
     }
+
+    /// Get current I/O timeout (in microseconds).
+    fn get_timeout_us(&self) -> Result<u64, CoreError> {
+        Ok(unsafe { self.timeout_config.as_ref().get_timeout_us() })
+    }
+
+    /// Set I/O timeout (in microseconds), re-registering the timeout
+    /// callback with the controller. Zero disables timeout detection.
+    fn set_timeout_us(&mut self, timeout_us: u64) -> Result<(), CoreError> {
+        self.register_timeout(timeout_us);
+        info!("{} I/O timeout set to {} us", self.name, timeout_us);
+        Ok(())
+    }
 }
 
 // I/O timeout handling for NVMe controller.
@@ -429,7 +564,12 @@ impl<'a> NvmeController<'a> {
         }
     }
 
-    pub(crate) fn configure_timeout(&mut self) {
+    /// Configure I/O timeout handling from the global defaults, with the
+    /// given timeout action taking precedence over the default action.
+    pub(crate) fn configure_timeout(
+        &mut self,
+        timeout_action: Option<DeviceTimeoutAction>,
+    ) {
         let device_defaults = nvme_bdev_running_config();
 
         if device_defaults.timeout_us == 0 {
@@ -442,8 +582,9 @@ impl<'a> NvmeController<'a> {
             return;
         }
 
-        let action = match DeviceTimeoutAction::try_from(
-            device_defaults.action_on_timeout,
+        let action = match timeout_action.map_or_else(
+            || DeviceTimeoutAction::try_from(device_defaults.action_on_timeout),
+            Ok,
         ) {
             Ok(action) => action,
             Err(e) => {
@@ -456,18 +597,20 @@ impl<'a> NvmeController<'a> {
         };
 
         self.set_timeout_action(action).unwrap();
+        self.set_timeout_us(device_defaults.timeout_us).unwrap();
+    }
 
+    /// Register the I/O timeout handler with the given timeout and record it
+    /// in the controller's timeout config.
+    fn register_timeout(&mut self, timeout_us: u64) {
         unsafe {
+            self.timeout_config.as_mut().set_timeout_us(timeout_us);
             spdk_nvme_ctrlr_register_timeout_callback(
                 self.ctrlr_as_ptr(),
-                device_defaults.timeout_us,
+                timeout_us,
                 Some(NvmeController::io_timeout_handler),
                 self.timeout_config.as_ptr().cast(),
             );
         }
-        info!(
-            "{} I/O timeout set to {} us",
-            self.name, device_defaults.timeout_us
-        );
     }
 }
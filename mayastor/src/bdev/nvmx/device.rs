@@ -7,6 +7,7 @@ use uuid::Uuid;
 
 use crate::{
     bdev::nvmx::{
+        controller::OpenDescriptors,
         controller_inner::SpdkNvmeController,
         NvmeController,
         NvmeControllerState,
@@ -41,6 +42,7 @@ pub struct NvmeDeviceDescriptor {
     io_device_id: u64,
     name: String,
     prchk_flags: u32,
+    descriptors: Arc<OpenDescriptors>,
 }
 
 impl NvmeDeviceDescriptor {
@@ -54,6 +56,7 @@ impl NvmeDeviceDescriptor {
                 name: controller.get_name(),
                 ctrlr: controller.controller().unwrap(),
                 prchk_flags: controller.flags(),
+                descriptors: controller.descriptor_opened(),
             }))
         } else {
             Err(CoreError::OpenBdev {
@@ -63,6 +66,12 @@ impl NvmeDeviceDescriptor {
     }
 }
 
+impl Drop for NvmeDeviceDescriptor {
+    fn drop(&mut self) {
+        self.descriptors.closed();
+    }
+}
+
 impl BlockDeviceDescriptor for NvmeDeviceDescriptor {
     fn get_device(&self) -> Box<dyn BlockDevice> {
         Box::new(NvmeBlockDevice::from_ns(&self.name, Arc::clone(&self.ns)))
@@ -75,7 +84,7 @@ impl BlockDeviceDescriptor for NvmeDeviceDescriptor {
             &self.name,
             self.io_device_id,
             self.ctrlr,
-            self.ns,
+            Arc::clone(&self.ns),
             self.prchk_flags,
         )?))
     }
@@ -111,10 +120,11 @@ impl NvmeBlockDevice {
             },
         )?;
 
-        let controller = controller.lock();
+        let mut controller = controller.lock();
 
         // Make sure controller is available.
         if controller.get_state() == NvmeControllerState::Running {
+            controller.touch();
             let descr = NvmeDeviceDescriptor::create(&controller)?;
             Ok(descr)
         } else {
@@ -278,6 +288,20 @@ impl DeviceIoController for NvmeDeviceIoController {
 
         controller.set_timeout_action(action)
     }
+
+    fn get_timeout_us(&self) -> Result<u64, CoreError> {
+        let controller = self.lookup_controller()?;
+        let controller = controller.lock();
+
+        controller.get_timeout_us()
+    }
+
+    fn set_timeout_us(&mut self, timeout_us: u64) -> Result<(), CoreError> {
+        let controller = self.lookup_controller()?;
+        let mut controller = controller.lock();
+
+        controller.set_timeout_us(timeout_us)
+    }
 }
 
 /*
@@ -288,10 +312,11 @@ pub fn lookup_by_name(name: &str) -> Option<Box<dyn BlockDevice>> {
         let controller = c.lock();
         // Make sure controller is available.
         if controller.get_state() == NvmeControllerState::Running {
-            let ns = controller
-                .namespace()
-                .expect("no namespaces for this controller");
-            return Some(Box::new(NvmeBlockDevice::from_ns(name, ns)));
+            if let Some(ns) = controller.namespace() {
+                return Some(Box::new(NvmeBlockDevice::from_ns(name, ns)));
+            }
+            warn!("{}: NVMe controller has no active namespace", name);
+            return None;
         }
     }
     debug!("{}: NVMe controller not found", name);
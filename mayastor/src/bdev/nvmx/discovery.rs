@@ -0,0 +1,141 @@
+//!
+//! Enumerates the subsystems which an NVMe-oF target advertises through its
+//! discovery controller, so that one of them can be attached with an nvmf URI.
+
+use std::{os::raw::c_void, ptr::NonNull, time::Duration};
+
+use nix::errno::Errno;
+
+use spdk_sys::{
+    spdk_nvme_ctrlr_opts,
+    spdk_nvme_probe_async,
+    spdk_nvme_probe_ctx,
+    spdk_nvme_probe_poll_async,
+    spdk_nvme_transport_id,
+};
+
+use crate::{
+    bdev::nvmx::controller::transport::{self, NvmeTransportId},
+    core::{CoreError, Reactors},
+    sleep::mayastor_sleep,
+};
+
+/// well-known NQN of the discovery controller of an NVMe-oF target
+const DISCOVERY_NQN: &str = "nqn.2014-08.org.nvmexpress.discovery";
+
+/// how often the probe is polled for completion
+const DISCOVERY_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// Called for every subsystem found in the discovery log page, the subsystems
+// are only collected and never attached.
+extern "C" fn discovery_probe_cb(
+    cb_ctx: *mut c_void,
+    trid: *const spdk_nvme_transport_id,
+    _opts: *mut spdk_nvme_ctrlr_opts,
+) -> bool {
+    let found = unsafe { &mut *(cb_ctx as *mut Vec<NvmeTransportId>) };
+    let trid = NvmeTransportId::from(unsafe { &*trid });
+    // referrals to other discovery controllers are not followed
+    if trid.subnqn() != DISCOVERY_NQN {
+        found.push(trid);
+    }
+    false
+}
+
+/// A probe in progress, together with the transport IDs it collects. The
+/// probe is only freed once polled to completion, so when dropped before
+/// then, as happens when the discovery is abandoned, it is polled to
+/// completion in the background.
+struct Probe {
+    ctx: Option<NonNull<spdk_nvme_probe_ctx>>,
+    found: Box<Vec<NvmeTransportId>>,
+}
+
+impl Probe {
+    /// Poll the probe once, returning 0 once it has completed and freed
+    /// itself, -EAGAIN while in progress or another negated errno on error.
+    fn poll(&mut self) -> i32 {
+        let ctx = match self.ctx {
+            Some(ctx) => ctx,
+            None => return 0,
+        };
+        let rc = unsafe { spdk_nvme_probe_poll_async(ctx.as_ptr()) };
+        if rc != -libc::EAGAIN {
+            self.ctx = None;
+        }
+        rc
+    }
+}
+
+impl Drop for Probe {
+    fn drop(&mut self) {
+        if self.ctx.is_none() {
+            return;
+        }
+        let mut probe = Probe {
+            ctx: self.ctx.take(),
+            found: std::mem::take(&mut self.found),
+        };
+        Reactors::current().send_future(async move {
+            while probe.poll() == -libc::EAGAIN {
+                let _ = mayastor_sleep(DISCOVERY_POLL_INTERVAL).await;
+            }
+        });
+    }
+}
+
+/// Connects to the discovery controller at `traddr`:`svcid` and returns the
+/// transport IDs of the subsystems listed in its discovery log page.
+pub async fn discover(
+    traddr: &str,
+    svcid: &str,
+) -> Result<Vec<NvmeTransportId>, CoreError> {
+    let trid = transport::Builder::new()
+        .with_traddr(traddr)
+        .with_svcid(svcid)
+        .with_subnqn(DISCOVERY_NQN)
+        .build();
+    let failed = |source: Errno| CoreError::NvmeDiscovery {
+        source,
+        traddr: traddr.to_string(),
+        svcid: svcid.to_string(),
+    };
+
+    // the collected transport IDs must stay in place until the probe has
+    // completed, as the callback keeps a pointer to them
+    let mut probe = Probe {
+        ctx: None,
+        found: Box::new(Vec::new()),
+    };
+    probe.ctx = Some(
+        NonNull::new(unsafe {
+            spdk_nvme_probe_async(
+                trid.as_ptr(),
+                &mut *probe.found as *mut _ as *mut c_void,
+                Some(discovery_probe_cb),
+                None,
+                None,
+            )
+        })
+        .ok_or_else(|| failed(Errno::ENODEV))?,
+    );
+
+    loop {
+        match probe.poll() {
+            0 => break,
+            errno if errno == -libc::EAGAIN => {
+                let _ = mayastor_sleep(DISCOVERY_POLL_INTERVAL).await;
+            }
+            errno => return Err(failed(Errno::from_i32(errno.abs()))),
+        }
+    }
+
+    let found = std::mem::take(&mut *probe.found);
+    debug!(
+        "discovered {} subsystem(s) at {}:{}",
+        found.len(),
+        traddr,
+        svcid
+    );
+    Ok(found)
+}
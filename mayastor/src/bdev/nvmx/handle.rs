@@ -388,6 +388,9 @@ fn alloc_nvme_io_ctx(
 ) -> Result<*mut NvmeIoCtx, CoreError> {
     let pool = NVME_IOCTX_POOL.get().unwrap();
     pool.get(ctx).ok_or_else(|| {
+        warn!(
+            "memory pool [nvme_ctrl_io_ctx] exhausted, consider raising --nvme-ctl-pool-size"
+        );
         io_type_to_err(op, libc::ENOMEM, offset_blocks, num_blocks)
     })
 }
@@ -405,6 +408,10 @@ fn check_channel_for_io(
     offset_blocks: u64,
     num_blocks: u64,
 ) -> Result<(), CoreError> {
+    if inner.is_shutdown() {
+        return Err(channel_shutdown(inner));
+    }
+
     let mut errno = 0;
 
     // Check against concurrent controller reset, which results in valid
@@ -420,6 +427,13 @@ fn check_channel_for_io(
     }
 }
 
+/// Error for I/O which is submitted to, or aborted by, a shutdown channel.
+fn channel_shutdown(inner: &NvmeIoChannelInner) -> CoreError {
+    CoreError::ChannelShutdown {
+        name: inner.device.device_name(),
+    }
+}
+
 /// Handler for controller reset operation.
 /// Serves as a proxy layer between NVMe controller and block device layer
 /// (represented by device I/O handle): we need to pass block device
@@ -512,6 +526,8 @@ impl BlockDeviceHandle for NvmeDeviceHandle {
                 num_blocks,
             );
             Ok(buffer.len())
+        } else if inner.is_shutdown() {
+            Err(channel_shutdown(inner))
         } else {
             Err(CoreError::ReadFailed {
                 offset,
@@ -585,6 +601,8 @@ impl BlockDeviceHandle for NvmeDeviceHandle {
                 num_blocks,
             );
             Ok(buffer.len())
+        } else if inner.is_shutdown() {
+            Err(channel_shutdown(inner))
         } else {
             Err(CoreError::WriteFailed {
                 offset,
@@ -916,6 +934,9 @@ impl BlockDeviceHandle for NvmeDeviceHandle {
         let inner = NvmeIoChannel::inner_from_channel(self.io_channel.as_ptr());
 
         // Make sure channel allows I/O.
+        if inner.is_shutdown() {
+            return Err(channel_shutdown(inner));
+        }
         if inner.qpair.is_none() {
             return Err(CoreError::NvmeAdminDispatch {
                 source: Errno::ENODEV,
@@ -1031,6 +1052,31 @@ impl BlockDeviceHandle for NvmeDeviceHandle {
         self.io_passthru(&cmd, Some(&mut buffer)).await
     }
 
+    /// NVMe Reservation Release
+    async fn nvme_resv_release(
+        &self,
+        current_key: u64,
+        resv_type: u8,
+        release_action: u8,
+    ) -> Result<(), CoreError> {
+        let mut cmd = spdk_sys::spdk_nvme_cmd::default();
+        cmd.set_opc(nvme_nvm_opcode::RESERVATION_RELEASE.into());
+        cmd.nsid = 0x1;
+        unsafe {
+            cmd.__bindgen_anon_1
+                .cdw10_bits
+                .resv_release
+                .set_rrela(release_action.into());
+            cmd.__bindgen_anon_1
+                .cdw10_bits
+                .resv_release
+                .set_rtype(resv_type.into());
+        }
+        let mut buffer = self.dma_malloc(8).unwrap();
+        buffer.as_mut_slice()[.. 8].copy_from_slice(&current_key.to_le_bytes());
+        self.io_passthru(&cmd, Some(&mut buffer)).await
+    }
+
     /// NVMe Reservation Report
     /// cdw11: bit 0- Extended Data Structure
     async fn nvme_resv_report(
@@ -1069,6 +1115,9 @@ impl BlockDeviceHandle for NvmeDeviceHandle {
         let inner = NvmeIoChannel::inner_from_channel(self.io_channel.as_ptr());
 
         // Make sure channel allows I/O.
+        if inner.is_shutdown() {
+            return Err(channel_shutdown(inner));
+        }
         if inner.qpair.is_none() {
             return Err(CoreError::NvmeIoPassthruDispatch {
                 source: Errno::ENODEV,
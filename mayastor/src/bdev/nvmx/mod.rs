@@ -4,9 +4,10 @@ use once_cell::sync::Lazy;
 use parking_lot::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 pub use channel::{NvmeControllerIoChannel, NvmeIoChannel, NvmeIoChannelInner};
-pub use controller::NvmeController;
+pub use controller::{transport::NvmeTransportId, NvmeController};
 pub use controller_state::NvmeControllerState;
 pub use device::{lookup_by_name, open_by_name, NvmeBlockDevice};
+pub use discovery::discover;
 pub use handle::{nvme_io_ctx_pool_init, NvmeDeviceHandle};
 pub use namespace::NvmeNamespace;
 pub(crate) use uri::NvmfDeviceTemplate;
@@ -21,6 +22,7 @@ mod controller;
 mod controller_inner;
 mod controller_state;
 mod device;
+mod discovery;
 mod handle;
 mod namespace;
 mod uri;
@@ -60,23 +62,32 @@ impl<'a> NVMeCtlrList<'a> {
         &self,
         name: T,
     ) -> Result<String, CoreError> {
-        let mut entries = self.write_lock();
+        let (controller, id_entry) = {
+            let mut entries = self.write_lock();
 
-        if !entries.contains_key(&name.to_string()) {
-            return Err(CoreError::BdevNotFound {
-                name: name.into(),
-            });
-        }
+            if !entries.contains_key(&name.to_string()) {
+                return Err(CoreError::BdevNotFound {
+                    name: name.into(),
+                });
+            }
 
-        // Remove 'controller name -> controller' mapping.
-        let e = entries.remove(&name.to_string()).unwrap();
-        let controller = e.lock();
+            // Remove 'controller name -> controller' mapping.
+            let controller = entries.remove(&name.to_string()).unwrap();
+            let id = controller.lock().id().to_string();
 
-        // Remove 'controller id->controller' mapping. This will remove the last
-        // reference as causes the controller to be dropped.
-        entries.remove(&controller.id().to_string());
+            // Remove 'controller id->controller' mapping.
+            let id_entry = entries.remove(&id);
+            (controller, id_entry)
+        };
 
         debug!("{}: NVMe controller has been removed from the list", name);
+
+        // Drop what may be the last references, which detaches the controller,
+        // only once it can no longer be found in the list and the list is
+        // unlocked.
+        drop(id_entry);
+        drop(controller);
+
         Ok(name.into())
     }
 
@@ -2,6 +2,7 @@ use std::ptr::NonNull;
 
 use spdk_sys::{
     spdk_nvme_ns,
+    spdk_nvme_ns_get_data,
     spdk_nvme_ns_get_extended_sector_size,
     spdk_nvme_ns_get_md_size,
     spdk_nvme_ns_get_num_sectors,
@@ -34,6 +35,13 @@ impl NvmeNamespace {
         .into()
     }
 
+    /// namespace globally unique identifier as a hex string, all zeroes if
+    /// the controller does not report one
+    pub fn nguid(&self) -> String {
+        let data = unsafe { &*spdk_nvme_ns_get_data(self.0.as_ptr()) };
+        data.nguid.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
     pub fn supports_compare(&self) -> bool {
         unsafe { spdk_nvme_ns_supports_compare(self.0.as_ptr()) }
     }
@@ -14,6 +14,7 @@ use std::{
     ffi::c_void,
     ptr::NonNull,
     sync::Arc,
+    time::Duration,
 };
 use url::Url;
 use uuid::Uuid;
@@ -30,6 +31,7 @@ use spdk_sys::{
 
 use crate::{
     bdev::{
+        dev::reject_unknown_parameters,
         nvmx::{
             controller,
             controller_inner::SpdkNvmeController,
@@ -40,15 +42,21 @@ use crate::{
         CreateDestroy,
         GetName,
     },
-    core::poller,
+    core::{poller, DeviceTimeoutAction},
     ffihelper::ErrnoResult,
     nexus_uri::{self, NexusBdevError},
-    subsys::Config,
+    sleep::mayastor_sleep,
+    subsys::{take_prewarmed, Config},
 };
 
 use super::controller::transport::NvmeTransportId;
 
 const DEFAULT_NVMF_PORT: u16 = 8420;
+
+/// how often, and how long in between, waiting for the removal of a controller
+/// with the same name is retried before attaching a new one
+const REATTACH_RETRIES: u32 = 50;
+const REATTACH_RETRY_INTERVAL: Duration = Duration::from_millis(100);
 // Callback to be called once NVMe controller is successfully created.
 extern "C" fn connect_attach_cb(
     _cb_ctx: *mut c_void,
@@ -82,6 +90,10 @@ pub struct NvmfDeviceTemplate {
     prchk_flags: u32,
     /// uuid of the spdk bdev
     uuid: Option<uuid::Uuid>,
+    /// number of I/O queues to request from the target
+    io_queues: Option<u32>,
+    /// action taken when an I/O times out, overriding the global default
+    timeout_action: Option<DeviceTimeoutAction>,
 }
 
 impl TryFrom<&Url> for NvmfDeviceTemplate {
@@ -143,6 +155,28 @@ impl TryFrom<&Url> for NvmfDeviceTemplate {
             },
         )?;
 
+        let io_queues = match parameters.remove("io_queues") {
+            Some(value) => {
+                Some(value.parse().context(nexus_uri::IntParamParseError {
+                    uri: url.to_string(),
+                    parameter: String::from("io_queues"),
+                })?)
+            }
+            None => None,
+        };
+
+        let timeout_action = match parameters.remove("timeout_action") {
+            Some(value) => Some(value.parse().map_err(|message| {
+                NexusBdevError::UriInvalid {
+                    uri: url.to_string(),
+                    message,
+                }
+            })?),
+            None => None,
+        };
+
+        reject_unknown_parameters(url, parameters)?;
+
         Ok(NvmfDeviceTemplate {
             name: url[url::Position::BeforeHost .. url::Position::AfterPath]
                 .to_string(),
@@ -152,6 +186,8 @@ impl TryFrom<&Url> for NvmfDeviceTemplate {
             subnqn: segments[0].to_string(),
             prchk_flags,
             uuid,
+            io_queues,
+            timeout_action,
         })
     }
 }
@@ -165,6 +201,7 @@ impl GetName for NvmfDeviceTemplate {
 // Context for an NVMe controller being created.
 pub(crate) struct NvmeControllerContext<'probe> {
     opts: NvmeControllerOpts,
+    timeout_action: Option<DeviceTimeoutAction>,
     name: String,
     trid: NvmeTransportId,
     sender: Option<oneshot::Sender<Result<(), Errno>>>,
@@ -208,11 +245,16 @@ impl<'probe> NvmeControllerContext<'probe> {
             opts = opts.with_hostnqn(host_nqn);
         }
 
+        if let Some(io_queues) = template.io_queues {
+            opts = opts.with_num_io_queues(io_queues);
+        }
+
         let (sender, receiver) = oneshot::channel::<ErrnoResult<()>>();
         let opts = opts.build();
 
         NvmeControllerContext {
             opts,
+            timeout_action: template.timeout_action,
             trid,
             name: template.get_name(),
             sender: Some(sender),
@@ -230,6 +272,11 @@ impl<'probe> NvmeControllerContext<'probe> {
         self.name.clone()
     }
 
+    /// the I/O timeout action requested for this controller, if any
+    pub fn timeout_action(&self) -> Option<DeviceTimeoutAction> {
+        self.timeout_action
+    }
+
     pub fn sender(&mut self) -> Sender<Result<(), Errno>> {
         self.sender.take().expect("no sender available")
     }
@@ -240,10 +287,39 @@ impl CreateDestroy for NvmfDeviceTemplate {
 
     async fn create(&self) -> Result<String, Self::Error> {
         let cname = self.get_name();
-        if NVME_CONTROLLERS.lookup_by_name(&cname).is_some() {
-            return Err(NexusBdevError::BdevExists {
-                name: cname,
-            });
+
+        // A controller pre-warmed at startup is adopted by its first user.
+        if take_prewarmed(&cname) {
+            if let Some(c) = NVME_CONTROLLERS.lookup_by_name(&cname) {
+                if c.lock().get_state() == NvmeControllerState::Running {
+                    debug!(?cname, "adopting pre-warmed controller");
+                    return Ok(cname);
+                }
+            }
+        }
+
+        // A controller being detached, i.e. because it was idle, is attached
+        // again once its removal completes.
+        let mut retries = REATTACH_RETRIES;
+        while let Some(c) = NVME_CONTROLLERS.lookup_by_name(&cname) {
+            let state = c.lock().get_state();
+            drop(c);
+
+            if retries == 0
+                || !matches!(
+                    state,
+                    NvmeControllerState::Unconfiguring
+                        | NvmeControllerState::Unconfigured
+                )
+            {
+                return Err(NexusBdevError::BdevExists {
+                    name: cname,
+                });
+            }
+
+            debug!(?cname, ?state, "waiting for controller removal");
+            retries -= 1;
+            let _ = mayastor_sleep(REATTACH_RETRY_INTERVAL).await;
         }
 
         // Insert a new controller instance (uninitialized) as a guard, and
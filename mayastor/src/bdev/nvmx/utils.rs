@@ -102,3 +102,98 @@ pub enum NvmeDsmAttribute {
     IntegralWrite = 0x2,
     Deallocate = 0x4,
 }
+
+/// Log page identifier of the SMART / Health Information log.
+pub const NVME_LOG_HEALTH_INFORMATION: u8 = 0x02;
+
+/// Size of the SMART / Health Information log page.
+pub const NVME_HEALTH_LOG_SIZE: u64 = 512;
+
+/// Critical fields of the SMART / Health Information log page.
+/// 128 bit counters saturate at u64::MAX.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct NvmeHealthLog {
+    pub critical_warning: u8,
+    /// Composite temperature, in Kelvin.
+    pub temperature: u16,
+    pub available_spare: u8,
+    pub available_spare_threshold: u8,
+    pub percentage_used: u8,
+    pub data_units_read: u64,
+    pub data_units_written: u64,
+    pub host_read_commands: u64,
+    pub host_write_commands: u64,
+    pub power_cycles: u64,
+    pub power_on_hours: u64,
+    pub unsafe_shutdowns: u64,
+    pub media_errors: u64,
+    pub num_error_info_log_entries: u64,
+}
+
+impl NvmeHealthLog {
+    /// Parse the log page as laid out by the NVMe specification.
+    pub fn parse(page: &[u8]) -> Option<Self> {
+        if page.len() < NVME_HEALTH_LOG_SIZE as usize {
+            return None;
+        }
+
+        let counter = |offset: usize| {
+            let mut raw = [0u8; 16];
+            raw.copy_from_slice(&page[offset .. offset + 16]);
+            let value = u128::from_le_bytes(raw);
+            if value > u64::MAX as u128 {
+                u64::MAX
+            } else {
+                value as u64
+            }
+        };
+
+        Some(Self {
+            critical_warning: page[0],
+            temperature: u16::from_le_bytes([page[1], page[2]]),
+            available_spare: page[3],
+            available_spare_threshold: page[4],
+            percentage_used: page[5],
+            data_units_read: counter(32),
+            data_units_written: counter(48),
+            host_read_commands: counter(64),
+            host_write_commands: counter(80),
+            power_cycles: counter(112),
+            power_on_hours: counter(128),
+            unsafe_shutdowns: counter(144),
+            media_errors: counter(160),
+            num_error_info_log_entries: counter(176),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nvme_health_log_parse() {
+        let mut page = vec![0u8; NVME_HEALTH_LOG_SIZE as usize];
+        page[0] = 0x4;
+        page[1 .. 3].copy_from_slice(&310u16.to_le_bytes());
+        page[3] = 100;
+        page[4] = 10;
+        page[5] = 3;
+        page[32 .. 48].copy_from_slice(&1234u128.to_le_bytes());
+        page[160 .. 176].copy_from_slice(&7u128.to_le_bytes());
+        page[176 .. 192].copy_from_slice(&u128::MAX.to_le_bytes());
+
+        let log = NvmeHealthLog::parse(&page).unwrap();
+        assert_eq!(log.critical_warning, 0x4);
+        assert_eq!(log.temperature, 310);
+        assert_eq!(log.available_spare, 100);
+        assert_eq!(log.available_spare_threshold, 10);
+        assert_eq!(log.percentage_used, 3);
+        assert_eq!(log.data_units_read, 1234);
+        assert_eq!(log.media_errors, 7);
+        assert_eq!(log.num_error_info_log_entries, u64::MAX);
+        assert_eq!(log.power_cycles, 0);
+
+        assert_eq!(NvmeHealthLog::parse(&page[.. 64]), None);
+    }
+}
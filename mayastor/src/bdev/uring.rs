@@ -8,7 +8,12 @@ use url::Url;
 use spdk_sys::{create_uring_bdev, delete_uring_bdev};
 
 use crate::{
-    bdev::{dev::reject_unknown_parameters, util::uri, CreateDestroy, GetName},
+    bdev::{
+        dev::reject_unknown_parameters,
+        util::{block_size, uri},
+        CreateDestroy,
+        GetName,
+    },
     core::Bdev,
     ffihelper::{cb_arg, done_errno_cb, ErrnoResult},
     nexus_uri::{self, NexusBdevError},
@@ -46,7 +51,7 @@ impl TryFrom<&Url> for Uring {
                     parameter: String::from("blk_size"),
                 })?
             }
-            None => 512,
+            None => block_size::default_blk_size(url.path()),
         };
 
         let uuid = uri::uuid(parameters.remove("uuid")).context(
@@ -0,0 +1,56 @@
+//! Determine the block size of aio and uring bdevs created without an
+//! explicit blk_size parameter.
+
+use std::{fs, path::Path};
+
+use nix::sys::stat::{major, minor, stat, SFlag};
+
+use crate::subsys::Config;
+
+/// Returns the block size to use for the file or device at the given path.
+/// The logical block size of a block device is used when it can be
+/// determined, otherwise the configured default block size is used. The
+/// physical block size is not used as 512e devices, which report a physical
+/// block size of 4096, can still be addressed in 512 byte blocks.
+pub(crate) fn default_blk_size(path: &str) -> u32 {
+    match logical_block_size(path) {
+        Some(blk_size) => {
+            debug!("detected logical block size {} of {}", blk_size, path);
+            blk_size
+        }
+        None => Config::get_or_init(Config::default).default_blk_size,
+    }
+}
+
+/// Read the logical block size of a block device from sysfs, or return None
+/// if the path is not a block device.
+fn logical_block_size(path: &str) -> Option<u32> {
+    let st = stat(path).ok()?;
+    if SFlag::from_bits_truncate(st.st_mode) & SFlag::S_IFMT != SFlag::S_IFBLK {
+        return None;
+    }
+
+    let sysfs =
+        format!("/sys/dev/block/{}:{}", major(st.st_rdev), minor(st.st_rdev));
+    // partitions do not have a queue directory, their parent device does
+    ["queue/logical_block_size", "../queue/logical_block_size"]
+        .iter()
+        .find_map(|attr| fs::read_to_string(Path::new(&sysfs).join(attr)).ok())
+        .and_then(|value| value.trim().parse().ok())
+        .filter(|blk_size| *blk_size >= 512)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::logical_block_size;
+
+    #[test]
+    fn logical_block_size_of_non_block_devices() {
+        let path = std::env::temp_dir().join("logical_block_size_test.img");
+        std::fs::write(&path, [0u8; 4096]).unwrap();
+        assert_eq!(logical_block_size(path.to_str().unwrap()), None);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(logical_block_size(path.to_str().unwrap()), None);
+    }
+}
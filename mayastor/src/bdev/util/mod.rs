@@ -1,2 +1,3 @@
+pub(super) mod block_size;
 pub(super) mod uri;
 pub mod uring;
@@ -1,5 +1,35 @@
 //! Utility functions for io_uring support
 
+use once_cell::sync::Lazy;
+
+/// Oldest kernel release with the io_uring features used by SPDK
+const MIN_KERNEL_VERSION: (u32, u32) = (5, 5);
+
+/// Whether uring bdevs can be used, determined once per process
+static URING_USABLE: Lazy<bool> = Lazy::new(|| {
+    let release = nix::sys::utsname::uname().release().to_string();
+    match parse_kernel_version(&release) {
+        Some(version) if version >= MIN_KERNEL_VERSION => {
+            info!("kernel {} supports io_uring, using uring bdevs", release);
+            true
+        }
+        Some(_) => {
+            warn!(
+                "kernel {} is older than {}.{}, using aio bdevs for uring",
+                release, MIN_KERNEL_VERSION.0, MIN_KERNEL_VERSION.1
+            );
+            false
+        }
+        None => {
+            warn!(
+                "cannot parse kernel release {}, using aio bdevs for uring",
+                release
+            );
+            false
+        }
+    }
+});
+
 /// Returns true if the running kernel supports io_uring
 pub fn kernel_support() -> bool {
     // Match SPDK_URING_QUEUE_DEPTH
@@ -12,3 +42,32 @@ pub fn kernel_support() -> bool {
         }
     }
 }
+
+/// Returns true if the running kernel release is recent enough to create
+/// uring bdevs, otherwise they fall back to aio
+pub fn kernel_version_support() -> bool {
+    *URING_USABLE
+}
+
+/// Parse the major and minor version from a kernel release string such as
+/// "5.10.0-8-amd64"
+fn parse_kernel_version(release: &str) -> Option<(u32, u32)> {
+    let mut numbers = release.split(|c: char| !c.is_ascii_digit());
+    let major = numbers.next()?.parse().ok()?;
+    let minor = numbers.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_kernel_version;
+
+    #[test]
+    fn kernel_version() {
+        assert_eq!(parse_kernel_version("5.10.0-8-amd64"), Some((5, 10)));
+        assert_eq!(parse_kernel_version("4.19.128"), Some((4, 19)));
+        assert_eq!(parse_kernel_version("5.4"), Some((5, 4)));
+        assert_eq!(parse_kernel_version("linux"), None);
+        assert!(parse_kernel_version("5.4.0").unwrap() < (5, 5));
+    }
+}
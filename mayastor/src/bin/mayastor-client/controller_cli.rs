@@ -4,7 +4,7 @@
 use super::context::Context;
 use crate::{context::OutputFormat, GrpcStatus};
 use ::rpc::mayastor as rpc;
-use clap::{App, AppSettings, ArgMatches, SubCommand};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 use colored_json::ToColoredJson;
 use snafu::ResultExt;
 use tonic::Status;
@@ -14,6 +14,14 @@ pub fn subcommands<'a, 'b>() -> App<'a, 'b> {
         SubCommand::with_name("list").about("List existing NVMe controllers");
     let stats = SubCommand::with_name("stats")
         .about("Display I/O statistics for NVMe controllers");
+    let health = SubCommand::with_name("health")
+        .about("Display the SMART / Health log page of an NVMe controller")
+        .arg(
+            Arg::with_name("name")
+                .required(true)
+                .index(1)
+                .help("name of the NVMe controller"),
+        );
 
     SubCommand::with_name("controller")
         .settings(&[
@@ -24,6 +32,7 @@ pub fn subcommands<'a, 'b>() -> App<'a, 'b> {
         .about("NVMe controllers")
         .subcommand(list)
         .subcommand(stats)
+        .subcommand(health)
 }
 
 pub async fn handler(
@@ -33,6 +42,7 @@ pub async fn handler(
     match matches.subcommand() {
         ("list", Some(args)) => list_controllers(ctx, args).await,
         ("stats", Some(args)) => controller_stats(ctx, args).await,
+        ("health", Some(args)) => controller_health(ctx, args).await,
         (cmd, _) => {
             Err(Status::not_found(format!("command {} does not exist", cmd)))
                 .context(GrpcStatus)
@@ -40,7 +50,7 @@ pub async fn handler(
     }
 }
 
-fn controller_state_to_str(idx: i32) -> String {
+pub(crate) fn controller_state_to_str(idx: i32) -> String {
     match rpc::NvmeControllerState::from_i32(idx).unwrap() {
         rpc::NvmeControllerState::New => "new",
         rpc::NvmeControllerState::Initializing => "init",
@@ -107,6 +117,60 @@ async fn controller_stats(
     Ok(())
 }
 
+async fn controller_health(
+    mut ctx: Context,
+    matches: &ArgMatches<'_>,
+) -> crate::Result<()> {
+    let name = matches.value_of("name").unwrap().to_string();
+
+    let response = ctx
+        .client
+        .get_nvme_controller_health(rpc::GetNvmeControllerHealthRequest {
+            name,
+        })
+        .await
+        .context(GrpcStatus)?;
+
+    match ctx.output {
+        OutputFormat::Json => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&response.get_ref())
+                    .unwrap()
+                    .to_colored_json_auto()
+                    .unwrap()
+            );
+        }
+        OutputFormat::Default => {
+            let h = response.get_ref();
+            let table = vec![vec![
+                h.name.clone(),
+                format!("{:#04x}", h.critical_warning),
+                format!("{}C", h.temperature as i64 - 273),
+                format!("{}%", h.available_spare),
+                format!("{}%", h.percentage_used),
+                h.media_errors.to_string(),
+                h.unsafe_shutdowns.to_string(),
+                h.power_on_hours.to_string(),
+            ]];
+
+            let hdr = vec![
+                "NAME",
+                "WARNING",
+                "TEMP",
+                "SPARE",
+                "USED",
+                "MEDIA_ERRORS",
+                "UNSAFE_SHUTDOWNS",
+                "POWER_ON_HOURS",
+            ];
+            ctx.print_list(hdr, table);
+        }
+    }
+
+    Ok(())
+}
+
 async fn list_controllers(
     mut ctx: Context,
     _matches: &ArgMatches<'_>,
@@ -141,11 +205,19 @@ async fn list_controllers(
                     let blk_size = c.blk_size.to_string();
                     let state = controller_state_to_str(c.state);
 
-                    vec![c.name.clone(), size, state, blk_size]
+                    vec![
+                        c.name.clone(),
+                        size,
+                        state,
+                        blk_size,
+                        c.serial.clone(),
+                        c.ns_uuid.clone(),
+                    ]
                 })
                 .collect();
 
-            let hdr = vec!["NAMEs", "SIZE", "STATE", "BLKSIZE"];
+            let hdr =
+                vec!["NAMEs", "SIZE", "STATE", "BLKSIZE", "SERIAL", "NSUUID"];
             ctx.print_list(hdr, table);
         }
     }
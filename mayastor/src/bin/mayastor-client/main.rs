@@ -94,6 +94,7 @@ async fn main() -> crate::Result<()> {
             Arg::with_name("output")
                 .short("o")
                 .long("output")
+                .alias("format")
                 .value_name("FORMAT")
                 .default_value("default")
                 .possible_values(&["default", "json"])
@@ -7,7 +7,7 @@ use crate::{
     GrpcStatus,
 };
 use ::rpc::mayastor as rpc;
-use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use clap::{value_t, App, AppSettings, Arg, ArgMatches, SubCommand};
 use colored_json::ToColoredJson;
 use snafu::ResultExt;
 use tonic::Status;
@@ -18,6 +18,7 @@ pub async fn handler(
 ) -> crate::Result<()> {
     match matches.subcommand() {
         ("fault", Some(args)) => fault(ctx, args).await,
+        ("timeout", Some(args)) => timeout(ctx, args).await,
         (cmd, _) => {
             Err(Status::not_found(format!("command {} does not exist", cmd)))
                 .context(GrpcStatus)
@@ -41,6 +42,27 @@ pub fn subcommands<'a, 'b>() -> App<'a, 'b> {
                 .help("uri of the child"),
         );
 
+    let timeout = SubCommand::with_name("timeout")
+        .about("set the I/O timeout of a child, resetting its controller when exceeded")
+        .arg(
+            Arg::with_name("uuid")
+                .required(true)
+                .index(1)
+                .help("uuid of the nexus"),
+        )
+        .arg(
+            Arg::with_name("uri")
+                .required(true)
+                .index(2)
+                .help("uri of the child"),
+        )
+        .arg(
+            Arg::with_name("timeout")
+                .required(true)
+                .index(3)
+                .help("I/O timeout in microseconds, 0 restores the default"),
+        );
+
     SubCommand::with_name("child")
         .settings(&[
             AppSettings::SubcommandRequiredElseHelp,
@@ -49,6 +71,7 @@ pub fn subcommands<'a, 'b>() -> App<'a, 'b> {
         ])
         .about("Nexus child management")
         .subcommand(fault)
+        .subcommand(timeout)
 }
 
 async fn fault(
@@ -94,3 +117,51 @@ async fn fault(
 
     Ok(())
 }
+
+async fn timeout(
+    mut ctx: Context,
+    matches: &ArgMatches<'_>,
+) -> crate::Result<()> {
+    let uuid = matches
+        .value_of("uuid")
+        .ok_or_else(|| Error::MissingValue {
+            field: "uuid".to_string(),
+        })?
+        .to_string();
+    let uri = matches
+        .value_of("uri")
+        .ok_or_else(|| Error::MissingValue {
+            field: "uri".to_string(),
+        })?
+        .to_string();
+    let io_timeout_us =
+        value_t!(matches.value_of("timeout"), u64).unwrap_or_else(|e| e.exit());
+
+    let response = ctx
+        .client
+        .child_operation(rpc::ChildNexusRequest {
+            uuid: uuid.clone(),
+            uri: uri.clone(),
+            action: rpc::ChildAction::SetIoTimeout as i32,
+            io_timeout_us,
+        })
+        .await
+        .context(GrpcStatus)?;
+
+    match ctx.output {
+        OutputFormat::Json => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&response.get_ref())
+                    .unwrap()
+                    .to_colored_json_auto()
+                    .unwrap()
+            );
+        }
+        OutputFormat::Default => {
+            println!("{}", uri);
+        }
+    };
+
+    Ok(())
+}
@@ -1,11 +1,12 @@
 use crate::{
     context::{Context, OutputFormat},
+    controller_cli::controller_state_to_str,
     nexus_child_cli,
     parse_size,
     Error,
     GrpcStatus,
 };
-use ::rpc::mayastor as rpc;
+use ::rpc::{mayastor as rpc, validate::parse_uuid};
 use byte_unit::Byte;
 use clap::{value_t, App, AppSettings, Arg, ArgMatches, SubCommand};
 use colored_json::ToColoredJson;
@@ -33,6 +34,22 @@ pub fn subcommands<'a, 'b>() -> App<'a, 'b> {
                 .multiple(true)
                 .index(3)
                 .help("list of children to add"),
+        )
+        .arg(
+            Arg::with_name("dry-run")
+                .short("d")
+                .long("dry-run")
+                .required(false)
+                .takes_value(false)
+                .help("only check that the nexus can be created"),
+        )
+        .arg(
+            Arg::with_name("block-size")
+                .short("b")
+                .long("block-size")
+                .value_name("BYTES")
+                .default_value("0")
+                .help("block size of the nexus (0 for that of the children)"),
         );
 
     let create_v2 = SubCommand::with_name("create2")
@@ -91,7 +108,10 @@ pub fn subcommands<'a, 'b>() -> App<'a, 'b> {
         .arg(Arg::with_name("uuid").required(true).index(1)
             .help("uuid for the nexus"))
         .arg(Arg::with_name("key").required(false).index(2)
-            .help("crypto key to use"));
+            .help("crypto key to use"))
+        .arg(Arg::with_name("min-healthy").short("m").long("min-healthy").value_name("NUMBER")
+            .default_value("0")
+            .help("Minimum number of healthy children required for publishing the nexus"));
 
     let unpublish = SubCommand::with_name("unpublish")
         .about("unpublish the nexus")
@@ -164,6 +184,24 @@ pub fn subcommands<'a, 'b>() -> App<'a, 'b> {
                 .long("show-children")
                 .required(false)
                 .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("state")
+                .short("s")
+                .long("state")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .possible_values(&["online", "degraded", "faulted"])
+                .help("only list nexus devices in this state"),
+        )
+        .arg(
+            Arg::with_name("faulted-children")
+                .short("f")
+                .long("faulted-children")
+                .required(false)
+                .takes_value(false)
+                .help("only list nexus devices with a faulted child"),
         );
 
     let list2 = SubCommand::with_name("list2")
@@ -185,6 +223,42 @@ pub fn subcommands<'a, 'b>() -> App<'a, 'b> {
                 .help("uuid of nexus"),
         );
 
+    let topology = SubCommand::with_name("topology")
+        .about("show the devices a nexus is built from")
+        .arg(
+            Arg::with_name("uuid")
+                .required(true)
+                .index(1)
+                .help("uuid of nexus"),
+        );
+
+    let verify = SubCommand::with_name("verify")
+        .about("compare the data of the open children of a nexus")
+        .arg(
+            Arg::with_name("uuid")
+                .required(true)
+                .index(1)
+                .help("uuid of nexus"),
+        )
+        .arg(
+            Arg::with_name("range")
+                .short("r")
+                .long("range")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("OFFSET:LENGTH")
+                .required_unless("full")
+                .help("range of the nexus to compare, e.g. 1GiB:16MiB"),
+        )
+        .arg(
+            Arg::with_name("full")
+                .long("full")
+                .takes_value(false)
+                .conflicts_with("range")
+                .help("compare the whole nexus, for small nexuses only"),
+        );
+
     SubCommand::with_name("nexus")
         .settings(&[
             AppSettings::SubcommandRequiredElseHelp,
@@ -203,6 +277,8 @@ pub fn subcommands<'a, 'b>() -> App<'a, 'b> {
         .subcommand(list)
         .subcommand(list2)
         .subcommand(children)
+        .subcommand(topology)
+        .subcommand(verify)
         .subcommand(nexus_child_cli::subcommands())
 }
 
@@ -217,6 +293,8 @@ pub async fn handler(
         ("list", Some(args)) => nexus_list(ctx, args).await,
         ("list2", Some(args)) => nexus_list_v2(ctx, args).await,
         ("children", Some(args)) => nexus_children(ctx, args).await,
+        ("topology", Some(args)) => nexus_topology(ctx, args).await,
+        ("verify", Some(args)) => nexus_verify(ctx, args).await,
         ("publish", Some(args)) => nexus_publish(ctx, args).await,
         ("unpublish", Some(args)) => nexus_unpublish(ctx, args).await,
         ("ana_state", Some(args)) => nexus_nvme_ana_state(ctx, args).await,
@@ -238,6 +316,9 @@ fn nexus_create_parse(
     ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
 )> {
     let uuid = matches.value_of("uuid").unwrap().to_string();
+    parse_uuid(&uuid)
+        .map_err(|e| Status::invalid_argument(e.to_string()))
+        .context(GrpcStatus)?;
     let size = parse_size(matches.value_of("size").ok_or_else(|| {
         Error::MissingValue {
             field: "size".to_string(),
@@ -261,6 +342,8 @@ async fn nexus_create(
     matches: &ArgMatches<'_>,
 ) -> crate::Result<()> {
     let (uuid, size, children) = nexus_create_parse(matches)?;
+    let block_size = value_t!(matches.value_of("block-size"), u32)
+        .unwrap_or_else(|e| e.exit());
 
     let response = ctx
         .client
@@ -268,6 +351,8 @@ async fn nexus_create(
             uuid: uuid.clone(),
             size,
             children,
+            dry_run: matches.is_present("dry-run"),
+            block_size,
         })
         .await
         .context(GrpcStatus)?;
@@ -371,9 +456,17 @@ async fn nexus_list(
     mut ctx: Context,
     matches: &ArgMatches<'_>,
 ) -> crate::Result<()> {
+    let states = matches
+        .values_of("state")
+        .map(|values| values.map(|s| nexus_state_from_str(s) as i32).collect())
+        .unwrap_or_default();
+
     let response = ctx
         .client
-        .list_nexus(rpc::Null {})
+        .list_nexus(rpc::ListNexusRequest {
+            states,
+            has_faulted_child: matches.is_present("faulted-children"),
+        })
         .await
         .context(GrpcStatus)?;
 
@@ -512,7 +605,7 @@ async fn nexus_children(
 
     let response = ctx
         .client
-        .list_nexus(rpc::Null {})
+        .list_nexus(rpc::ListNexusRequest::default())
         .await
         .context(GrpcStatus)?;
 
@@ -555,6 +648,170 @@ async fn nexus_children(
     Ok(())
 }
 
+async fn nexus_topology(
+    mut ctx: Context,
+    matches: &ArgMatches<'_>,
+) -> crate::Result<()> {
+    let uuid = matches
+        .value_of("uuid")
+        .ok_or_else(|| Error::MissingValue {
+            field: "uuid".to_string(),
+        })?
+        .to_string();
+
+    let response = ctx
+        .client
+        .get_nexus_topology(rpc::GetNexusTopologyRequest {
+            uuid,
+        })
+        .await
+        .context(GrpcStatus)?;
+
+    match ctx.output {
+        OutputFormat::Json => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(response.get_ref())
+                    .unwrap()
+                    .to_colored_json_auto()
+                    .unwrap()
+            );
+        }
+        OutputFormat::Default => {
+            let table = response
+                .get_ref()
+                .children
+                .iter()
+                .map(|c| {
+                    let (uri, state) = c
+                        .child
+                        .as_ref()
+                        .map(|ch| {
+                            (ch.uri.clone(), child_state_to_str(ch.state))
+                        })
+                        .unwrap_or_default();
+                    let (ctrlr_state, target) = c
+                        .controller
+                        .as_ref()
+                        .map(|ctrlr| {
+                            (
+                                controller_state_to_str(ctrlr.state),
+                                format!(
+                                    "{}:{}/{}",
+                                    ctrlr.traddr, ctrlr.trsvcid, ctrlr.subnqn
+                                ),
+                            )
+                        })
+                        .unwrap_or_default();
+                    vec![
+                        uri,
+                        state.to_string(),
+                        c.device_name.clone(),
+                        c.driver.clone(),
+                        ctrlr_state,
+                        target,
+                    ]
+                })
+                .collect();
+            ctx.print_list(
+                vec![
+                    "NAME",
+                    "STATE",
+                    "DEVICE",
+                    "DRIVER",
+                    "CONTROLLER",
+                    "TARGET",
+                ],
+                table,
+            );
+        }
+    };
+
+    Ok(())
+}
+
+async fn nexus_verify(
+    mut ctx: Context,
+    matches: &ArgMatches<'_>,
+) -> crate::Result<()> {
+    let uuid = matches
+        .value_of("uuid")
+        .ok_or_else(|| Error::MissingValue {
+            field: "uuid".to_string(),
+        })?
+        .to_string();
+    let ranges = matches
+        .values_of("range")
+        .map(|values| values.map(parse_range).collect())
+        .unwrap_or_else(|| Ok(Vec::new()))
+        .map_err(|s| Status::invalid_argument(format!("Bad range '{}'", s)))
+        .context(GrpcStatus)?;
+
+    let response = ctx
+        .client
+        .verify_nexus(rpc::VerifyNexusRequest {
+            uuid,
+            ranges,
+            full_scan: matches.is_present("full"),
+        })
+        .await
+        .context(GrpcStatus)?;
+
+    match ctx.output {
+        OutputFormat::Json => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(response.get_ref())
+                    .unwrap()
+                    .to_colored_json_auto()
+                    .unwrap()
+            );
+        }
+        OutputFormat::Default => {
+            let reply = response.get_ref();
+            ctx.v1(&format!(
+                "Compared {} against {}",
+                ctx.units(Byte::from_bytes(reply.bytes_compared.into())),
+                reply.reference
+            ));
+            if reply.mismatches.is_empty() {
+                ctx.v1("All children match");
+                return Ok(());
+            }
+            let table = reply
+                .mismatches
+                .iter()
+                .flat_map(|m| {
+                    m.ranges.iter().map(move |r| {
+                        vec![
+                            m.uri.clone(),
+                            r.offset.to_string(),
+                            r.length.to_string(),
+                        ]
+                    })
+                })
+                .collect();
+            ctx.print_list(vec!["NAME", ">OFFSET", ">LENGTH"], table);
+        }
+    };
+
+    Ok(())
+}
+
+/// parse a range given as OFFSET:LENGTH, both with an optional unit
+fn parse_range(src: &str) -> Result<rpc::NexusRange, String> {
+    let mut parts = src.splitn(2, ':');
+    match (parts.next(), parts.next()) {
+        (Some(offset), Some(length)) => Ok(rpc::NexusRange {
+            offset: parse_size(offset).map_err(|_| src.to_string())?.get_bytes()
+                as u64,
+            length: parse_size(length).map_err(|_| src.to_string())?.get_bytes()
+                as u64,
+        }),
+        _ => Err(src.to_string()),
+    }
+}
+
 async fn nexus_publish(
     mut ctx: Context,
     matches: &ArgMatches<'_>,
@@ -579,12 +836,16 @@ async fn nexus_publish(
         }
     };
 
+    let min_healthy_children = value_t!(matches.value_of("min-healthy"), u32)
+        .unwrap_or_else(|e| e.exit());
+
     let response = ctx
         .client
         .publish_nexus(rpc::PublishNexusRequest {
             uuid,
             key,
             share: protocol.into(),
+            min_healthy_children,
         })
         .await
         .context(GrpcStatus)?;
@@ -813,6 +1074,15 @@ fn nexus_state_to_str(idx: i32) -> &'static str {
     }
 }
 
+fn nexus_state_from_str(state: &str) -> rpc::NexusState {
+    match state {
+        "online" => rpc::NexusState::NexusOnline,
+        "degraded" => rpc::NexusState::NexusDegraded,
+        "faulted" => rpc::NexusState::NexusFaulted,
+        _ => rpc::NexusState::NexusUnknown,
+    }
+}
+
 fn child_state_to_str(idx: i32) -> &'static str {
     match rpc::ChildState::from_i32(idx).unwrap() {
         rpc::ChildState::ChildUnknown => "unknown",
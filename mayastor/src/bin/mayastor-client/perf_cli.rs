@@ -1,8 +1,8 @@
 //!
 //! Methods related to the gathering of performance statistics.
 //!
-//! At present we only have get_resource_usage() which is
-//! essentially the result of a getrusage(2) system call.
+//! get_resource_usage() is essentially the result of a getrusage(2) system
+//! call, get_reactor_stats() shows how busy each reactor core is.
 
 use super::{
     context::{Context, OutputFormat},
@@ -17,6 +17,8 @@ use tonic::Status;
 pub fn subcommands<'a, 'b>() -> App<'a, 'b> {
     let resource =
         SubCommand::with_name("resource").about("Resource usage statistics");
+    let reactors =
+        SubCommand::with_name("reactors").about("Reactor core statistics");
 
     SubCommand::with_name("perf")
         .settings(&[
@@ -26,6 +28,7 @@ pub fn subcommands<'a, 'b>() -> App<'a, 'b> {
         ])
         .about("Performance statistics")
         .subcommand(resource)
+        .subcommand(reactors)
 }
 
 pub async fn handler(
@@ -34,6 +37,7 @@ pub async fn handler(
 ) -> crate::Result<()> {
     match matches.subcommand() {
         ("resource", Some(args)) => get_resource_usage(ctx, args).await,
+        ("reactors", Some(args)) => get_reactor_stats(ctx, args).await,
         (cmd, _) => {
             Err(Status::not_found(format!("command {} does not exist", cmd)))
                 .context(GrpcStatus)
@@ -89,3 +93,66 @@ async fn get_resource_usage(
 
     Ok(())
 }
+
+async fn get_reactor_stats(
+    mut ctx: Context,
+    _matches: &ArgMatches<'_>,
+) -> crate::Result<()> {
+    ctx.v2("Requesting reactor statistics");
+
+    let response = ctx
+        .client
+        .get_reactor_stats(rpc::Null {})
+        .await
+        .context(GrpcStatus)?;
+
+    match ctx.output {
+        OutputFormat::Json => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(response.get_ref())
+                    .unwrap()
+                    .to_colored_json_auto()
+                    .unwrap()
+            );
+        }
+        OutputFormat::Default => {
+            let table = response
+                .get_ref()
+                .reactors
+                .iter()
+                .map(|r| {
+                    let avg = |total: u64| {
+                        if r.events == 0 {
+                            0
+                        } else {
+                            total / r.events
+                        }
+                    };
+                    vec![
+                        r.core.to_string(),
+                        r.events.to_string(),
+                        avg(r.queue_time_us).to_string(),
+                        r.max_queue_time_us.to_string(),
+                        avg(r.exec_time_us).to_string(),
+                        r.max_exec_time_us.to_string(),
+                    ]
+                })
+                .collect();
+
+            ctx.print_list(
+                vec![
+                    ">CORE",
+                    ">EVENTS",
+                    ">AVG_QUEUE_US",
+                    ">MAX_QUEUE_US",
+                    ">AVG_EXEC_US",
+                    ">MAX_EXEC_US",
+                ],
+                table,
+            );
+        }
+    };
+
+    Ok(())
+}
@@ -1,5 +1,6 @@
 use crate::{
     context::{Context, OutputFormat},
+    parse_size,
     Error,
     GrpcStatus,
 };
@@ -25,6 +26,12 @@ pub fn subcommands<'a, 'b>() -> App<'a, 'b> {
                 .multiple(true)
                 .index(2)
                 .help("Disk device files"),
+        )
+        .arg(
+            Arg::with_name("cluster-size")
+                .long("cluster-size")
+                .takes_value(true)
+                .help("Cluster size with optional unit suffix, a power of two"),
         );
     let destroy = SubCommand::with_name("destroy")
         .about("Destroy storage pool")
@@ -33,6 +40,13 @@ pub fn subcommands<'a, 'b>() -> App<'a, 'b> {
                 .required(true)
                 .index(1)
                 .help("Storage pool name"),
+        )
+        .arg(
+            Arg::with_name("force")
+                .long("force")
+                .short("f")
+                .takes_value(false)
+                .help("Destroy the replicas of the pool first, even if shared"),
         );
     SubCommand::with_name("pool")
         .settings(&[
@@ -78,12 +92,20 @@ async fn create(
         })?
         .map(|dev| dev.to_owned())
         .collect();
+    let cluster_size = match matches.value_of("cluster-size") {
+        Some(size) => parse_size(size)
+            .map_err(|s| Status::invalid_argument(format!("Bad size '{}'", s)))
+            .context(GrpcStatus)?
+            .get_bytes() as u32,
+        None => 0,
+    };
 
     let response = ctx
         .client
         .create_pool(rpc::CreatePoolRequest {
             name: name.clone(),
             disks,
+            cluster_size,
         })
         .await
         .context(GrpcStatus)?;
@@ -116,11 +138,13 @@ async fn destroy(
             field: "pool".to_string(),
         })?
         .to_owned();
+    let force = matches.is_present("force");
 
     let response = ctx
         .client
         .destroy_pool(rpc::DestroyPoolRequest {
             name: name.clone(),
+            force,
         })
         .await
         .context(GrpcStatus)?;
@@ -137,6 +161,9 @@ async fn destroy(
         }
         OutputFormat::Default => {
             println!("{}", &name);
+            for replica in &response.get_ref().replicas {
+                println!("  destroyed replica {}", replica);
+            }
         }
     };
 
@@ -177,18 +204,27 @@ async fn list(
                 .map(|p| {
                     let cap = Byte::from_bytes(p.capacity.into());
                     let used = Byte::from_bytes(p.used.into());
+                    let cluster = Byte::from_bytes(p.cluster_size.into());
                     let state = pool_state_to_str(p.state);
                     vec![
                         p.name.clone(),
                         state.to_string(),
                         ctx.units(cap),
                         ctx.units(used),
+                        ctx.units(cluster),
                         p.disks.join(" "),
                     ]
                 })
                 .collect();
             ctx.print_list(
-                vec!["NAME", "STATE", ">CAPACITY", ">USED", "DISKS"],
+                vec![
+                    "NAME",
+                    "STATE",
+                    ">CAPACITY",
+                    ">USED",
+                    ">CLUSTER",
+                    "DISKS",
+                ],
                 table,
             );
         }
@@ -3,12 +3,16 @@
 
 use crate::{
     context::{Context, OutputFormat},
+    parse_size,
     Error,
     GrpcStatus,
 };
 use ::rpc::mayastor as rpc;
-use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use byte_unit::Byte;
+use chrono::{SecondsFormat, TimeZone, Utc};
+use clap::{value_t, App, AppSettings, Arg, ArgMatches, SubCommand};
 use colored_json::ToColoredJson;
+use serde_json::json;
 use snafu::ResultExt;
 use tonic::Status;
 
@@ -21,9 +25,13 @@ pub async fn handler(
         ("stop", Some(args)) => stop(ctx, args).await,
         ("pause", Some(args)) => pause(ctx, args).await,
         ("resume", Some(args)) => resume(ctx, args).await,
+        ("pause-all", Some(args)) => pause_all(ctx, args).await,
+        ("resume-all", Some(args)) => resume_all(ctx, args).await,
+        ("throttle", Some(args)) => throttle(ctx, args).await,
         ("state", Some(args)) => state(ctx, args).await,
         ("stats", Some(args)) => stats(ctx, args).await,
         ("progress", Some(args)) => progress(ctx, args).await,
+        ("history", Some(args)) => history(ctx, args).await,
         (cmd, _) => {
             Err(Status::not_found(format!("command {} does not exist", cmd)))
                 .context(GrpcStatus)
@@ -45,6 +53,20 @@ pub fn subcommands<'a, 'b>() -> App<'a, 'b> {
                 .required(true)
                 .index(2)
                 .help("uri of child to start rebuilding"),
+        )
+        .arg(
+            Arg::with_name("verify")
+                .long("verify")
+                .required(false)
+                .takes_value(false)
+                .help("compare the rebuilt data with the source once copied"),
+        )
+        .arg(
+            Arg::with_name("verify-percent")
+                .long("verify-percent")
+                .value_name("PERCENT")
+                .requires("verify")
+                .help("percentage of the rebuilt data verified (default 100)"),
         );
 
     let stop = SubCommand::with_name("stop")
@@ -92,6 +114,39 @@ pub fn subcommands<'a, 'b>() -> App<'a, 'b> {
                 .help("uri of child to resume rebuilding"),
         );
 
+    let pause_all = SubCommand::with_name("pause-all")
+        .about("pauses all rebuilds of a nexus")
+        .arg(
+            Arg::with_name("uuid")
+                .required(true)
+                .index(1)
+                .help("uuid of the nexus"),
+        );
+
+    let resume_all = SubCommand::with_name("resume-all")
+        .about("resumes all paused rebuilds of a nexus")
+        .arg(
+            Arg::with_name("uuid")
+                .required(true)
+                .index(1)
+                .help("uuid of the nexus"),
+        );
+
+    let throttle = SubCommand::with_name("throttle")
+        .about("limits the copy rate of the rebuilds of a nexus")
+        .arg(
+            Arg::with_name("uuid")
+                .required(true)
+                .index(1)
+                .help("uuid of the nexus"),
+        )
+        .arg(
+            Arg::with_name("rate")
+                .required(true)
+                .index(2)
+                .help("copy rate per second, 0 for unlimited"),
+        );
+
     let state = SubCommand::with_name("state")
         .about("gets the rebuild state of the child")
         .arg(
@@ -137,6 +192,15 @@ pub fn subcommands<'a, 'b>() -> App<'a, 'b> {
                 .help("uri of child to get the rebuild progress from"),
         );
 
+    let history = SubCommand::with_name("history")
+        .about("shows the rebuilds of a nexus which have finished")
+        .arg(
+            Arg::with_name("uuid")
+                .required(true)
+                .index(1)
+                .help("uuid of the nexus"),
+        );
+
     SubCommand::with_name("rebuild")
         .settings(&[
             AppSettings::SubcommandRequiredElseHelp,
@@ -148,9 +212,13 @@ pub fn subcommands<'a, 'b>() -> App<'a, 'b> {
         .subcommand(stop)
         .subcommand(pause)
         .subcommand(resume)
+        .subcommand(pause_all)
+        .subcommand(resume_all)
+        .subcommand(throttle)
         .subcommand(state)
         .subcommand(stats)
         .subcommand(progress)
+        .subcommand(history)
 }
 
 async fn start(
@@ -169,12 +237,21 @@ async fn start(
             field: "uri".to_string(),
         })?
         .to_string();
+    let verify_percent = if !matches.is_present("verify") {
+        0
+    } else if matches.is_present("verify-percent") {
+        value_t!(matches.value_of("verify-percent"), u32)
+            .unwrap_or_else(|e| e.exit())
+    } else {
+        100
+    };
 
     let response = ctx
         .client
         .start_rebuild(rpc::StartRebuildRequest {
             uuid: uuid.clone(),
             uri: uri.clone(),
+            verify_percent,
         })
         .await
         .context(GrpcStatus)?;
@@ -326,6 +403,135 @@ async fn resume(
     Ok(())
 }
 
+async fn pause_all(
+    mut ctx: Context,
+    matches: &ArgMatches<'_>,
+) -> crate::Result<()> {
+    let uuid = matches
+        .value_of("uuid")
+        .ok_or_else(|| Error::MissingValue {
+            field: "uuid".to_string(),
+        })?
+        .to_string();
+
+    let response = ctx
+        .client
+        .pause_rebuilds(rpc::PauseRebuildsRequest {
+            uuid: uuid.clone(),
+        })
+        .await
+        .context(GrpcStatus)?;
+
+    match ctx.output {
+        OutputFormat::Json => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&response.get_ref())
+                    .unwrap()
+                    .to_colored_json_auto()
+                    .unwrap()
+            );
+        }
+        OutputFormat::Default => {
+            println!(
+                "paused {} rebuild(s) of nexus {}",
+                response.get_ref().jobs,
+                uuid
+            );
+        }
+    };
+
+    Ok(())
+}
+
+async fn resume_all(
+    mut ctx: Context,
+    matches: &ArgMatches<'_>,
+) -> crate::Result<()> {
+    let uuid = matches
+        .value_of("uuid")
+        .ok_or_else(|| Error::MissingValue {
+            field: "uuid".to_string(),
+        })?
+        .to_string();
+
+    let response = ctx
+        .client
+        .resume_rebuilds(rpc::ResumeRebuildsRequest {
+            uuid: uuid.clone(),
+        })
+        .await
+        .context(GrpcStatus)?;
+
+    match ctx.output {
+        OutputFormat::Json => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&response.get_ref())
+                    .unwrap()
+                    .to_colored_json_auto()
+                    .unwrap()
+            );
+        }
+        OutputFormat::Default => {
+            println!(
+                "resumed {} rebuild(s) of nexus {}",
+                response.get_ref().jobs,
+                uuid
+            );
+        }
+    };
+
+    Ok(())
+}
+
+async fn throttle(
+    mut ctx: Context,
+    matches: &ArgMatches<'_>,
+) -> crate::Result<()> {
+    let uuid = matches
+        .value_of("uuid")
+        .ok_or_else(|| Error::MissingValue {
+            field: "uuid".to_string(),
+        })?
+        .to_string();
+    let rate = matches
+        .value_of("rate")
+        .ok_or_else(|| Error::MissingValue {
+            field: "rate".to_string(),
+        })?;
+    let bytes_per_sec = parse_size(rate)
+        .map_err(|s| Status::invalid_argument(format!("Bad rate '{}'", s)))
+        .context(GrpcStatus)?
+        .get_bytes() as u64;
+
+    let response = ctx
+        .client
+        .set_rebuild_throttle(rpc::SetRebuildThrottleRequest {
+            uuid: uuid.clone(),
+            bytes_per_sec,
+        })
+        .await
+        .context(GrpcStatus)?;
+
+    match ctx.output {
+        OutputFormat::Json => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&response.get_ref())
+                    .unwrap()
+                    .to_colored_json_auto()
+                    .unwrap()
+            );
+        }
+        OutputFormat::Default => {
+            println!("{}", &uuid);
+        }
+    };
+
+    Ok(())
+}
+
 async fn state(
     mut ctx: Context,
     matches: &ArgMatches<'_>,
@@ -354,9 +560,13 @@ async fn state(
 
     match ctx.output {
         OutputFormat::Json => {
+            let state = json!({
+                "uri": uri,
+                "state": response.get_ref().state,
+            });
             println!(
                 "{}",
-                serde_json::to_string_pretty(&response.get_ref())
+                serde_json::to_string_pretty(&state)
                     .unwrap()
                     .to_colored_json_auto()
                     .unwrap()
@@ -470,6 +680,69 @@ async fn progress(
         .await
         .context(GrpcStatus)?;
 
+    match ctx.output {
+        OutputFormat::Json => {
+            let progress = json!({
+                "uri": uri,
+                "progress": response.get_ref().progress,
+                "rate_bytes_per_sec": response.get_ref().rate_bytes_per_sec,
+                "eta_seconds": response.get_ref().eta_seconds,
+                "rate_limit_bytes_per_sec": response.get_ref().rate_limit_bytes_per_sec,
+            });
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&progress)
+                    .unwrap()
+                    .to_colored_json_auto()
+                    .unwrap()
+            );
+        }
+        OutputFormat::Default => {
+            let response = &response.get_ref();
+            ctx.print_list(
+                vec!["progress (%)", "rate", "limit", "eta (s)"],
+                vec![vec![
+                    response.progress.to_string(),
+                    format!(
+                        "{}/s",
+                        ctx.units(Byte::from_bytes(
+                            response.rate_bytes_per_sec.into()
+                        ))
+                    ),
+                    match response.rate_limit_bytes_per_sec {
+                        0 => "none".to_string(),
+                        limit => format!(
+                            "{}/s",
+                            ctx.units(Byte::from_bytes(limit.into()))
+                        ),
+                    },
+                    response.eta_seconds.to_string(),
+                ]],
+            );
+        }
+    };
+    Ok(())
+}
+
+async fn history(
+    mut ctx: Context,
+    matches: &ArgMatches<'_>,
+) -> crate::Result<()> {
+    let uuid = matches
+        .value_of("uuid")
+        .ok_or_else(|| Error::MissingValue {
+            field: "uuid".to_string(),
+        })?
+        .to_string();
+
+    let response = ctx
+        .client
+        .get_rebuild_history(rpc::RebuildHistoryRequest {
+            uuid: uuid.clone(),
+        })
+        .await
+        .context(GrpcStatus)?;
+
     match ctx.output {
         OutputFormat::Json => {
             println!(
@@ -481,9 +754,33 @@ async fn progress(
             );
         }
         OutputFormat::Default => {
+            let records = &response.get_ref().records;
+            if records.is_empty() {
+                ctx.v1(&format!("No rebuilds have finished on nexus {}", uuid));
+                return Ok(());
+            }
+
+            let table = records
+                .iter()
+                .map(|r| {
+                    let start = Utc.timestamp_millis(r.start_time_ms as i64);
+                    let duration =
+                        r.end_time_ms.saturating_sub(r.start_time_ms);
+                    vec![
+                        r.child_uri.clone(),
+                        r.src_uri.clone(),
+                        r.state.clone(),
+                        start.to_rfc3339_opts(SecondsFormat::Secs, true),
+                        format!("{:.1}", duration as f64 / 1000.0),
+                        ctx.units(Byte::from_bytes(r.bytes_recovered.into())),
+                    ]
+                })
+                .collect();
             ctx.print_list(
-                vec!["progress (%)"],
-                vec![vec![response.get_ref().progress.to_string()]],
+                vec![
+                    "CHILD", "SOURCE", "STATE", "STARTED", "TIME (s)", "COPIED",
+                ],
+                table,
             );
         }
     };
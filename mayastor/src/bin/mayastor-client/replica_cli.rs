@@ -68,6 +68,29 @@ pub fn subcommands<'a, 'b>() -> App<'a, 'b> {
                 .index(2)
                 .help("Name of a protocol (nvmf, iscsi) used for sharing or \"none\" to unshare the replica"));
 
+    let migrate = SubCommand::with_name("migrate")
+        .about("Migrate replica to another pool")
+        .arg(
+            Arg::with_name("uuid")
+                .required(true)
+                .index(1)
+                .help("Replica uuid"),
+        )
+        .arg(
+            Arg::with_name("pool")
+                .required(true)
+                .index(2)
+                .help("Storage pool name to migrate the replica to"),
+        )
+        .arg(
+            Arg::with_name("rate-limit")
+                .short("r")
+                .long("rate-limit")
+                .takes_value(true)
+                .value_name("NUMBER")
+                .help("Copy rate per second (default unlimited)"),
+        );
+
     SubCommand::with_name("replica")
         .settings(&[
             AppSettings::SubcommandRequiredElseHelp,
@@ -78,6 +101,7 @@ pub fn subcommands<'a, 'b>() -> App<'a, 'b> {
         .subcommand(create)
         .subcommand(destroy)
         .subcommand(share)
+        .subcommand(migrate)
         .subcommand(SubCommand::with_name("list").about("List replicas"))
         .subcommand(
             SubCommand::with_name("stats").about("IO stats of replicas"),
@@ -93,6 +117,7 @@ pub async fn handler(
         ("destroy", Some(args)) => replica_destroy(ctx, args).await,
         ("list", Some(args)) => replica_list(ctx, args).await,
         ("share", Some(args)) => replica_share(ctx, args).await,
+        ("migrate", Some(args)) => replica_migrate(ctx, args).await,
         ("stats", Some(args)) => replica_stat(ctx, args).await,
         (cmd, _) => {
             Err(Status::not_found(format!("command {} does not exist", cmd)))
@@ -224,18 +249,29 @@ async fn replica_list(
                 .map(|r| {
                     let proto = replica_protocol_to_str(r.share);
                     let size = ctx.units(Byte::from_bytes(r.size.into()));
+                    let allocated =
+                        ctx.units(Byte::from_bytes(r.allocated_bytes.into()));
                     vec![
                         r.pool.clone(),
                         r.uuid.clone(),
                         r.thin.to_string(),
                         proto.to_string(),
                         size,
+                        allocated,
                         r.uri.clone(),
                     ]
                 })
                 .collect();
             ctx.print_list(
-                vec!["POOL", "NAME", ">THIN", ">SHARE", ">SIZE", "URI"],
+                vec![
+                    "POOL",
+                    "NAME",
+                    ">THIN",
+                    ">SHARE",
+                    ">SIZE",
+                    ">ALLOCATED",
+                    "URI",
+                ],
                 table,
             );
         }
@@ -279,6 +315,50 @@ async fn replica_share(
     Ok(())
 }
 
+async fn replica_migrate(
+    mut ctx: Context,
+    matches: &ArgMatches<'_>,
+) -> crate::Result<()> {
+    let uuid = matches.value_of("uuid").unwrap().to_owned();
+    let pool = matches.value_of("pool").unwrap().to_owned();
+    let rate_limit = match matches.value_of("rate-limit") {
+        Some(rate) => parse_size(rate)
+            .map_err(|s| {
+                Status::invalid_argument(format!("Bad rate limit '{}'", s))
+            })
+            .context(GrpcStatus)?
+            .get_bytes() as u64,
+        None => 0,
+    };
+
+    let response = ctx
+        .client
+        .migrate_replica(rpc::MigrateReplicaRequest {
+            uuid,
+            pool,
+            rate_limit,
+        })
+        .await
+        .context(GrpcStatus)?;
+
+    match ctx.output {
+        OutputFormat::Json => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&response.get_ref())
+                    .unwrap()
+                    .to_colored_json_auto()
+                    .unwrap()
+            );
+        }
+        OutputFormat::Default => {
+            println!("{}", &response.get_ref().uri);
+        }
+    };
+
+    Ok(())
+}
+
 async fn replica_stat(
     mut ctx: Context,
     _matches: &ArgMatches<'_>,
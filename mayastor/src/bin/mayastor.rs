@@ -1,7 +1,7 @@
 #[macro_use]
 extern crate tracing;
 
-use std::path::Path;
+use std::{io, path::Path};
 
 use futures::future::FutureExt;
 use structopt::StructOpt;
@@ -26,7 +26,8 @@ use mayastor::{
 
 const GIT_VERSION: &str =
     git_version!(args = ["--tags", "--abbrev=12"], fallback = "unknown");
-const PAGES_NEEDED: u32 = 1024;
+/// hugepage memory needed at least when no memory size (-s) is configured
+const MIN_HUGEPAGE_MEMORY_MB: u64 = 256;
 
 mayastor::CPS_INIT!();
 fn start_tokio_runtime(args: &MayastorCliArgs) {
@@ -65,31 +66,115 @@ fn start_tokio_runtime(args: &MayastorCliArgs) {
     });
 }
 
-fn hugepage_check() {
-    let hugepage_path = Path::new("/sys/kernel/mm/hugepages/hugepages-2048kB");
-    let nr_pages: u32 = sysfs::parse_value(hugepage_path, "nr_hugepages")
-        .expect("failed to read the number of pages");
-    let free_pages: u32 = sysfs::parse_value(hugepage_path, "free_hugepages")
-        .expect("failed to read the number of free pages");
-    if nr_pages < PAGES_NEEDED {
-        error!(?PAGES_NEEDED, ?nr_pages, "insufficient pages available");
-        if !cfg!(debug_assertions) {
-            std::process::exit(1)
-        }
+/// Reserve the needed number of hugepages and return the number of pages
+/// granted by the kernel, which may be fewer when memory is fragmented. In
+/// that case memory is compacted, if allowed, and the reservation retried
+/// once.
+fn reserve_hugepages(
+    pages_needed: u32,
+    compaction: bool,
+    mut read: impl FnMut() -> Result<u32, io::Error>,
+    mut write: impl FnMut(u32) -> Result<(), io::Error>,
+    mut compact: impl FnMut() -> Result<(), io::Error>,
+) -> Result<u32, io::Error> {
+    let nr_pages = read()?;
+    if nr_pages >= pages_needed {
+        return Ok(nr_pages);
     }
 
-    if free_pages < PAGES_NEEDED {
-        error!(
-            ?PAGES_NEEDED,
-            ?nr_pages,
-            "insufficient free pages available"
+    info!(?pages_needed, ?nr_pages, "reserving hugepages");
+    if let Err(error) = write(pages_needed) {
+        warn!(?error, "failed to reserve hugepages");
+    }
+
+    let nr_pages = read()?;
+    if nr_pages >= pages_needed || !compaction {
+        return Ok(nr_pages);
+    }
+
+    info!(
+        ?pages_needed,
+        ?nr_pages,
+        "compacting memory to reserve hugepages"
+    );
+    if let Err(error) = compact() {
+        warn!(?error, "failed to compact memory");
+    }
+    if let Err(error) = write(pages_needed) {
+        warn!(?error, "failed to reserve hugepages");
+    }
+
+    read()
+}
+
+/// Make sure the requested number of hugepages of the requested size is
+/// reserved, trying to reserve them when that is not the case yet.
+fn hugepage_check(args: &MayastorCliArgs) -> Result<(), io::Error> {
+    let pages_needed = args.hugepages;
+    let hugepage_path = format!(
+        "/sys/kernel/mm/hugepages/hugepages-{}kB",
+        args.hugepage_size
+    );
+    let hugepage_path = Path::new(&hugepage_path);
+
+    if !hugepage_path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "hugepages of {}kB are not supported by the kernel",
+                args.hugepage_size
+            ),
+        ));
+    }
+
+    let nr_pages = reserve_hugepages(
+        pages_needed,
+        !args.no_hugepage_compaction,
+        || sysfs::parse_value(hugepage_path, "nr_hugepages"),
+        |pages| sysfs::write_value(hugepage_path, "nr_hugepages", pages),
+        || sysfs::write_value(Path::new("/proc/sys/vm"), "compact_memory", 1),
+    )?;
+    if nr_pages < pages_needed {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!(
+                "only {} of the {} requested hugepages of {}kB could be reserved",
+                nr_pages, pages_needed, args.hugepage_size
+            ),
+        ));
+    }
+
+    let free_pages: u32 = sysfs::parse_value(hugepage_path, "free_hugepages")?;
+
+    if free_pages < pages_needed {
+        warn!(
+            ?pages_needed,
+            ?free_pages,
+            "not all reserved pages are free"
         );
-        if !cfg!(debug_assertions) {
-            std::process::exit(1)
-        }
+    }
+
+    // Pages may have been grabbed by another process, in which case SPDK
+    // fails deep within its initialisation, so bail out early.
+    let memory_mb = if args.mem_size > 0 {
+        args.mem_size as u64
+    } else {
+        MIN_HUGEPAGE_MEMORY_MB
+    };
+    let min_free_pages = (memory_mb * 1024 + args.hugepage_size as u64 - 1)
+        / args.hugepage_size as u64;
+    if (free_pages as u64) < min_free_pages {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!(
+                "{} free hugepages of {}kB available, {} needed for {}MiB of memory",
+                free_pages, args.hugepage_size, min_free_pages, memory_mb
+            ),
+        ));
     }
 
     info!("free_pages: {} nr_pages: {}", free_pages, nr_pages);
+    Ok(())
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -105,7 +190,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         logger::init("INFO");
     }
 
-    hugepage_check();
+    hugepage_check(&args)?;
 
     let nvme_core_path = Path::new("/sys/module/nvme_core/parameters");
     let nvme_mp: String =
@@ -144,3 +229,70 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     ms.fini();
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::reserve_hugepages;
+    use std::{cell::Cell, io};
+
+    /// Simulates nr_hugepages where every write grants at most `grants`
+    /// pages, with `after_compaction` pages once memory has been compacted.
+    fn reserve(
+        reserved: u32,
+        grants: u32,
+        after_compaction: u32,
+        compaction: bool,
+    ) -> (u32, u32) {
+        let nr_pages = Cell::new(reserved);
+        let available = Cell::new(grants);
+        let compactions = Cell::new(0);
+
+        let granted = reserve_hugepages(
+            512,
+            compaction,
+            || Ok(nr_pages.get()),
+            |pages| {
+                nr_pages.set(pages.min(available.get()));
+                Ok(())
+            },
+            || {
+                compactions.set(compactions.get() + 1);
+                available.set(after_compaction);
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        (granted, compactions.get())
+    }
+
+    #[test]
+    fn hugepages_granted() {
+        // enough pages reserved already, nothing is written
+        assert_eq!(reserve(1024, 0, 0, true), (1024, 0));
+        // all pages granted on the first attempt
+        assert_eq!(reserve(0, 512, 0, true), (512, 0));
+    }
+
+    #[test]
+    fn hugepages_compaction() {
+        // the retry after compaction grants all pages
+        assert_eq!(reserve(0, 100, 512, true), (512, 1));
+        // compaction happens once only, still short
+        assert_eq!(reserve(0, 100, 300, true), (300, 1));
+        // compaction is disabled
+        assert_eq!(reserve(0, 100, 512, false), (100, 0));
+    }
+
+    #[test]
+    fn hugepages_read_error() {
+        let result = reserve_hugepages(
+            512,
+            true,
+            || Err(io::Error::new(io::ErrorKind::NotFound, "no such file")),
+            |_| Ok(()),
+            || Ok(()),
+        );
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -26,8 +26,10 @@ use spdk_sys::{
     spdk_bdev_get_uuid,
     spdk_bdev_io_stat,
     spdk_bdev_io_type_supported,
+    spdk_bdev_module_release_bdev,
     spdk_bdev_next,
     spdk_bdev_open_ext,
+    spdk_bdev_unregister,
     spdk_uuid,
     spdk_uuid_copy,
     spdk_uuid_generate,
@@ -38,6 +40,7 @@ use crate::{
     core::{
         share::{Protocol, Share},
         uuid::Uuid,
+        BdevUnregister,
         BlockDeviceIoStats,
         CoreError,
         Descriptor,
@@ -48,7 +51,14 @@ use crate::{
         UnshareIscsi,
         UnshareNvmf,
     },
-    ffihelper::{cb_arg, AsStr, FfiResult, IntoCString},
+    ffihelper::{
+        cb_arg,
+        done_errno_cb,
+        AsStr,
+        ErrnoResult,
+        FfiResult,
+        IntoCString,
+    },
     subsys::NvmfSubsystem,
     target::{iscsi, nvmf, Side},
 };
@@ -232,6 +242,57 @@ impl Bdev {
         }
     }
 
+    /// returns true if the bdev has been opened by anyone
+    pub fn is_open(&self) -> bool {
+        !unsafe { self.0.as_ref().internal.open_descs.tqh_first }.is_null()
+    }
+
+    /// Forcibly remove the bdev regardless of the module that created it, for
+    /// recovering from a bdev leaked by a partially failed operation. Any claim
+    /// on the bdev is released. A bdev which is open or shared may be serving
+    /// I/O and is only removed when forced, its users are told about the
+    /// removal and the removal completes once they have all closed it.
+    pub async fn force_remove(self, force: bool) -> Result<(), CoreError> {
+        let name = self.name();
+        let shared = matches!(
+            self.shared(),
+            Some(Protocol::Nvmf) | Some(Protocol::Iscsi)
+        );
+
+        if (shared || self.is_open()) && !force {
+            return Err(CoreError::BdevBusy {
+                name,
+            });
+        }
+
+        warn!(
+            "force removing bdev {} (claimed by: {:?}, open: {}, shared: {})",
+            name,
+            self.claimed_by(),
+            self.is_open(),
+            shared
+        );
+
+        if shared {
+            self.unshare().await?;
+        }
+
+        if self.is_claimed() {
+            unsafe { spdk_bdev_module_release_bdev(self.as_ptr()) };
+        }
+
+        let (s, r) = oneshot::channel::<ErrnoResult<()>>();
+        unsafe {
+            spdk_bdev_unregister(self.as_ptr(), Some(done_errno_cb), cb_arg(s));
+        }
+
+        r.await.expect("bdev unregister callback is gone").context(
+            BdevUnregister {
+                name,
+            },
+        )
+    }
+
     /// construct bdev from raw pointer
     pub fn from_ptr(bdev: *mut spdk_bdev) -> Option<Bdev> {
         NonNull::new(bdev).map(Bdev)
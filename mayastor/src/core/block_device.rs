@@ -197,6 +197,17 @@ pub trait BlockDeviceHandle {
         })
     }
 
+    async fn nvme_resv_release(
+        &self,
+        _current_key: u64,
+        _resv_type: u8,
+        _release_action: u8,
+    ) -> Result<(), CoreError> {
+        Err(CoreError::NotSupported {
+            source: Errno::EOPNOTSUPP,
+        })
+    }
+
     async fn nvme_resv_report(
         &self,
         _cdw11: u32,
@@ -251,12 +262,30 @@ impl ToString for DeviceTimeoutAction {
     }
 }
 
+impl std::str::FromStr for DeviceTimeoutAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "abort" => Ok(Self::Abort),
+            "reset" => Ok(Self::Reset),
+            "ignore" | "none" => Ok(Self::Ignore),
+            "hotremove" | "hot_remove" => Ok(Self::HotRemove),
+            _ => Err(format!("invalid I/O timeout action: {}", s)),
+        }
+    }
+}
+
 pub trait DeviceIoController {
     fn get_timeout_action(&self) -> Result<DeviceTimeoutAction, CoreError>;
     fn set_timeout_action(
         &mut self,
         action: DeviceTimeoutAction,
     ) -> Result<(), CoreError>;
+    /// Get the I/O timeout (in microseconds), 0 means timeouts are disabled.
+    fn get_timeout_us(&self) -> Result<u64, CoreError>;
+    /// Set the I/O timeout (in microseconds), overriding the global default.
+    fn set_timeout_us(&mut self, timeout_us: u64) -> Result<(), CoreError>;
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -264,4 +293,7 @@ pub enum DeviceEventType {
     DeviceRemoved,
     DeviceResized,
     MediaManagement,
+    /// The device did not respond within its keep-alive timeout and is
+    /// being reset.
+    KeepAliveTimeout,
 }
@@ -132,6 +132,16 @@ pub struct MayastorCliArgs {
     #[structopt(long = "nvme-ctl-pool-size", default_value = "65535")]
     /// Number of entries in memory pool for NVMe controller I/O contexts
     pub nvme_ctl_io_ctx_pool_size: u64,
+    #[structopt(long = "hugepage-size", default_value = "2048")]
+    /// Size of the hugepages to use in KiB, i.e. 2048 or 1048576.
+    pub hugepage_size: u32,
+    #[structopt(long = "hugepages", default_value = "1024")]
+    /// Number of hugepages to reserve at startup.
+    pub hugepages: u32,
+    #[structopt(long = "no-hugepage-compaction")]
+    /// Do not compact memory and retry when fewer hugepages than requested
+    /// could be reserved.
+    pub no_hugepage_compaction: bool,
 }
 
 /// Mayastor features.
@@ -172,6 +182,9 @@ impl Default for MayastorCliArgs {
             core_list: None,
             bdev_io_ctx_pool_size: 65535,
             nvme_ctl_io_ctx_pool_size: 65535,
+            hugepage_size: 2048,
+            hugepages: 1024,
+            no_hugepage_compaction: false,
         }
     }
 }
@@ -733,6 +746,9 @@ impl MayastorEnvironment {
             config.import_pools();
         }
 
+        // connect to the targets we expect to be used soon
+        subsys::prewarm_controllers(Config::get().nvme_prewarm_uris.clone());
+
         self
     }
 
@@ -10,6 +10,7 @@ use nix::errno::Errno;
 
 use spdk_sys::{
     spdk_bdev_desc,
+    spdk_bdev_flush,
     spdk_bdev_free_io,
     spdk_bdev_io,
     spdk_bdev_nvme_admin_passthru_ro,
@@ -213,6 +214,33 @@ impl BdevHandle {
         }
     }
 
+    /// flush the whole bdev
+    pub async fn flush(&self) -> Result<(), CoreError> {
+        let (s, r) = oneshot::channel::<bool>();
+        let errno = unsafe {
+            spdk_bdev_flush(
+                self.desc.as_ptr(),
+                self.channel.as_ptr(),
+                0,
+                self.desc.get_bdev().size_in_bytes(),
+                Some(Self::io_completion_cb),
+                cb_arg(s),
+            )
+        };
+
+        if errno != 0 {
+            return Err(CoreError::FlushDispatch {
+                source: Errno::from_i32(errno.abs()),
+            });
+        }
+
+        if r.await.expect("Failed awaiting flush IO") {
+            Ok(())
+        } else {
+            Err(CoreError::FlushFailed {})
+        }
+    }
+
     /// create a snapshot, only works for nvme bdev
     /// returns snapshot time as u64 seconds since Unix epoch
     pub async fn create_snapshot(&self) -> Result<u64, CoreError> {
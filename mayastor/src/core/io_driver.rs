@@ -11,7 +11,7 @@ use spdk_sys::{
 };
 
 use crate::{
-    core::{Bdev, Cores, Descriptor, DmaBuf, IoChannel, Mthread},
+    core::{Bdev, Cores, Descriptor, DmaBuf, IoChannel, Mthread, WithResult},
     ffihelper::pair,
     nexus_uri::bdev_create,
 };
@@ -231,7 +231,7 @@ impl Job {
         self as *const _ as *mut _
     }
     /// start the job that will dispatch an IO up to the provided queue depth
-    fn start(mut self) -> Box<Job> {
+    fn start(mut self) -> WithResult<Box<Job>> {
         let thread =
             Mthread::new(format!("job_{}", self.bdev.name()), self.core)
                 .unwrap();
@@ -382,14 +382,19 @@ impl JobQueue {
 
     /// start the job
     pub fn start(&self, job: Job) {
-        self.inner.lock().unwrap().push(job.start());
+        match job.start() {
+            Ok(job) => self.inner.lock().unwrap().push(job),
+            Err(e) => error!("failed to start job: {}", e),
+        }
     }
 
     /// stop the job by bdev name
     pub async fn stop(&self, bdevname: &str) {
         if let Some(mut job) = self.lookup(bdevname) {
             job.stop().await.unwrap();
-            job.thread.unwrap().with(|| drop(job));
+            if let Err(e) = job.thread.unwrap().with(|| drop(job)) {
+                error!("failed to drop job: {}", e);
+            }
         }
     }
 
@@ -401,7 +406,9 @@ impl JobQueue {
         let mut inner = self.inner.lock().unwrap();
         while let Some(mut job) = inner.pop() {
             job.stop().await.unwrap();
-            job.thread.unwrap().with(|| drop(job));
+            if let Err(e) = job.thread.unwrap().with(|| drop(job)) {
+                error!("failed to drop job: {}", e);
+            }
         }
     }
 
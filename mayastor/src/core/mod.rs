@@ -43,15 +43,22 @@ pub use nvme::{
     nvme_reservation_acquire_action,
     nvme_reservation_register_action,
     nvme_reservation_register_cptpl,
+    nvme_reservation_release_action,
     nvme_reservation_type,
     GenericStatusCode,
     NvmeCommandStatus,
     NvmeStatus,
 };
-pub use reactor::{Reactor, ReactorState, Reactors, REACTOR_LIST};
+pub use reactor::{
+    Reactor,
+    ReactorState,
+    ReactorStats,
+    Reactors,
+    REACTOR_LIST,
+};
 pub use runtime::spawn;
 pub use share::{Protocol, Share};
-pub use thread::Mthread;
+pub use thread::{Mthread, WithResult};
 
 use crate::{bdev::nexus_lookup, subsys::NvmfError, target::iscsi};
 
@@ -121,6 +128,10 @@ pub enum CoreError {
     ResetDispatch {
         source: Errno,
     },
+    #[snafu(display("Failed to dispatch flush: {}", source))]
+    FlushDispatch {
+        source: Errno,
+    },
     #[snafu(display(
         "Failed to dispatch NVMe Admin command {:x}h: {}",
         opcode,
@@ -159,8 +170,24 @@ pub enum CoreError {
         offset: u64,
         len: u64,
     },
+    #[snafu(display("Unmap failed at offset {} length {}", offset, len))]
+    UnmapFailed {
+        offset: u64,
+        len: u64,
+    },
+    #[snafu(display(
+        "Write zeroes failed at offset {} length {}",
+        offset,
+        len
+    ))]
+    WriteZeroesFailed {
+        offset: u64,
+        len: u64,
+    },
     #[snafu(display("Reset failed"))]
     ResetFailed {},
+    #[snafu(display("Flush failed"))]
+    FlushFailed {},
     #[snafu(display("NVMe Admin command {:x}h failed", opcode))]
     NvmeAdminFailed {
         opcode: u16,
@@ -193,6 +220,19 @@ pub enum CoreError {
     ReactorError {
         source: Errno,
     },
+    #[snafu(display("NVMe controller {} not found", name))]
+    NvmeControllerNotFound {
+        name: String,
+    },
+    #[snafu(display(
+        "NVMe controller {} does not support log page {:x}h",
+        name,
+        log_page
+    ))]
+    NvmeLogPageUnsupported {
+        name: String,
+        log_page: u8,
+    },
     #[snafu(display("Failed to allocate DMA buffer of {} bytes", size))]
     DmaAllocationError {
         size: u64,
@@ -203,6 +243,30 @@ pub enum CoreError {
     },
     #[snafu(display("No devices available for I/O"))]
     NoDevicesAvailable {},
+    #[snafu(display("I/O channel of {} is shut down", name))]
+    ChannelShutdown {
+        name: String,
+    },
+    #[snafu(display("Bdev {} is in use and may be serving I/O", name))]
+    BdevBusy {
+        name: String,
+    },
+    #[snafu(display("Failed to unregister bdev {}: {}", name, source))]
+    BdevUnregister {
+        source: Errno,
+        name: String,
+    },
+    #[snafu(display(
+        "Failed to discover NVMe-oF subsystems at {}:{}: {}",
+        traddr,
+        svcid,
+        source
+    ))]
+    NvmeDiscovery {
+        source: Errno,
+        traddr: String,
+        svcid: String,
+    },
 }
 
 // Generic I/O completion status for block devices, which supports per-protocol
@@ -218,7 +218,7 @@ impl From<&Bio> for NvmeStatus {
 
 /// NVMe Admin opcode, from nvme_spec.h
 pub mod nvme_admin_opc {
-    // pub const GET_LOG_PAGE: u8 = 0x02;
+    pub const GET_LOG_PAGE: u8 = 0x02;
     pub const IDENTIFY: u8 = 0x06;
     // pub const ABORT: u8 = 0x08;
     // pub const SET_FEATURES: u8 = 0x09;
@@ -239,7 +239,7 @@ pub mod nvme_nvm_opcode {
     pub const RESERVATION_REGISTER: u8 = 0x0d;
     pub const RESERVATION_REPORT: u8 = 0x0e;
     pub const RESERVATION_ACQUIRE: u8 = 0x11;
-    // pub const RESERVATION_RELEASE: u8 = 0x15;
+    pub const RESERVATION_RELEASE: u8 = 0x15;
 }
 
 pub mod nvme_reservation_type {
@@ -269,6 +269,11 @@ pub mod nvme_reservation_acquire_action {
     pub const PREEMPT_ABORT: u8 = 0x2;
 }
 
+pub mod nvme_reservation_release_action {
+    pub const RELEASE: u8 = 0x0;
+    pub const CLEAR: u8 = 0x1;
+}
+
 impl NvmeCommandStatus {
     pub fn from_command_status_raw(sct: i32, sc: i32) -> Self {
         match StatusCodeType::from(sct) {
@@ -36,11 +36,13 @@ use std::{
     os::raw::c_void,
     pin::Pin,
     slice::Iter,
-    time::Duration,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
 };
 
 use crossbeam::channel::{unbounded, Receiver, Sender};
 use futures::{
+    channel::oneshot,
     task::{Context, Poll},
     Future,
 };
@@ -79,6 +81,51 @@ impl Display for ReactorState {
     }
 }
 
+/// Statistics of the futures which were sent to a reactor from any core.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ReactorStats {
+    /// core of the reactor
+    pub core: u32,
+    /// number of futures dispatched to the reactor
+    pub events: u64,
+    /// total time the futures waited before the reactor picked them up
+    pub queue_time: Duration,
+    /// longest time a future waited before the reactor picked it up
+    pub max_queue_time: Duration,
+    /// total time from the start to the completion of the futures
+    pub exec_time: Duration,
+    /// longest time from the start to the completion of a future
+    pub max_exec_time: Duration,
+}
+
+/// Counters behind ['ReactorStats'], updated by the core of the reactor and
+/// read from any core. Times are in microseconds.
+#[derive(Debug, Default)]
+struct EventCounters {
+    events: AtomicU64,
+    queue_us: AtomicU64,
+    max_queue_us: AtomicU64,
+    exec_us: AtomicU64,
+    max_exec_us: AtomicU64,
+}
+
+impl EventCounters {
+    fn queued(&self, time: Duration) {
+        let us = time.as_micros() as u64;
+        self.events.fetch_add(1, Ordering::Relaxed);
+        self.queue_us.fetch_add(us, Ordering::Relaxed);
+        self.max_queue_us.fetch_max(us, Ordering::Relaxed);
+    }
+
+    fn executed(&self, time: Duration) {
+        let us = time.as_micros() as u64;
+        self.exec_us.fetch_add(us, Ordering::Relaxed);
+        self.max_exec_us.fetch_max(us, Ordering::Relaxed);
+    }
+}
+
+type QueuedFuture = (Instant, Pin<Box<dyn Future<Output = ()> + 'static>>);
+
 #[derive(Debug)]
 pub struct Reactors(Vec<Reactor>);
 
@@ -107,9 +154,11 @@ pub struct Reactor {
     /// represents the state of the reactor
     flags: Cell<ReactorState>,
     /// sender and Receiver for sending futures across cores without going
-    /// through FFI
-    sx: Sender<Pin<Box<dyn Future<Output = ()> + 'static>>>,
-    rx: Receiver<Pin<Box<dyn Future<Output = ()> + 'static>>>,
+    /// through FFI, along with the time the future was sent
+    sx: Sender<QueuedFuture>,
+    rx: Receiver<QueuedFuture>,
+    /// statistics of the futures received on the channel
+    counters: EventCounters,
 }
 
 thread_local! {
@@ -252,6 +301,35 @@ impl Reactors {
     pub fn iter() -> Iter<'static, Reactor> {
         REACTOR_LIST.get().unwrap().into_iter()
     }
+
+    /// run the future returned by the closure on the reactor of the given
+    /// core, the result is delivered through the returned receiver. The
+    /// closure is called on the target core, so the future itself does not
+    /// have to be Send.
+    pub fn spawn_on_core_async<F, Fut, R>(
+        core: u32,
+        f: F,
+    ) -> Result<oneshot::Receiver<R>, CoreError>
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = R> + 'static,
+        R: Send + 'static,
+    {
+        let reactor =
+            Self::get_by_core(core).ok_or(CoreError::ReactorError {
+                source: Errno::EINVAL,
+            })?;
+
+        let (s, r) = oneshot::channel::<R>();
+        reactor.send_future(async move {
+            let result = f().await;
+            if s.send(result).is_err() {
+                debug!("receiver of future spawned on core {} is gone", core);
+            }
+        });
+
+        Ok(r)
+    }
 }
 
 impl<'a> IntoIterator for &'a Reactors {
@@ -267,8 +345,7 @@ impl Reactor {
     /// create a new ['Reactor'] instance
     fn new(core: u32) -> Self {
         // create a channel to receive futures on
-        let (sx, rx) =
-            unbounded::<Pin<Box<dyn Future<Output = ()> + 'static>>>();
+        let (sx, rx) = unbounded::<QueuedFuture>();
 
         Self {
             threads: RefCell::new(VecDeque::new()),
@@ -277,6 +354,7 @@ impl Reactor {
             flags: Cell::new(ReactorState::Init),
             sx,
             rx,
+            counters: EventCounters::default(),
         }
     }
 
@@ -307,10 +385,17 @@ impl Reactor {
         });
     }
 
-    /// receive futures if any
+    /// receive futures if any, accounting for the time they were queued and
+    /// the time they take to complete
     fn receive_futures(&self) {
-        self.rx.try_iter().for_each(|m| {
-            self.spawn_local(m).detach();
+        self.rx.try_iter().for_each(|(queued, m)| {
+            self.counters.queued(queued.elapsed());
+            self.spawn_local(async move {
+                let started = Instant::now();
+                m.await;
+                Reactors::current().counters.executed(started.elapsed());
+            })
+            .detach();
         });
     }
 
@@ -319,7 +404,7 @@ impl Reactor {
     where
         F: Future<Output = ()> + 'static,
     {
-        self.sx.send(Box::pin(future)).unwrap();
+        self.sx.send((Instant::now(), Box::pin(future))).unwrap();
     }
 
     /// spawn a future locally on this core; note that you can *not* use the
@@ -419,6 +504,21 @@ impl Reactor {
         self.lcore
     }
 
+    /// returns the statistics of the futures sent to this reactor
+    pub fn stats(&self) -> ReactorStats {
+        let c = &self.counters;
+        let time =
+            |v: &AtomicU64| Duration::from_micros(v.load(Ordering::Relaxed));
+        ReactorStats {
+            core: self.lcore,
+            events: c.events.load(Ordering::Relaxed),
+            queue_time: time(&c.queue_us),
+            max_queue_time: time(&c.max_queue_us),
+            exec_time: time(&c.exec_us),
+            max_exec_time: time(&c.max_exec_us),
+        }
+    }
+
     /// poll this reactor to complete any work that is pending
     pub fn poll_reactor(&self) {
         loop {
@@ -2,6 +2,7 @@ use std::ffi::{c_void, CString};
 
 use snafu::Snafu;
 use spdk_sys::{
+    spdk_cpuset_get_cpu,
     spdk_get_thread,
     spdk_set_thread,
     spdk_thread,
@@ -9,6 +10,7 @@ use spdk_sys::{
     spdk_thread_destroy,
     spdk_thread_exit,
     spdk_thread_get_by_id,
+    spdk_thread_get_cpumask,
     spdk_thread_is_exited,
     spdk_thread_poll,
     spdk_thread_send_msg,
@@ -17,14 +19,28 @@ use spdk_sys::{
 use crate::core::{cpu_cores::CpuMask, CoreError, Cores, Reactors};
 use futures::channel::oneshot::{channel, Receiver, Sender};
 use nix::errno::Errno;
-use std::{fmt::Debug, future::Future, ptr::NonNull};
+use std::{
+    any::Any,
+    fmt::Debug,
+    future::Future,
+    panic::AssertUnwindSafe,
+    ptr::NonNull,
+};
 
 #[derive(Debug, Snafu)]
 pub enum Error {
     #[snafu(display("Event spawned from a non-spdk thread"))]
     InvalidThread {},
+    #[snafu(display("Failed to create thread {} on core {}", name, core))]
+    ThreadCreate { name: String, core: u32 },
+    #[snafu(display("Closure panicked on thread {}: {}", name, message))]
+    Panicked { name: String, message: String },
 }
 
+/// Result of executing a closure within the context of a thread, a panic
+/// inside the closure is returned as [`Error::Panicked`].
+pub type WithResult<T> = Result<T, Error>;
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 /// struct that wraps an SPDK thread. The name thread is chosen poorly and
 /// should not be confused with an actual thread. Consider it more to be
@@ -64,6 +80,29 @@ impl Mthread {
         .map(Mthread)
     }
 
+    /// Create a new thread on the given core and run the closure with it as
+    /// context. The thread is polled until it has no more work to do, before
+    /// the thread and the value returned by the closure are returned.
+    pub fn spawn_thread<F, R>(
+        name: String,
+        core: u32,
+        f: F,
+    ) -> Result<(Mthread, R), Error>
+    where
+        F: FnOnce() -> R,
+    {
+        let thread =
+            Self::new(name.clone(), core).ok_or(Error::ThreadCreate {
+                name,
+                core,
+            })?;
+
+        let out = thread.with(f)?;
+        while unsafe { spdk_thread_poll(thread.0.as_ptr(), 0, 0) } > 0 {}
+
+        Ok((thread, out))
+    }
+
     pub fn id(&self) -> u64 {
         unsafe { (self.0.as_ref()).id }
     }
@@ -73,14 +112,22 @@ impl Mthread {
     /// Avoid any blocking calls as it will block the whole reactor. Also, avoid
     /// long-running functions. In general if you follow the nodejs event loop
     /// model, you should be good.
-    pub fn with<T, F: FnOnce() -> T>(self, f: F) -> T {
+    ///
+    /// A panic inside the closure is caught so that it does not unwind across
+    /// the FFI boundary, the previous thread is restored and the panic is
+    /// returned as an error. This relies on panics unwinding, which is why
+    /// no profile of the workspace sets `panic = "abort"`.
+    pub fn with<T, F: FnOnce() -> T>(self, f: F) -> WithResult<T> {
         let th = Self::current();
         self.enter();
-        let out = f();
+        let out = std::panic::catch_unwind(AssertUnwindSafe(f));
         if let Some(t) = th {
             t.enter();
         }
-        out
+        out.map_err(|e| Error::Panicked {
+            name: self.name().to_string(),
+            message: panic_message(e),
+        })
     }
 
     #[inline]
@@ -102,6 +149,21 @@ impl Mthread {
         NonNull::new(unsafe { spdk_get_thread() }).map(Mthread)
     }
 
+    /// As `Mthread::from`, but returns None rather than panicking when the
+    /// thread is NULL.
+    pub fn from_null_checked(t: *mut spdk_thread) -> Option<Mthread> {
+        NonNull::new(t).map(Mthread)
+    }
+
+    /// Returns the reactor core the thread is scheduled on, i.e. the first
+    /// core of its cpumask which has a reactor.
+    pub fn core(&self) -> Option<u32> {
+        let mask = unsafe { spdk_thread_get_cpumask(self.0.as_ptr()) };
+        Reactors::iter()
+            .map(|r| r.core())
+            .find(|core| unsafe { spdk_cpuset_get_cpu(mask, *core) })
+    }
+
     pub fn name(&self) -> &str {
         unsafe {
             std::ffi::CStr::from_ptr(&self.0.as_ref().name[0])
@@ -277,3 +339,14 @@ impl Mthread {
         }
     }
 }
+
+/// extract the message from the payload of a caught panic
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
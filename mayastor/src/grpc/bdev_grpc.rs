@@ -12,6 +12,7 @@ use rpc::mayastor::{
     BdevUri,
     Bdevs,
     CreateReply,
+    ForceRemoveBdevRequest,
     Null,
 };
 
@@ -165,4 +166,33 @@ impl BdevRpc for BdevSvc {
             .map_err(Status::from)
             .map(Response::new)
     }
+
+    #[instrument(level = "debug", err)]
+    async fn force_remove(
+        &self,
+        request: Request<ForceRemoveBdevRequest>,
+    ) -> GrpcResult<Null> {
+        let args = request.into_inner();
+
+        if !args.confirm {
+            return Err(Status::invalid_argument(format!(
+                "force removing bdev {} may crash its users, confirm to proceed",
+                args.name
+            )));
+        }
+
+        let name = args.name.clone();
+        let rx = rpc_submit::<_, _, CoreError>(async move {
+            match Bdev::lookup_by_name(&args.name) {
+                Some(bdev) => bdev.force_remove(args.force).await.map(Some),
+                None => Ok(None),
+            }
+        })?;
+
+        rx.await
+            .map_err(|_| Status::cancelled("cancelled"))?
+            .map_err(Status::from)?
+            .ok_or_else(|| Status::not_found(name))
+            .map(|_| Response::new(Null {}))
+    }
 }
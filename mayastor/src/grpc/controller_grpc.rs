@@ -3,6 +3,7 @@ use crate::{
         nexus::nexus_bdev,
         NvmeController,
         NvmeControllerState,
+        NvmeHealthLog,
         NVME_CONTROLLERS,
     },
     core::{BlockDeviceIoStats, CoreError},
@@ -16,16 +17,29 @@ use std::convert::From;
 use tonic::{Response, Status};
 
 impl<'a> NvmeController<'a> {
-    fn to_grpc(&self) -> rpc::NvmeController {
-        let (size, blk_size) = self
-            .namespace()
+    pub(crate) fn to_grpc(&self) -> rpc::NvmeController {
+        let ns = self.namespace();
+        let (size, blk_size) = ns
+            .as_ref()
             .map_or((0, 0), |ns| (ns.size_in_bytes(), ns.block_len() as u32));
+        let trid = self.transport_id();
 
         rpc::NvmeController {
             name: self.name.to_string(),
             state: rpc::NvmeControllerState::from(self.get_state()) as i32,
             size,
             blk_size,
+            num_io_queues: self.num_io_queues(),
+            model: self.model().unwrap_or_default(),
+            serial: self.serial().unwrap_or_default(),
+            ns_uuid: ns
+                .as_ref()
+                .map(|ns| ns.uuid().to_string())
+                .unwrap_or_default(),
+            ns_nguid: ns.as_ref().map(|ns| ns.nguid()).unwrap_or_default(),
+            traddr: trid.as_ref().map(|t| t.traddr()).unwrap_or_default(),
+            trsvcid: trid.as_ref().map(|t| t.svcid()).unwrap_or_default(),
+            subnqn: trid.as_ref().map(|t| t.subnqn()).unwrap_or_default(),
         }
     }
 }
@@ -51,6 +65,28 @@ impl From<NvmeControllerState> for rpc::NvmeControllerState {
     }
 }
 
+impl rpc::NvmeControllerHealth {
+    fn new(name: &str, log: NvmeHealthLog) -> Self {
+        Self {
+            name: name.to_string(),
+            critical_warning: log.critical_warning as u32,
+            temperature: log.temperature as u32,
+            available_spare: log.available_spare as u32,
+            available_spare_threshold: log.available_spare_threshold as u32,
+            percentage_used: log.percentage_used as u32,
+            data_units_read: log.data_units_read,
+            data_units_written: log.data_units_written,
+            host_read_commands: log.host_read_commands,
+            host_write_commands: log.host_write_commands,
+            power_cycles: log.power_cycles,
+            power_on_hours: log.power_on_hours,
+            unsafe_shutdowns: log.unsafe_shutdowns,
+            media_errors: log.media_errors,
+            num_error_info_log_entries: log.num_error_info_log_entries,
+        }
+    }
+}
+
 impl From<BlockDeviceIoStats> for rpc::NvmeControllerIoStats {
     fn from(b: BlockDeviceIoStats) -> Self {
         Self {
@@ -135,3 +171,33 @@ pub async fn list_controllers() -> GrpcResult<rpc::ListNvmeControllersReply> {
         .map_err(Status::from)
         .map(Response::new)
 }
+
+pub async fn controller_health(
+    name: String,
+) -> GrpcResult<rpc::NvmeControllerHealth> {
+    let rx = rpc_submit::<_, _, CoreError>(async move {
+        let ctrlr =
+            NVME_CONTROLLERS.lookup_by_name(&name).ok_or_else(|| {
+                CoreError::NvmeControllerNotFound {
+                    name: name.clone(),
+                }
+            })?;
+
+        let (s, r) = oneshot::channel::<Result<NvmeHealthLog, CoreError>>();
+
+        ctrlr.lock().get_health_log(
+            |log, ch| {
+                done_cb(ch, log);
+            },
+            cb_arg(s),
+        )?;
+
+        let log = r.await.expect("Failed awaiting at health log")?;
+        Ok(rpc::NvmeControllerHealth::new(&name, log))
+    })?;
+
+    rx.await
+        .map_err(|_| Status::cancelled("cancelled"))?
+        .map_err(Status::from)
+        .map(Response::new)
+}
@@ -0,0 +1,159 @@
+//! Deduplication of retried gRPC requests.
+//!
+//! The control plane retries requests which timed out, even though the
+//! original request may have completed. A request carrying an idempotency key
+//! in its metadata is only executed once: the reply of the first successful
+//! request with a given key is returned to any request with the same key, for
+//! as long as the key is remembered. Failed requests are not remembered so
+//! that they can be retried.
+
+use std::{
+    any::Any,
+    collections::HashMap,
+    future::Future,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use once_cell::sync::Lazy;
+use tonic::{Request, Response, Status};
+
+use crate::grpc::{GrpcClientContext, GrpcResult};
+
+/// name of the request metadata holding the idempotency key
+pub const IDEMPOTENCY_KEY: &str = "x-idempotency-key";
+/// how long the reply of a keyed request is remembered
+const IDEMPOTENCY_TTL: Duration = Duration::from_secs(300);
+
+static IDEMPOTENCY_CACHE: Lazy<IdempotencyCache> =
+    Lazy::new(|| IdempotencyCache::new(IDEMPOTENCY_TTL));
+
+/// the reply to a keyed request along with the request it answers
+struct Entry {
+    method: String,
+    args: String,
+    reply: Box<dyn Any + Send>,
+    expires: Instant,
+}
+
+/// replies of keyed requests which completed recently
+struct IdempotencyCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl IdempotencyCache {
+    fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// the remembered reply for the key, a key may not be reused for a
+    /// different request
+    fn lookup<T: Clone + 'static>(
+        &self,
+        key: &str,
+        ctx: &GrpcClientContext,
+    ) -> Result<Option<T>, Status> {
+        let mut entries = self.entries.lock().expect("lock poisoned");
+        let now = Instant::now();
+        entries.retain(|_, e| e.expires > now);
+
+        match entries.get(key) {
+            Some(e) if e.method != ctx.id || e.args != ctx.args => {
+                Err(Status::invalid_argument(format!(
+                    "idempotency key {} was used for a different request",
+                    key
+                )))
+            }
+            Some(e) => Ok(e.reply.downcast_ref::<T>().cloned()),
+            None => Ok(None),
+        }
+    }
+
+    fn insert<T: Clone + Send + 'static>(
+        &self,
+        key: String,
+        ctx: &GrpcClientContext,
+        reply: &T,
+    ) {
+        self.entries.lock().expect("lock poisoned").insert(
+            key,
+            Entry {
+                method: ctx.id.clone(),
+                args: ctx.args.clone(),
+                reply: Box::new(reply.clone()),
+                expires: Instant::now() + self.ttl,
+            },
+        );
+    }
+}
+
+/// the idempotency key of the request, if any
+pub(crate) fn idempotency_key<T>(request: &Request<T>) -> Option<String> {
+    request
+        .metadata()
+        .get(IDEMPOTENCY_KEY)
+        .and_then(|v| v.to_str().ok())
+        .filter(|k| !k.is_empty())
+        .map(String::from)
+}
+
+/// Run the request unless a request with the same idempotency key completed
+/// already, in which case its reply is returned instead. Requests must be
+/// serialized by the caller for a retry racing the original request to be
+/// deduplicated.
+pub(crate) async fn idempotent<T, F>(
+    key: Option<String>,
+    ctx: GrpcClientContext,
+    f: F,
+) -> GrpcResult<T>
+where
+    T: Clone + Send + 'static,
+    F: Future<Output = GrpcResult<T>>,
+{
+    let key = match key {
+        Some(key) => key,
+        None => return f.await,
+    };
+
+    if let Some(reply) = IDEMPOTENCY_CACHE.lookup::<T>(&key, &ctx)? {
+        info!("{}: returning reply of request with key {}", ctx.id, key);
+        return Ok(Response::new(reply));
+    }
+
+    let reply = f.await?;
+    IDEMPOTENCY_CACHE.insert(key, &ctx, reply.get_ref());
+    Ok(reply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx(args: &str) -> GrpcClientContext {
+        GrpcClientContext {
+            args: args.into(),
+            id: "create_pool".into(),
+        }
+    }
+
+    #[test]
+    fn lookup() {
+        let cache = IdempotencyCache::new(Duration::from_secs(60));
+        assert_eq!(cache.lookup::<u32>("k", &ctx("a")).unwrap(), None);
+
+        cache.insert("k".into(), &ctx("a"), &7u32);
+        assert_eq!(cache.lookup::<u32>("k", &ctx("a")).unwrap(), Some(7));
+        assert!(cache.lookup::<u32>("k", &ctx("b")).is_err());
+    }
+
+    #[test]
+    fn expiry() {
+        let cache = IdempotencyCache::new(Duration::from_millis(0));
+        cache.insert("k".into(), &ctx("a"), &7u32);
+        assert_eq!(cache.lookup::<u32>("k", &ctx("a")).unwrap(), None);
+    }
+}
@@ -10,21 +10,30 @@
 
 use crate::{
     bdev::{
-        nexus::{instances, nexus_bdev},
-        nexus_create,
+        nexus::{instances, nexus_bdev, nexus_event},
+        nexus_create_cancellable,
+        nexus_create_dry_run,
         nexus_create_v2,
         Reason,
+        ReservationOp,
     },
     core::{
         Bdev,
         BlockDeviceIoStats,
         CoreError,
+        Cores,
         MayastorFeatures,
         Protocol,
+        Reactors,
         Share,
     },
     grpc::{
-        controller_grpc::{controller_stats, list_controllers},
+        controller_grpc::{
+            controller_health,
+            controller_stats,
+            list_controllers,
+        },
+        idempotency::{idempotency_key, idempotent},
         mayastor_grpc::nexus_bdev::NexusNvmeParams,
         nexus_grpc::{
             nexus_add_child,
@@ -38,14 +47,15 @@ use crate::{
         Serializer,
     },
     host::{blk_device, resource},
-    lvs::{Error as LvsError, Lvol, Lvs},
+    lvs::{Error as LvsError, Lvol, Lvs, PoolMetadata},
     nexus_uri::NexusBdevError,
-    subsys::PoolConfig,
+    subsys::{prewarm_status, PoolConfig},
 };
-use futures::FutureExt;
+use futures::{channel::oneshot, FutureExt};
 use nix::errno::Errno;
 use rpc::mayastor::*;
-use std::{convert::TryFrom, fmt::Debug, ops::Deref, time::Duration};
+use std::{convert::TryFrom, fmt::Debug, ops::Deref, pin::Pin, time::Duration};
+use tokio::sync::broadcast::error::RecvError;
 use tonic::{Request, Response, Status};
 #[derive(Debug)]
 struct UnixStream(tokio::net::UnixStream);
@@ -125,6 +135,19 @@ impl MayastorSvc {
     }
 }
 
+/// look up the lvol of a replica by its uuid
+fn replica_lookup(uuid: &str) -> Result<Lvol, LvsError> {
+    match Bdev::lookup_by_name(uuid) {
+        Some(bdev) => Lvol::try_from(bdev),
+        None => Err(LvsError::InvalidBdev {
+            source: NexusBdevError::BdevNotFound {
+                name: uuid.to_string(),
+            },
+            name: uuid.to_string(),
+        }),
+    }
+}
+
 impl From<LvsError> for Status {
     fn from(e: LvsError) -> Self {
         match e {
@@ -140,9 +163,36 @@ impl From<LvsError> for Status {
                     Status::invalid_argument(e.to_string())
                 }
             }
+            LvsError::RepNoSpace {
+                ..
+            } => Status::resource_exhausted(e.to_string()),
             LvsError::ReplicaShareProtocol {
                 ..
             } => Status::invalid_argument(e.to_string()),
+            LvsError::MetadataParse {
+                ..
+            } => Status::invalid_argument(e.to_string()),
+            LvsError::MetadataVersion {
+                ..
+            } => Status::invalid_argument(e.to_string()),
+            LvsError::PoolNotFound {
+                ..
+            } => Status::not_found(e.to_string()),
+            LvsError::ChecksumsDisabled {
+                ..
+            } => Status::failed_precondition(e.to_string()),
+            LvsError::ChecksumsStale {
+                ..
+            } => Status::failed_precondition(e.to_string()),
+            LvsError::MigrateInvalid {
+                ..
+            } => Status::failed_precondition(e.to_string()),
+            LvsError::MigrateInterrupted {
+                ..
+            } => Status::aborted(e.to_string()),
+            LvsError::MigrateBusy {
+                ..
+            } => Status::aborted(e.to_string()),
 
             LvsError::Destroy {
                 source, ..
@@ -176,6 +226,7 @@ impl From<Lvs> for Pool {
             state: PoolState::PoolOnline.into(),
             capacity: l.capacity(),
             used: l.used(),
+            cluster_size: l.cluster_size(),
         }
     }
 }
@@ -200,6 +251,8 @@ impl From<Lvol> for Replica {
             size: l.size(),
             share: l.shared().unwrap().into(),
             uri: l.share_uri().unwrap(),
+            core: l.core(),
+            allocated_bytes: l.allocated(),
         }
     }
 }
@@ -214,14 +267,24 @@ impl From<MayastorFeatures> for rpc::mayastor::MayastorFeatures {
 
 #[tonic::async_trait]
 impl mayastor_server::Mayastor for MayastorSvc {
+    type SubscribeChildEventsStream = Pin<
+        Box<
+            dyn futures::Stream<Item = Result<ChildFaultedEvent, Status>>
+                + Send
+                + Sync,
+        >,
+    >;
+
     #[named]
     async fn create_pool(
         &self,
         request: Request<CreatePoolRequest>,
     ) -> GrpcResult<Pool> {
+        let key = idempotency_key(&request);
+        let ctx = GrpcClientContext::new(&request, function_name!());
         self.locked(
-            GrpcClientContext::new(&request, function_name!()),
-            async move {
+            ctx.clone(),
+            idempotent(key, ctx, async move {
                 let args = request.into_inner();
 
                 if args.disks.is_empty() {
@@ -239,7 +302,7 @@ impl mayastor_server::Mayastor for MayastorSvc {
                     .map_err(|_| Status::cancelled("cancelled"))?
                     .map_err(Status::from)
                     .map(Response::new)
-            },
+            }),
         )
         .await
     }
@@ -248,13 +311,14 @@ impl mayastor_server::Mayastor for MayastorSvc {
     async fn destroy_pool(
         &self,
         request: Request<DestroyPoolRequest>,
-    ) -> GrpcResult<Null> {
+    ) -> GrpcResult<DestroyPoolReply> {
         self.locked(
             GrpcClientContext::new(&request, function_name!()),
             async move {
                 let args = request.into_inner();
                 info!("{:?}", args);
                 let rx = rpc_submit::<_, _, LvsError>(async move {
+                    let mut replicas = Vec::new();
                     if let Some(pool) = Lvs::lookup(&args.name) {
                         // Remove pool from current config and export to file.
                         // Do this BEFORE we actually destroy the pool.
@@ -262,9 +326,125 @@ impl mayastor_server::Mayastor for MayastorSvc {
                         config.delete(&args.name);
                         config.export().await;
 
+                        if args.force {
+                            let lvols: Vec<Lvol> = pool
+                                .lvols()
+                                .map(|lvols| lvols.collect())
+                                .unwrap_or_default();
+                            for lvol in lvols {
+                                replicas.push(lvol.destroy().await?);
+                            }
+                            if !replicas.is_empty() {
+                                warn!(
+                                    "Destroyed replicas {:?} of pool {}",
+                                    replicas, args.name
+                                );
+                            }
+                        }
+
                         pool.destroy().await?;
                     }
-                    Ok(Null {})
+                    Ok(DestroyPoolReply {
+                        replicas,
+                    })
+                })?;
+
+                rx.await
+                    .map_err(|_| Status::cancelled("cancelled"))?
+                    .map_err(Status::from)
+                    .map(Response::new)
+            },
+        )
+        .await
+    }
+
+    #[named]
+    async fn export_pool_metadata(
+        &self,
+        request: Request<ExportPoolMetadataRequest>,
+    ) -> GrpcResult<ExportPoolMetadataReply> {
+        self.locked(
+            GrpcClientContext::new(&request, function_name!()),
+            async move {
+                let args = request.into_inner();
+                let rx = rpc_submit::<_, _, LvsError>(async move {
+                    match Lvs::lookup(&args.name) {
+                        Some(pool) => Ok(ExportPoolMetadataReply {
+                            document: PoolMetadata::from(&pool).to_document(),
+                        }),
+                        None => Err(LvsError::Invalid {
+                            source: Errno::ENOENT,
+                            msg: format!("Pool {} not found", args.name),
+                        }),
+                    }
+                })?;
+
+                rx.await
+                    .map_err(|_| Status::cancelled("cancelled"))?
+                    .map_err(Status::from)
+                    .map(Response::new)
+            },
+        )
+        .await
+    }
+
+    #[named]
+    async fn import_pool_metadata(
+        &self,
+        request: Request<ImportPoolMetadataRequest>,
+    ) -> GrpcResult<Pool> {
+        self.locked(
+            GrpcClientContext::new(&request, function_name!()),
+            async move {
+                let args = request.into_inner();
+                let rx = rpc_submit::<_, _, LvsError>(async move {
+                    let metadata = PoolMetadata::from_document(&args.document)?;
+                    let pool = metadata.import().await?;
+                    // Capture current pool config and export to file.
+                    PoolConfig::capture().export().await;
+                    Ok(Pool::from(pool))
+                })?;
+
+                rx.await
+                    .map_err(|_| Status::cancelled("cancelled"))?
+                    .map_err(Status::from)
+                    .map(Response::new)
+            },
+        )
+        .await
+    }
+
+    #[named]
+    async fn test_pool(
+        &self,
+        request: Request<TestPoolRequest>,
+    ) -> GrpcResult<TestPoolReply> {
+        self.locked(
+            GrpcClientContext::new(&request, function_name!()),
+            async move {
+                let args = request.into_inner();
+                let rx = rpc_submit::<_, _, LvsError>(async move {
+                    let pool = Lvs::lookup(&args.name).ok_or_else(|| {
+                        LvsError::PoolNotFound {
+                            name: args.name.clone(),
+                        }
+                    })?;
+
+                    Ok(match pool.self_test().await {
+                        Ok(latency) => TestPoolReply {
+                            success: true,
+                            message: String::new(),
+                            latency: latency.as_micros() as u64,
+                        },
+                        Err(error) => {
+                            warn!("{}", error);
+                            TestPoolReply {
+                                success: false,
+                                message: error.to_string(),
+                                latency: 0,
+                            }
+                        }
+                    })
                 })?;
 
                 rx.await
@@ -306,7 +486,9 @@ impl mayastor_server::Mayastor for MayastorSvc {
         &self,
         request: Request<CreateReplicaRequest>,
     ) -> GrpcResult<Replica> {
-        self.locked(GrpcClientContext::new(&request, function_name!()), async move {
+        let key = idempotency_key(&request);
+        let ctx = GrpcClientContext::new(&request, function_name!());
+        self.locked(ctx.clone(), idempotent(key, ctx, async move {
         let rx = rpc_submit(async move {
             let args = request.into_inner();
             if Lvs::lookup(&args.pool).is_none() {
@@ -331,7 +513,29 @@ impl mayastor_server::Mayastor for MayastorSvc {
             }
 
             let p = Lvs::lookup(&args.pool).unwrap();
-            match p.create_lvol(&args.uuid, args.size, false).await {
+
+            // a thick replica is allocated up front, in whole clusters
+            if !args.thin {
+                let cluster = u64::from(p.cluster_size()).max(1);
+                let size = args
+                    .size
+                    .checked_add(cluster - 1)
+                    .map(|s| s / cluster * cluster)
+                    .ok_or_else(|| LvsError::Invalid {
+                        source: Errno::EINVAL,
+                        msg: format!("replica size {} is too large", args.size),
+                    })?;
+                if size > p.available() {
+                    return Err(LvsError::RepNoSpace {
+                        name: args.uuid,
+                        pool: args.pool,
+                        size,
+                        available: p.available(),
+                    });
+                }
+            }
+
+            match p.create_lvol(&args.uuid, args.size, args.thin).await {
                 Ok(lvol)
                     if Protocol::try_from(args.share)? == Protocol::Nvmf =>
                 {
@@ -363,7 +567,7 @@ impl mayastor_server::Mayastor for MayastorSvc {
             .map_err(|_| Status::cancelled("cancelled"))?
             .map_err(Status::from)
             .map(Response::new)
-        }).await
+        })).await
     }
 
     #[named]
@@ -519,23 +723,172 @@ impl mayastor_server::Mayastor for MayastorSvc {
         .await
     }
 
+    #[named]
+    async fn update_replica_checksums(
+        &self,
+        request: Request<ReplicaChecksumsRequest>,
+    ) -> GrpcResult<ReplicaChecksumsReply> {
+        self.locked(
+            GrpcClientContext::new(&request, function_name!()),
+            async move {
+                let args = request.into_inner();
+                let rx = rpc_submit::<_, _, LvsError>(async move {
+                    let manifest =
+                        replica_lookup(&args.uuid)?.update_checksums().await?;
+                    Ok(ReplicaChecksumsReply {
+                        region_size: manifest.region_size,
+                        regions: manifest.checksums.len() as u64,
+                    })
+                })?;
+
+                rx.await
+                    .map_err(|_| Status::cancelled("cancelled"))?
+                    .map_err(Status::from)
+                    .map(Response::new)
+            },
+        )
+        .await
+    }
+
+    #[named]
+    async fn verify_replica_checksums(
+        &self,
+        request: Request<ReplicaChecksumsRequest>,
+    ) -> GrpcResult<VerifyReplicaChecksumsReply> {
+        self.locked(
+            GrpcClientContext::new(&request, function_name!()),
+            async move {
+                let args = request.into_inner();
+                let rx = rpc_submit::<_, _, LvsError>(async move {
+                    let (manifest, mismatches) =
+                        replica_lookup(&args.uuid)?.verify_checksums().await?;
+                    Ok(VerifyReplicaChecksumsReply {
+                        region_size: manifest.region_size,
+                        regions: manifest.checksums.len() as u64,
+                        mismatches,
+                    })
+                })?;
+
+                rx.await
+                    .map_err(|_| Status::cancelled("cancelled"))?
+                    .map_err(Status::from)
+                    .map(Response::new)
+            },
+        )
+        .await
+    }
+
+    #[named]
+    async fn disable_replica_checksums(
+        &self,
+        request: Request<ReplicaChecksumsRequest>,
+    ) -> GrpcResult<Null> {
+        self.locked(
+            GrpcClientContext::new(&request, function_name!()),
+            async move {
+                let args = request.into_inner();
+                let rx = rpc_submit::<_, _, LvsError>(async move {
+                    replica_lookup(&args.uuid)?.disable_checksums().await?;
+                    Ok(Null {})
+                })?;
+
+                rx.await
+                    .map_err(|_| Status::cancelled("cancelled"))?
+                    .map_err(Status::from)
+                    .map(Response::new)
+            },
+        )
+        .await
+    }
+
+    #[named]
+    async fn migrate_replica(
+        &self,
+        request: Request<MigrateReplicaRequest>,
+    ) -> GrpcResult<Replica> {
+        self.locked(
+            GrpcClientContext::new(&request, function_name!()),
+            async move {
+                let args = request.into_inner();
+                let rx = rpc_submit::<_, _, LvsError>(async move {
+                    let pool = Lvs::lookup(&args.pool).ok_or_else(|| {
+                        LvsError::PoolNotFound {
+                            name: args.pool.clone(),
+                        }
+                    })?;
+                    let lvol = replica_lookup(&args.uuid)?
+                        .migrate(&pool, args.rate_limit)
+                        .await?;
+                    Ok(Replica::from(lvol))
+                })?;
+
+                rx.await
+                    .map_err(|_| Status::cancelled("cancelled"))?
+                    .map_err(Status::from)
+                    .map(Response::new)
+            },
+        )
+        .await
+    }
+
     #[named]
     async fn create_nexus(
         &self,
         request: Request<CreateNexusRequest>,
     ) -> GrpcResult<Nexus> {
+        let key = idempotency_key(&request);
+        let ctx = GrpcClientContext::new(&request, function_name!());
         self.locked(
-            GrpcClientContext::new(&request, function_name!()),
-            async move {
+            ctx.clone(),
+            idempotent(key, ctx, async move {
                 let args = request.into_inner();
+                // dropping the sender, as happens when the client gives up on
+                // the request, cancels the creation of the nexus
+                let (_cancel, cancelled) = oneshot::channel::<()>();
                 let rx = rpc_submit::<_, _, nexus_bdev::Error>(async move {
                     let uuid = args.uuid.clone();
                     let name = uuid_to_name(&args.uuid)?;
-                    nexus_create(
+                    // an unspecified block size is that of the children, a
+                    // fixed default would not match all of them
+                    let block_len = match args.block_size {
+                        0 => None,
+                        block_size => Some(u64::from(block_size)),
+                    };
+                    if args.dry_run {
+                        nexus_create_dry_run(
+                            &name,
+                            args.size,
+                            block_len,
+                            &args.children,
+                        )
+                        .await?;
+                        info!("Nexus {} can be created", uuid);
+                        return Ok(Nexus {
+                            uuid,
+                            size: args.size,
+                            state: NexusState::NexusOnline as i32,
+                            children: args
+                                .children
+                                .into_iter()
+                                .map(|uri| Child {
+                                    uri,
+                                    state: ChildState::ChildOnline as i32,
+                                    rebuild_progress: -1,
+                                    ..Default::default()
+                                })
+                                .collect(),
+                            device_uri: String::new(),
+                            rebuilds: 0,
+                            core: Cores::current(),
+                        });
+                    }
+                    nexus_create_cancellable(
                         &name,
                         args.size,
+                        block_len,
                         Some(&args.uuid),
                         &args.children,
+                        cancelled,
                     )
                     .await?;
                     let nexus = nexus_lookup(&uuid)?;
@@ -546,7 +899,7 @@ impl mayastor_server::Mayastor for MayastorSvc {
                     .map_err(|_| Status::cancelled("cancelled"))?
                     .map_err(Status::from)
                     .map(Response::new)
-            },
+            }),
         )
         .await
     }
@@ -556,9 +909,11 @@ impl mayastor_server::Mayastor for MayastorSvc {
         &self,
         request: Request<CreateNexusV2Request>,
     ) -> GrpcResult<Nexus> {
+        let key = idempotency_key(&request);
+        let ctx = GrpcClientContext::new(&request, function_name!());
         self.locked(
-            GrpcClientContext::new(&request, function_name!()),
-            async move {
+            ctx.clone(),
+            idempotent(key, ctx, async move {
                 let args = request.into_inner();
                 let rx = rpc_submit::<_, _, nexus_bdev::Error>(async move {
                     nexus_create_v2(
@@ -581,7 +936,7 @@ impl mayastor_server::Mayastor for MayastorSvc {
                     .map_err(|_| Status::cancelled("cancelled"))?
                     .map_err(Status::from)
                     .map(Response::new)
-            },
+            }),
         )
         .await
     }
@@ -612,11 +967,22 @@ impl mayastor_server::Mayastor for MayastorSvc {
 
     async fn list_nexus(
         &self,
-        request: Request<Null>,
+        request: Request<ListNexusRequest>,
     ) -> GrpcResult<ListNexusReply> {
         let args = request.into_inner();
         trace!("{:?}", args);
 
+        if let Some(state) = args
+            .states
+            .iter()
+            .find(|s| NexusState::from_i32(**s).is_none())
+        {
+            return Err(Status::invalid_argument(format!(
+                "invalid nexus state {}",
+                state
+            )));
+        }
+
         let rx = rpc_submit::<_, _, nexus_bdev::Error>(async move {
             Ok(ListNexusReply {
                 nexus_list: instances()
@@ -624,6 +990,7 @@ impl mayastor_server::Mayastor for MayastorSvc {
                     .filter(|n| {
                         n.state.lock().deref() != &nexus_bdev::NexusState::Init
                     })
+                    .filter(|n| n.matches(&args))
                     .map(|n| n.to_grpc())
                     .collect::<Vec<_>>(),
             })
@@ -723,6 +1090,165 @@ impl mayastor_server::Mayastor for MayastorSvc {
             .map(Response::new)
     }
 
+    #[named]
+    async fn set_nexus_write_policy(
+        &self,
+        request: Request<SetNexusWritePolicyRequest>,
+    ) -> GrpcResult<Null> {
+        self.locked(
+            GrpcClientContext::new(&request, function_name!()),
+            async move {
+                let args = request.into_inner();
+                let policy = match NexusWritePolicy::from_i32(args.policy) {
+                    Some(NexusWritePolicy::WriteThroughAll) => {
+                        nexus_bdev::NexusWritePolicy::WriteThroughAll
+                    }
+                    Some(NexusWritePolicy::WriteBack) => {
+                        nexus_bdev::NexusWritePolicy::WriteBack {
+                            slow_children: args.slow_children,
+                        }
+                    }
+                    None => {
+                        return Err(Status::invalid_argument(format!(
+                            "invalid write policy {}",
+                            args.policy
+                        )))
+                    }
+                };
+
+                let rx = rpc_submit::<_, _, nexus_bdev::Error>(async move {
+                    nexus_lookup(&args.uuid)?.set_write_policy(policy).await?;
+                    Ok(Null {})
+                })?;
+
+                rx.await
+                    .map_err(|_| Status::cancelled("cancelled"))?
+                    .map_err(Status::from)
+                    .map(Response::new)
+            },
+        )
+        .await
+    }
+
+    #[named]
+    async fn set_nexus_read_policy(
+        &self,
+        request: Request<SetNexusReadPolicyRequest>,
+    ) -> GrpcResult<Null> {
+        self.locked(
+            GrpcClientContext::new(&request, function_name!()),
+            async move {
+                let args = request.into_inner();
+                let policy = match NexusReadPolicy::from_i32(args.policy) {
+                    Some(NexusReadPolicy::RoundRobin) => {
+                        nexus_bdev::NexusReadPolicy::RoundRobin
+                    }
+                    Some(NexusReadPolicy::PreferLocal) => {
+                        nexus_bdev::NexusReadPolicy::PreferLocal
+                    }
+                    Some(NexusReadPolicy::FailoverOnly) => {
+                        nexus_bdev::NexusReadPolicy::FailoverOnly
+                    }
+                    None => {
+                        return Err(Status::invalid_argument(format!(
+                            "invalid read policy {}",
+                            args.policy
+                        )))
+                    }
+                };
+
+                let rx = rpc_submit::<_, _, nexus_bdev::Error>(async move {
+                    nexus_lookup(&args.uuid)?.set_read_policy(policy).await;
+                    Ok(Null {})
+                })?;
+
+                rx.await
+                    .map_err(|_| Status::cancelled("cancelled"))?
+                    .map_err(Status::from)
+                    .map(Response::new)
+            },
+        )
+        .await
+    }
+
+    #[named]
+    async fn rewrite_nexus_labels(
+        &self,
+        request: Request<RewriteNexusLabelsRequest>,
+    ) -> GrpcResult<Null> {
+        self.locked(
+            GrpcClientContext::new(&request, function_name!()),
+            async move {
+                let rx = rpc_submit::<_, _, nexus_bdev::Error>(async move {
+                    let args = request.into_inner();
+                    trace!("{:?}", args);
+                    nexus_lookup(&args.uuid)?.rewrite_labels().await?;
+                    Ok(Null {})
+                })?;
+
+                rx.await
+                    .map_err(|_| Status::cancelled("cancelled"))?
+                    .map_err(Status::from)
+                    .map(Response::new)
+            },
+        )
+        .await
+    }
+
+    #[named]
+    async fn get_nexus_topology(
+        &self,
+        request: Request<GetNexusTopologyRequest>,
+    ) -> GrpcResult<NexusTopology> {
+        self.locked(
+            GrpcClientContext::new(&request, function_name!()),
+            async move {
+                let args = request.into_inner();
+                let rx = rpc_submit::<_, _, nexus_bdev::Error>(async move {
+                    trace!("{:?}", args);
+                    Ok(nexus_lookup(&args.uuid)?.to_grpc_topology())
+                })?;
+
+                rx.await
+                    .map_err(|_| Status::cancelled("cancelled"))?
+                    .map_err(Status::from)
+                    .map(Response::new)
+            },
+        )
+        .await
+    }
+
+    async fn subscribe_child_events(
+        &self,
+        _request: Request<Null>,
+    ) -> GrpcResult<Self::SubscribeChildEventsStream> {
+        // not serialized with the other methods as the stream is long lived
+        let events = nexus_event::subscribe();
+        let stream = futures::stream::unfold(events, |mut events| async move {
+            loop {
+                match events.recv().await {
+                    Ok(nexus_event::ChildEvent::Faulted(event)) => {
+                        return Some((
+                            Ok(ChildFaultedEvent::from(event)),
+                            events,
+                        ))
+                    }
+                    // the stream only carries faulted children
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!(
+                            "child event subscriber missed {} events",
+                            missed
+                        );
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        });
+
+        Ok(Response::new(Box::pin(stream)))
+    }
+
     async fn publish_nexus(
         &self,
         request: Request<PublishNexusRequest>,
@@ -753,8 +1279,9 @@ impl mayastor_server::Mayastor for MayastorSvc {
                 }
             };
 
-            let device_uri =
-                nexus_lookup(&args.uuid)?.share(share_protocol, key).await?;
+            let nexus = nexus_lookup(&args.uuid)?;
+            nexus.check_healthy_children(args.min_healthy_children)?;
+            let device_uri = nexus.share(share_protocol, key).await?;
 
             info!("Published nexus {} under {}", uuid, device_uri);
             Ok(PublishNexusReply {
@@ -839,6 +1366,86 @@ impl mayastor_server::Mayastor for MayastorSvc {
             .map(Response::new)
     }
 
+    async fn nexus_child_reservation(
+        &self,
+        request: Request<NexusChildReservationRequest>,
+    ) -> GrpcResult<Null> {
+        let rx = rpc_submit::<_, _, nexus_bdev::Error>(async move {
+            let args = request.into_inner();
+            trace!("{:?}", args);
+            let resv_type = match NvmeReservationType::from_i32(args.resv_type)
+            {
+                Some(NvmeReservationType::NvmeResvTypeInvalid) | None => {
+                    return Err(nexus_bdev::Error::InvalidArguments {
+                        name: args.uuid.clone(),
+                        args: format!(
+                            "invalid reservation type {}",
+                            args.resv_type
+                        ),
+                    });
+                }
+                Some(resv_type) => resv_type as u8,
+            };
+            let op = match NvmeReservationAction::from_i32(args.action) {
+                Some(NvmeReservationAction::NvmeResvRegister) => {
+                    ReservationOp::Register {
+                        key: args.key,
+                    }
+                }
+                Some(NvmeReservationAction::NvmeResvUnregister) => {
+                    ReservationOp::Unregister {
+                        key: args.key,
+                    }
+                }
+                Some(NvmeReservationAction::NvmeResvAcquire) => {
+                    ReservationOp::Acquire {
+                        key: args.key,
+                        resv_type,
+                    }
+                }
+                Some(NvmeReservationAction::NvmeResvRelease) => {
+                    ReservationOp::Release {
+                        key: args.key,
+                        resv_type,
+                    }
+                }
+                Some(NvmeReservationAction::NvmeResvPreempt) => {
+                    ReservationOp::Preempt {
+                        key: args.key,
+                        preempt_key: args.preempt_key,
+                        resv_type,
+                    }
+                }
+                None => {
+                    return Err(nexus_bdev::Error::InvalidArguments {
+                        name: args.uuid.clone(),
+                        args: format!(
+                            "invalid reservation action {}",
+                            args.action
+                        ),
+                    });
+                }
+            };
+            debug!(
+                "Reservation {:?} on child {} of nexus {}",
+                op, args.uri, args.uuid
+            );
+            nexus_lookup(&args.uuid)?
+                .child_reservation(&args.uri, op)
+                .await?;
+            info!(
+                "Reservation {:?} done on child {} of nexus {}",
+                op, args.uri, args.uuid
+            );
+            Ok(Null {})
+        })?;
+
+        rx.await
+            .map_err(|_| Status::cancelled("cancelled"))?
+            .map_err(Status::from)
+            .map(Response::new)
+    }
+
     #[named]
     async fn child_operation(
         &self,
@@ -851,17 +1458,25 @@ impl mayastor_server::Mayastor for MayastorSvc {
                     let args = request.into_inner();
                     trace!("{:?}", args);
 
-                    let onl = match args.action {
-                        1 => Ok(true),
-                        0 => Ok(false),
-                        _ => Err(nexus_bdev::Error::InvalidKey {}),
-                    }?;
+                    let action = ChildAction::from_i32(args.action)
+                        .ok_or(nexus_bdev::Error::InvalidKey {})?;
 
                     let nexus = nexus_lookup(&args.uuid)?;
-                    if onl {
-                        nexus.online_child(&args.uri).await?;
-                    } else {
-                        nexus.offline_child(&args.uri).await?;
+                    match action {
+                        ChildAction::Online => {
+                            nexus.online_child(&args.uri).await?;
+                        }
+                        ChildAction::Offline => {
+                            nexus.offline_child(&args.uri).await?;
+                        }
+                        ChildAction::SetIoTimeout => {
+                            let timeout_us = match args.io_timeout_us {
+                                0 => None,
+                                timeout_us => Some(timeout_us),
+                            };
+                            nexus
+                                .set_child_io_timeout(&args.uri, timeout_us)?;
+                        }
                     }
 
                     Ok(Null {})
@@ -888,7 +1503,10 @@ impl mayastor_server::Mayastor for MayastorSvc {
                 trace!("{:?}", args);
                 let rx = rpc_submit::<_, _, nexus_bdev::Error>(async move {
                     nexus_lookup(&args.uuid)?
-                        .start_rebuild(&args.uri)
+                        .start_rebuild_with_verify(
+                            &args.uri,
+                            args.verify_percent,
+                        )
                         .await
                         .map(|_| {})?;
                     Ok(Null {})
@@ -975,6 +1593,82 @@ impl mayastor_server::Mayastor for MayastorSvc {
         .await
     }
 
+    #[named]
+    async fn set_rebuild_throttle(
+        &self,
+        request: Request<SetRebuildThrottleRequest>,
+    ) -> GrpcResult<Null> {
+        self.locked(
+            GrpcClientContext::new(&request, function_name!()),
+            async move {
+                let msg = request.into_inner();
+                let rx = rpc_submit::<_, _, nexus_bdev::Error>(async move {
+                    nexus_lookup(&msg.uuid)?
+                        .set_rebuild_throttle(msg.bytes_per_sec);
+                    Ok(Null {})
+                })?;
+
+                rx.await
+                    .map_err(|_| Status::cancelled("cancelled"))?
+                    .map_err(Status::from)
+                    .map(Response::new)
+            },
+        )
+        .await
+    }
+
+    #[named]
+    async fn pause_rebuilds(
+        &self,
+        request: Request<PauseRebuildsRequest>,
+    ) -> GrpcResult<RebuildJobsReply> {
+        self.locked(
+            GrpcClientContext::new(&request, function_name!()),
+            async move {
+                let msg = request.into_inner();
+                let rx = rpc_submit::<_, _, nexus_bdev::Error>(async move {
+                    let jobs =
+                        nexus_lookup(&msg.uuid)?.pause_rebuilds().await?;
+                    Ok(RebuildJobsReply {
+                        jobs,
+                    })
+                })?;
+
+                rx.await
+                    .map_err(|_| Status::cancelled("cancelled"))?
+                    .map_err(Status::from)
+                    .map(Response::new)
+            },
+        )
+        .await
+    }
+
+    #[named]
+    async fn resume_rebuilds(
+        &self,
+        request: Request<ResumeRebuildsRequest>,
+    ) -> GrpcResult<RebuildJobsReply> {
+        self.locked(
+            GrpcClientContext::new(&request, function_name!()),
+            async move {
+                let msg = request.into_inner();
+                let rx = rpc_submit::<_, _, nexus_bdev::Error>(async move {
+                    let jobs =
+                        nexus_lookup(&msg.uuid)?.resume_rebuilds().await?;
+                    Ok(RebuildJobsReply {
+                        jobs,
+                    })
+                })?;
+
+                rx.await
+                    .map_err(|_| Status::cancelled("cancelled"))?
+                    .map_err(Status::from)
+                    .map(Response::new)
+            },
+        )
+        .await
+    }
+
     #[named]
     async fn get_rebuild_state(
         &self,
@@ -1043,6 +1737,29 @@ impl mayastor_server::Mayastor for MayastorSvc {
         .await
     }
 
+    #[named]
+    async fn get_rebuild_history(
+        &self,
+        request: Request<RebuildHistoryRequest>,
+    ) -> GrpcResult<RebuildHistoryReply> {
+        self.locked(
+            GrpcClientContext::new(&request, function_name!()),
+            async move {
+                let args = request.into_inner();
+                trace!("{:?}", args);
+                let rx = rpc_submit::<_, _, nexus_bdev::Error>(async move {
+                    Ok(nexus_lookup(&args.uuid)?.get_rebuild_history())
+                })?;
+
+                rx.await
+                    .map_err(|_| Status::cancelled("cancelled"))?
+                    .map_err(Status::from)
+                    .map(Response::new)
+            },
+        )
+        .await
+    }
+
     async fn create_snapshot(
         &self,
         request: Request<CreateSnapshotRequest>,
@@ -1063,6 +1780,38 @@ impl mayastor_server::Mayastor for MayastorSvc {
             .map(Response::new)
     }
 
+    #[named]
+    async fn verify_nexus(
+        &self,
+        request: Request<VerifyNexusRequest>,
+    ) -> GrpcResult<VerifyNexusReply> {
+        self.locked(
+            GrpcClientContext::new(&request, function_name!()),
+            async move {
+                let rx = rpc_submit::<_, _, nexus_bdev::Error>(async move {
+                    let args = request.into_inner();
+                    debug!("Verifying nexus {} ...", args.uuid);
+                    let reply = nexus_lookup(&args.uuid)?
+                        .verify(args.ranges, args.full_scan)
+                        .await?;
+                    info!(
+                        "Verified {} bytes of nexus {}, {} child(ren) differ",
+                        reply.bytes_compared,
+                        args.uuid,
+                        reply.mismatches.len()
+                    );
+                    Ok(reply)
+                })?;
+
+                rx.await
+                    .map_err(|_| Status::cancelled("cancelled"))?
+                    .map_err(Status::from)
+                    .map(Response::new)
+            },
+        )
+        .await
+    }
+
     async fn list_block_devices(
         &self,
         request: Request<ListBlockDevicesRequest>,
@@ -1087,6 +1836,28 @@ impl mayastor_server::Mayastor for MayastorSvc {
         Ok(Response::new(reply))
     }
 
+    async fn get_reactor_stats(
+        &self,
+        _request: Request<Null>,
+    ) -> GrpcResult<GetReactorStatsReply> {
+        let reactors = Reactors::iter()
+            .map(|r| {
+                let stats = r.stats();
+                ReactorStats {
+                    core: stats.core,
+                    events: stats.events,
+                    queue_time_us: stats.queue_time.as_micros() as u64,
+                    max_queue_time_us: stats.max_queue_time.as_micros() as u64,
+                    exec_time_us: stats.exec_time.as_micros() as u64,
+                    max_exec_time_us: stats.max_exec_time.as_micros() as u64,
+                }
+            })
+            .collect();
+        Ok(Response::new(GetReactorStatsReply {
+            reactors,
+        }))
+    }
+
     async fn list_nvme_controllers(
         &self,
         _request: Request<Null>,
@@ -1101,6 +1872,13 @@ impl mayastor_server::Mayastor for MayastorSvc {
         controller_stats().await
     }
 
+    async fn get_nvme_controller_health(
+        &self,
+        request: Request<GetNvmeControllerHealthRequest>,
+    ) -> GrpcResult<NvmeControllerHealth> {
+        controller_health(request.into_inner().name).await
+    }
+
     async fn get_mayastor_info(
         &self,
         _request: Request<Null>,
@@ -1114,6 +1892,13 @@ impl mayastor_server::Mayastor for MayastorSvc {
             )
             .to_string(),
             supported_features: Some(features),
+            prewarm: prewarm_status()
+                .into_iter()
+                .map(|s| rpc::mayastor::PrewarmStatus {
+                    uri: s.uri,
+                    connected: s.connected,
+                })
+                .collect(),
         };
 
         Ok(Response::new(reply))
@@ -4,6 +4,7 @@ use std::{
 };
 
 use futures::{channel::oneshot::Receiver, Future};
+pub use idempotency::IDEMPOTENCY_KEY;
 pub use server::MayastorGrpcServer;
 use tonic::{Response, Status};
 
@@ -31,17 +32,29 @@ impl From<NexusBdevError> for tonic::Status {
 
 impl From<CoreError> for tonic::Status {
     fn from(e: CoreError) -> Self {
-        Status::internal(e.to_string())
+        match e {
+            CoreError::BdevBusy {
+                ..
+            } => Status::failed_precondition(e.to_string()),
+            CoreError::NvmeControllerNotFound {
+                ..
+            } => Status::not_found(e.to_string()),
+            CoreError::NvmeLogPageUnsupported {
+                ..
+            } => Status::unimplemented(e.to_string()),
+            e => Status::internal(e.to_string()),
+        }
     }
 }
 mod bdev_grpc;
 mod controller_grpc;
+mod idempotency;
 mod json_grpc;
 mod mayastor_grpc;
 mod nexus_grpc;
 mod server;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct GrpcClientContext {
     pub args: String,
     pub id: String,
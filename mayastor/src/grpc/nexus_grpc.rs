@@ -1,14 +1,23 @@
 //! Helpers related to nexus grpc methods.
 
-use ::rpc::mayastor as rpc;
+use ::rpc::{mayastor as rpc, validate::parse_uuid};
 use std::convert::From;
-use uuid::Uuid;
 
 use crate::{
-    bdev::nexus::{
-        instances,
-        nexus_bdev::{Error, Nexus, NexusStatus},
-        nexus_child::{ChildState, NexusChild, Reason},
+    bdev::{
+        nexus::{
+            instances,
+            nexus_bdev::{Error, Nexus, NexusReadPolicy, NexusStatus},
+            nexus_child::{
+                ChildState,
+                ConfigReason,
+                NexusChild,
+                Reason,
+                RebuildRole,
+            },
+            nexus_event::ChildFaultedEvent,
+        },
+        NVME_CONTROLLERS,
     },
     rebuild::RebuildJob,
 };
@@ -19,7 +28,7 @@ impl From<ChildState> for rpc::ChildState {
     fn from(child: ChildState) -> Self {
         match child {
             ChildState::Init => rpc::ChildState::ChildDegraded,
-            ChildState::ConfigInvalid => rpc::ChildState::ChildFaulted,
+            ChildState::ConfigInvalid(_) => rpc::ChildState::ChildFaulted,
             ChildState::Open => rpc::ChildState::ChildOnline,
             ChildState::Destroying => rpc::ChildState::ChildDegraded,
             ChildState::Closed => rpc::ChildState::ChildDegraded,
@@ -30,6 +39,60 @@ impl From<ChildState> for rpc::ChildState {
         }
     }
 }
+impl From<Reason> for rpc::ChildFaultReason {
+    fn from(reason: Reason) -> Self {
+        match reason {
+            Reason::Unknown | Reason::OutOfSync => {
+                rpc::ChildFaultReason::FaultReasonUnknown
+            }
+            Reason::CantOpen => rpc::ChildFaultReason::FaultReasonCantOpen,
+            Reason::RebuildFailed => {
+                rpc::ChildFaultReason::FaultReasonRebuildFailed
+            }
+            Reason::IoError => rpc::ChildFaultReason::FaultReasonIoError,
+            Reason::Rpc => rpc::ChildFaultReason::FaultReasonRpc,
+        }
+    }
+}
+
+impl From<ConfigReason> for rpc::ChildConfigReason {
+    fn from(reason: ConfigReason) -> Self {
+        match reason {
+            ConfigReason::TooSmall => {
+                rpc::ChildConfigReason::ConfigReasonTooSmall
+            }
+            ConfigReason::BlockSizeMismatch => {
+                rpc::ChildConfigReason::ConfigReasonBlockSize
+            }
+            ConfigReason::ReadOnly => {
+                rpc::ChildConfigReason::ConfigReasonReadOnly
+            }
+        }
+    }
+}
+
+impl From<ChildFaultedEvent> for rpc::ChildFaultedEvent {
+    fn from(event: ChildFaultedEvent) -> Self {
+        Self {
+            uuid: name_to_uuid(&event.nexus).to_string(),
+            uri: event.child,
+            reason: rpc::ChildFaultReason::from(event.reason) as i32,
+            io_errors: event.io_errors,
+        }
+    }
+}
+
+impl From<RebuildRole> for rpc::ChildRebuildRole {
+    fn from(role: RebuildRole) -> Self {
+        match role {
+            RebuildRole::None => rpc::ChildRebuildRole::RebuildRoleNone,
+            RebuildRole::Source => rpc::ChildRebuildRole::RebuildRoleSource,
+            RebuildRole::Destination => {
+                rpc::ChildRebuildRole::RebuildRoleDestination
+            }
+        }
+    }
+}
 impl From<NexusStatus> for rpc::NexusState {
     fn from(nexus: NexusStatus) -> Self {
         match nexus {
@@ -40,6 +103,16 @@ impl From<NexusStatus> for rpc::NexusState {
     }
 }
 
+impl From<NexusReadPolicy> for rpc::NexusReadPolicy {
+    fn from(policy: NexusReadPolicy) -> Self {
+        match policy {
+            NexusReadPolicy::RoundRobin => rpc::NexusReadPolicy::RoundRobin,
+            NexusReadPolicy::PreferLocal => rpc::NexusReadPolicy::PreferLocal,
+            NexusReadPolicy::FailoverOnly => rpc::NexusReadPolicy::FailoverOnly,
+        }
+    }
+}
+
 impl NexusChild {
     /// Convert nexus child object to grpc representation.
     ///
@@ -50,6 +123,36 @@ impl NexusChild {
             uri: self.get_name().to_string(),
             state: rpc::ChildState::from(self.state()) as i32,
             rebuild_progress: self.get_rebuild_progress(),
+            rebuild_role: rpc::ChildRebuildRole::from(self.rebuild_role())
+                as i32,
+            config_reason: match self.state() {
+                ChildState::ConfigInvalid(reason) => {
+                    rpc::ChildConfigReason::from(reason) as i32
+                }
+                _ if self.is_read_only() => {
+                    rpc::ChildConfigReason::ConfigReasonReadOnly as i32
+                }
+                _ => rpc::ChildConfigReason::ConfigReasonNone as i32,
+            },
+        }
+    }
+
+    /// Convert nexus child object to grpc representation of the child along
+    /// with its block device and, for NVMe-oF children, its controller.
+    pub fn to_grpc_topology(&self) -> rpc::ChildTopology {
+        let device = self.get_device().ok();
+        let controller = device.and_then(|d| {
+            NVME_CONTROLLERS
+                .lookup_by_name(d.device_name())
+                .map(|c| c.lock().to_grpc())
+        });
+
+        rpc::ChildTopology {
+            child: Some(self.to_grpc()),
+            device_name: device.map(|d| d.device_name()).unwrap_or_default(),
+            driver: device.map(|d| d.driver_name()).unwrap_or_default(),
+            product: device.map(|d| d.product_name()).unwrap_or_default(),
+            controller,
         }
     }
 }
@@ -71,6 +174,8 @@ impl Nexus {
                 .map(|ch| ch.to_grpc())
                 .collect::<Vec<_>>(),
             rebuilds: RebuildJob::count() as u32,
+            core: self.core(),
+            read_policy: rpc::NexusReadPolicy::from(self.read_policy()) as i32,
         }
     }
 
@@ -87,6 +192,32 @@ impl Nexus {
                 .map(|ch| ch.to_grpc())
                 .collect::<Vec<_>>(),
             rebuilds: RebuildJob::count() as u32,
+            core: self.core(),
+            read_policy: rpc::NexusReadPolicy::from(self.read_policy()) as i32,
+        }
+    }
+
+    /// Check whether the nexus passes the filters of a list request.
+    pub fn matches(&self, filter: &rpc::ListNexusRequest) -> bool {
+        let state = rpc::NexusState::from(self.status()) as i32;
+        (filter.states.is_empty() || filter.states.contains(&state))
+            && (!filter.has_faulted_child
+                || self.children.iter().any(|ch| {
+                    rpc::ChildState::from(ch.state())
+                        == rpc::ChildState::ChildFaulted
+                }))
+    }
+
+    /// Convert nexus object to grpc representation of the devices the nexus
+    /// is built from.
+    pub fn to_grpc_topology(&self) -> rpc::NexusTopology {
+        rpc::NexusTopology {
+            nexus: Some(self.to_grpc()),
+            children: self
+                .children
+                .iter()
+                .map(|ch| ch.to_grpc_topology())
+                .collect::<Vec<_>>(),
         }
     }
 }
@@ -107,7 +238,7 @@ fn name_to_uuid(name: &str) -> &str {
 /// Convert the UUID to a nexus name in the form of "nexus-{uuid}".
 /// Return error if the UUID is not valid.
 pub fn uuid_to_name(uuid: &str) -> Result<String, Error> {
-    match Uuid::parse_str(uuid) {
+    match parse_uuid(uuid) {
         Ok(uuid) => Ok(format!("nexus-{}", uuid.to_hyphenated().to_string())),
         Err(_) => Err(Error::InvalidUuid {
             uuid: uuid.to_owned(),
@@ -0,0 +1,445 @@
+//! Optional per region checksum manifest of a replica. Verifying the replica
+//! against its manifest detects silent corruption of data which still reads
+//! back without an error. Maintaining the manifest requires reading back the
+//! replica, so it is opt-in: it is only kept for replicas on which it was
+//! explicitly created.
+//!
+//! Once checksums are enabled, the requests submitted to the replica bdev are
+//! intercepted by swapping its function table for a copy whose submit handler
+//! marks the regions covered by writes as dirty before passing them on. Only
+//! the dirty regions are read back when the manifest is updated, and verifying
+//! the replica refreshes them rather than reporting them as corrupted. Dirty
+//! regions are not persisted, so a manifest has to be updated again after a
+//! restart before the replica can be verified.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    convert::TryInto,
+    sync::Mutex,
+};
+
+use crc::crc32::{self, Hasher32};
+use nix::errno::Errno;
+use once_cell::sync::{Lazy, OnceCell};
+use snafu::ResultExt;
+
+use spdk_sys::{spdk_bdev, spdk_bdev_fn_table, spdk_bdev_io, spdk_io_channel};
+
+use crate::{
+    core::{BdevHandle, Bio, CoreError, IoType},
+    lvs::{error::ChecksumIo, Error, Lvol, PropName},
+};
+
+/// smallest size of a region covered by one checksum
+pub const CHECKSUM_MIN_REGION_SIZE: u64 = 1 << 20;
+/// the manifest is stored as a single property of the lvol which has to fit
+/// within a metadata page, larger replicas use larger regions instead
+const CHECKSUM_MAX_REGIONS: u64 = 512;
+/// size of the reads issued when computing the checksums
+const CHECKSUM_IO_SIZE: u64 = 1 << 20;
+
+/// regions written to on each tracked replica, keyed by its bdev
+static CHECKSUM_DIRTY: Lazy<Mutex<HashMap<usize, DirtyRegions>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// function table installed on tracked replicas
+static CHECKSUM_FN_TBL: OnceCell<TrackingFnTable> = OnceCell::new();
+
+/// regions of a replica written to since their checksums were computed
+struct DirtyRegions {
+    region_size: u64,
+    dirty: BTreeSet<u64>,
+}
+
+/// Copy of the function table shared by all lvol bdevs, with the submit
+/// handler replaced by one which tracks the written regions.
+struct TrackingFnTable {
+    original: *const spdk_bdev_fn_table,
+    f_tbl: spdk_bdev_fn_table,
+}
+
+unsafe impl Sync for TrackingFnTable {}
+
+unsafe impl Send for TrackingFnTable {}
+
+impl TrackingFnTable {
+    fn new(original: *const spdk_bdev_fn_table) -> Self {
+        let mut f_tbl = unsafe { *original };
+        f_tbl.submit_request = Some(Self::io_submit);
+        Self {
+            original,
+            f_tbl,
+        }
+    }
+
+    /// mark the regions written by the IO dirty before submitting it to the
+    /// lvol, so that a region is never considered clean while being written
+    extern "C" fn io_submit(
+        channel: *mut spdk_io_channel,
+        io: *mut spdk_bdev_io,
+    ) {
+        let bio = Bio::from(io);
+        match bio.io_type() {
+            IoType::Write
+            | IoType::WriteZeros
+            | IoType::Unmap
+            | IoType::CompareAndWrite => {
+                let start = bio.offset() * bio.block_len();
+                let end = start + bio.num_blocks() * bio.block_len();
+                let key = bio.bdev().as_ptr() as usize;
+                if let Some(regions) =
+                    CHECKSUM_DIRTY.lock().unwrap().get_mut(&key)
+                {
+                    regions.mark(start, end);
+                }
+            }
+            _ => {}
+        }
+
+        let table = CHECKSUM_FN_TBL.get().expect("checksum table is gone");
+        unsafe {
+            let submit = (*table.original).submit_request.unwrap();
+            submit(channel, io)
+        }
+    }
+}
+
+impl DirtyRegions {
+    /// mark the regions overlapping the given byte range dirty
+    fn mark(&mut self, start: u64, end: u64) {
+        if end > start {
+            let first = start / self.region_size;
+            let last = (end - 1) / self.region_size;
+            self.dirty.extend(first ..= last);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// checksums of consecutive regions of a replica
+pub struct ChecksumManifest {
+    /// size of each region in bytes, the last one may be shorter
+    pub region_size: u64,
+    /// crc32 of each region
+    pub checksums: Vec<u32>,
+}
+
+impl ChecksumManifest {
+    /// size of the regions used for a replica of the given size
+    fn region_size_for(size: u64) -> u64 {
+        let region_size =
+            (size + CHECKSUM_MAX_REGIONS - 1) / CHECKSUM_MAX_REGIONS;
+        let units = (region_size + CHECKSUM_MIN_REGION_SIZE - 1)
+            / CHECKSUM_MIN_REGION_SIZE;
+        units.max(1) * CHECKSUM_MIN_REGION_SIZE
+    }
+
+    /// encode the manifest as stored on disk
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(8 + 4 * self.checksums.len());
+        bytes.extend_from_slice(&self.region_size.to_le_bytes());
+        self.checksums
+            .iter()
+            .for_each(|c| bytes.extend_from_slice(&c.to_le_bytes()));
+        bytes
+    }
+
+    /// decode the manifest as stored on disk
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < 8 || (bytes.len() - 8) % 4 != 0 {
+            return None;
+        }
+
+        let region_size = u64::from_le_bytes(bytes[.. 8].try_into().ok()?);
+        if region_size == 0 {
+            return None;
+        }
+
+        Some(Self {
+            region_size,
+            checksums: bytes[8 ..]
+                .chunks_exact(4)
+                .map(|c| u32::from_le_bytes(c.try_into().unwrap()))
+                .collect(),
+        })
+    }
+
+    /// indexes of the regions whose checksums differ from the other manifest,
+    /// regions which are present in only one of them are included
+    pub fn mismatches(&self, other: &ChecksumManifest) -> Vec<u64> {
+        let regions = self.checksums.len().max(other.checksums.len());
+        (0 .. regions)
+            .filter(|i| self.checksums.get(*i) != other.checksums.get(*i))
+            .map(|i| i as u64)
+            .collect()
+    }
+}
+
+impl Lvol {
+    /// Compute the checksum manifest of the replica and store it on disk,
+    /// which enables checksums on the replica if they were not already. Only
+    /// the regions written to since the last update are read back if the
+    /// replica has been tracked since.
+    pub async fn update_checksums(&self) -> Result<ChecksumManifest, Error> {
+        let region_size = ChecksumManifest::region_size_for(self.size());
+
+        let manifest = match self.checksums()? {
+            Some(mut manifest)
+                if manifest.region_size == region_size && self.is_tracked() =>
+            {
+                let dirty = self.take_dirty();
+                self.refresh_regions(&mut manifest, &dirty).await?;
+                manifest
+            }
+            _ => {
+                // writes racing with the computation are tracked from here
+                self.start_tracking(region_size);
+                self.compute_checksums(region_size).await?
+            }
+        };
+
+        self.set_raw(PropName::Checksums, &manifest.to_bytes())
+            .await?;
+
+        info!(
+            "{}: stored checksums of {} regions",
+            self,
+            manifest.checksums.len()
+        );
+        Ok(manifest)
+    }
+
+    /// the stored checksum manifest, if checksums are enabled
+    pub fn checksums(&self) -> Result<Option<ChecksumManifest>, Error> {
+        match self.get_raw(PropName::Checksums) {
+            Ok(bytes) => ChecksumManifest::from_bytes(&bytes)
+                .map(Some)
+                .ok_or_else(|| Error::ChecksumManifest {
+                    name: self.name(),
+                }),
+            Err(Error::GetProperty {
+                source: Errno::ENOENT,
+                ..
+            }) => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// remove the stored checksum manifest
+    pub async fn disable_checksums(&self) -> Result<(), Error> {
+        self.stop_tracking();
+        if self.checksums()?.is_none() {
+            return Ok(());
+        }
+        self.remove_raw(PropName::Checksums).await
+    }
+
+    /// Read the replica and compare it against the stored checksum manifest.
+    /// Returns the indexes of the regions which do not match. The checksums
+    /// of regions written to since the manifest was updated are refreshed
+    /// instead of being compared, and regions written to while the replica
+    /// is verified are left to the next verification. Fails if the replica
+    /// has not been tracked since the manifest was updated.
+    pub async fn verify_checksums(
+        &self,
+    ) -> Result<(ChecksumManifest, Vec<u64>), Error> {
+        let mut stored =
+            self.checksums()?.ok_or_else(|| Error::ChecksumsDisabled {
+                name: self.name(),
+            })?;
+
+        if !self.is_tracked() {
+            return Err(Error::ChecksumsStale {
+                name: self.name(),
+            });
+        }
+
+        let written = self.take_dirty();
+        let current = self.compute_checksums(stored.region_size).await?;
+        let racing = self.take_dirty();
+        self.mark_dirty(&racing);
+
+        let mismatches = stored
+            .mismatches(&current)
+            .into_iter()
+            .filter(|r| !written.contains(r) && !racing.contains(r))
+            .collect::<Vec<_>>();
+
+        if !written.is_empty() {
+            written.difference(&racing).for_each(|r| {
+                let r = *r as usize;
+                if let (Some(s), Some(c)) =
+                    (stored.checksums.get_mut(r), current.checksums.get(r))
+                {
+                    *s = *c;
+                }
+            });
+            self.set_raw(PropName::Checksums, &stored.to_bytes())
+                .await?;
+        }
+
+        if !mismatches.is_empty() {
+            warn!(
+                "{}: {} regions do not match their checksum",
+                self,
+                mismatches.len()
+            );
+        }
+
+        Ok((stored, mismatches))
+    }
+
+    /// stop tracking the writes to the replica, restoring its function table
+    pub(super) fn stop_tracking(&self) {
+        let bdev = self.as_bdev().as_ptr();
+        if CHECKSUM_DIRTY
+            .lock()
+            .unwrap()
+            .remove(&(bdev as usize))
+            .is_some()
+            && Self::has_tracking_table(bdev)
+        {
+            let table = CHECKSUM_FN_TBL.get().unwrap();
+            unsafe { (*bdev).fn_table = table.original };
+        }
+    }
+
+    /// start tracking the writes to the replica with no region dirty
+    fn start_tracking(&self, region_size: u64) {
+        let bdev = self.as_bdev().as_ptr();
+        CHECKSUM_DIRTY.lock().unwrap().insert(
+            bdev as usize,
+            DirtyRegions {
+                region_size,
+                dirty: BTreeSet::new(),
+            },
+        );
+
+        if !Self::has_tracking_table(bdev) {
+            let table = CHECKSUM_FN_TBL.get_or_init(|| {
+                TrackingFnTable::new(unsafe { (*bdev).fn_table })
+            });
+            assert_eq!(unsafe { (*bdev).fn_table }, table.original);
+            unsafe { (*bdev).fn_table = &table.f_tbl };
+        }
+    }
+
+    /// Whether writes to the replica are tracked. The table is checked as
+    /// well, as the bdev of a replica which was not destroyed through us,
+    /// like when its pool is exported, may be reused by another one.
+    fn is_tracked(&self) -> bool {
+        let bdev = self.as_bdev().as_ptr();
+        CHECKSUM_DIRTY
+            .lock()
+            .unwrap()
+            .contains_key(&(bdev as usize))
+            && Self::has_tracking_table(bdev)
+    }
+
+    /// whether the tracking function table is installed on the bdev
+    fn has_tracking_table(bdev: *mut spdk_bdev) -> bool {
+        CHECKSUM_FN_TBL.get().map_or(false, |table| unsafe {
+            (*bdev).fn_table == &table.f_tbl as *const _
+        })
+    }
+
+    /// take the regions written to since the last call
+    fn take_dirty(&self) -> BTreeSet<u64> {
+        let key = self.as_bdev().as_ptr() as usize;
+        CHECKSUM_DIRTY
+            .lock()
+            .unwrap()
+            .get_mut(&key)
+            .map(|regions| std::mem::take(&mut regions.dirty))
+            .unwrap_or_default()
+    }
+
+    /// mark the given regions dirty again
+    fn mark_dirty(&self, regions: &BTreeSet<u64>) {
+        let key = self.as_bdev().as_ptr() as usize;
+        if let Some(tracked) = CHECKSUM_DIRTY.lock().unwrap().get_mut(&key) {
+            tracked.dirty.extend(regions);
+        }
+    }
+
+    /// recompute the checksums of the given regions of the manifest
+    async fn refresh_regions(
+        &self,
+        manifest: &mut ChecksumManifest,
+        regions: &BTreeSet<u64>,
+    ) -> Result<(), Error> {
+        if regions.is_empty() {
+            return Ok(());
+        }
+
+        let handle = self.checksum_handle()?;
+        for region in regions {
+            if let Some(checksum) = manifest.checksums.get_mut(*region as usize)
+            {
+                *checksum = self
+                    .compute_region(&handle, *region, manifest.region_size)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// read the whole replica computing the checksum of each region
+    async fn compute_checksums(
+        &self,
+        region_size: u64,
+    ) -> Result<ChecksumManifest, Error> {
+        let handle = self.checksum_handle()?;
+        let regions = (self.size() + region_size - 1) / region_size;
+        let mut checksums = Vec::with_capacity(regions as usize);
+
+        for region in 0 .. regions {
+            checksums
+                .push(self.compute_region(&handle, region, region_size).await?);
+        }
+
+        Ok(ChecksumManifest {
+            region_size,
+            checksums,
+        })
+    }
+
+    /// open a read only handle to the replica
+    fn checksum_handle(&self) -> Result<BdevHandle, Error> {
+        BdevHandle::open(&self.name(), false, false).context(ChecksumIo {
+            name: self.name(),
+        })
+    }
+
+    /// read one region of the replica and compute its checksum
+    async fn compute_region(
+        &self,
+        handle: &BdevHandle,
+        region: u64,
+        region_size: u64,
+    ) -> Result<u32, Error> {
+        let region_end = ((region + 1) * region_size).min(self.size());
+        let mut digest = crc32::Digest::new(crc32::IEEE);
+        let mut offset = region * region_size;
+
+        while offset < region_end {
+            let len = CHECKSUM_IO_SIZE.min(region_end - offset);
+            let mut buf =
+                handle.dma_malloc(len).map_err(|_| Error::ChecksumIo {
+                    source: CoreError::DmaAllocationError {
+                        size: len,
+                    },
+                    name: self.name(),
+                })?;
+
+            handle.read_at(offset, &mut buf).await.context(ChecksumIo {
+                name: self.name(),
+            })?;
+
+            digest.write(buf.as_slice());
+            offset += len;
+        }
+
+        Ok(digest.sum32())
+    }
+}
@@ -1,7 +1,13 @@
 use nix::errno::Errno;
 use snafu::Snafu;
 
-use crate::{core::CoreError, lvs::PropName, nexus_uri::NexusBdevError};
+use crate::{
+    core::CoreError,
+    lvs::PropName,
+    nexus_uri::NexusBdevError,
+    rebuild::RebuildError,
+    subsys::NvmfError,
+};
 
 #[derive(Debug, Snafu)]
 #[snafu(visibility = "pub(crate)")]
@@ -35,6 +41,20 @@ pub enum Error {
     #[snafu(display("errno: {} failed to create lvol {}", source, name))]
     RepCreate { source: Errno, name: String },
 
+    #[snafu(display(
+        "pool {} has {} bytes available, replica {} requires {}",
+        pool,
+        available,
+        name,
+        size
+    ))]
+    RepNoSpace {
+        name: String,
+        pool: String,
+        size: u64,
+        available: u64,
+    },
+
     #[snafu(display("failed to destroy lvol {}", name))]
     RepDestroy { source: Errno, name: String },
 
@@ -66,8 +86,56 @@ pub enum Error {
     },
     #[snafu(display("failed to sync properties {}", name))]
     SyncProperty { source: Errno, name: String },
+    #[snafu(display("failed to make {} read-only", name))]
+    SetReadOnly { source: Errno, name: String },
     #[snafu(display("invalid property value: {}", name))]
     Property { source: Errno, name: String },
     #[snafu(display("invalid replica share protocol value: {}", value))]
     ReplicaShareProtocol { value: i32 },
+    #[snafu(display("invalid pool metadata: {}", source))]
+    MetadataParse { source: serde_json::Error },
+    #[snafu(display("unsupported pool metadata version {}", version))]
+    MetadataVersion { version: u32 },
+    #[snafu(display("pool {} not found", name))]
+    PoolNotFound { name: String },
+    #[snafu(display("self test of pool {} failed: {}", name, source))]
+    SelfTest { source: CoreError, name: String },
+    #[snafu(display("self test of pool {} read back corrupted data", name))]
+    SelfTestVerify { name: String },
+    #[snafu(display("checksums are not enabled on replica {}", name))]
+    ChecksumsDisabled { name: String },
+    #[snafu(display(
+        "replica {} was not tracked since its checksums were updated",
+        name
+    ))]
+    ChecksumsStale { name: String },
+    #[snafu(display("invalid checksum manifest on replica {}", name))]
+    ChecksumManifest { name: String },
+    #[snafu(display("failed to checksum replica {}: {}", name, source))]
+    ChecksumIo { source: CoreError, name: String },
+    #[snafu(display("replica {} cannot be migrated: {}", name, msg))]
+    MigrateInvalid { name: String, msg: String },
+    #[snafu(display("failed to copy replica {}: {}", name, source))]
+    MigrateCopy { source: RebuildError, name: String },
+    #[snafu(display(
+        "migration of replica {} was interrupted at block {}",
+        name,
+        blk
+    ))]
+    MigrateInterrupted { name: String, blk: u64 },
+    #[snafu(display(
+        "replica {} is written to faster than it can be caught up with",
+        name
+    ))]
+    MigrateBusy { name: String },
+    #[snafu(display("failed to catch up replica {}: {}", name, source))]
+    MigrateIo { source: CoreError, name: String },
+    #[snafu(display("failed to switch the share of {}: {}", name, source))]
+    MigrateShare { source: NvmfError, name: String },
+    #[snafu(display("errno: {} failed to snapshot lvol {}", source, name))]
+    Snapshot { source: Errno, name: String },
+    #[snafu(display("errno: {} failed to clone lvol {}", source, name))]
+    Clone { source: Errno, name: String },
+    #[snafu(display("errno: {} failed to inflate lvol {}", source, name))]
+    Inflate { source: Errno, name: String },
 }
@@ -13,9 +13,13 @@ use pin_utils::core_reexport::fmt::Formatter;
 use tracing::instrument;
 
 use spdk_sys::{
+    lvol_allocated_clusters,
+    lvol_md_thread,
     spdk_blob_get_xattr_value,
     spdk_blob_is_read_only,
     spdk_blob_is_snapshot,
+    spdk_blob_remove_xattr,
+    spdk_blob_set_read_only,
     spdk_blob_set_xattr,
     spdk_blob_sync_md,
     spdk_lvol,
@@ -26,7 +30,7 @@ use spdk_sys::{
 
 use crate::{
     bdev::nexus::nexus_bdev::Nexus,
-    core::{Bdev, CoreError, Mthread, Protocol, Share},
+    core::{Bdev, CoreError, Cores, Mthread, Protocol, Share},
     ffihelper::{
         cb_arg,
         errno_result_from_i32,
@@ -51,6 +55,11 @@ pub enum PropValue {
 #[non_exhaustive]
 pub enum PropName {
     Shared,
+    /// per region checksum manifest of the lvol data, see [`Lvol::set_raw`]
+    Checksums,
+    /// first block not yet copied by an interrupted migration, stored on the
+    /// lvol the replica is migrated to
+    MigrateCheckpoint,
 }
 
 impl From<PropValue> for PropName {
@@ -65,6 +74,8 @@ impl Display for PropName {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let name = match self {
             PropName::Shared => "shared",
+            PropName::Checksums => "checksums",
+            PropName::MigrateCheckpoint => "migrate_checkpoint",
         };
         write!(f, "{}", name)
     }
@@ -223,11 +234,34 @@ impl Lvol {
         }
     }
 
+    /// returns the reactor core running the metadata thread of the
+    /// blobstore of the lvol, which services its creation, resizing and
+    /// destruction, or the current core if that thread is unknown
+    pub fn core(&self) -> u32 {
+        Mthread::from_null_checked(unsafe { lvol_md_thread(self.0.as_ptr()) })
+            .and_then(|thread| thread.core())
+            .unwrap_or_else(Cores::current)
+    }
+
     /// returns a boolean indicating if the lvol is thin provisioned
     pub fn is_thin(&self) -> bool {
         unsafe { self.0.as_ref().thin_provision }
     }
 
+    /// returns the number of bytes allocated to the lvol in its pool, which
+    /// is less than its size for a thin provisioned lvol
+    pub fn allocated(&self) -> u64 {
+        if !self.is_thin() {
+            return self.size();
+        }
+        let cluster_size = unsafe {
+            Lvs(NonNull::new_unchecked(self.0.as_ref().lvol_store))
+                .cluster_size()
+        };
+        let clusters = unsafe { lvol_allocated_clusters(self.0.as_ptr()) };
+        std::cmp::min(clusters * u64::from(cluster_size), self.size())
+    }
+
     /// returns a boolean indicating if the lvol is read-only
     pub fn is_read_only(&self) -> bool {
         unsafe { spdk_blob_is_read_only(self.0.as_ref().blob) }
@@ -238,9 +272,26 @@ impl Lvol {
         unsafe { spdk_blob_is_snapshot(self.0.as_ref().blob) }
     }
 
+    /// number of writes and unmaps completed on the lvol since it was loaded
+    pub(crate) async fn writes_completed(&self) -> Result<u64, CoreError> {
+        self.as_bdev()
+            .stats()
+            .await
+            .map(|s| s.num_write_ops + s.num_unmap_ops)
+    }
+
     /// destroy the lvol
     #[instrument(level = "debug", err)]
     pub async fn destroy(self) -> Result<String, Error> {
+        // we must always unshare before destroying bdev
+        let _ = self.unshare().await;
+
+        self.destroy_unshared().await
+    }
+
+    /// destroy the lvol which must not be shared, or whose namespace was
+    /// removed from its subsystem already
+    pub(super) async fn destroy_unshared(self) -> Result<String, Error> {
         extern "C" fn destroy_cb(sender: *mut c_void, errno: i32) {
             let sender =
                 unsafe { Box::from_raw(sender as *mut oneshot::Sender<i32>) };
@@ -248,9 +299,7 @@ impl Lvol {
         }
 
         let name = self.name();
-
-        // we must always unshare before destroying bdev
-        let _ = self.unshare().await;
+        self.stop_tracking();
 
         let (s, r) = pair::<i32>();
         unsafe {
@@ -309,6 +358,28 @@ impl Lvol {
             }
         };
 
+        self.sync_metadata().await
+    }
+
+    /// make the lvol read-only, for good
+    #[instrument(level = "debug", err)]
+    pub async fn set_read_only(&self) -> Result<(), Error> {
+        let blob = unsafe { self.0.as_ref().blob };
+        assert!(!blob.is_null());
+
+        unsafe { spdk_blob_set_read_only(blob) }.to_result(|e| {
+            Error::SetReadOnly {
+                source: Errno::from_i32(e),
+                name: self.name(),
+            }
+        })?;
+
+        self.sync_metadata().await
+    }
+
+    /// write the blob metadata, including its properties, to disk
+    async fn sync_metadata(&self) -> Result<(), Error> {
+        let blob = unsafe { self.0.as_ref().blob };
         let (s, r) = pair::<i32>();
         unsafe {
             spdk_blob_sync_md(blob, Some(Self::blob_sync_cb), cb_arg(s));
@@ -319,9 +390,82 @@ impl Lvol {
                 source: Errno::from_i32(e),
                 name: self.name(),
             }
+        })
+    }
+
+    /// write an opaque binary property on to the lvol which is stored on disk,
+    /// the value must fit within a single metadata page
+    #[instrument(level = "debug", skip(value), err)]
+    pub async fn set_raw(
+        &self,
+        prop: PropName,
+        value: &[u8],
+    ) -> Result<(), Error> {
+        let blob = unsafe { self.0.as_ref().blob };
+        assert!(!blob.is_null());
+
+        let name = prop.to_string().into_cstring();
+        unsafe {
+            spdk_blob_set_xattr(
+                blob,
+                name.as_ptr(),
+                value.as_ptr() as *const _,
+                value.len() as u16,
+            )
+        }
+        .to_result(|e| Error::SetProperty {
+            source: Errno::from_i32(e),
+            prop,
+            name: self.name(),
         })?;
 
-        Ok(())
+        self.sync_metadata().await
+    }
+
+    /// remove a property from the lvol on disk
+    #[instrument(level = "debug", err)]
+    pub async fn remove_raw(&self, prop: PropName) -> Result<(), Error> {
+        let blob = unsafe { self.0.as_ref().blob };
+        assert!(!blob.is_null());
+
+        let name = prop.to_string().into_cstring();
+        unsafe { spdk_blob_remove_xattr(blob, name.as_ptr()) }.to_result(
+            |e| Error::SetProperty {
+                source: Errno::from_i32(e),
+                prop,
+                name: self.name(),
+            },
+        )?;
+
+        self.sync_metadata().await
+    }
+
+    /// read an opaque binary property from this lvol
+    pub fn get_raw(&self, prop: PropName) -> Result<Vec<u8>, Error> {
+        let blob = unsafe { self.0.as_ref().blob };
+        assert!(!blob.is_null());
+
+        let name = prop.to_string().into_cstring();
+        let mut value: *const u8 = std::ptr::null();
+        let mut value_len: u64 = 0;
+        unsafe {
+            spdk_blob_get_xattr_value(
+                blob,
+                name.as_ptr(),
+                &mut value as *mut *const u8 as *mut *const c_void,
+                &mut value_len,
+            )
+        }
+        .to_result(|e| Error::GetProperty {
+            source: Errno::from_i32(e),
+            prop,
+            name: self.name(),
+        })?;
+
+        Ok(
+            unsafe { std::slice::from_raw_parts(value, value_len as usize) }
+                .to_vec(),
+        )
     }
 
     /// get/read a property from this lvol from disk
@@ -358,6 +502,12 @@ impl Lvol {
                     }),
                 }
             }
+            PropName::Checksums | PropName::MigrateCheckpoint => {
+                Err(Error::Property {
+                    source: Errno::EINVAL,
+                    name: self.name(),
+                })
+            }
         }
     }
 
@@ -425,10 +575,13 @@ impl Lvol {
                 error!("vbdev_lvol_create_snapshot errno {}", errno);
             }
             // Must complete IO on thread IO was submitted from
-            Mthread::from(unsafe {
+            if let Err(e) = Mthread::from(unsafe {
                 spdk_sys::spdk_bdev_io_get_thread(bio_ptr.cast())
             })
-            .with(|| Nexus::io_completion_local(errno == 0, bio_ptr));
+            .with(|| Nexus::io_completion_local(errno == 0, bio_ptr))
+            {
+                error!("snapshot IO completion failed: {}", e);
+            }
         }
 
         let c_snapshot_name = snapshot_name.into_cstring();
@@ -1,8 +1,15 @@
-use std::{convert::TryFrom, fmt::Debug, os::raw::c_void, ptr::NonNull};
+use std::{
+    convert::TryFrom,
+    fmt::Debug,
+    os::raw::c_void,
+    ptr::NonNull,
+    time::{Duration, Instant},
+};
 
 use futures::channel::oneshot;
 use nix::errno::Errno;
 use pin_utils::core_reexport::fmt::Formatter;
+use snafu::ResultExt;
 use tracing::instrument;
 
 use rpc::mayastor::CreatePoolRequest;
@@ -30,12 +37,24 @@ use url::Url;
 
 use crate::{
     bdev::Uri,
-    core::{Bdev, IoType, Share, Uuid},
+    core::{Bdev, BdevHandle, CoreError, IoType, Share, Uuid},
     ffihelper::{cb_arg, pair, AsStr, ErrnoResult, FfiResult, IntoCString},
-    lvs::{Error, Lvol, PropName, PropValue},
+    lvs::{error::SelfTest, Error, Lvol, PropName, PropValue},
     nexus_uri::{bdev_destroy, NexusBdevError},
 };
 
+/// bounds of the cluster size of a pool, clusters are the unit in which
+/// space is allocated to replicas
+pub const MIN_CLUSTER_SIZE: u32 = 64 * 1024;
+pub const MAX_CLUSTER_SIZE: u32 = 1024 * 1024 * 1024;
+
+/// size of the scratch lvol created by the pool self test
+const SELF_TEST_LVOL_SIZE: u64 = 4 * 1024 * 1024;
+/// size of the pattern written and read back by the pool self test
+const SELF_TEST_IO_SIZE: u64 = 64 * 1024;
+/// byte pattern written by the pool self test
+const SELF_TEST_PATTERN: u8 = 0xa5;
+
 impl From<*mut spdk_lvol_store> for Lvs {
     fn from(p: *mut spdk_lvol_store) -> Self {
         Lvs(NonNull::new(p).unwrap())
@@ -167,6 +186,12 @@ impl Lvs {
         self.capacity() - self.available()
     }
 
+    /// returns the cluster size of the store
+    pub fn cluster_size(&self) -> u32 {
+        let blobs = unsafe { self.0.as_ref().blobstore };
+        unsafe { spdk_bs_get_cluster_size(blobs) as u32 }
+    }
+
     /// returns the base bdev of this lvs
     pub fn base_bdev(&self) -> Bdev {
         Bdev::from(unsafe {
@@ -239,8 +264,13 @@ impl Lvs {
     }
 
     #[instrument(level = "debug", err)]
-    /// Create a pool on base bdev
-    pub async fn create(name: &str, bdev: &str) -> Result<Lvs, Error> {
+    /// Create a pool on base bdev, with the default cluster size if the given
+    /// cluster size is 0
+    pub async fn create(
+        name: &str,
+        bdev: &str,
+        cluster_size: u32,
+    ) -> Result<Lvs, Error> {
         let pool_name = name.into_cstring();
         let bdev_name = bdev.into_cstring();
 
@@ -249,7 +279,7 @@ impl Lvs {
             vbdev_lvs_create(
                 bdev_name.as_ptr(),
                 pool_name.as_ptr(),
-                0,
+                cluster_size,
                 // We used to clear a pool with UNMAP but that takes awfully
                 // long time on large SSDs (~ can take an hour). Clearing the
                 // pool is not necessary. Clearing the lvol must be done, but
@@ -274,7 +304,12 @@ impl Lvs {
 
         match Self::lookup(name) {
             Some(pool) => {
-                info!("The pool '{}' has been created on {}", name, bdev);
+                info!(
+                    "The pool '{}' has been created on {} with cluster size {}",
+                    name,
+                    bdev,
+                    pool.cluster_size()
+                );
                 Ok(pool)
             }
             None => Err(Error::PoolCreate {
@@ -284,11 +319,27 @@ impl Lvs {
         }
     }
 
-    /// imports the pool if it exists, otherwise try to create it
+    /// imports the pool if it exists, otherwise try to create it. The cluster
+    /// size only applies to a newly created pool, an existing pool keeps its
+    /// cluster size.
     #[instrument(level = "debug", err)]
     pub async fn create_or_import(
         args: CreatePoolRequest,
     ) -> Result<Lvs, Error> {
+        if args.cluster_size != 0
+            && (!args.cluster_size.is_power_of_two()
+                || args.cluster_size < MIN_CLUSTER_SIZE
+                || args.cluster_size > MAX_CLUSTER_SIZE)
+        {
+            return Err(Error::Invalid {
+                source: Errno::EINVAL,
+                msg: format!(
+                    "invalid cluster size {}, must be a power of two between {} and {}",
+                    args.cluster_size, MIN_CLUSTER_SIZE, MAX_CLUSTER_SIZE
+                ),
+            });
+        }
+
         if args.disks.len() != 1 {
             return Err(Error::Invalid {
                 source: Errno::EINVAL,
@@ -359,7 +410,7 @@ impl Lvs {
             Err(Error::Import {
                 source, ..
             }) if source == Errno::EILSEQ => {
-                match Self::create(&args.name, &bdev).await {
+                match Self::create(&args.name, &bdev, args.cluster_size).await {
                     Err(create) => {
                         let _ = parsed.destroy().await.map_err(|_e| {
                             // we failed to delete the base_bdev be loud about it
@@ -558,4 +609,66 @@ impl Lvs {
         info!("created {}", lvol);
         Ok(lvol)
     }
+
+    /// Check that the pool is usable end to end by writing a pattern to a
+    /// scratch lvol and reading it back. The scratch lvol is destroyed also
+    /// when the test fails. Returns the time taken by the I/O.
+    pub async fn self_test(&self) -> Result<Duration, Error> {
+        let name = format!("selftest-{}", uuid::Uuid::new_v4());
+        let lvol = self.create_lvol(&name, SELF_TEST_LVOL_SIZE, false).await?;
+
+        let result = self.self_test_io(&lvol).await;
+
+        match lvol.destroy().await {
+            Ok(_) => result,
+            Err(error) => {
+                error!(
+                    "pool {}: failed to destroy {}: {}",
+                    self.name(),
+                    name,
+                    error
+                );
+                result.and(Err(error))
+            }
+        }
+    }
+
+    /// write the self test pattern to the lvol and verify it
+    async fn self_test_io(&self, lvol: &Lvol) -> Result<Duration, Error> {
+        let handle =
+            BdevHandle::open(&lvol.name(), true, false).context(SelfTest {
+                name: self.name(),
+            })?;
+
+        let mut buf = handle.dma_malloc(SELF_TEST_IO_SIZE).map_err(|_| {
+            Error::SelfTest {
+                source: CoreError::DmaAllocationError {
+                    size: SELF_TEST_IO_SIZE,
+                },
+                name: self.name().to_string(),
+            }
+        })?;
+
+        let start = Instant::now();
+
+        buf.fill(SELF_TEST_PATTERN);
+        handle.write_at(0, &buf).await.context(SelfTest {
+            name: self.name(),
+        })?;
+
+        buf.fill(0);
+        handle.read_at(0, &mut buf).await.context(SelfTest {
+            name: self.name(),
+        })?;
+
+        let elapsed = start.elapsed();
+
+        if buf.as_slice().iter().any(|b| *b != SELF_TEST_PATTERN) {
+            return Err(Error::SelfTestVerify {
+                name: self.name().to_string(),
+            });
+        }
+
+        Ok(elapsed)
+    }
 }
@@ -0,0 +1,134 @@
+//! Structural metadata of a pool. It can be exported as a portable document
+//! that allows recreating the pool and its replicas, but not their data, on a
+//! fresh instance after a node has been lost.
+
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+
+use rpc::mayastor::CreatePoolRequest;
+
+use crate::{
+    core::{Bdev, Protocol, Share},
+    lvs::{error::MetadataParse, Error, Lvol, Lvs},
+    replica::ShareType,
+};
+
+/// version of the metadata document, bumped on incompatible changes
+pub const POOL_METADATA_VERSION: u32 = 1;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+/// Structural metadata of a pool
+pub struct PoolMetadata {
+    /// version of the document
+    pub version: u32,
+    /// name of the pool
+    pub name: String,
+    /// URIs of the devices claimed by the pool
+    pub disks: Vec<String>,
+    /// cluster size of the pool in bytes, 0 for the default
+    #[serde(default)]
+    pub cluster_size: u32,
+    /// replicas of the pool, snapshots are not included
+    pub replicas: Vec<ReplicaMetadata>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+/// Structural metadata of a replica
+pub struct ReplicaMetadata {
+    /// name of the replica
+    pub uuid: String,
+    /// size of the replica in bytes
+    pub size: u64,
+    /// thin provisioning
+    pub thin: bool,
+    /// share type if shared
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub share: Option<ShareType>,
+}
+
+impl From<Lvol> for ReplicaMetadata {
+    fn from(lvol: Lvol) -> Self {
+        Self {
+            uuid: lvol.name(),
+            size: lvol.size(),
+            thin: lvol.is_thin(),
+            share: match lvol.shared() {
+                Some(Protocol::Nvmf) => Some(ShareType::Nvmf),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl From<&Lvs> for PoolMetadata {
+    fn from(lvs: &Lvs) -> Self {
+        let base = lvs.base_bdev();
+        Self {
+            version: POOL_METADATA_VERSION,
+            name: lvs.name().to_string(),
+            disks: vec![base.bdev_uri().unwrap_or_else(|| base.name())],
+            cluster_size: lvs.cluster_size(),
+            replicas: lvs
+                .lvols()
+                .map(|lvols| {
+                    lvols
+                        .filter(|l| !l.is_snapshot())
+                        .map(ReplicaMetadata::from)
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl PoolMetadata {
+    /// serialize the metadata into a portable document
+    pub fn to_document(&self) -> String {
+        serde_json::to_string_pretty(self)
+            .expect("pool metadata is always serializable")
+    }
+
+    /// parse the metadata from a document, rejecting unsupported versions
+    pub fn from_document(document: &str) -> Result<Self, Error> {
+        let metadata: Self =
+            serde_json::from_str(document).context(MetadataParse {})?;
+
+        if metadata.version != POOL_METADATA_VERSION {
+            return Err(Error::MetadataVersion {
+                version: metadata.version,
+            });
+        }
+
+        Ok(metadata)
+    }
+
+    /// Create or import the pool described by the metadata and create its
+    /// replicas. Replicas which exist already are left untouched.
+    pub async fn import(self) -> Result<Lvs, Error> {
+        let pool = Lvs::create_or_import(CreatePoolRequest {
+            name: self.name,
+            disks: self.disks,
+            cluster_size: self.cluster_size,
+        })
+        .await?;
+
+        for replica in self.replicas {
+            if Bdev::lookup_by_name(&replica.uuid).is_some() {
+                debug!("replica {} exists, skipping", replica.uuid);
+                continue;
+            }
+
+            let lvol = pool
+                .create_lvol(&replica.uuid, replica.size, replica.thin)
+                .await?;
+
+            if replica.share == Some(ShareType::Nvmf) {
+                lvol.share_nvmf(None).await?;
+            }
+        }
+
+        Ok(pool)
+    }
+}
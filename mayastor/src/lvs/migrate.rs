@@ -0,0 +1,522 @@
+//! Migration of a replica to another pool.
+//!
+//! The data of the replica is copied by a rebuild job to a staging lvol on
+//! the target pool while the replica stays in use. The rebuild job locks each
+//! segment of the replica while it copies it, but writes to the segments which
+//! were copied already are not mirrored to the staging lvol. Those are caught
+//! up at the end, with the replica still in use, by comparing both copies and
+//! copying the segments which differ. This is repeated until a pass completes
+//! without the replica being written to, as told by its I/O statistics. The
+//! share of the replica is then paused, and if nothing was written since, the
+//! switch over happens, so that the share is only paused for the switch.
+//!
+//! The bdev of an lvol is named after the lvol when the lvol is opened, so the
+//! staging lvol cannot be renamed to take the place of the replica. Instead,
+//! the staging lvol is snapshotted and the snapshot is cloned under the name
+//! of the replica. The namespace of the subsystem the replica is shared by is
+//! swapped over to the clone, keeping the NGUID of the replica, hosts stay
+//! connected throughout. The clone is inflated afterwards so that the snapshot
+//! can be destroyed. As the replica and the clone cannot exist at once, the
+//! replica is destroyed right before the clone is created, once everything
+//! else is in place.
+//!
+//! An interrupted copy is resumed when the migration is requested again: the
+//! staging lvol records the first block which may not have been copied.
+
+use std::{
+    convert::{TryFrom, TryInto},
+    ffi::c_void,
+    ptr::NonNull,
+};
+
+use futures::channel::oneshot;
+use nix::errno::Errno;
+use snafu::ResultExt;
+
+use spdk_sys::{
+    spdk_lvol,
+    spdk_lvol_inflate,
+    vbdev_lvol_create_clone,
+    vbdev_lvol_create_snapshot,
+};
+
+use crate::{
+    core::{Bdev, BdevHandle, CoreError, Protocol, Share},
+    ffihelper::{cb_arg, pair, ErrnoResult, FfiResult, IntoCString},
+    lvs::{
+        error::{MigrateCopy, MigrateIo, MigrateShare},
+        Error,
+        Lvol,
+        Lvs,
+        PropName,
+        PropValue,
+    },
+    rebuild::{ClientOperations, RebuildJob, RebuildState},
+    subsys::NvmfSubsystem,
+};
+
+/// suffix of the name of the lvol the replica is copied to
+const STAGING_SUFFIX: &str = "-migrate";
+/// suffix of the name of the snapshot the migrated replica is cloned from
+const SNAPSHOT_SUFFIX: &str = "-migrate-snap";
+/// size of the I/O issued when catching up with the writes to the replica
+const CATCH_UP_IO_SIZE: u64 = 1 << 20;
+/// number of passes catching up with the writes to the replica before the
+/// migration is given up on
+const CATCH_UP_PASSES: u32 = 8;
+
+impl Lvol {
+    /// Migrate the replica to the given pool and return the lvol which
+    /// replaced it. The copy is throttled to `rate_limit` bytes per second,
+    /// unless 0. If the copy is interrupted it is resumed from where it
+    /// stopped by the next migration of the replica to the same pool.
+    pub async fn migrate(
+        self,
+        pool: &Lvs,
+        rate_limit: u64,
+    ) -> Result<Lvol, Error> {
+        self.migrate_check(pool)?;
+
+        let (staging, checkpoint) = self.migrate_staging(pool).await?;
+        self.migrate_copy(&staging, checkpoint, rate_limit).await?;
+
+        let name = self.name();
+        let subsystem = match self.shared() {
+            Some(Protocol::Nvmf) => NvmfSubsystem::nqn_lookup(&name),
+            _ => None,
+        };
+
+        match &subsystem {
+            // returns with the subsystem paused
+            Some(ss) => self.migrate_catch_up(&staging, ss).await?,
+            // the replica may have been written to since an earlier attempt
+            // copied some of it
+            None if checkpoint > 0 => {
+                self.catch_up(&staging).await?;
+            }
+            None => {}
+        }
+
+        let result = self.migrate_switch(&staging, subsystem.as_ref()).await;
+
+        if let Some(ss) = &subsystem {
+            if let Err(e) = ss.resume().await {
+                error!("failed to resume the share of {}: {}", name, e);
+            }
+        }
+
+        let (replica, snapshot) = result?;
+        info!("migrated {} to pool {}", name, pool.name());
+
+        // the migration is complete, failing to free the space taken by the
+        // staging lvol and the snapshot does not undo it
+        if let Err(e) = staging.destroy_unshared().await {
+            error!("failed to destroy the staging lvol of {}: {}", name, e);
+        }
+        match replica.inflate().await {
+            Ok(()) => {
+                if let Err(e) = snapshot.destroy_unshared().await {
+                    error!("failed to destroy the snapshot of {}: {}", name, e);
+                }
+            }
+            Err(e) => error!("{}, keeping its snapshot", e),
+        }
+
+        Ok(replica)
+    }
+
+    /// check that the replica can be migrated to the given pool
+    fn migrate_check(&self, pool: &Lvs) -> Result<(), Error> {
+        let invalid = |msg: String| Error::MigrateInvalid {
+            name: self.name(),
+            msg,
+        };
+
+        if self.pool() == pool.name() {
+            return Err(invalid(format!("already on pool {}", pool.name())));
+        }
+        if self.is_snapshot() {
+            return Err(invalid("snapshots cannot be migrated".into()));
+        }
+
+        // writes by the nvmf target can be held off while switching over, any
+        // other user of the replica could write to it behind our back
+        match self.as_bdev().claimed_by() {
+            None => Ok(()),
+            Some(t) if t == "NVMe-oF Target" => Ok(()),
+            Some(t) => Err(invalid(format!("in use by {}", t))),
+        }
+    }
+
+    /// the lvol on the target pool the replica is copied to, along with the
+    /// first block to copy when an earlier migration was interrupted
+    async fn migrate_staging(&self, pool: &Lvs) -> Result<(Lvol, u64), Error> {
+        let name = format!("{}{}", self.name(), STAGING_SUFFIX);
+
+        let bdev = match Bdev::lookup_by_name(&name) {
+            Some(bdev) => bdev,
+            None => {
+                let lvol = pool
+                    .create_lvol(&name, self.size(), self.is_thin())
+                    .await?;
+                return Ok((lvol, 0));
+            }
+        };
+
+        let lvol = Lvol::try_from(bdev)?;
+        if lvol.pool() != pool.name() || lvol.size() < self.size() {
+            return Err(Error::MigrateInvalid {
+                name: self.name(),
+                msg: format!("{} exists already", name),
+            });
+        }
+
+        let checkpoint = lvol
+            .get_raw(PropName::MigrateCheckpoint)
+            .ok()
+            .and_then(|v| v.as_slice().try_into().ok())
+            .map(u64::from_le_bytes)
+            .unwrap_or(0);
+
+        info!(
+            "resuming the migration of {} at {}",
+            self.name(),
+            checkpoint
+        );
+        Ok((lvol, checkpoint))
+    }
+
+    /// copy the replica to the staging lvol from the given block onwards,
+    /// recording where the copy stopped if it does not complete
+    async fn migrate_copy(
+        &self,
+        staging: &Lvol,
+        checkpoint: u64,
+        rate_limit: u64,
+    ) -> Result<(), Error> {
+        let name = self.name();
+        let source = format!("bdev:///{}", name);
+        let destination = format!("bdev:///{}", staging.name());
+
+        // an earlier attempt copied everything, catching up is all that is
+        // left to do
+        if checkpoint >= self.as_bdev().num_blocks() {
+            return Ok(());
+        }
+
+        // the range of a segment is locked on the replica itself while the
+        // segment is copied, which holds off the writes to it
+        let job = RebuildJob::create(
+            &name,
+            &source,
+            &destination,
+            0 .. self.as_bdev().num_blocks(),
+            |_, _| {},
+        )
+        .context(MigrateCopy {
+            name: name.clone(),
+        })?;
+
+        job.set_rate_limit(rate_limit);
+        let started = job
+            .skip_to(checkpoint)
+            .and_then(|_| job.as_client().start());
+
+        let state = match started {
+            Ok(done) => done.await.unwrap_or(RebuildState::Failed),
+            Err(error) => {
+                let _ = RebuildJob::remove(&destination);
+                return Err(error).context(MigrateCopy {
+                    name,
+                });
+            }
+        };
+
+        let job = RebuildJob::remove(&destination).context(MigrateCopy {
+            name: name.clone(),
+        })?;
+
+        if state == RebuildState::Completed {
+            let _ = staging.remove_raw(PropName::MigrateCheckpoint).await;
+            return Ok(());
+        }
+
+        let blk = job.checkpoint();
+        if let Some(e) = &job.error {
+            error!("failed to copy {}: {}", name, e);
+        }
+        staging
+            .set_raw(PropName::MigrateCheckpoint, &blk.to_le_bytes())
+            .await?;
+
+        Err(Error::MigrateInterrupted {
+            name,
+            blk,
+        })
+    }
+
+    /// Catch up with the writes to the shared replica while it is in use
+    /// until a pass sees no writes, then pause the subsystem and check that
+    /// nothing was written since the pass started. The subsystem is only left
+    /// paused when that is the case, for the duration of the switch rather
+    /// than for a comparison of both copies. When the replica keeps being
+    /// written to, the migration is given up on with the copy recorded as
+    /// complete, so that the next migration only has to catch up.
+    async fn migrate_catch_up(
+        &self,
+        staging: &Lvol,
+        ss: &NvmfSubsystem,
+    ) -> Result<(), Error> {
+        let name = self.name();
+
+        for pass in 0 .. CATCH_UP_PASSES {
+            let writes = self.write_count().await?;
+            let copied = self.catch_up(staging).await?;
+            debug!(
+                "caught up {} bytes written to {} in pass {}",
+                copied, name, pass
+            );
+            if self.write_count().await? != writes {
+                continue;
+            }
+
+            ss.pause().await.context(MigrateShare {
+                name: name.clone(),
+            })?;
+            // writes in flight are completed once paused
+            match self.write_count().await {
+                Ok(count) if count == writes => return Ok(()),
+                Ok(_) => {}
+                Err(e) => {
+                    if let Err(e) = ss.resume().await {
+                        error!("failed to resume the share of {}: {}", name, e);
+                    }
+                    return Err(e);
+                }
+            }
+            ss.resume().await.context(MigrateShare {
+                name: name.clone(),
+            })?;
+        }
+
+        staging
+            .set_raw(
+                PropName::MigrateCheckpoint,
+                &self.as_bdev().num_blocks().to_le_bytes(),
+            )
+            .await?;
+        Err(Error::MigrateBusy {
+            name,
+        })
+    }
+
+    /// number of writes and unmaps the replica completed so far
+    async fn write_count(&self) -> Result<u64, Error> {
+        self.writes_completed().await.context(MigrateIo {
+            name: self.name(),
+        })
+    }
+
+    /// Replace the replica with a clone of the staging lvol, named after the
+    /// replica. If the replica is shared, the subsystem must be paused and the
+    /// clone becomes its namespace, with the NGUID of the replica. Returns the
+    /// clone and the snapshot it was created from.
+    ///
+    /// The replica is only destroyed once the snapshot exists and the
+    /// namespace is removed, right before the clone which takes its name is
+    /// created. Should that fail, its namespace is restored. Should the clone
+    /// fail to be
+    /// created, the data of the replica is only left in the staging lvol,
+    /// which then becomes the namespace so that hosts can still reach it.
+    async fn migrate_switch(
+        self,
+        staging: &Lvol,
+        subsystem: Option<&NvmfSubsystem>,
+    ) -> Result<(Lvol, Lvol), Error> {
+        let name = self.name();
+        let bdev = self.as_bdev();
+        let nguid = bdev.uuid();
+
+        let snapshot = staging
+            .snapshot(&format!("{}{}", name, SNAPSHOT_SUFFIX))
+            .await?;
+
+        if let Some(ss) = subsystem {
+            if let Err(e) = ss.remove_namespace() {
+                Self::discard_snapshot(&name, snapshot).await;
+                return Err(e).context(MigrateShare {
+                    name: name.clone(),
+                });
+            }
+        }
+
+        if let Err(e) = self.destroy_unshared().await {
+            if let Some(ss) = subsystem {
+                if let Err(e) = ss.add_namespace_with_nguid(&bdev, nguid) {
+                    error!("failed to restore the share of {}: {}", name, e);
+                }
+            }
+            Self::discard_snapshot(&name, snapshot).await;
+            return Err(e);
+        }
+
+        let replica = match snapshot.clone_as(&name).await {
+            Ok(replica) => replica,
+            Err(e) => {
+                error!(
+                    "{}, the data of {} is left in {}",
+                    e,
+                    name,
+                    staging.name()
+                );
+                if let Some(ss) = subsystem {
+                    if let Err(e) =
+                        ss.add_namespace_with_nguid(&staging.as_bdev(), nguid)
+                    {
+                        error!("failed to share {}: {}", staging.name(), e);
+                    }
+                }
+                return Err(e);
+            }
+        };
+
+        if let Some(ss) = subsystem {
+            ss.add_namespace_with_nguid(&replica.as_bdev(), nguid)
+                .context(MigrateShare {
+                    name: name.clone(),
+                })?;
+            replica.set(PropValue::Shared(true)).await?;
+        }
+
+        Ok((replica, snapshot))
+    }
+
+    /// destroy the snapshot of a switch which did not happen, the staging lvol
+    /// being its only clone
+    async fn discard_snapshot(name: &str, snapshot: Lvol) {
+        if let Err(e) = snapshot.destroy_unshared().await {
+            error!("failed to destroy the snapshot of {}: {}", name, e);
+        }
+    }
+
+    /// Copy the segments of the replica which differ from the staging lvol
+    /// and return the number of bytes copied. Writes to the replica meanwhile
+    /// may not be caught up with.
+    async fn catch_up(&self, staging: &Lvol) -> Result<u64, Error> {
+        let name = self.name();
+        let source =
+            BdevHandle::open(&name, false, false).context(MigrateIo {
+                name: name.clone(),
+            })?;
+        let destination = BdevHandle::open(&staging.name(), true, false)
+            .context(MigrateIo {
+                name: name.clone(),
+            })?;
+
+        let size = self.size();
+        let mut copied = 0;
+        let mut offset = 0;
+
+        while offset < size {
+            let len = std::cmp::min(CATCH_UP_IO_SIZE, size - offset);
+            let dma_error = |_| Error::MigrateIo {
+                source: CoreError::DmaAllocationError {
+                    size: len,
+                },
+                name: name.clone(),
+            };
+            let mut data = source.dma_malloc(len).map_err(dma_error)?;
+            let mut copy = destination.dma_malloc(len).map_err(dma_error)?;
+
+            source.read_at(offset, &mut data).await.context(MigrateIo {
+                name: name.clone(),
+            })?;
+            destination.read_at(offset, &mut copy).await.context(
+                MigrateIo {
+                    name: name.clone(),
+                },
+            )?;
+
+            if data.as_slice() != copy.as_slice() {
+                destination.write_at(offset, &data).await.context(
+                    MigrateIo {
+                        name: name.clone(),
+                    },
+                )?;
+                copied += len;
+            }
+
+            offset += len;
+        }
+
+        Ok(copied)
+    }
+
+    /// create a snapshot of the lvol with the given name
+    async fn snapshot(&self, name: &str) -> Result<Lvol, Error> {
+        let (s, r) = pair::<ErrnoResult<*mut spdk_lvol>>();
+
+        let cname = name.into_cstring();
+        unsafe {
+            vbdev_lvol_create_snapshot(
+                self.0.as_ptr(),
+                cname.as_ptr(),
+                Some(Lvol::lvol_cb),
+                cb_arg(s),
+            )
+        };
+
+        r.await
+            .expect("lvol snapshot callback dropped")
+            .map_err(|e| Error::Snapshot {
+                source: e,
+                name: self.name(),
+            })
+            .map(|lvol| Lvol(NonNull::new(lvol).unwrap()))
+    }
+
+    /// create a clone of this snapshot with the given name
+    async fn clone_as(&self, name: &str) -> Result<Lvol, Error> {
+        let (s, r) = pair::<ErrnoResult<*mut spdk_lvol>>();
+
+        let cname = name.into_cstring();
+        unsafe {
+            vbdev_lvol_create_clone(
+                self.0.as_ptr(),
+                cname.as_ptr(),
+                Some(Lvol::lvol_cb),
+                cb_arg(s),
+            )
+        };
+
+        r.await
+            .expect("lvol clone callback dropped")
+            .map_err(|e| Error::Clone {
+                source: e,
+                name: self.name(),
+            })
+            .map(|lvol| Lvol(NonNull::new(lvol).unwrap()))
+    }
+
+    /// allocate all clusters of this clone, copying the data of its snapshot
+    /// so that it no longer depends on it
+    async fn inflate(&self) -> Result<(), Error> {
+        extern "C" fn inflate_cb(sender: *mut c_void, errno: i32) {
+            let sender =
+                unsafe { Box::from_raw(sender as *mut oneshot::Sender<i32>) };
+            sender.send(errno).unwrap();
+        }
+
+        let (s, r) = pair::<i32>();
+        unsafe {
+            spdk_lvol_inflate(self.0.as_ptr(), Some(inflate_cb), cb_arg(s))
+        };
+
+        r.await
+            .expect("lvol inflate callback is gone")
+            .to_result(|e| Error::Inflate {
+                source: Errno::from_i32(e),
+                name: self.name(),
+            })
+    }
+}
@@ -1,7 +1,12 @@
+pub use checksum::ChecksumManifest;
 pub use error::Error;
 pub use lvol::{Lvol, PropName, PropValue};
 pub use lvs_pool::Lvs;
+pub use metadata::{PoolMetadata, ReplicaMetadata, POOL_METADATA_VERSION};
 
+mod checksum;
 mod error;
 mod lvol;
 mod lvs_pool;
+mod metadata;
+mod migrate;
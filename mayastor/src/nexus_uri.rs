@@ -1,6 +1,9 @@
 use std::{convert::TryFrom, num::ParseIntError, str::ParseBoolError};
 
-use crate::{bdev::Uri, core::Bdev};
+use crate::{
+    bdev::Uri,
+    core::{Bdev, CoreError},
+};
 use futures::channel::oneshot::Canceled;
 use nix::errno::Errno;
 use snafu::Snafu;
@@ -69,6 +72,8 @@ pub enum NexusBdevError {
     DestroyBdev { source: Errno, name: String },
     #[snafu(display("Command canceled for bdev {}", name))]
     CancelBdev { source: Canceled, name: String },
+    #[snafu(display("Failed to fill bdev {}", name))]
+    FillBdev { source: CoreError, name: String },
 }
 
 /// Parse URI and create bdev described in the URI.
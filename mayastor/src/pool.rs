@@ -64,6 +64,14 @@ impl Pool {
         }
     }
 
+    /// Get cluster size of the pool in bytes.
+    pub fn get_cluster_size(&self) -> u32 {
+        unsafe {
+            let lvs = &*self.lvs_ptr;
+            spdk_bs_get_cluster_size(lvs.blobstore) as u32
+        }
+    }
+
     /// Get free space in the pool in bytes.
     pub fn get_free(&self) -> u64 {
         unsafe {
@@ -128,6 +136,7 @@ impl From<Pool> for rpc::Pool {
             state: rpc::PoolState::PoolOnline as i32,
             capacity: pool.get_capacity(),
             used: pool.get_capacity() - pool.get_free(),
+            cluster_size: pool.get_cluster_size(),
         }
     }
 }
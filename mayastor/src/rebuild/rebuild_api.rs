@@ -1,6 +1,6 @@
 #![warn(missing_docs)]
 
-use std::fmt;
+use std::{fmt, time::SystemTime};
 
 use crossbeam::channel::{Receiver, Sender};
 use futures::channel::oneshot;
@@ -72,6 +72,12 @@ pub enum RebuildError {
     },
     #[snafu(display("Failed to get bdev name from URI {}", uri))]
     BdevInvalidUri { source: NexusBdevError, uri: String },
+    #[snafu(display(
+        "Rebuilt bdev {} does not match the source at blk {}",
+        bdev,
+        blk
+    ))]
+    VerifyMismatch { blk: u64, bdev: String },
 }
 
 #[derive(Debug, PartialEq, Copy, Clone)]
@@ -122,6 +128,11 @@ pub struct RebuildJob {
     pub(super) next: u64,
     pub(super) segment_size_blks: u64,
     pub(super) task_pool: RebuildTasks,
+    /// transfer rate limit in bytes per second, 0 if unlimited
+    pub(super) rate_limit: u64,
+    /// percentage of the rebuilt segments verified once the copy is done,
+    /// 0 if not verified
+    pub(super) verify_percent: u32,
     pub(super) notify_fn: fn(String, String) -> (),
     /// channel used to signal rebuild update
     pub notify_chan: (Sender<RebuildState>, Receiver<RebuildState>),
@@ -131,6 +142,8 @@ pub struct RebuildJob {
     pub(super) complete_chan: Vec<oneshot::Sender<RebuildState>>,
     /// rebuild copy error, if any
     pub error: Option<RebuildError>,
+    /// time at which the job was started, if it has been
+    pub(super) start_time: Option<SystemTime>,
 
     // Pre-opened descriptors for source/destination block device.
     pub(super) src_descriptor: Box<dyn BlockDeviceDescriptor>,
@@ -163,6 +176,30 @@ pub struct RebuildStats {
     pub tasks_total: u64,
     /// number of current active tasks
     pub tasks_active: u64,
+    /// transfer rate in bytes per second, averaged over the last few seconds
+    pub rate_bytes_per_sec: u64,
+    /// estimated time remaining in seconds, 0 if unknown
+    pub eta_seconds: u64,
+}
+
+/// Summary of a rebuild job which has finished, kept by the nexus once the
+/// job itself has been removed
+#[derive(Debug, Clone)]
+pub struct RebuildRecord {
+    /// source URI of the healthy child the job rebuilt from
+    pub source: String,
+    /// target URI of the child which was rebuilt
+    pub destination: String,
+    /// time at which the job was started
+    pub start_time: SystemTime,
+    /// time at which the job reached its final state
+    pub end_time: SystemTime,
+    /// number of bytes copied to the destination
+    pub bytes_recovered: u64,
+    /// final state of the job
+    pub state: RebuildState,
+    /// description of the error which failed the job, if any
+    pub error: String,
 }
 
 /// Public facing operations on a Rebuild Job
@@ -245,6 +282,12 @@ impl RebuildJob {
         self.states.current
     }
 
+    /// State the rebuild job will be in once any pending operation has
+    /// taken effect
+    pub fn target_state(&self) -> RebuildState {
+        self.states.target()
+    }
+
     /// Error description
     pub fn error_desc(&self) -> String {
         match self.error.as_ref() {
@@ -253,6 +296,50 @@ impl RebuildJob {
         }
     }
 
+    /// Limits the transfer rate of the job to `bytes_per_sec`, 0 removes
+    /// the limit. Takes effect from the next segment copied.
+    pub fn set_rate_limit(&mut self, bytes_per_sec: u64) {
+        if self.rate_limit != bytes_per_sec {
+            self.rate_limit = bytes_per_sec;
+            // segments copied under the previous limit do not count
+            // towards the new one
+            self.task_pool.pace_from_now();
+        }
+    }
+
+    /// Transfer rate limit of the job in bytes per second, 0 if unlimited
+    pub fn rate_limit(&self) -> u64 {
+        self.rate_limit
+    }
+
+    /// Verifies `percent` of the rebuilt segments once the copy is done by
+    /// comparing them with the source, 0 disables the verification. A
+    /// mismatch fails the job. Values above 100 are capped.
+    pub fn set_verify_percent(&mut self, percent: u32) {
+        self.verify_percent = std::cmp::min(percent, 100);
+    }
+
+    /// Percentage of the rebuilt segments verified, 0 if not verified
+    pub fn verify_percent(&self) -> u32 {
+        self.verify_percent
+    }
+
+    /// Summary of the job as it is now, meant to be taken once the job is
+    /// done. A job which never started is recorded as starting now.
+    pub fn to_record(&self) -> RebuildRecord {
+        let stats = self.stats();
+        let end_time = SystemTime::now();
+        RebuildRecord {
+            source: self.source.clone(),
+            destination: self.destination.clone(),
+            start_time: self.start_time.unwrap_or(end_time),
+            end_time,
+            bytes_recovered: stats.blocks_recovered * stats.block_size,
+            state: self.state(),
+            error: self.error_desc(),
+        }
+    }
+
     /// ClientOperations trait
     /// todo: nexus should use this for all interaction with the job
     pub fn as_client(&mut self) -> &mut impl ClientOperations {
@@ -1,6 +1,10 @@
 #![warn(missing_docs)]
 
-use std::{cell::UnsafeCell, collections::HashMap};
+use std::{
+    cell::UnsafeCell,
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant, SystemTime},
+};
 
 use crossbeam::channel::unbounded;
 use futures::{
@@ -13,7 +17,11 @@ use snafu::ResultExt;
 use spdk_sys::{spdk_get_thread, SPDK_BDEV_LARGE_BUF_MAX_SIZE};
 
 use crate::{
-    bdev::{device_open, VerboseError},
+    bdev::{
+        device_open,
+        nexus::nexus_bdev_verify::chunk_checksum,
+        VerboseError,
+    },
     core::{
         Bdev,
         BlockDevice,
@@ -24,6 +32,7 @@ use crate::{
         Reactors,
     },
     nexus_uri::bdev_get_name,
+    sleep::mayastor_sleep,
 };
 
 use super::rebuild_api::*;
@@ -53,6 +62,8 @@ struct TaskResult {
 const SEGMENT_TASKS: usize = 16;
 /// Size of each segment used by the copy task
 pub const SEGMENT_SIZE: u64 = SPDK_BDEV_LARGE_BUF_MAX_SIZE as u64;
+/// Window over which the rebuild transfer rate is averaged
+const RATE_WINDOW: Duration = Duration::from_secs(5);
 
 /// Each rebuild task needs a unique buffer to read/write from source to target
 /// A mpsc channel is used to communicate with the management task
@@ -75,6 +86,55 @@ pub(super) struct RebuildTasks {
     total: usize,
 
     segments_done: u64,
+    rate: RebuildRate,
+    /// time and number of segments done from which the copy is paced to the
+    /// rate limit, moved on whenever the job is run or the limit changes
+    paced_from: (Instant, u64),
+}
+
+impl RebuildTasks {
+    /// Paces the copy to the rate limit from this point on only
+    pub(super) fn pace_from_now(&mut self) {
+        self.paced_from = (Instant::now(), self.segments_done);
+    }
+}
+
+/// Moving average of the rebuild transfer rate
+/// Samples of the number of segments done are kept for the last
+/// `RATE_WINDOW` so that the rate follows changes in the rebuild speed
+#[derive(Debug, Default)]
+pub(super) struct RebuildRate {
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl RebuildRate {
+    /// Records the number of segments done at this point in time
+    fn sample(&mut self, segments_done: u64) {
+        let now = Instant::now();
+        self.samples.push_back((now, segments_done));
+        // keep a single sample older than the window as the baseline
+        while self.samples.len() > 2
+            && now.duration_since(self.samples[1].0) >= RATE_WINDOW
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Clears all samples, eg: when the rebuild is paused
+    fn reset(&mut self) {
+        self.samples.clear();
+    }
+
+    /// Rate in segments per second over the sampled window
+    fn segments_per_sec(&self) -> f64 {
+        match (self.samples.front(), self.samples.back()) {
+            (Some(first), Some(last)) if last.0 > first.0 => {
+                (last.1 - first.1) as f64
+                    / last.0.duration_since(first.0).as_secs_f64()
+            }
+            _ => 0.0,
+        }
+    }
 }
 
 /// Checks whether a range is contained within another range
@@ -163,6 +223,8 @@ impl RebuildJob {
             active: 0,
             total: SEGMENT_TASKS,
             segments_done: 0,
+            rate: RebuildRate::default(),
+            paced_from: (Instant::now(), 0),
         };
 
         for _ in 0 .. tasks.total {
@@ -197,11 +259,14 @@ impl RebuildJob {
             block_size,
             segment_size_blks,
             task_pool: tasks,
+            rate_limit: 0,
+            verify_percent: 0,
             notify_fn,
             notify_chan: unbounded::<RebuildState>(),
             states: Default::default(),
             complete_chan: Vec::new(),
             error: None,
+            start_time: None,
             src_descriptor,
             dst_descriptor,
         })
@@ -211,6 +276,9 @@ impl RebuildJob {
     // awaits each completion. When any task completes it kicks off another
     // until the bdev is fully rebuilt
     async fn run(&mut self) {
+        self.task_pool.pace_from_now();
+        self.task_pool.rate.reset();
+        self.task_pool.rate.sample(self.task_pool.segments_done);
         self.start_all_tasks();
         while self.task_pool.active > 0 {
             match self.await_one_task().await {
@@ -218,6 +286,7 @@ impl RebuildJob {
                     None => {
                         match self.states.pending {
                             None | Some(RebuildState::Running) => {
+                                self.throttle().await;
                                 self.start_task_by_id(r.id);
                             }
                             _ => {
@@ -244,9 +313,106 @@ impl RebuildJob {
                 }
             }
         }
+
+        if self.verify_percent > 0
+            && self.states.pending_equals(RebuildState::Completed)
+        {
+            if let Err(e) = self.verify().await {
+                error!(
+                    "Rebuild job {}: verification failed: {}",
+                    self.destination,
+                    e.verbose()
+                );
+                self.error = Some(e);
+                self.fail();
+            }
+        }
         self.reconcile();
     }
 
+    /// Reads back `verify_percent` of the rebuilt segments from both the
+    /// source and the destination and compares their checksums. The verified
+    /// segments are spread evenly over the range. Stops early, without error,
+    /// if the job is terminated meanwhile.
+    async fn verify(&mut self) -> Result<(), RebuildError> {
+        let segments =
+            (self.range.end - self.range.start + self.segment_size_blks - 1)
+                / self.segment_size_blks;
+        info!(
+            "Rebuild job {}: verifying {}% of {} segments",
+            self.destination, self.verify_percent, segments
+        );
+
+        for segment in 0 .. segments {
+            if !Self::verify_segment(segment, self.verify_percent) {
+                continue;
+            }
+            if !self.states.pending_equals(RebuildState::Completed) {
+                break;
+            }
+            let blk = self.range.start + segment * self.segment_size_blks;
+            self.locked_verify_one(blk).await?;
+        }
+        Ok(())
+    }
+
+    /// Whether the segment is among the `percent` of the segments verified
+    fn verify_segment(segment: u64, percent: u32) -> bool {
+        let percent = u64::from(percent);
+        (segment * percent) % 100 < percent
+    }
+
+    /// Delays the copy of the next segment for as long as the transfer rate
+    /// since the job was last run, or its rate limit last changed, is above
+    /// the rate limit of the job
+    async fn throttle(&self) {
+        if self.rate_limit == 0 {
+            return;
+        }
+
+        let started = self.task_pool.paced_from;
+        let bytes = (self.task_pool.segments_done - started.1)
+            * self.segment_size_blks
+            * self.block_size;
+        let due =
+            Duration::from_secs_f64(bytes as f64 / self.rate_limit as f64);
+
+        if let Some(delay) = due.checked_sub(started.0.elapsed()) {
+            let _ = mayastor_sleep(delay).await;
+        }
+    }
+
+    /// Skips the blocks before `blk` which must then have been copied
+    /// already, eg: by an earlier job which was interrupted. Only allowed
+    /// before the job is started.
+    pub fn skip_to(&mut self, blk: u64) -> Result<(), RebuildError> {
+        if self.state() != RebuildState::Init
+            || blk < self.range.start
+            || blk > self.range.end
+        {
+            return Err(RebuildError::InvalidParameters {});
+        }
+
+        // the segments are copied whole, round down to the segment boundary
+        let segments = (blk - self.range.start) / self.segment_size_blks;
+        self.next = self.range.start + segments * self.segment_size_blks;
+        self.task_pool.segments_done = segments;
+        Ok(())
+    }
+
+    /// The first block which may not have been copied yet, all blocks of the
+    /// range before it have been copied. Only accurate once the job is no
+    /// longer running, so that it can be used to resume the copy with
+    /// [`RebuildJob::skip_to`].
+    pub fn checkpoint(&self) -> u64 {
+        self.task_pool
+            .tasks
+            .iter()
+            .filter_map(|t| t.error.as_ref().map(|e| e.blk))
+            .min()
+            .unwrap_or(self.next)
+    }
+
     /// Return the size of the segment to be copied.
     fn get_segment_size_blks(&self, blk: u64) -> u64 {
         // Adjust the segments size for the last segment
@@ -312,6 +478,66 @@ impl RebuildJob {
         result
     }
 
+    /// Compares one segment of the source and the destination with the LBA
+    /// range locked, so that front end writes cannot interleave with the
+    /// reads. See [`RebuildJob::locked_copy_one`] for the safety of the
+    /// RangeContext.
+    async fn locked_verify_one(&self, blk: u64) -> Result<(), RebuildError> {
+        let len = self.get_segment_size_blks(blk);
+        let mut ctx = RangeContext::new(blk - self.range.start, len);
+        let ch = self
+            .nexus_descriptor
+            .get_channel()
+            .expect("Failed to get nexus channel");
+
+        self.nexus_descriptor
+            .lock_lba_range(&mut ctx, &ch)
+            .await
+            .context(RangeLockError {
+                blk,
+                len,
+            })?;
+
+        let result = self.verify_one(blk, len).await;
+
+        self.nexus_descriptor
+            .unlock_lba_range(&mut ctx, &ch)
+            .await
+            .context(RangeUnLockError {
+                blk,
+                len,
+            })?;
+
+        result
+    }
+
+    /// Compares the checksums of `len` blocks from `blk` of the source and
+    /// the destination.
+    async fn verify_one(&self, blk: u64, len: u64) -> Result<(), RebuildError> {
+        let source_hdl = Self::get_io_handle(&*self.src_descriptor)?;
+        let destination_hdl = Self::get_io_handle(&*self.dst_descriptor)?;
+        let (offset, size) = (blk * self.block_size, len * self.block_size);
+
+        let source = chunk_checksum(&*source_hdl, offset, size).await.context(
+            ReadIoError {
+                bdev: &self.source,
+            },
+        )?;
+        let destination = chunk_checksum(&*destination_hdl, offset, size)
+            .await
+            .context(ReadIoError {
+                bdev: &self.destination,
+            })?;
+
+        if source != destination {
+            return Err(RebuildError::VerifyMismatch {
+                blk,
+                bdev: self.destination.clone(),
+            });
+        }
+        Ok(())
+    }
+
     /// Copies one segment worth of data from source into destination.
     async fn copy_one(
         &mut self,
@@ -407,6 +633,11 @@ impl RebuildJob {
                 "Rebuild job {}: changing state from {:?} to {:?}",
                 self.destination, old, new
             );
+            if new.done() {
+                self.complete_chan.drain(..).for_each(|s| {
+                    let _ = s.send(new);
+                });
+            }
             self.notify();
         }
     }
@@ -502,9 +733,26 @@ impl ClientOperations for RebuildJob {
 
         let progress = (blocks_recovered * 100) / blocks_total;
 
+        let (rate_bytes_per_sec, eta_seconds) = match self.state() {
+            RebuildState::Running => {
+                let rate = self.task_pool.rate.segments_per_sec()
+                    * (self.segment_size_blks * self.block_size) as f64;
+                let remaining =
+                    (blocks_total - blocks_recovered) * self.block_size;
+                let eta = if rate > 0.0 {
+                    (remaining as f64 / rate).ceil() as u64
+                } else {
+                    0
+                };
+                (rate as u64, eta)
+            }
+            _ => (0, 0),
+        };
+
         info!(
             "State: {}, Src: {}, Dst: {}, range: {:?}, next: {}, \
-             block_size: {}, segment_sz: {}, recovered_blks: {}, progress: {}%",
+             block_size: {}, segment_sz: {}, recovered_blks: {}, progress: {}%, \
+             rate: {} B/s, eta: {}s",
             self.state(),
             self.source,
             self.destination,
@@ -514,6 +762,8 @@ impl ClientOperations for RebuildJob {
             self.segment_size_blks,
             blocks_recovered,
             progress,
+            rate_bytes_per_sec,
+            eta_seconds,
         );
 
         RebuildStats {
@@ -524,6 +774,8 @@ impl ClientOperations for RebuildJob {
             block_size: self.block_size,
             tasks_total: self.task_pool.total as u64,
             tasks_active: self.task_pool.active as u64,
+            rate_bytes_per_sec,
+            eta_seconds,
         }
     }
 
@@ -531,6 +783,7 @@ impl ClientOperations for RebuildJob {
         &mut self,
     ) -> Result<oneshot::Receiver<RebuildState>, RebuildError> {
         self.exec_client_op(RebuildOperation::Start)?;
+        self.start_time.get_or_insert_with(SystemTime::now);
         let end_channel = oneshot::channel();
         self.complete_chan.push(end_channel.0);
         Ok(end_channel.1)
@@ -613,6 +866,7 @@ impl RebuildJob {
             self.task_pool.active -= 1;
             if f.error.is_none() {
                 self.task_pool.segments_done += 1;
+                self.task_pool.rate.sample(self.task_pool.segments_done);
             } else {
                 self.task_pool.tasks[f.id].error = Some(f.clone());
             }
@@ -717,6 +971,11 @@ impl RebuildStates {
         }
     }
 
+    /// the state the job is heading to, ie the pending state if there is one
+    pub(super) fn target(&self) -> RebuildState {
+        self.pending.unwrap_or(self.current)
+    }
+
     /// a change to `state` is pending
     fn pending_equals(&self, state: RebuildState) -> bool {
         self.pending == Some(state)
@@ -3,14 +3,19 @@
 //! Replica is a logical data volume exported over nvmf (in SPDK terminology
 //! an lvol). Here we define methods for easy management of replicas.
 #![allow(dead_code)]
-use std::{ffi::CStr, os::raw::c_char};
+use std::{ffi::CStr, os::raw::c_char, ptr::NonNull};
 
 use ::rpc::mayastor as rpc;
 use snafu::{ResultExt, Snafu};
 
-use spdk_sys::{spdk_lvol, vbdev_lvol_get_from_bdev};
+use spdk_sys::{lvol_md_thread, spdk_lvol, vbdev_lvol_get_from_bdev};
 
-use crate::{core::Bdev, subsys::NvmfError, target};
+use crate::{
+    core::{Bdev, Cores, Mthread},
+    lvs::Lvol,
+    subsys::NvmfError,
+    target,
+};
 
 /// These are high-level context errors one for each rpc method.
 #[derive(Debug, Snafu)]
@@ -205,10 +210,24 @@ impl Replica {
         }
     }
 
+    /// Return the reactor core running the metadata thread of the pool of
+    /// the replica, or the current core if that thread is unknown.
+    pub fn core(&self) -> u32 {
+        Mthread::from_null_checked(unsafe { lvol_md_thread(self.lvol_ptr) })
+            .and_then(|thread| thread.core())
+            .unwrap_or_else(Cores::current)
+    }
+
     /// Return if replica has been thin provisioned.
     pub fn is_thin(&self) -> bool {
         unsafe { (*self.lvol_ptr).thin_provision }
     }
+
+    /// Get number of bytes allocated to the replica in its pool.
+    pub fn get_allocated(&self) -> u64 {
+        Lvol(NonNull::new(self.lvol_ptr).expect("replica without lvol"))
+            .allocated()
+    }
 }
 
 /// Iterator over replicas
@@ -294,6 +313,8 @@ impl From<Replica> for rpc::Replica {
                 None => rpc::ShareProtocolReplica::ReplicaNone,
             } as i32,
             uri: r.get_share_uri(),
+            core: r.core(),
+            allocated_bytes: r.get_allocated(),
         }
     }
 }
@@ -43,6 +43,7 @@ impl RpcErrorCode for Error {
 
 pub(crate) mod opts;
 pub(crate) mod pool;
+pub(crate) mod prewarm;
 
 pub static CONFIG: OnceCell<Config> = OnceCell::new();
 
@@ -130,6 +131,15 @@ pub struct Config {
     pub bdev_opts: BdevOpts,
     /// nexus specific options
     pub nexus_opts: NexusOpts,
+    /// URIs of NVMe-oF targets to connect to at startup
+    pub nvme_prewarm_uris: Vec<String>,
+    /// block size of aio and uring bdevs created without a blk_size, when
+    /// the block size of the backing device cannot be detected
+    pub default_blk_size: u32,
+    /// reject device URIs with unrecognized query parameters, unless
+    /// overridden with the "strict" parameter of the URI; when off, they are
+    /// ignored with a warning instead
+    pub strict_uri_parameters: bool,
 }
 
 impl Default for Config {
@@ -141,6 +151,9 @@ impl Default for Config {
             nvme_bdev_opts: Default::default(),
             bdev_opts: Default::default(),
             nexus_opts: Default::default(),
+            nvme_prewarm_uris: Vec::new(),
+            default_blk_size: 512,
+            strict_uri_parameters: true,
         }
     }
 }
@@ -205,6 +218,9 @@ impl Config {
             nvme_bdev_opts: self.nvme_bdev_opts.get(),
             bdev_opts: self.bdev_opts.get(),
             nexus_opts: self.nexus_opts.get(),
+            nvme_prewarm_uris: self.nvme_prewarm_uris.clone(),
+            default_blk_size: self.default_blk_size,
+            strict_uri_parameters: self.strict_uri_parameters,
         }
     }
 
@@ -26,6 +26,8 @@ use std::{
     str::FromStr,
 };
 
+use crate::bdev::LabelPolicy;
+
 pub trait GetOpts {
     fn get(&self) -> Self;
     fn set(&self) -> bool {
@@ -51,6 +53,17 @@ pub struct NexusOpts {
     pub iscsi_nexus_port: u16,
     /// Port for replica target portal
     pub iscsi_replica_port: u16,
+    /// how to handle children with invalid labels when opening a nexus
+    pub label_policy: LabelPolicy,
+    /// open a nexus whose children all lack a valid label without writing
+    /// new labels, serving I/O from where the data partition would be
+    pub label_recovery: bool,
+    /// time allowed for each label read of a child when opening a nexus,
+    /// after which the child is faulted (0 disables the timeout)
+    pub label_read_timeout_ms: u64,
+    /// data copied for the writes in the background of each slow child of a
+    /// nexus, beyond which writes wait for the slow child
+    pub background_write_max_mb: u64,
 }
 
 /// Default nvmf port used for replicas.
@@ -73,6 +86,10 @@ impl Default for NexusOpts {
             iscsi_enable: true,
             iscsi_nexus_port: ISCSI_PORT_NEXUS,
             iscsi_replica_port: ISCSI_PORT_REPLICA,
+            label_policy: LabelPolicy::default(),
+            label_recovery: false,
+            label_read_timeout_ms: 5_000,
+            background_write_max_mb: 64,
         }
     }
 }
@@ -242,6 +259,8 @@ pub struct NvmeBdevOpts {
     pub io_queue_requests: u32,
     /// allow for batching of commands
     pub delay_cmd_submit: bool,
+    /// detach controllers without I/O channels after this long, 0 disables
+    pub idle_detach_timeout_ms: u64,
 }
 
 impl GetOpts for NvmeBdevOpts {
@@ -250,7 +269,11 @@ impl GetOpts for NvmeBdevOpts {
         unsafe {
             bdev_nvme_get_opts(&opts as *const _ as *mut spdk_bdev_nvme_opts)
         };
-        opts.into()
+        // not an SPDK option, so not known by SPDK
+        Self {
+            idle_detach_timeout_ms: self.idle_detach_timeout_ms,
+            ..opts.into()
+        }
     }
 
     fn set(&self) -> bool {
@@ -266,7 +289,7 @@ impl GetOpts for NvmeBdevOpts {
 impl Default for NvmeBdevOpts {
     fn default() -> Self {
         Self {
-            action_on_timeout: 4,
+            action_on_timeout: try_from_env("NVME_ACTION_ON_TIMEOUT", 4),
             timeout_us: try_from_env("NVME_TIMEOUT_US", 5_000_000),
             keep_alive_timeout_ms: try_from_env("NVME_KATO_MS", 1_000),
             retry_count: try_from_env("NVME_RETRY_COUNT", 0),
@@ -281,6 +304,10 @@ impl Default for NvmeBdevOpts {
             nvme_ioq_poll_period_us: try_from_env("NVME_IOQ_POLL_PERIOD_US", 0),
             io_queue_requests: 0,
             delay_cmd_submit: true,
+            idle_detach_timeout_ms: try_from_env(
+                "NVME_IDLE_DETACH_TIMEOUT_MS",
+                0,
+            ),
         }
     }
 }
@@ -300,6 +327,7 @@ impl From<spdk_bdev_nvme_opts> for NvmeBdevOpts {
             nvme_ioq_poll_period_us: o.nvme_ioq_poll_period_us,
             io_queue_requests: o.io_queue_requests,
             delay_cmd_submit: o.delay_cmd_submit,
+            idle_detach_timeout_ms: 0,
         }
     }
 }
@@ -166,6 +166,9 @@ struct Pool {
     name: String,
     /// bdevs to create outside of the nexus control
     disks: Vec<String>,
+    /// cluster size in bytes, 0 for the default
+    #[serde(default)]
+    cluster_size: u32,
     /// list of replicas (not required, informational only)
     #[serde(skip_serializing)]
     replicas: Option<Vec<Replica>>,
@@ -177,6 +180,7 @@ impl From<&Pool> for CreatePoolRequest {
         Self {
             name: pool.name.clone(),
             disks: pool.disks.clone(),
+            cluster_size: pool.cluster_size,
         }
     }
 }
@@ -188,6 +192,7 @@ impl From<SpdkPool> for Pool {
         Self {
             name: pool.get_name().to_string(),
             disks: vec![base.bdev_uri().unwrap_or_else(|| base.name())],
+            cluster_size: pool.get_cluster_size(),
             replicas: None,
         }
     }
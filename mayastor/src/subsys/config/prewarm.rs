@@ -0,0 +1,115 @@
+//! Pre-connect NVMe controllers of frequently used targets at startup, such
+//! that the controllers are already running when the first volume is staged
+//! and the first I/O does not have to pay for the connection setup.
+//! Targets which cannot be reached at startup are retried in the background.
+//! A pre-warmed controller is handed over to the first device create for its
+//! target, i.e. when it is added as a nexus child, rather than failing as it
+//! exists already.
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::Mutex,
+    time::Duration,
+};
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::{
+    bdev::{device_create, VerboseError},
+    core::{Cores, Reactor, Reactors},
+    sleep::mayastor_sleep,
+};
+
+/// Interval between connection attempts to unreachable targets
+const PREWARM_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+/// Number of background connection attempts before giving up
+const PREWARM_RETRIES: u32 = 60;
+
+/// Connection state of each pre-warmed target, keyed by URI
+static PREWARM_STATUS: Lazy<Mutex<BTreeMap<String, bool>>> =
+    Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+/// Names of the pre-warmed controllers which have not been handed over yet
+static PREWARMED: Lazy<Mutex<HashSet<String>>> =
+    Lazy::new(|| Mutex::new(HashSet::new()));
+
+#[derive(Debug, Serialize)]
+pub struct PrewarmStatus {
+    /// URI of the target
+    pub uri: String,
+    /// whether the controller has been connected
+    pub connected: bool,
+}
+
+/// Return the connection state of all pre-warmed targets.
+pub fn prewarm_status() -> Vec<PrewarmStatus> {
+    PREWARM_STATUS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(uri, connected)| PrewarmStatus {
+            uri: uri.clone(),
+            connected: *connected,
+        })
+        .collect()
+}
+
+/// Take over the pre-warmed controller with the given name, returning false
+/// if it was not pre-warmed or has been taken over already.
+pub(crate) fn take_prewarmed(name: &str) -> bool {
+    PREWARMED.lock().unwrap().remove(name)
+}
+
+/// Try to connect all given targets, returning the ones that failed.
+async fn connect(uris: Vec<String>) -> Vec<String> {
+    let mut failed = Vec::new();
+    for uri in uris {
+        match device_create(&uri).await {
+            Ok(name) => {
+                info!("pre-warmed NVMe controller {} for {}", name, uri);
+                PREWARMED.lock().unwrap().insert(name);
+                PREWARM_STATUS.lock().unwrap().insert(uri, true);
+            }
+            Err(error) => {
+                warn!("failed to pre-warm {}: {}", uri, error.verbose());
+                PREWARM_STATUS.lock().unwrap().insert(uri.clone(), false);
+                failed.push(uri);
+            }
+        }
+    }
+    failed
+}
+
+/// Keep retrying to connect the given targets in the background.
+async fn retry(mut uris: Vec<String>) {
+    for _ in 0 .. PREWARM_RETRIES {
+        if mayastor_sleep(PREWARM_RETRY_INTERVAL).await.is_err() {
+            error!("failed to wait for the pre-warm retry interval");
+            return;
+        }
+        uris = connect(uris).await;
+        if uris.is_empty() {
+            return;
+        }
+    }
+    warn!(
+        "giving up pre-warming {} unreachable target(s): {:?}",
+        uris.len(),
+        uris
+    );
+}
+
+/// Connect the controllers of the given targets, tolerating targets that
+/// cannot be reached right now, which are retried in the background.
+pub fn prewarm_controllers(uris: Vec<String>) {
+    assert_eq!(Cores::current(), Cores::first());
+    if uris.is_empty() {
+        return;
+    }
+
+    info!("pre-warming {} NVMe controller(s)", uris.len());
+    let failed = Reactor::block_on(connect(uris)).unwrap_or_default();
+    if !failed.is_empty() {
+        Reactors::master().send_future(retry(failed));
+    }
+}
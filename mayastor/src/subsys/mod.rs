@@ -1,9 +1,11 @@
 //!
 //! Main file to register additional subsystems
 
+pub(crate) use config::prewarm::take_prewarmed;
 pub use config::{
     opts::{NexusOpts, NvmeBdevOpts},
     pool::PoolConfig,
+    prewarm::{prewarm_controllers, prewarm_status, PrewarmStatus},
     Config,
     ConfigSubsystem,
 };
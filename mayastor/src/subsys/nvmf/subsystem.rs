@@ -15,6 +15,7 @@ use spdk_sys::{
     nvmf_subsystem_set_cntlid_range,
     spdk_bdev_nvme_opts,
     spdk_nvmf_ns_get_bdev,
+    spdk_nvmf_ns_get_id,
     spdk_nvmf_ns_opts,
     spdk_nvmf_subsystem,
     spdk_nvmf_subsystem_add_listener,
@@ -29,6 +30,7 @@ use spdk_sys::{
     spdk_nvmf_subsystem_get_nqn,
     spdk_nvmf_subsystem_listener_get_trid,
     spdk_nvmf_subsystem_pause,
+    spdk_nvmf_subsystem_remove_ns,
     spdk_nvmf_subsystem_resume,
     spdk_nvmf_subsystem_set_allow_any_host,
     spdk_nvmf_subsystem_set_ana_reporting,
@@ -195,8 +197,18 @@ impl NvmfSubsystem {
 
     /// add the given bdev to this namespace
     pub fn add_namespace(&self, bdev: &Bdev) -> Result<(), Error> {
+        self.add_namespace_with_nguid(bdev, bdev.uuid())
+    }
+
+    /// add the given bdev to this namespace, with the given NGUID rather
+    /// than the UUID of the bdev
+    pub fn add_namespace_with_nguid(
+        &self,
+        bdev: &Bdev,
+        nguid: uuid::Uuid,
+    ) -> Result<(), Error> {
         let opts = spdk_nvmf_ns_opts {
-            nguid: *bdev.uuid().as_bytes(),
+            nguid: *nguid.as_bytes(),
             ..Default::default()
         };
         let bdev_cname = CString::new(bdev.name()).unwrap();
@@ -224,6 +236,32 @@ impl NvmfSubsystem {
         }
     }
 
+    /// remove the namespace of this subsystem, releasing its bdev; the
+    /// subsystem must be paused
+    pub fn remove_namespace(&self) -> Result<(), Error> {
+        let bdev = self.bdev().map(|b| b.name()).unwrap_or_default();
+        let ns = unsafe { spdk_nvmf_subsystem_get_first_ns(self.0.as_ptr()) };
+
+        if ns.is_null() {
+            return Err(Error::RemoveNamespace {
+                bdev,
+                msg: "subsystem has no namespace".to_string(),
+            });
+        }
+
+        let ns_id = unsafe { spdk_nvmf_ns_get_id(ns) };
+        if unsafe { spdk_nvmf_subsystem_remove_ns(self.0.as_ptr(), ns_id) } != 0
+        {
+            Err(Error::RemoveNamespace {
+                bdev,
+                msg: format!("failed to remove namespace ID {}", ns_id),
+            })
+        } else {
+            info!("removed NS ID {}", ns_id);
+            Ok(())
+        }
+    }
+
     /// destroy the subsystem
     pub fn destroy(&self) {
         unsafe { spdk_nvmf_subsystem_destroy(self.0.as_ptr()) }
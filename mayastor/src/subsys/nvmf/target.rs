@@ -225,7 +225,7 @@ impl Target {
 
     /// init the poll groups implementation
     async fn create_poll_group(tgt: *mut spdk_nvmf_tgt, mt: Mthread) {
-        mt.with(|| {
+        let result = mt.with(|| {
             let pg = PollGroup::new(tgt, mt);
 
             Reactors::master().send_future(async move {
@@ -245,6 +245,10 @@ impl Target {
                 });
             });
         });
+
+        if let Err(e) = result {
+            error!("failed to create poll group: {}", e);
+        }
     }
     /// poll function that the acceptor runs
     extern "C" fn acceptor_poll(tgt: *mut c_void) -> i32 {
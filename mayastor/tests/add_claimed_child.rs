@@ -0,0 +1,47 @@
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup, VerboseError},
+    core::{Bdev, MayastorCliArgs},
+    nexus_uri::bdev_create,
+};
+
+pub mod common;
+use common::MayastorTest;
+
+#[tokio::test]
+async fn add_claimed_child() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        nexus_create(
+            "claimed_nexus",
+            32 * 1024 * 1024,
+            None,
+            &["malloc:///claimed0?size_mb=64".into()],
+        )
+        .await
+        .unwrap();
+
+        // claim the bdev as another nexus would
+        bdev_create("malloc:///claimed1?size_mb=64").await.unwrap();
+        let desc = Bdev::open_by_name("claimed1", true).unwrap();
+        assert!(desc.claim());
+
+        let nexus = nexus_lookup("claimed_nexus").unwrap();
+        let error = nexus
+            .add_child("bdev:///claimed1", false)
+            .await
+            .expect_err("added a claimed child");
+        assert!(
+            error
+                .verbose()
+                .ends_with("Child is already claimed by NEXUS_CAS_MODULE"),
+            "{}",
+            error.verbose()
+        );
+        assert_eq!(nexus.children.len(), 1);
+
+        desc.release();
+        drop(desc);
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
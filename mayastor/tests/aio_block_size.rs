@@ -0,0 +1,55 @@
+use mayastor::{
+    bdev::{device_create, device_destroy, device_lookup, nexus_create},
+    core::MayastorCliArgs,
+    subsys::Config,
+};
+
+pub mod common;
+use common::MayastorTest;
+
+static NXNAME: &str = "block_size_nexus";
+static DISK1: &str = "/tmp/block_size1.img";
+static DISK2: &str = "/tmp/block_size2.img";
+static DETECTED: &str = "aio:///tmp/block_size1.img";
+static REQUESTED: &str = "aio:///tmp/block_size2.img?blk_size=512";
+
+#[tokio::test]
+/// An aio bdev created without a blk_size gets the configured default block
+/// size, as a file has no physical block size, while a requested block size
+/// is used as is. A nexus cannot be created from children whose block sizes
+/// differ.
+async fn aio_block_size() {
+    common::truncate_file(DISK1, 64 * 1024);
+    common::truncate_file(DISK2, 64 * 1024);
+
+    Config::get_or_init(|| Config {
+        default_blk_size: 4096,
+        ..Default::default()
+    });
+
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        let name = device_create(DETECTED).await.unwrap();
+        assert_eq!(device_lookup(&name).unwrap().block_len(), 4096);
+        device_destroy(DETECTED).await.unwrap();
+
+        let name = device_create(REQUESTED).await.unwrap();
+        assert_eq!(device_lookup(&name).unwrap().block_len(), 512);
+        device_destroy(REQUESTED).await.unwrap();
+
+        assert!(nexus_create(
+            NXNAME,
+            32 * 1024 * 1024,
+            None,
+            &[DETECTED.into(), REQUESTED.into()]
+        )
+        .await
+        .is_err());
+        assert!(device_lookup(DISK1).is_none());
+        assert!(device_lookup(DISK2).is_none());
+    })
+    .await;
+
+    common::delete_file(&[DISK1.into(), DISK2.into()]);
+}
@@ -437,6 +437,7 @@ async fn unmap_share_test() {
         Lvs::create_or_import(CreatePoolRequest {
             name: "tpool".into(),
             disks: vec![BDEVNAME1.into()],
+            cluster_size: 0,
         })
         .await
         .unwrap();
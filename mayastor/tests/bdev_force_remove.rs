@@ -0,0 +1,56 @@
+use common::MayastorTest;
+use mayastor::{
+    core::{Bdev, CoreError, MayastorCliArgs, Share},
+    nexus_uri::bdev_create,
+};
+
+pub mod common;
+
+#[tokio::test]
+async fn bdev_force_remove() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    // a bdev left claimed by an owner that is gone
+    ms.spawn(async {
+        bdev_create("malloc:///leaked?blk_size=512&size_mb=8")
+            .await
+            .unwrap();
+        let desc = Bdev::open_by_name("leaked", true).unwrap();
+        assert!(desc.claim());
+        drop(desc);
+
+        let bdev = Bdev::lookup_by_name("leaked").unwrap();
+        assert!(bdev.is_claimed());
+        assert!(!bdev.is_open());
+
+        bdev.force_remove(false).await.unwrap();
+        assert!(Bdev::lookup_by_name("leaked").is_none());
+    })
+    .await;
+
+    // bdevs which may be serving I/O are only removed when forced
+    ms.spawn(async {
+        bdev_create("malloc:///busy?blk_size=512&size_mb=8")
+            .await
+            .unwrap();
+
+        let desc = Bdev::open_by_name("busy", true).unwrap();
+        let bdev = Bdev::lookup_by_name("busy").unwrap();
+        assert!(matches!(
+            bdev.force_remove(false).await,
+            Err(CoreError::BdevBusy { .. })
+        ));
+        drop(desc);
+
+        let bdev = Bdev::lookup_by_name("busy").unwrap();
+        bdev.share_nvmf(None).await.unwrap();
+        assert!(matches!(
+            bdev.clone().force_remove(false).await,
+            Err(CoreError::BdevBusy { .. })
+        ));
+
+        bdev.force_remove(true).await.unwrap();
+        assert!(Bdev::lookup_by_name("busy").is_none());
+    })
+    .await;
+}
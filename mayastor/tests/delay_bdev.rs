@@ -0,0 +1,80 @@
+use std::time::{Duration, Instant};
+
+use common::MayastorTest;
+use mayastor::{
+    core::{Bdev, MayastorCliArgs},
+    nexus_uri::{bdev_create, bdev_destroy, NexusBdevError},
+};
+
+pub mod common;
+
+const READ_LATENCY_US: u64 = 20_000;
+
+#[tokio::test]
+async fn delay_bdev() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    // invalid URIs are rejected before anything is created
+    ms.spawn(async {
+        for uri in &[
+            "delay:///",
+            "delay:///malloc0/extra",
+            "delay:///malloc0?avg_read_us=fast",
+            "delay:///malloc0?avg_read_us=100&p99_read_us=10",
+            "delay:///malloc0?avg_latency_us=100",
+        ] {
+            match bdev_create(uri).await {
+                Err(NexusBdevError::UriInvalid {
+                    ..
+                })
+                | Err(NexusBdevError::IntParamParseError {
+                    ..
+                }) => {}
+                result => panic!("{} should be invalid: {:?}", uri, result),
+            }
+        }
+
+        // the base bdev must exist
+        assert!(matches!(
+            bdev_create("delay:///malloc0?avg_read_us=10").await,
+            Err(NexusBdevError::BdevNotFound { .. })
+        ));
+    })
+    .await;
+
+    ms.spawn(async {
+        bdev_create("malloc:///malloc0?blk_size=512&size_mb=64")
+            .await
+            .unwrap();
+        let name = bdev_create(&format!(
+            "delay:///malloc0?avg_read_us={}&avg_write_us=0",
+            READ_LATENCY_US
+        ))
+        .await
+        .unwrap();
+        assert_eq!(name, "malloc0_delay");
+    })
+    .await;
+
+    ms.spawn(async {
+        let h = Bdev::open_by_name("malloc0_delay", true)
+            .unwrap()
+            .into_handle()
+            .unwrap();
+        let mut buf = h.dma_malloc(4096).unwrap();
+
+        let start = Instant::now();
+        h.read_at(0, &mut buf).await.unwrap();
+        assert!(
+            start.elapsed() >= Duration::from_micros(READ_LATENCY_US),
+            "read completed without the injected latency"
+        );
+    })
+    .await;
+
+    ms.spawn(async {
+        bdev_destroy("delay:///malloc0").await.unwrap();
+        bdev_destroy("malloc:///malloc0").await.unwrap();
+    })
+    .await;
+}
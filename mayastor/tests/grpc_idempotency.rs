@@ -0,0 +1,100 @@
+use mayastor::grpc::IDEMPOTENCY_KEY;
+use rpc::mayastor::{
+    CreatePoolRequest,
+    CreateReplicaRequest,
+    DestroyReplicaRequest,
+    Null,
+};
+use tonic::{Code, Request};
+
+pub mod common;
+use common::compose::Builder;
+
+static UUID: &str = "8f6e2b61-7c1a-4f0e-9d86-3a2b1c5d4e7f";
+
+fn keyed<T>(message: T, key: &str) -> Request<T> {
+    let mut request = Request::new(message);
+    request
+        .metadata_mut()
+        .insert(IDEMPOTENCY_KEY, key.parse().unwrap());
+    request
+}
+
+fn replica() -> CreateReplicaRequest {
+    CreateReplicaRequest {
+        uuid: UUID.to_string(),
+        pool: "tpool".to_string(),
+        size: 4 * 1024 * 1024,
+        thin: false,
+        share: 0,
+    }
+}
+
+#[tokio::test]
+async fn grpc_idempotency() {
+    let test = Builder::new()
+        .name("grpc-idempotency")
+        .with_clean(true)
+        .network("10.1.0.0/16")
+        .add_container("ms1")
+        .build()
+        .await
+        .unwrap();
+
+    let mut handles = test.grpc_handles().await.unwrap();
+    let gdl = handles.get_mut(0).unwrap();
+
+    gdl.mayastor
+        .create_pool(keyed(
+            CreatePoolRequest {
+                name: "tpool".to_string(),
+                disks: vec!["malloc:///disk0?size_mb=64".into()],
+                cluster_size: 0,
+            },
+            "pool-1",
+        ))
+        .await
+        .unwrap();
+
+    let first = gdl
+        .mayastor
+        .create_replica(keyed(replica(), "replica-1"))
+        .await
+        .unwrap()
+        .into_inner();
+
+    // remove the replica behind the back of the retried request, which must
+    // not create it again
+    gdl.mayastor
+        .destroy_replica(DestroyReplicaRequest {
+            uuid: UUID.to_string(),
+        })
+        .await
+        .unwrap();
+
+    let retried = gdl
+        .mayastor
+        .create_replica(keyed(replica(), "replica-1"))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(first, retried);
+
+    let replicas = gdl.mayastor.list_replicas(Null {}).await.unwrap();
+    assert!(replicas.into_inner().replicas.is_empty());
+
+    // a key cannot be reused for a different request
+    let mut other = replica();
+    other.size *= 2;
+    let error = gdl
+        .mayastor
+        .create_replica(keyed(other, "replica-1"))
+        .await
+        .unwrap_err();
+    assert_eq!(error.code(), Code::InvalidArgument);
+
+    // requests without a key are executed every time
+    gdl.mayastor.create_replica(replica()).await.unwrap();
+    let replicas = gdl.mayastor.list_replicas(Null {}).await.unwrap();
+    assert_eq!(replicas.into_inner().replicas.len(), 1);
+}
@@ -0,0 +1,39 @@
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup},
+    core::MayastorCliArgs,
+    nexus_uri::{bdev_create, NexusBdevError},
+};
+
+pub mod common;
+use common::MayastorTest;
+
+#[tokio::test]
+/// A loopback URI referring to a bdev which does not exist is rejected when
+/// the device is created, naming the bdev it was resolved to.
+async fn loopback_missing_bdev() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        for uri in &["bdev:///lo_typo", "loopback:///lo_typo"] {
+            match bdev_create(uri).await {
+                Err(NexusBdevError::BdevNotFound {
+                    name,
+                }) => assert_eq!(name, "lo_typo"),
+                result => panic!("unexpected result {:?} for {}", result, uri),
+            }
+        }
+
+        bdev_create("malloc:///lo_base?size_mb=64").await.unwrap();
+        let error = nexus_create(
+            "lo_nexus",
+            32 * 1024 * 1024,
+            None,
+            &["bdev:///lo_base".into(), "bdev:///lo_typo".into()],
+        )
+        .await
+        .expect_err("nexus with a missing child must not be created");
+        assert!(error.to_string().contains("bdev lo_typo not found"));
+        assert!(nexus_lookup("lo_nexus").is_none());
+    })
+    .await;
+}
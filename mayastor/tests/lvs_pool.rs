@@ -34,6 +34,7 @@ async fn lvs_pool_test() {
         Lvs::create_or_import(CreatePoolRequest {
             name: "tpool".into(),
             disks: vec!["aio:///tmp/disk1.img".into()],
+            cluster_size: 0,
         })
         .await
         .unwrap();
@@ -46,6 +47,7 @@ async fn lvs_pool_test() {
         assert!(Lvs::create_or_import(CreatePoolRequest {
             name: "tpool".into(),
             disks: vec!["aio:///tmp/disk1.img".into()],
+            cluster_size: 0,
         })
         .await
         .is_ok())
@@ -57,7 +59,9 @@ async fn lvs_pool_test() {
     // have an idempotent snafu, we dont crash and
     // burn
     ms.spawn(async {
-        assert!(Lvs::create("tpool", "aio:///tmp/disk1.img").await.is_err())
+        assert!(Lvs::create("tpool", "aio:///tmp/disk1.img", 0)
+            .await
+            .is_err())
     })
     .await;
 
@@ -111,7 +115,9 @@ async fn lvs_pool_test() {
         assert!(Lvs::import("tpool", "aio:///tmp/disk1.img").await.is_err());
 
         assert_eq!(Lvs::iter().count(), 0);
-        assert!(Lvs::create("tpool", "aio:///tmp/disk1.img").await.is_ok());
+        assert!(Lvs::create("tpool", "aio:///tmp/disk1.img", 0)
+            .await
+            .is_ok());
 
         let pool = Lvs::lookup("tpool").unwrap();
         assert_ne!(uuid, pool.uuid());
@@ -137,6 +143,7 @@ async fn lvs_pool_test() {
         let pool2 = Lvs::create_or_import(CreatePoolRequest {
             name: "tpool2".to_string(),
             disks: vec!["malloc:///malloc0?size_mb=64".to_string()],
+            cluster_size: 0,
         })
         .await
         .unwrap();
@@ -165,6 +172,7 @@ async fn lvs_pool_test() {
         let pool = Lvs::create_or_import(CreatePoolRequest {
             name: "tpool".to_string(),
             disks: vec!["aio:///tmp/disk1.img".to_string()],
+            cluster_size: 0,
         })
         .await
         .unwrap();
@@ -286,6 +294,7 @@ async fn lvs_pool_test() {
         let pool = Lvs::create_or_import(CreatePoolRequest {
             name: "tpool".into(),
             disks: vec!["aio:///tmp/disk1.img".into()],
+            cluster_size: 0,
         })
         .await
         .unwrap();
@@ -314,6 +323,7 @@ async fn lvs_pool_test() {
         Lvs::create_or_import(CreatePoolRequest {
             name: "jpool".into(),
             disks: vec!["aio:///tmp/disk1.img".into()],
+            cluster_size: 0,
         })
         .await
         .err()
@@ -328,6 +338,7 @@ async fn lvs_pool_test() {
         let pool = Lvs::create_or_import(CreatePoolRequest {
             name: "tpool2".into(),
             disks: vec!["/tmp/disk2.img".into()],
+            cluster_size: 0,
         })
         .await
         .unwrap();
@@ -0,0 +1,58 @@
+use rpc::mayastor::{CreatePoolRequest, Null};
+use tonic::Code;
+
+pub mod common;
+use common::compose::Builder;
+
+const MB: u32 = 1024 * 1024;
+
+#[tokio::test]
+async fn lvs_pool_cluster_size() {
+    let test = Builder::new()
+        .name("lvs-pool-cluster-size")
+        .with_clean(true)
+        .network("10.1.0.0/16")
+        .add_container("ms1")
+        .build()
+        .await
+        .unwrap();
+
+    let mut handles = test.grpc_handles().await.unwrap();
+    let gdl = handles.get_mut(0).unwrap();
+
+    for (name, cluster_size) in &[("small", MB), ("large", 16 * MB)] {
+        let pool = gdl
+            .mayastor
+            .create_pool(CreatePoolRequest {
+                name: name.to_string(),
+                disks: vec![format!("malloc:///{}?size_mb=128", name)],
+                cluster_size: *cluster_size,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(pool.cluster_size, *cluster_size);
+    }
+
+    let pools = gdl.mayastor.list_pools(Null {}).await.unwrap().into_inner();
+    for pool in &pools.pools {
+        let expected = if pool.name == "small" { MB } else { 16 * MB };
+        assert_eq!(pool.cluster_size, expected, "pool {}", pool.name);
+        // the capacity is allocated in whole clusters
+        assert_eq!(pool.capacity % expected as u64, 0);
+    }
+
+    // the cluster size must be a power of two within bounds
+    for cluster_size in &[3 * MB, 4096, 2048 * MB] {
+        let error = gdl
+            .mayastor
+            .create_pool(CreatePoolRequest {
+                name: "invalid".to_string(),
+                disks: vec!["malloc:///invalid?size_mb=128".into()],
+                cluster_size: *cluster_size,
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), Code::InvalidArgument);
+    }
+}
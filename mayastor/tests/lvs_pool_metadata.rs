@@ -0,0 +1,136 @@
+use rpc::mayastor::{
+    CreatePoolRequest,
+    CreateReplicaRequest,
+    ExportPoolMetadataRequest,
+    ImportPoolMetadataRequest,
+    Null,
+    ShareProtocolReplica,
+};
+use tonic::Code;
+
+pub mod common;
+use common::compose::Builder;
+
+const POOL: &str = "tpool";
+const DISK: &str = "malloc:///disk0?size_mb=64";
+const UUID1: &str = "cdc2a7db-3ac3-403a-af80-7fadc1581c47";
+const UUID2: &str = "5af42b60-5f45-4d4b-9a5c-1f0a2f1f2a10";
+
+#[tokio::test]
+/// Export the metadata of a pool and recreate the pool structure from it on
+/// a fresh instance.
+async fn lvs_pool_metadata() {
+    let test = Builder::new()
+        .name("lvs-pool-metadata")
+        .with_clean(true)
+        .network("10.1.0.0/16")
+        .add_container("ms1")
+        .add_container("ms2")
+        .build()
+        .await
+        .unwrap();
+
+    let mut hdls = test.grpc_handles().await.unwrap();
+
+    hdls[0]
+        .mayastor
+        .create_pool(CreatePoolRequest {
+            name: POOL.to_string(),
+            disks: vec![DISK.into()],
+            cluster_size: 0,
+        })
+        .await
+        .unwrap();
+
+    for (uuid, thin, share) in &[
+        (UUID1, false, ShareProtocolReplica::ReplicaNvmf),
+        (UUID2, true, ShareProtocolReplica::ReplicaNone),
+    ] {
+        hdls[0]
+            .mayastor
+            .create_replica(CreateReplicaRequest {
+                uuid: uuid.to_string(),
+                pool: POOL.to_string(),
+                size: 8 * 1024 * 1024,
+                thin: *thin,
+                share: *share as i32,
+            })
+            .await
+            .unwrap();
+    }
+
+    let document = hdls[0]
+        .mayastor
+        .export_pool_metadata(ExportPoolMetadataRequest {
+            name: POOL.to_string(),
+        })
+        .await
+        .unwrap()
+        .into_inner()
+        .document;
+
+    // exporting an unknown pool fails
+    hdls[0]
+        .mayastor
+        .export_pool_metadata(ExportPoolMetadataRequest {
+            name: "unknown".to_string(),
+        })
+        .await
+        .expect_err("pool does not exist");
+
+    // a document with an unsupported version is rejected
+    let mut value: serde_json::Value = serde_json::from_str(&document).unwrap();
+    value["version"] = serde_json::json!(999);
+    let status = hdls[1]
+        .mayastor
+        .import_pool_metadata(ImportPoolMetadataRequest {
+            document: value.to_string(),
+        })
+        .await
+        .expect_err("version must be validated");
+    assert_eq!(status.code(), Code::InvalidArgument);
+    assert!(hdls[1]
+        .mayastor
+        .list_pools(Null {})
+        .await
+        .unwrap()
+        .into_inner()
+        .pools
+        .is_empty());
+
+    let pool = hdls[1]
+        .mayastor
+        .import_pool_metadata(ImportPoolMetadataRequest {
+            document,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(pool.name, POOL);
+
+    let mut original = hdls[0]
+        .mayastor
+        .list_replicas(Null {})
+        .await
+        .unwrap()
+        .into_inner()
+        .replicas;
+    let mut imported = hdls[1]
+        .mayastor
+        .list_replicas(Null {})
+        .await
+        .unwrap()
+        .into_inner()
+        .replicas;
+    original.sort_by(|a, b| a.uuid.cmp(&b.uuid));
+    imported.sort_by(|a, b| a.uuid.cmp(&b.uuid));
+
+    assert_eq!(imported.len(), 2);
+    for (o, i) in original.iter().zip(imported.iter()) {
+        assert_eq!(o.uuid, i.uuid);
+        assert_eq!(o.pool, i.pool);
+        assert_eq!(o.size, i.size);
+        assert_eq!(o.thin, i.thin);
+        assert_eq!(o.share, i.share);
+    }
+}
@@ -30,6 +30,7 @@ async fn lvs_pool_rpc() {
         .create_pool(CreatePoolRequest {
             name: "tpool".to_string(),
             disks: vec!["malloc:///disk0?size_mb=64".into()],
+            cluster_size: 0,
         })
         .await
         .unwrap();
@@ -38,6 +39,7 @@ async fn lvs_pool_rpc() {
         .create_pool(CreatePoolRequest {
             name: "tpool".to_string(),
             disks: vec!["malloc:///disk0?size_mb=64".into()],
+            cluster_size: 0,
         })
         .await
         .unwrap();
@@ -46,8 +48,59 @@ async fn lvs_pool_rpc() {
 
     assert_eq!(list.into_inner().pools.len(), 1);
 
-    // create replica not shared
+    // a thick replica larger than the pool is rejected up front
+    let status = gdl
+        .mayastor
+        .create_replica(CreateReplicaRequest {
+            uuid: "cdc2a7db-3ac3-403a-af80-7fadc1581c47".to_string(),
+            pool: "tpool".to_string(),
+            size: 128 * 1024 * 1024,
+            thin: false,
+            share: 0,
+        })
+        .await
+        .expect_err("replica must not fit in the pool");
+    assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+
+    // as is one whose size overflows when rounded up to whole clusters
+    let status = gdl
+        .mayastor
+        .create_replica(CreateReplicaRequest {
+            uuid: "cdc2a7db-3ac3-403a-af80-7fadc1581c47".to_string(),
+            pool: "tpool".to_string(),
+            size: u64::MAX,
+            thin: false,
+            share: 0,
+        })
+        .await
+        .expect_err("replica size must not overflow");
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+    // while a thin replica is only allocated as it is written to
+    let replica = gdl
+        .mayastor
+        .create_replica(CreateReplicaRequest {
+            uuid: "a0b3a0a6-1e4c-4b6e-8f1e-3d7a9c4b2f10".to_string(),
+            pool: "tpool".to_string(),
+            size: 128 * 1024 * 1024,
+            thin: true,
+            share: 0,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(replica.thin);
+    assert_eq!(replica.allocated_bytes, 0);
     gdl.mayastor
+        .destroy_replica(DestroyReplicaRequest {
+            uuid: replica.uuid,
+        })
+        .await
+        .unwrap();
+
+    // create replica not shared
+    let replica = gdl
+        .mayastor
         .create_replica(CreateReplicaRequest {
             uuid: "cdc2a7db-3ac3-403a-af80-7fadc1581c47".to_string(),
             pool: "tpool".to_string(),
@@ -56,7 +109,11 @@ async fn lvs_pool_rpc() {
             share: 0,
         })
         .await
-        .unwrap();
+        .unwrap()
+        .into_inner();
+    // a thick replica is allocated in full
+    assert!(!replica.thin);
+    assert_eq!(replica.allocated_bytes, replica.size);
 
     // should succeed
     gdl.mayastor
@@ -128,12 +185,52 @@ async fn lvs_pool_rpc() {
         .unwrap();
 
     // destroy the pool
+    let reply = gdl
+        .mayastor
+        .destroy_pool(DestroyPoolRequest {
+            name: "tpool".to_string(),
+            force: false,
+        })
+        .await
+        .unwrap();
+    assert!(reply.into_inner().replicas.is_empty());
+
+    // a pool with a shared replica is destroyed by force
     gdl.mayastor
+        .create_pool(CreatePoolRequest {
+            name: "tpool".to_string(),
+            disks: vec!["malloc:///disk0?size_mb=64".into()],
+            cluster_size: 0,
+        })
+        .await
+        .unwrap();
+
+    gdl.mayastor
+        .create_replica(CreateReplicaRequest {
+            uuid: "cdc2a7db-3ac3-403a-af80-7fadc1581c47".to_string(),
+            pool: "tpool".to_string(),
+            size: 4 * 1024,
+            thin: false,
+            share: 1,
+        })
+        .await
+        .unwrap();
+
+    let reply = gdl
+        .mayastor
         .destroy_pool(DestroyPoolRequest {
             name: "tpool".to_string(),
+            force: true,
         })
         .await
         .unwrap();
+    assert_eq!(
+        reply.into_inner().replicas,
+        vec!["cdc2a7db-3ac3-403a-af80-7fadc1581c47".to_string()]
+    );
+
+    let list = gdl.mayastor.list_pools(Null {}).await.unwrap();
+    assert!(list.into_inner().pools.is_empty());
 
     test.logs("ms1").await.unwrap();
 }
@@ -0,0 +1,55 @@
+use rpc::mayastor::{CreatePoolRequest, Null, TestPoolRequest};
+use tonic::Code;
+
+pub mod common;
+use common::compose::Builder;
+
+#[tokio::test]
+/// The self test writes to and reads from a scratch replica, which must not
+/// be left behind.
+async fn lvs_pool_self_test() {
+    let test = Builder::new()
+        .name("lvs-pool-self-test")
+        .with_clean(true)
+        .network("10.1.0.0/16")
+        .add_container("ms1")
+        .build()
+        .await
+        .unwrap();
+
+    let mut handles = test.grpc_handles().await.unwrap();
+    let gdl = handles.get_mut(0).unwrap();
+
+    gdl.mayastor
+        .create_pool(CreatePoolRequest {
+            name: "tpool".to_string(),
+            disks: vec!["malloc:///disk0?size_mb=64".into()],
+            cluster_size: 0,
+        })
+        .await
+        .unwrap();
+
+    let reply = gdl
+        .mayastor
+        .test_pool(TestPoolRequest {
+            name: "tpool".to_string(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(reply.success, "{}", reply.message);
+    assert!(reply.message.is_empty());
+
+    // the scratch replica is gone
+    let replicas = gdl.mayastor.list_replicas(Null {}).await.unwrap();
+    assert!(replicas.into_inner().replicas.is_empty());
+
+    let status = gdl
+        .mayastor
+        .test_pool(TestPoolRequest {
+            name: "unknown".to_string(),
+        })
+        .await
+        .expect_err("pool does not exist");
+    assert_eq!(status.code(), Code::NotFound);
+}
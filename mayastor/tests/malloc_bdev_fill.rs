@@ -0,0 +1,69 @@
+use common::MayastorTest;
+use mayastor::{
+    core::{Bdev, MayastorCliArgs},
+    nexus_uri::{bdev_create, bdev_destroy},
+};
+
+pub mod common;
+
+/// Reads the first `len` bytes and the last block of the bdev
+async fn read(name: &str, len: u64) -> (Vec<u8>, Vec<u8>) {
+    let handle = Bdev::open_by_name(name, false)
+        .unwrap()
+        .into_handle()
+        .unwrap();
+    let bdev = handle.get_bdev();
+
+    let mut head = handle.dma_malloc(len).unwrap();
+    handle.read_at(0, &mut head).await.unwrap();
+    let mut tail = handle.dma_malloc(bdev.block_len() as u64).unwrap();
+    handle
+        .read_at(bdev.size_in_bytes() - bdev.block_len() as u64, &mut tail)
+        .await
+        .unwrap();
+
+    (head.as_slice().to_vec(), tail.as_slice().to_vec())
+}
+
+#[tokio::test]
+async fn malloc_bdev_fill() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        let uris = [
+            "malloc:///fill0?size_mb=4&fill=0xa5",
+            "malloc:///fill1?size_mb=4&fill=3c",
+            "malloc:///random0?size_mb=4&pattern=random:42",
+            "malloc:///random1?size_mb=4&pattern=random:42",
+            "malloc:///random2?size_mb=4&pattern=random:7",
+        ];
+        for uri in &uris {
+            bdev_create(uri).await.unwrap();
+        }
+
+        let (head, tail) = read("fill0", 4096).await;
+        assert!(head.iter().chain(tail.iter()).all(|b| *b == 0xa5));
+        let (head, tail) = read("fill1", 4096).await;
+        assert!(head.iter().chain(tail.iter()).all(|b| *b == 0x3c));
+
+        // the same seed gives the same contents, across the whole disk
+        let random0 = read("random0", 4096).await;
+        assert_eq!(random0, read("random1", 4096).await);
+        assert_ne!(random0, read("random2", 4096).await);
+        assert!(random0.0.iter().any(|b| *b != random0.0[0]));
+
+        for uri in &uris {
+            bdev_destroy(uri).await.unwrap();
+        }
+
+        for uri in &[
+            "malloc:///bad0?size_mb=4&fill=0x100",
+            "malloc:///bad1?size_mb=4&fill=zz",
+            "malloc:///bad2?size_mb=4&pattern=random",
+            "malloc:///bad3?size_mb=4&pattern=zero:1",
+            "malloc:///bad4?size_mb=4&fill=0&pattern=random:1",
+        ] {
+            assert!(bdev_create(uri).await.is_err());
+        }
+    })
+    .await;
+}
@@ -0,0 +1,47 @@
+use mayastor::{
+    bdev::{nexus_create_with_block_len, nexus_lookup},
+    core::{Bdev, MayastorCliArgs},
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "BlockSizeNexus";
+static NEXUS_SIZE: u64 = 10 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///malloc0?blk_size=512&size_mb=10";
+static CHILD_2: &str = "malloc:///malloc1?blk_size=512&size_mb=10";
+
+#[tokio::test]
+/// The block size requested for a nexus must be valid and match the block
+/// size of its children.
+async fn nexus_block_size() {
+    let ms = common::MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        let children = [CHILD_1.to_string(), CHILD_2.to_string()];
+
+        // not a power of two
+        assert!(nexus_create_with_block_len(
+            NEXUS_NAME, NEXUS_SIZE, 1000, None, &children
+        )
+        .await
+        .is_err());
+        assert!(nexus_lookup(NEXUS_NAME).is_none());
+
+        // children with a different block size
+        assert!(nexus_create_with_block_len(
+            NEXUS_NAME, NEXUS_SIZE, 4096, None, &children
+        )
+        .await
+        .is_err());
+        assert!(nexus_lookup(NEXUS_NAME).is_none());
+
+        nexus_create_with_block_len(
+            NEXUS_NAME, NEXUS_SIZE, 512, None, &children,
+        )
+        .await
+        .unwrap();
+        assert_eq!(Bdev::lookup_by_name(NEXUS_NAME).unwrap().block_len(), 512);
+
+        nexus_lookup(NEXUS_NAME).unwrap().destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,121 @@
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup, ChildState, ConfigReason},
+    core::MayastorCliArgs,
+    lvs::Lvs,
+    nexus_uri::{bdev_create, bdev_destroy},
+};
+use rpc::mayastor::{ChildConfigReason, CreatePoolRequest};
+
+pub mod common;
+use common::MayastorTest;
+
+static NXNAME: &str = "config_nexus";
+static NXNAME_RO: &str = "config_nexus_ro";
+static BASE: &str = "malloc:///cfg_base?size_mb=64&blk_size=512";
+static SMALL: &str = "bdev:///cfg_small";
+static BLOCK: &str = "bdev:///cfg_block";
+static POOL: &str = "cfg_pool";
+static LVOL: &str = "3c8a0f5e-8e0c-4b0c-9d4b-4d3c9c0a6a51";
+
+#[tokio::test]
+/// Replace offlined children with incompatible devices and expect the reason
+/// they cannot be onlined to be reported along with their state. A read-only
+/// child is not rejected but its config reason reports it.
+async fn nexus_child_config_invalid() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        bdev_create("malloc:///cfg_small?size_mb=64&blk_size=512")
+            .await
+            .unwrap();
+        bdev_create("malloc:///cfg_block?size_mb=64&blk_size=512")
+            .await
+            .unwrap();
+        let pool = Lvs::create_or_import(CreatePoolRequest {
+            name: POOL.to_string(),
+            disks: vec![
+                "malloc:///cfg_pool_disk?size_mb=128&blk_size=512".to_string()
+            ],
+            cluster_size: 0,
+        })
+        .await
+        .unwrap();
+        let lvol = pool
+            .create_lvol(LVOL, 64 * 1024 * 1024, false)
+            .await
+            .unwrap();
+        let read_only = format!("loopback:///{}", LVOL);
+
+        nexus_create(
+            NXNAME,
+            32 * 1024 * 1024,
+            None,
+            &[BASE.into(), SMALL.into(), BLOCK.into(), read_only.clone()],
+        )
+        .await
+        .unwrap();
+
+        let nexus = nexus_lookup(NXNAME).unwrap();
+        nexus.offline_child(SMALL).await.unwrap();
+        nexus.offline_child(BLOCK).await.unwrap();
+
+        bdev_destroy("malloc:///cfg_small").await.unwrap();
+        bdev_create("malloc:///cfg_small?size_mb=16&blk_size=512")
+            .await
+            .unwrap();
+        bdev_destroy("malloc:///cfg_block").await.unwrap();
+        bdev_create("malloc:///cfg_block?size_mb=64&blk_size=4096")
+            .await
+            .unwrap();
+
+        assert!(nexus.online_child(SMALL).await.is_err());
+        assert!(nexus.online_child(BLOCK).await.is_err());
+
+        assert_eq!(
+            nexus.children[1].state(),
+            ChildState::ConfigInvalid(ConfigReason::TooSmall)
+        );
+        assert_eq!(
+            nexus.children[1].to_grpc().config_reason,
+            ChildConfigReason::ConfigReasonTooSmall as i32
+        );
+
+        assert_eq!(
+            nexus.children[2].state(),
+            ChildState::ConfigInvalid(ConfigReason::BlockSizeMismatch)
+        );
+        assert_eq!(
+            nexus.children[2].to_grpc().config_reason,
+            ChildConfigReason::ConfigReasonBlockSize as i32
+        );
+
+        assert_eq!(
+            nexus.children[0].to_grpc().config_reason,
+            ChildConfigReason::ConfigReasonNone as i32
+        );
+        assert_eq!(
+            nexus.children[3].to_grpc().config_reason,
+            ChildConfigReason::ConfigReasonNone as i32
+        );
+
+        nexus.destroy().await.unwrap();
+
+        // the lvol keeps the labels written above, so a nexus can still be
+        // opened on top of it once it is read-only
+        lvol.set_read_only().await.unwrap();
+        nexus_create(NXNAME_RO, 32 * 1024 * 1024, None, &[read_only.clone()])
+            .await
+            .unwrap();
+
+        let nexus = nexus_lookup(NXNAME_RO).unwrap();
+        assert_eq!(nexus.children[0].state(), ChildState::Open);
+        assert_eq!(
+            nexus.children[0].to_grpc().config_reason,
+            ChildConfigReason::ConfigReasonReadOnly as i32
+        );
+
+        nexus.destroy().await.unwrap();
+        Lvs::lookup(POOL).unwrap().destroy().await.unwrap();
+    })
+    .await;
+}
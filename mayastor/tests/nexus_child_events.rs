@@ -0,0 +1,49 @@
+use mayastor::{
+    bdev::{nexus_create, nexus_event, nexus_lookup, ChildEvent, Reason},
+    core::MayastorCliArgs,
+};
+use tokio::sync::broadcast::error::TryRecvError;
+
+pub mod common;
+
+static NEXUS_NAME: &str = "ChildEventsNexus";
+static NEXUS_SIZE: u64 = 10 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///malloc0?blk_size=512&size_mb=10";
+static CHILD_2: &str = "malloc:///malloc1?blk_size=512&size_mb=10";
+
+#[tokio::test]
+/// Faulting a child raises an event for every subscriber.
+async fn nexus_child_events() {
+    let ms = common::MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        nexus_create(
+            NEXUS_NAME,
+            NEXUS_SIZE,
+            None,
+            &[CHILD_1.to_string(), CHILD_2.to_string()],
+        )
+        .await
+        .unwrap();
+
+        let mut events = nexus_event::subscribe();
+
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        nexus.fault_child(CHILD_2, Reason::Rpc).await.unwrap();
+
+        let event = match events.try_recv() {
+            Ok(ChildEvent::Faulted(event)) => event,
+            other => panic!("expected a child faulted event: {:?}", other),
+        };
+        assert_eq!(event.nexus, NEXUS_NAME);
+        assert_eq!(event.child, CHILD_2);
+        assert_eq!(event.reason, Reason::Rpc);
+        assert_eq!(event.io_errors, 0);
+
+        // faulting a faulted child again is not a transition
+        nexus.fault_child(CHILD_2, Reason::Rpc).await.unwrap();
+        assert_eq!(events.try_recv().unwrap_err(), TryRecvError::Empty);
+
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
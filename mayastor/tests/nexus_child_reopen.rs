@@ -0,0 +1,46 @@
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup, ChildState, Reason},
+    core::MayastorCliArgs,
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "ReopenChildNexus";
+static NEXUS_SIZE: u64 = 10 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///reopen0?blk_size=512&size_mb=10";
+static CHILD_2: &str = "malloc:///reopen1?blk_size=512&size_mb=10";
+
+#[tokio::test]
+/// A faulted child is brought back by onlining it, without recreating the
+/// nexus, and gets rebuilt.
+async fn nexus_child_reopen() {
+    let ms = common::MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        nexus_create(
+            NEXUS_NAME,
+            NEXUS_SIZE,
+            None,
+            &[CHILD_1.to_string(), CHILD_2.to_string()],
+        )
+        .await
+        .unwrap();
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+
+        nexus.fault_child(CHILD_2, Reason::IoError).await.unwrap();
+        let child = nexus.children.iter().find(|c| c.name == CHILD_2).unwrap();
+        assert_eq!(child.state(), ChildState::Faulted(Reason::IoError));
+
+        nexus.online_child(CHILD_2).await.unwrap();
+        let child = nexus.children.iter().find(|c| c.name == CHILD_2).unwrap();
+        assert!(matches!(
+            child.state(),
+            ChildState::Faulted(Reason::OutOfSync) | ChildState::Open
+        ));
+
+        // a healthy child cannot be reopened
+        assert!(nexus.online_child(CHILD_1).await.is_err());
+
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,124 @@
+//! NVMe reservation operations on nexus children, as used for fencing
+use common::bdev_io;
+use mayastor::{bdev::nexus_create, core::MayastorCliArgs};
+use rpc::mayastor::{
+    CreateNexusRequest,
+    CreatePoolRequest,
+    CreateReplicaRequest,
+    NexusChildReservationRequest,
+    NvmeReservationAction,
+    NvmeReservationType,
+};
+
+pub mod common;
+use common::{compose::Builder, MayastorTest};
+
+static POOL_NAME: &str = "tpool";
+static NXNAME: &str = "nexus0";
+static UUID: &str = "cdc2a7db-3ac3-403a-af80-7fadc1581c47";
+static HOSTNQN: &str = "nqn.2019-05.io.openebs";
+
+#[tokio::test]
+/// Create a nexus on node 2 with a replica on node 1 as its child and have it
+/// acquire a write exclusive reservation on that child. A nexus on another
+/// host using the same replica must then be fenced off from writing to it.
+async fn nexus_child_reservation_fence() {
+    let test = Builder::new()
+        .name("nexus_child_reservation_test")
+        .network("10.1.0.0/16")
+        .add_container("ms1")
+        .add_container("ms2")
+        .with_clean(true)
+        .build()
+        .await
+        .unwrap();
+
+    let mut hdls = test.grpc_handles().await.unwrap();
+
+    // create a pool and a replica shared over nvmf on node 1
+    hdls[0]
+        .mayastor
+        .create_pool(CreatePoolRequest {
+            name: POOL_NAME.to_string(),
+            disks: vec!["malloc:///disk0?size_mb=64".into()],
+            cluster_size: 0,
+        })
+        .await
+        .unwrap();
+
+    hdls[0]
+        .mayastor
+        .create_replica(CreateReplicaRequest {
+            uuid: UUID.to_string(),
+            pool: POOL_NAME.to_string(),
+            size: 32 * 1024 * 1024,
+            thin: false,
+            share: 1,
+        })
+        .await
+        .unwrap();
+
+    let child_uri =
+        format!("nvmf://{}:8420/{}:{}", hdls[0].endpoint.ip(), HOSTNQN, UUID);
+
+    // create nexus on node 2 with the replica as child
+    hdls[1]
+        .mayastor
+        .create_nexus(CreateNexusRequest {
+            uuid: UUID.to_string(),
+            size: 32 * 1024 * 1024,
+            children: vec![child_uri.clone()],
+            dry_run: false,
+            block_size: 0,
+        })
+        .await
+        .unwrap();
+
+    // register and acquire a write exclusive reservation from node 2
+    let resv_key = 0xabcd_ef00_1234_5678;
+    for action in &[
+        NvmeReservationAction::NvmeResvRegister,
+        NvmeReservationAction::NvmeResvAcquire,
+    ] {
+        hdls[1]
+            .mayastor
+            .nexus_child_reservation(NexusChildReservationRequest {
+                uuid: UUID.to_string(),
+                uri: child_uri.clone(),
+                action: *action as i32,
+                key: resv_key,
+                preempt_key: 0,
+                resv_type: NvmeReservationType::NvmeResvTypeWriteExclusive
+                    as i32,
+            })
+            .await
+            .unwrap();
+    }
+
+    // an invalid reservation type is rejected
+    hdls[1]
+        .mayastor
+        .nexus_child_reservation(NexusChildReservationRequest {
+            uuid: UUID.to_string(),
+            uri: child_uri.clone(),
+            action: NvmeReservationAction::NvmeResvAcquire as i32,
+            key: resv_key,
+            preempt_key: 0,
+            resv_type: NvmeReservationType::NvmeResvTypeInvalid as i32,
+        })
+        .await
+        .expect_err("invalid reservation type should be rejected");
+
+    // writes from another host must be rejected by the target
+    let mayastor = MayastorTest::new(MayastorCliArgs::default());
+    mayastor
+        .spawn(async move {
+            nexus_create(NXNAME, 32 * 1024 * 1024, None, &[child_uri])
+                .await
+                .unwrap();
+            bdev_io::write_some(NXNAME, 0, 0xff)
+                .await
+                .expect_err("writes from a fenced host should fail");
+        })
+        .await;
+}
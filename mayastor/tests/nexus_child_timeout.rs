@@ -0,0 +1,88 @@
+use mayastor::{
+    bdev::{device_lookup, nexus_create, nexus_lookup},
+    core::{Bdev, DeviceTimeoutAction, MayastorCliArgs, Share},
+    nexus_uri::{bdev_create, bdev_get_name},
+    subsys::{Config, NvmfSubsystem},
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "TimeoutChildNexus";
+static NEXUS_SIZE: u64 = 10 * 1024 * 1024;
+static LOCAL_CHILD: &str = "malloc:///timeout1?blk_size=512&size_mb=10";
+
+/// I/O timeout of the remote child, well above the global default.
+const CHILD_IO_TIMEOUT_US: u64 = 120_000_000;
+
+#[tokio::test]
+/// The I/O timeout of a child applies to the controller of that child only,
+/// and can be restored to the global default.
+async fn nexus_child_timeout() {
+    let ms = common::MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        bdev_create("malloc:///timeout0?blk_size=512&size_mb=10")
+            .await
+            .unwrap();
+        let bdev = Bdev::lookup_by_name("timeout0").unwrap();
+        bdev.share_nvmf(None).await.unwrap();
+        let remote_child = NvmfSubsystem::nqn_lookup("timeout0")
+            .unwrap()
+            .uri_endpoints()
+            .unwrap()
+            .remove(0);
+
+        nexus_create(
+            NEXUS_NAME,
+            NEXUS_SIZE,
+            None,
+            &[remote_child.clone(), LOCAL_CHILD.to_string()],
+        )
+        .await
+        .unwrap();
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+
+        nexus
+            .set_child_io_timeout(&remote_child, Some(CHILD_IO_TIMEOUT_US))
+            .unwrap();
+        let child = nexus
+            .children
+            .iter()
+            .find(|c| c.name == remote_child)
+            .unwrap();
+        assert_eq!(child.io_timeout_us(), Some(CHILD_IO_TIMEOUT_US));
+
+        let device = device_lookup(&bdev_get_name(&remote_child).unwrap())
+            .expect("child device must exist");
+        let io_controller = device.get_io_controller().unwrap();
+        assert_eq!(
+            io_controller.get_timeout_us().unwrap(),
+            CHILD_IO_TIMEOUT_US
+        );
+        assert_eq!(
+            io_controller.get_timeout_action().unwrap(),
+            DeviceTimeoutAction::Reset
+        );
+
+        // restore the global default
+        nexus.set_child_io_timeout(&remote_child, None).unwrap();
+        assert_eq!(
+            io_controller.get_timeout_us().unwrap(),
+            Config::get().nvme_bdev_opts.timeout_us
+        );
+
+        // a local child has no controller to reset
+        assert!(nexus
+            .set_child_io_timeout(LOCAL_CHILD, Some(CHILD_IO_TIMEOUT_US))
+            .is_err());
+        let child = nexus
+            .children
+            .iter()
+            .find(|c| c.name == LOCAL_CHILD)
+            .unwrap();
+        assert_eq!(child.io_timeout_us(), None);
+
+        nexus.destroy().await.unwrap();
+        bdev.unshare().await.unwrap();
+    })
+    .await;
+}
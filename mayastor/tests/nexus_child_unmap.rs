@@ -0,0 +1,59 @@
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup},
+    core::{BlockDeviceHandle, IoType, MayastorCliArgs},
+};
+
+pub mod common;
+use common::MayastorTest;
+
+static NEXUS_NAME: &str = "child_unmap_nexus";
+const BLOCK_SIZE: u64 = 512;
+
+#[tokio::test]
+/// Unmap and write zeroes over data written to a child, and expect it to read
+/// back as zeroes.
+async fn nexus_child_unmap() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        nexus_create(
+            NEXUS_NAME,
+            32 * 1024 * 1024,
+            None,
+            &["malloc:///unmap0?blk_size=512&size_mb=64".into()],
+        )
+        .await
+        .unwrap();
+
+        let child = &nexus_lookup(NEXUS_NAME).unwrap().children[0];
+        assert!(child.io_type_supported(IoType::Unmap));
+        assert!(child.io_type_supported(IoType::WriteZeros));
+
+        let hdl = child.get_io_handle().unwrap();
+        let mut buf = hdl.dma_malloc(64 * 1024).unwrap();
+        let offset = 1024 * 1024;
+
+        buf.fill(0xff);
+        hdl.write_at(offset, &buf).await.unwrap();
+        child.unmap_at(offset, buf.len()).await.unwrap();
+        hdl.read_at(offset, &mut buf).await.unwrap();
+        assert!(buf.as_slice().iter().all(|b| *b == 0));
+
+        buf.fill(0xff);
+        hdl.write_at(offset, &buf).await.unwrap();
+        child.write_zeroes_at(offset, buf.len()).await.unwrap();
+        hdl.read_at(offset, &mut buf).await.unwrap();
+        assert!(buf.as_slice().iter().all(|b| *b == 0));
+
+        // only whole blocks within the child can be unmapped
+        assert!(child.unmap_at(offset + 1, BLOCK_SIZE).await.is_err());
+        assert!(child.unmap_at(offset, BLOCK_SIZE - 1).await.is_err());
+        assert!(child
+            .write_zeroes_at(64 * 1024 * 1024, BLOCK_SIZE)
+            .await
+            .is_err());
+
+        nexus_lookup(NEXUS_NAME).unwrap().destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,106 @@
+use common::{compose::Builder, MayastorTest};
+use mayastor::{
+    bdev::{nexus_create, nexus_event, nexus_lookup, ChildEvent},
+    core::MayastorCliArgs,
+    subsys::{Config, NvmeBdevOpts},
+};
+use rpc::mayastor::{BdevShareRequest, BdevUri, Null};
+use tokio::{
+    sync::broadcast::error::TryRecvError,
+    time::{Duration, Instant},
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "unreachable_nexus";
+
+#[tokio::test]
+/// A child whose keep-alive times out raises an unreachable event.
+async fn nexus_child_unreachable() {
+    Config::get_or_init(|| Config {
+        nvme_bdev_opts: NvmeBdevOpts {
+            keep_alive_timeout_ms: 2_000,
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .apply();
+
+    let test = Builder::new()
+        .name("cargo-test")
+        .network("10.1.0.0/16")
+        .add_container("ms1")
+        .with_clean(true)
+        .build()
+        .await
+        .unwrap();
+
+    let mut hdls = test.grpc_handles().await.unwrap();
+    hdls[0].bdev.list(Null {}).await.unwrap();
+    hdls[0]
+        .bdev
+        .create(BdevUri {
+            uri: "malloc:///disk0?size_mb=64".into(),
+        })
+        .await
+        .unwrap();
+    hdls[0]
+        .bdev
+        .share(BdevShareRequest {
+            name: "disk0".into(),
+            proto: "nvmf".into(),
+        })
+        .await
+        .unwrap();
+
+    let child = format!(
+        "nvmf://{}:8420/nqn.2019-05.io.openebs:disk0",
+        hdls[0].endpoint.ip()
+    );
+
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    let events = {
+        let child = child.clone();
+        ms.spawn(async move {
+            nexus_create(NEXUS_NAME, 32 * 1024 * 1024, None, &[child])
+                .await
+                .unwrap();
+            nexus_event::subscribe()
+        })
+        .await
+    };
+
+    test.pause("ms1").await.unwrap();
+
+    // the keep-alive times out within a few multiples of its timeout
+    let deadline = Instant::now() + Duration::from_secs(30);
+    let mut events = Some(events);
+    let event = loop {
+        let mut rx = events.take().unwrap();
+        let (rx, event) = ms
+            .spawn(async move {
+                let event = rx.try_recv();
+                (rx, event)
+            })
+            .await;
+        match event {
+            Ok(ChildEvent::Unreachable(event)) => break event,
+            Ok(_) | Err(TryRecvError::Empty) => {}
+            Err(error) => panic!("failed to receive child events: {}", error),
+        }
+        assert!(Instant::now() < deadline, "no unreachable event raised");
+        events = Some(rx);
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    };
+
+    assert_eq!(event.nexus, NEXUS_NAME);
+    assert_eq!(event.child, child);
+
+    test.thaw("ms1").await.unwrap();
+
+    ms.spawn(async {
+        nexus_lookup(NEXUS_NAME).unwrap().destroy().await.unwrap();
+    })
+    .await;
+}
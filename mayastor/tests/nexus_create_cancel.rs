@@ -0,0 +1,93 @@
+use std::{net::TcpListener, time::Duration};
+
+use futures::channel::oneshot;
+
+use mayastor::{
+    bdev::{device_lookup, nexus_create_cancellable, nexus_lookup},
+    core::MayastorCliArgs,
+};
+
+pub mod common;
+use common::MayastorTest;
+
+static NEXUS_NAME: &str = "create_cancel_nexus";
+static NEXUS_SIZE: u64 = 10 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///d0?blk_size=512&size_mb=12";
+static CHILD_2: &str = "malloc:///d1?blk_size=512&size_mb=12";
+
+#[tokio::test]
+/// A cancelled nexus create leaves neither the nexus nor any of its children
+/// behind, a create which is not cancelled goes ahead.
+async fn nexus_create_cancel() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        let children = vec![CHILD_1.to_string(), CHILD_2.to_string()];
+
+        let (cancel, cancelled) = oneshot::channel::<()>();
+        cancel.send(()).unwrap();
+        assert!(nexus_create_cancellable(
+            NEXUS_NAME, NEXUS_SIZE, None, None, &children, cancelled
+        )
+        .await
+        .is_err());
+        assert!(nexus_lookup(NEXUS_NAME).is_none());
+        assert!(device_lookup("d0").is_none());
+        assert!(device_lookup("d1").is_none());
+
+        // dropping the sender cancels the create as well
+        let (cancel, cancelled) = oneshot::channel::<()>();
+        drop(cancel);
+        assert!(nexus_create_cancellable(
+            NEXUS_NAME, NEXUS_SIZE, None, None, &children, cancelled
+        )
+        .await
+        .is_err());
+        assert!(nexus_lookup(NEXUS_NAME).is_none());
+
+        let (_cancel, cancelled) = oneshot::channel::<()>();
+        nexus_create_cancellable(
+            NEXUS_NAME, NEXUS_SIZE, None, None, &children, cancelled,
+        )
+        .await
+        .unwrap();
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        assert_eq!(nexus.children.len(), 2);
+
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+
+    // A target which accepts the connection but never answers it, so that
+    // the creation of the second child is still going on when cancelled.
+    let target = TcpListener::bind("127.0.0.1:0").unwrap();
+    let stalled = format!(
+        "nvmf://127.0.0.1:{}/nqn.2019-05.io.openebs:stalled",
+        target.local_addr().unwrap().port()
+    );
+
+    let (cancel, cancelled) = oneshot::channel::<()>();
+    let create = ms.spawn(async move {
+        let children = vec![CHILD_1.to_string(), stalled];
+        nexus_create_cancellable(
+            NEXUS_NAME, NEXUS_SIZE, None, None, &children, cancelled,
+        )
+        .await
+        .map_err(|error| error.to_string())
+    });
+    let cancel_after_first_child = async {
+        while !ms.spawn(async { device_lookup("d0").is_some() }).await {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        cancel.send(()).unwrap();
+    };
+
+    let (result, _) = futures::join!(create, cancel_after_first_child);
+    assert!(result.unwrap_err().contains("cancelled"));
+
+    ms.spawn(async {
+        assert!(nexus_lookup(NEXUS_NAME).is_none());
+        assert!(device_lookup("d0").is_none());
+    })
+    .await;
+}
@@ -26,6 +26,8 @@ async fn nexus_create_destroy() {
                 uuid: uuid::Uuid::new_v4().to_string(),
                 size: 10 * 1024 * 1024,
                 children: vec![format!("malloc:///d{}?size_mb=10", i)],
+                dry_run: false,
+                block_size: 0,
             })
             .await;
 
@@ -83,6 +85,8 @@ async fn create_nexuses(handle: &mut RpcHandle, count: usize) -> Vec<Nexus> {
                 uuid: uuid::Uuid::new_v4().to_string(),
                 size: 10 * 1024 * 1024,
                 children: vec![format!("malloc:///d{}?size_mb=10", i)],
+                dry_run: false,
+                block_size: 0,
             })
             .await
             .unwrap();
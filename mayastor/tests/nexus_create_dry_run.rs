@@ -0,0 +1,104 @@
+use mayastor::{
+    bdev::{
+        device_create,
+        device_destroy,
+        device_lookup,
+        nexus_create_dry_run,
+        nexus_lookup,
+    },
+    core::MayastorCliArgs,
+};
+
+pub mod common;
+use common::MayastorTest;
+
+static NEXUS_NAME: &str = "dry_run_nexus";
+static NEXUS_SIZE: u64 = 8 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///d0?blk_size=512&size_mb=10";
+static CHILD_2: &str = "malloc:///d1?blk_size=512&size_mb=10";
+
+#[tokio::test]
+/// A dry run validates the children of a nexus without leaving a nexus or the
+/// devices of its children behind.
+async fn nexus_create_dry_run_test() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        let children = vec![CHILD_1.to_string(), CHILD_2.to_string()];
+        nexus_create_dry_run(NEXUS_NAME, NEXUS_SIZE, None, &children)
+            .await
+            .unwrap();
+        assert!(nexus_lookup(NEXUS_NAME).is_none());
+        assert!(device_lookup("d0").is_none());
+        assert!(device_lookup("d1").is_none());
+
+        // a device which exists already is left alone
+        device_create(CHILD_1).await.unwrap();
+        nexus_create_dry_run(NEXUS_NAME, NEXUS_SIZE, None, &children)
+            .await
+            .unwrap();
+        assert!(device_lookup("d0").is_some());
+        assert!(device_lookup("d1").is_none());
+        device_destroy(CHILD_1).await.unwrap();
+
+        // children too small for the nexus
+        assert!(nexus_create_dry_run(
+            NEXUS_NAME,
+            2 * NEXUS_SIZE,
+            None,
+            &children
+        )
+        .await
+        .is_err());
+
+        // children with different block sizes
+        assert!(nexus_create_dry_run(
+            NEXUS_NAME,
+            NEXUS_SIZE,
+            None,
+            &[
+                CHILD_1.to_string(),
+                "malloc:///d2?blk_size=4096&size_mb=10".to_string()
+            ],
+        )
+        .await
+        .is_err());
+
+        // a requested block size the children do not have, or an invalid one
+        nexus_create_dry_run(NEXUS_NAME, NEXUS_SIZE, Some(512), &children)
+            .await
+            .unwrap();
+        assert!(nexus_create_dry_run(
+            NEXUS_NAME,
+            NEXUS_SIZE,
+            Some(4096),
+            &children
+        )
+        .await
+        .is_err());
+        assert!(nexus_create_dry_run(
+            NEXUS_NAME,
+            NEXUS_SIZE,
+            Some(1000),
+            &children
+        )
+        .await
+        .is_err());
+
+        // a child URI which does not parse
+        assert!(nexus_create_dry_run(
+            NEXUS_NAME,
+            NEXUS_SIZE,
+            None,
+            &["bogus:///d3".to_string()],
+        )
+        .await
+        .is_err());
+
+        assert!(nexus_lookup(NEXUS_NAME).is_none());
+        for name in &["d0", "d1", "d2"] {
+            assert!(device_lookup(name).is_none());
+        }
+    })
+    .await;
+}
@@ -77,6 +77,12 @@ fn test_known_label() {
     let mut hdr: GptHeader = GptHeader::from_slice(&hdr_buf).unwrap();
     assert_eq!(hdr.self_checksum, CRC32);
     assert_eq!(hdr.guid.to_string(), HDR_GUID,);
+    assert!(hdr.verify_checksum().unwrap());
+
+    // a header corrupted behind the back of its checksum is rejected
+    let mut corrupted = hdr_buf;
+    corrupted[40] ^= 0x01;
+    assert!(GptHeader::from_slice(&corrupted).is_err());
 
     let mut part_buf: [u8; 128 * 128] = [0; 128 * 128];
     file.seek(SeekFrom::Start(1024)).unwrap();
@@ -0,0 +1,96 @@
+use std::{process::Command, time::SystemTime};
+
+use mayastor::{
+    bdev::{
+        nexus_create,
+        nexus_lookup,
+        ChildState,
+        Guid,
+        MetaDataIndex,
+        NexusMetaData,
+        NexusStatus,
+        Reason,
+    },
+    core::{mayastor_env_stop, MayastorCliArgs, MayastorEnvironment, Reactor},
+};
+
+const DISKNAME1: &str = "/tmp/gen_disk1.img";
+const BDEVNAME1: &str = "aio:///tmp/gen_disk1.img?blk_size=512";
+const DISKNAME2: &str = "/tmp/gen_disk2.img";
+const BDEVNAME2: &str = "aio:///tmp/gen_disk2.img?blk_size=512";
+const NXNAME: &str = "generation_nexus";
+
+pub mod common;
+
+#[test]
+fn nexus_label_generation_test() {
+    common::mayastor_test_init();
+    common::truncate_file(DISKNAME1, 64 * 1024);
+    common::truncate_file(DISKNAME2, 64 * 1024);
+
+    let status = MayastorEnvironment::new(MayastorCliArgs::default())
+        .start(|| Reactor::block_on(start()).unwrap())
+        .unwrap();
+    assert_eq!(status, 0);
+
+    for disk in &[DISKNAME1, DISKNAME2] {
+        let output = Command::new("rm")
+            .args(&["-f", disk])
+            .output()
+            .expect("failed delete test file");
+        assert!(output.status.success());
+    }
+}
+
+async fn start() {
+    let children = vec![BDEVNAME1.to_string(), BDEVNAME2.to_string()];
+    nexus_create(NXNAME, 32 * 1024 * 1024, None, &children)
+        .await
+        .unwrap();
+
+    // write an index to both children, with the second one being ahead
+    write_index(0, 1).await;
+    write_index(1, 5).await;
+
+    nexus_lookup(NXNAME).unwrap().destroy().await.unwrap();
+
+    // reopen the nexus, the child with the highest generation wins
+    nexus_create(NXNAME, 32 * 1024 * 1024, None, &children)
+        .await
+        .unwrap();
+
+    let nexus = nexus_lookup(NXNAME).unwrap();
+    assert_eq!(
+        nexus.get_child_by_name(BDEVNAME1).unwrap().state(),
+        ChildState::Faulted(Reason::OutOfSync)
+    );
+    assert_eq!(
+        nexus.get_child_by_name(BDEVNAME2).unwrap().state(),
+        ChildState::Open
+    );
+    assert_eq!(nexus.status(), NexusStatus::Degraded);
+
+    nexus.destroy().await.unwrap();
+    mayastor_env_stop(0);
+}
+
+/// Write a new index with the given generation to a child of the nexus.
+async fn write_index(child: usize, generation: u64) {
+    let nexus = nexus_lookup(NXNAME).unwrap();
+    let child = &nexus.children[child];
+
+    let mut index = MetaDataIndex::new(
+        Guid::new_random(),
+        child.guid,
+        child.metadata_index_lba,
+        4,
+    );
+    // generation is incremented when the index is written out
+    index.generation = generation - 1;
+    NexusMetaData::create_index(child, &mut index, &SystemTime::now())
+        .await
+        .unwrap();
+
+    let index = NexusMetaData::get_index(child).await.unwrap().unwrap();
+    assert_eq!(index.generation, generation);
+}
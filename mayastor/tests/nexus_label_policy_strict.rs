@@ -0,0 +1,104 @@
+use std::{
+    fs::OpenOptions,
+    io::{Read, Seek, SeekFrom, Write},
+};
+
+use common::{
+    error_bdev::{
+        create_error_bdev,
+        inject_error,
+        SPDK_BDEV_IO_TYPE_WRITE,
+        VBDEV_IO_FAILURE,
+    },
+    MayastorTest,
+};
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup, LabelPolicy},
+    core::MayastorCliArgs,
+    subsys::{Config, NexusOpts},
+};
+
+pub mod common;
+
+static NXNAME: &str = "label_policy_strict_nexus";
+static DISK: &str = "/tmp/label_policy_strict.img";
+static CHILD: &str = "aio:///tmp/label_policy_strict.img?blk_size=512";
+static ERR_DISK: &str = "/tmp/label_policy_strict_err.img";
+static ERR_DEVICE: &str = "label_policy_strict_err";
+static ERR: &str = "bdev:///EE_label_policy_strict_err";
+
+#[tokio::test]
+/// With the strict label policy a nexus does not open when a child has a bad
+/// label that cannot be rewritten.
+async fn nexus_label_policy_strict() {
+    common::truncate_file(DISK, 64 * 1024);
+    common::truncate_file(ERR_DISK, 64 * 1024);
+
+    Config::get_or_init(|| Config {
+        nexus_opts: NexusOpts {
+            label_policy: LabelPolicy::Strict,
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        create_error_bdev(ERR_DEVICE, ERR_DISK);
+        nexus_create(
+            NXNAME,
+            32 * 1024 * 1024,
+            None,
+            &[CHILD.into(), ERR.into()],
+        )
+        .await
+        .unwrap();
+        nexus_lookup(NXNAME).unwrap().destroy().await.unwrap();
+    })
+    .await;
+
+    corrupt_labels(ERR_DISK);
+
+    ms.spawn(async {
+        // the bad label cannot be replaced
+        inject_error(
+            &format!("EE_{}", ERR_DEVICE),
+            SPDK_BDEV_IO_TYPE_WRITE,
+            VBDEV_IO_FAILURE,
+            1000,
+        );
+        assert!(nexus_create(
+            NXNAME,
+            32 * 1024 * 1024,
+            None,
+            &[CHILD.into(), ERR.into()]
+        )
+        .await
+        .is_err());
+        assert!(nexus_lookup(NXNAME).is_none());
+    })
+    .await;
+
+    common::delete_file(&[DISK.into(), ERR_DISK.into()]);
+}
+
+/// Flip a bit in both GPT headers of a disk image, so that neither passes
+/// its checksum.
+fn corrupt_labels(disk: &str) {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(disk)
+        .unwrap();
+    let len = file.metadata().unwrap().len();
+    for header in &[512, len - 512] {
+        let mut byte = [0u8; 1];
+        file.seek(SeekFrom::Start(header + 40)).unwrap();
+        file.read_exact(&mut byte).unwrap();
+        byte[0] ^= 0x01;
+        file.seek(SeekFrom::Start(header + 40)).unwrap();
+        file.write_all(&byte).unwrap();
+    }
+    file.sync_all().unwrap();
+}
@@ -0,0 +1,161 @@
+use std::{
+    fs::OpenOptions,
+    io::{Read, Seek, SeekFrom, Write},
+};
+
+use common::bdev_io;
+use mayastor::{
+    bdev::{
+        nexus_create,
+        nexus_lookup,
+        ChildState,
+        NexusLabelStatus,
+        NexusStatus,
+        Reason,
+    },
+    core::{mayastor_env_stop, MayastorCliArgs, MayastorEnvironment, Reactor},
+    nexus_uri::bdev_create,
+    subsys::{Config, NexusOpts},
+};
+
+const DISKNAME1: &str = "/tmp/recovery_disk1.img";
+const BDEVNAME1: &str = "aio:///tmp/recovery_disk1.img?blk_size=512";
+const DISKNAME2: &str = "/tmp/recovery_disk2.img";
+const BDEVNAME2: &str = "aio:///tmp/recovery_disk2.img?blk_size=512";
+const NXNAME: &str = "recovery_nexus";
+const NXNAME_STALLED: &str = "recovery_stalled_nexus";
+/// a child whose reads take far longer than the label read timeout
+const STALLED: &str = "delay:///recovery_stall?avg_read_us=3000000";
+const STALL_MS: u64 = 3_000;
+
+pub mod common;
+
+#[test]
+fn nexus_label_recovery_test() {
+    common::mayastor_test_init();
+    common::truncate_file(DISKNAME1, 64 * 1024);
+    common::truncate_file(DISKNAME2, 64 * 1024);
+
+    Config::get_or_init(|| Config {
+        nexus_opts: NexusOpts {
+            label_recovery: true,
+            label_read_timeout_ms: 1_000,
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+
+    let status = MayastorEnvironment::new(MayastorCliArgs::default())
+        .start(|| {
+            Reactor::block_on(start()).unwrap();
+            // let the abandoned reads of the stalled child complete before
+            // closing it
+            common::reactor_run_millis(STALL_MS);
+            Reactor::block_on(async {
+                nexus_lookup(NXNAME_STALLED)
+                    .unwrap()
+                    .destroy()
+                    .await
+                    .unwrap();
+                mayastor_env_stop(0);
+            })
+            .unwrap();
+        })
+        .unwrap();
+    assert_eq!(status, 0);
+
+    common::delete_file(&[DISKNAME1.into(), DISKNAME2.into()]);
+}
+
+/// Flip a bit of the first usable LBA in the primary GPT header, or in the
+/// secondary one in the last block, leaving its checksum and the partition
+/// table untouched.
+fn corrupt_header(disk: &str, primary: bool) {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(disk)
+        .unwrap();
+    let header = if primary {
+        512
+    } else {
+        file.metadata().unwrap().len() - 512
+    };
+    let mut byte = [0u8; 1];
+    file.seek(SeekFrom::Start(header + 40)).unwrap();
+    file.read_exact(&mut byte).unwrap();
+    byte[0] ^= 0x01;
+    file.seek(SeekFrom::Start(header + 40)).unwrap();
+    file.write_all(&byte).unwrap();
+    file.sync_all().unwrap();
+}
+
+/// Damage both GPT labels of a disk image while leaving the data partition
+/// intact.
+fn corrupt_labels(disk: &str) {
+    corrupt_header(disk, true);
+    corrupt_header(disk, false);
+}
+
+async fn start() {
+    let children = vec![BDEVNAME1.to_string(), BDEVNAME2.to_string()];
+    // blank disks are labelled as usual, there is nothing to recover
+    nexus_create(NXNAME, 32 * 1024 * 1024, None, &children)
+        .await
+        .unwrap();
+    assert!(!nexus_lookup(NXNAME).unwrap().is_label_recovery());
+    bdev_io::write_some(NXNAME, 0, 0xaa).await.unwrap();
+    nexus_lookup(NXNAME).unwrap().destroy().await.unwrap();
+
+    corrupt_labels(DISKNAME1);
+    corrupt_labels(DISKNAME2);
+
+    // the nexus opens without labels and still serves the data
+    nexus_create(NXNAME, 32 * 1024 * 1024, None, &children)
+        .await
+        .unwrap();
+    let nexus = nexus_lookup(NXNAME).unwrap();
+    assert!(nexus.is_label_recovery());
+    assert_eq!(nexus.status(), NexusStatus::Degraded);
+    for child in &nexus.children {
+        assert!(child.probe_label().await.is_err());
+    }
+    bdev_io::read_some(NXNAME, 0, 0xaa).await.unwrap();
+
+    // rewriting the labels brings the nexus back online
+    nexus.rewrite_labels().await.unwrap();
+    assert!(!nexus.is_label_recovery());
+    assert_eq!(nexus.status(), NexusStatus::Online);
+    for child in &nexus.children {
+        assert!(child.probe_label().await.is_ok());
+    }
+    bdev_io::read_some(NXNAME, 0, 0xaa).await.unwrap();
+
+    // a primary header failing its checksum is replaced by the backup
+    corrupt_header(DISKNAME1, true);
+    let label = nexus.children[0].probe_label().await.unwrap();
+    assert_eq!(label.status, NexusLabelStatus::Secondary);
+
+    nexus.destroy().await.unwrap();
+
+    // a child whose label reads stall is faulted and left out when checking
+    // whether the labels need recovering
+    bdev_create("malloc:///recovery_stall?size_mb=64&blk_size=512")
+        .await
+        .unwrap();
+    nexus_create(
+        NXNAME_STALLED,
+        32 * 1024 * 1024,
+        None,
+        &[STALLED.to_string(), BDEVNAME1.to_string()],
+    )
+    .await
+    .unwrap();
+    let nexus = nexus_lookup(NXNAME_STALLED).unwrap();
+    assert!(!nexus.is_label_recovery());
+    assert_eq!(
+        nexus.children[0].state(),
+        ChildState::Faulted(Reason::IoError)
+    );
+    assert_eq!(nexus.children[1].state(), ChildState::Open);
+}
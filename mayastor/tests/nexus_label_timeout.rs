@@ -0,0 +1,143 @@
+use std::time::Duration;
+
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup, ChildState, Reason},
+    core::MayastorCliArgs,
+    lvs::Lvs,
+    nexus_uri::bdev_create,
+    subsys::{Config, NexusOpts},
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+use common::MayastorTest;
+
+static HEALTHY: &str = "malloc:///timeout_healthy?size_mb=64&blk_size=512";
+static POOL: &str = "timeout_pool";
+static LVOL: &str = "8d5b0bd0-4c55-4a2b-9e0a-2b4f2c6f3a11";
+
+/// label reads are given up after this long
+const LABEL_READ_TIMEOUT: Duration = Duration::from_millis(1_000);
+/// latency of the reads of a stalled child
+const STALL: Duration = Duration::from_secs(3);
+
+/// A child whose reads stall, which comes back long after the label reads
+/// have been given up.
+fn stalled(name: &str) -> String {
+    format!("delay:///{}?avg_read_us={}", name, STALL.as_micros())
+}
+
+#[tokio::test]
+/// A child whose label reads stall is faulted and the nexus is created with
+/// the remaining children, whether the stall happens while the labels are
+/// updated or while they are validated.
+async fn nexus_label_read_timeout() {
+    Config::get_or_init(|| Config {
+        nexus_opts: NexusOpts {
+            label_read_timeout_ms: LABEL_READ_TIMEOUT.as_millis() as u64,
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .apply();
+
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        for base in &["timeout_base1", "timeout_base2"] {
+            bdev_create(&format!("malloc:///{}?size_mb=64&blk_size=512", base))
+                .await
+                .unwrap();
+        }
+
+        // the stall happens while updating the labels
+        nexus_create(
+            "timeout_update",
+            32 * 1024 * 1024,
+            None,
+            &[HEALTHY.into(), stalled("timeout_base1")],
+        )
+        .await
+        .unwrap();
+
+        let nexus = nexus_lookup("timeout_update").unwrap();
+        assert_eq!(nexus.children[0].state(), ChildState::Open);
+        assert_eq!(
+            nexus.children[1].state(),
+            ChildState::Faulted(Reason::IoError)
+        );
+    })
+    .await;
+
+    // let the abandoned reads complete before closing the children
+    tokio::time::sleep(STALL).await;
+
+    ms.spawn(async {
+        nexus_lookup("timeout_update")
+            .unwrap()
+            .destroy()
+            .await
+            .unwrap();
+
+        // Labels of a read-only child cannot be updated, which stops the
+        // update before it reaches the next child. The labels are validated
+        // regardless, and the stall happens then.
+        let pool = Lvs::create_or_import(CreatePoolRequest {
+            name: POOL.to_string(),
+            disks: vec!["malloc:///timeout_pool_disk?size_mb=128&blk_size=512"
+                .to_string()],
+            cluster_size: 0,
+        })
+        .await
+        .unwrap();
+        let lvol = pool
+            .create_lvol(LVOL, 64 * 1024 * 1024, false)
+            .await
+            .unwrap();
+        let read_only = format!("loopback:///{}", LVOL);
+
+        nexus_create(
+            "timeout_labelled",
+            32 * 1024 * 1024,
+            None,
+            &[read_only.clone()],
+        )
+        .await
+        .unwrap();
+        nexus_lookup("timeout_labelled")
+            .unwrap()
+            .destroy()
+            .await
+            .unwrap();
+        lvol.set_read_only().await.unwrap();
+
+        nexus_create(
+            "timeout_validate",
+            32 * 1024 * 1024,
+            None,
+            &[read_only, stalled("timeout_base2")],
+        )
+        .await
+        .unwrap();
+
+        let nexus = nexus_lookup("timeout_validate").unwrap();
+        assert_eq!(nexus.children[0].state(), ChildState::Open);
+        assert_eq!(
+            nexus.children[1].state(),
+            ChildState::Faulted(Reason::IoError)
+        );
+    })
+    .await;
+
+    tokio::time::sleep(STALL).await;
+
+    ms.spawn(async {
+        nexus_lookup("timeout_validate")
+            .unwrap()
+            .destroy()
+            .await
+            .unwrap();
+        Lvs::lookup(POOL).unwrap().destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,55 @@
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup, Reason},
+    core::MayastorCliArgs,
+};
+use rpc::mayastor::{ListNexusRequest, NexusState};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "ListFilterNexus";
+static NEXUS_SIZE: u64 = 10 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///malloc0?blk_size=512&size_mb=10";
+static CHILD_2: &str = "malloc:///malloc1?blk_size=512&size_mb=10";
+
+fn filter(states: &[NexusState], has_faulted_child: bool) -> ListNexusRequest {
+    ListNexusRequest {
+        states: states.iter().map(|s| *s as i32).collect(),
+        has_faulted_child,
+    }
+}
+
+#[tokio::test]
+async fn nexus_list_filter() {
+    let ms = common::MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        nexus_create(
+            NEXUS_NAME,
+            NEXUS_SIZE,
+            None,
+            &[CHILD_1.to_string(), CHILD_2.to_string()],
+        )
+        .await
+        .unwrap();
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+
+        // an empty filter matches any nexus
+        assert!(nexus.matches(&ListNexusRequest::default()));
+        assert!(nexus.matches(&filter(&[NexusState::NexusOnline], false)));
+        assert!(!nexus.matches(&filter(&[NexusState::NexusDegraded], false)));
+        assert!(!nexus.matches(&filter(&[], true)));
+
+        nexus.fault_child(CHILD_2, Reason::Rpc).await.unwrap();
+
+        assert!(nexus.matches(&ListNexusRequest::default()));
+        assert!(!nexus.matches(&filter(&[NexusState::NexusOnline], false)));
+        assert!(nexus.matches(&filter(
+            &[NexusState::NexusOnline, NexusState::NexusDegraded],
+            false
+        )));
+        assert!(nexus.matches(&filter(&[], true)));
+        assert!(nexus.matches(&filter(&[NexusState::NexusDegraded], true)));
+
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
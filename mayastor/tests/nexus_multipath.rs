@@ -119,6 +119,7 @@ async fn nexus_multipath() {
         .create_pool(CreatePoolRequest {
             name: POOL_NAME.to_string(),
             disks: vec!["malloc:///disk0?size_mb=64".into()],
+            cluster_size: 0,
         })
         .await
         .unwrap();
@@ -143,6 +144,8 @@ async fn nexus_multipath() {
             uuid: UUID.to_string(),
             size: 32 * 1024 * 1024,
             children: [format!("loopback:///{}", UUID)].to_vec(),
+            dry_run: false,
+            block_size: 0,
         })
         .await
         .unwrap();
@@ -178,6 +181,7 @@ async fn nexus_multipath() {
             uuid: UUID.to_string(),
             key: "".to_string(),
             share: ShareProtocolNexus::NexusNvmf as i32,
+            min_healthy_children: 0,
         })
         .await
         .unwrap();
@@ -342,6 +346,7 @@ async fn nexus_resv_acquire() {
         .create_pool(CreatePoolRequest {
             name: POOL_NAME.to_string(),
             disks: vec!["malloc:///disk0?size_mb=64".into()],
+            cluster_size: 0,
         })
         .await
         .unwrap();
@@ -0,0 +1,46 @@
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup, Reason},
+    core::MayastorCliArgs,
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "MinHealthyNexus";
+static NEXUS_SIZE: u64 = 10 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///malloc0?blk_size=512&size_mb=10";
+static CHILD_2: &str = "malloc:///malloc1?blk_size=512&size_mb=10";
+
+#[tokio::test]
+/// A nexus is only published while enough of its children are healthy.
+async fn nexus_publish_min_healthy() {
+    let ms = common::MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        nexus_create(
+            NEXUS_NAME,
+            NEXUS_SIZE,
+            None,
+            &[CHILD_1.to_string(), CHILD_2.to_string()],
+        )
+        .await
+        .unwrap();
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+
+        assert!(nexus.check_healthy_children(0).is_ok());
+        assert!(nexus.check_healthy_children(2).is_ok());
+        assert!(nexus.check_healthy_children(3).is_err());
+
+        nexus.fault_child(CHILD_2, Reason::Rpc).await.unwrap();
+
+        assert!(nexus.check_healthy_children(1).is_ok());
+        let error = nexus
+            .check_healthy_children(2)
+            .expect_err("a faulted child is not healthy");
+        assert_eq!(
+            tonic::Status::from(error).code(),
+            tonic::Code::FailedPrecondition
+        );
+
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
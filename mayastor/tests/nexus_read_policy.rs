@@ -0,0 +1,92 @@
+use common::MayastorTest;
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup, NexusReadPolicy},
+    core::{Bdev, BdevHandle, MayastorCliArgs, Share},
+    nexus_uri::bdev_create,
+    subsys::NvmfSubsystem,
+};
+
+pub mod common;
+
+static NXNAME: &str = "read_policy_nexus";
+static LOCAL: &str = "malloc:///rp_local?blk_size=512&size_mb=64";
+static NEXUS_SIZE: u64 = 32 * 1024 * 1024;
+const READS: u64 = 16;
+
+/// read READS blocks of 4KiB from the nexus
+async fn reads() {
+    let h = BdevHandle::open(NXNAME, false, false).unwrap();
+    let mut buf = h.dma_malloc(4096).unwrap();
+    for i in 0 .. READS {
+        h.read_at(i * 4096, &mut buf).await.unwrap();
+    }
+}
+
+/// number of reads completed by the bdev
+async fn read_ops(name: &str) -> u64 {
+    Bdev::lookup_by_name(name)
+        .unwrap()
+        .stats()
+        .await
+        .unwrap()
+        .num_read_ops
+}
+
+#[tokio::test]
+/// Reads are sent to the local child only, unless the read policy rotates
+/// between all children.
+async fn nexus_read_policy() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        bdev_create("malloc:///rp_remote?blk_size=512&size_mb=64")
+            .await
+            .unwrap();
+        let remote = Bdev::lookup_by_name("rp_remote").unwrap();
+        remote.share_nvmf(None).await.unwrap();
+        let remote_child = NvmfSubsystem::nqn_lookup("rp_remote")
+            .unwrap()
+            .uri_endpoints()
+            .unwrap()
+            .remove(0);
+
+        nexus_create(
+            NXNAME,
+            NEXUS_SIZE,
+            None,
+            &[remote_child, LOCAL.to_string()],
+        )
+        .await
+        .unwrap();
+        let nexus = nexus_lookup(NXNAME).unwrap();
+        assert_eq!(nexus.read_policy(), NexusReadPolicy::RoundRobin);
+
+        for policy in
+            &[NexusReadPolicy::PreferLocal, NexusReadPolicy::FailoverOnly]
+        {
+            nexus_lookup(NXNAME).unwrap().set_read_policy(*policy).await;
+            let (local, remote) =
+                (read_ops("rp_local").await, read_ops("rp_remote").await);
+            reads().await;
+            assert_eq!(read_ops("rp_local").await - local, READS);
+            assert_eq!(read_ops("rp_remote").await, remote);
+        }
+
+        nexus_lookup(NXNAME)
+            .unwrap()
+            .set_read_policy(NexusReadPolicy::RoundRobin)
+            .await;
+        let (local, remote) =
+            (read_ops("rp_local").await, read_ops("rp_remote").await);
+        reads().await;
+        assert!(read_ops("rp_local").await > local);
+        assert!(read_ops("rp_remote").await > remote);
+
+        nexus_lookup(NXNAME).unwrap().destroy().await.unwrap();
+        Bdev::lookup_by_name("rp_remote")
+            .unwrap()
+            .unshare()
+            .await
+            .unwrap();
+    })
+    .await;
+}
@@ -5,7 +5,7 @@ use once_cell::sync::{Lazy, OnceCell};
 use tracing::error;
 
 use mayastor::{
-    bdev::{device_open, nexus_lookup},
+    bdev::{device_open, nexus_lookup, RebuildRole},
     core::{MayastorCliArgs, Mthread},
     rebuild::{RebuildJob, RebuildState},
 };
@@ -269,6 +269,22 @@ async fn rebuild_replica() {
         nexus.pause_rebuild(&get_dev(NUM_CHILDREN)).await.unwrap();
         assert_eq!(RebuildJob::lookup_src(&src).len(), 1);
 
+        for child in nexus.children.iter() {
+            let role = if child.name == src {
+                RebuildRole::Source
+            } else if child.name == get_dev(NUM_CHILDREN) {
+                RebuildRole::Destination
+            } else {
+                RebuildRole::None
+            };
+            assert_eq!(
+                child.rebuild_role(),
+                role,
+                "unexpected rebuild role for child {}",
+                child.name
+            );
+        }
+
         nexus
             .add_child(&get_dev(NUM_CHILDREN + 1), true)
             .await
@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup},
+    core::MayastorCliArgs,
+};
+
+pub mod common;
+use common::MayastorTest;
+
+static NEXUS_NAME: &str = "rebuild_history_nexus";
+static NEXUS_SIZE: u64 = 8 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///d0?blk_size=512&size_mb=10";
+static CHILD_2: &str = "malloc:///d1?blk_size=512&size_mb=10";
+
+#[tokio::test]
+/// A rebuild which has finished is kept in the rebuild history of the nexus.
+async fn nexus_rebuild_history() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        nexus_create(NEXUS_NAME, NEXUS_SIZE, None, &[CHILD_1.to_string()])
+            .await
+            .unwrap();
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        assert!(nexus.get_rebuild_history().records.is_empty());
+
+        nexus.add_child(CHILD_2, false).await.unwrap();
+    })
+    .await;
+
+    // the job is removed from the nexus once the rebuild has finished
+    loop {
+        let done = ms
+            .spawn(async {
+                let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+                nexus.get_rebuild_state(CHILD_2).await.is_err()
+            })
+            .await;
+        if done {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    ms.spawn(async {
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        let history = nexus.get_rebuild_history();
+        assert_eq!(history.records.len(), 1);
+
+        let record = &history.records[0];
+        assert_eq!(record.child_uri, CHILD_2);
+        assert_eq!(record.src_uri, CHILD_1);
+        assert_eq!(record.state, "completed");
+        assert!(record.error.is_empty());
+        assert!(record.start_time_ms > 0);
+        assert!(record.end_time_ms >= record.start_time_ms);
+        assert_eq!(record.bytes_recovered, NEXUS_SIZE);
+
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,64 @@
+use std::time::Duration;
+
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup},
+    core::MayastorCliArgs,
+    rebuild::{RebuildJob, RebuildState},
+};
+
+pub mod common;
+use common::{wait_for_rebuild, MayastorTest};
+
+static NEXUS_NAME: &str = "rebuild_pause_all_nexus";
+static NEXUS_SIZE: u64 = 60 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///d0?blk_size=512&size_mb=64";
+static CHILD_2: &str = "malloc:///d1?blk_size=512&size_mb=64";
+
+#[tokio::test]
+/// All rebuilds of a nexus can be paused and resumed at once, repeating
+/// either operation leaves the rebuilds as they are.
+async fn nexus_rebuild_pause_all() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        nexus_create(NEXUS_NAME, NEXUS_SIZE, None, &[CHILD_1.to_string()])
+            .await
+            .unwrap();
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+
+        // nothing to pause yet
+        assert_eq!(nexus.pause_rebuilds().await.unwrap(), 0);
+
+        nexus.add_child(CHILD_2, true).await.unwrap();
+        let _ = nexus.start_rebuild(CHILD_2).await.unwrap();
+        // keep the rebuild going for the duration of the test
+        RebuildJob::lookup(CHILD_2)
+            .unwrap()
+            .set_rate_limit(1024 * 1024);
+        wait_for_rebuild(
+            CHILD_2.to_string(),
+            RebuildState::Running,
+            Duration::from_secs(1),
+        );
+
+        assert_eq!(nexus.pause_rebuilds().await.unwrap(), 1);
+        assert_eq!(nexus.pause_rebuilds().await.unwrap(), 0);
+        wait_for_rebuild(
+            CHILD_2.to_string(),
+            RebuildState::Paused,
+            Duration::from_secs(1),
+        );
+        assert_eq!(nexus.pause_rebuilds().await.unwrap(), 0);
+
+        assert_eq!(nexus.resume_rebuilds().await.unwrap(), 1);
+        assert_eq!(nexus.resume_rebuilds().await.unwrap(), 0);
+        wait_for_rebuild(
+            CHILD_2.to_string(),
+            RebuildState::Running,
+            Duration::from_secs(1),
+        );
+
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup},
+    core::MayastorCliArgs,
+    rebuild::{RebuildJob, RebuildState},
+};
+
+pub mod common;
+use common::{wait_for_rebuild, MayastorTest};
+
+static NEXUS_NAME: &str = "rebuild_throttle_nexus";
+static NEXUS_SIZE: u64 = 60 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///d0?blk_size=512&size_mb=64";
+static CHILD_2: &str = "malloc:///d1?blk_size=512&size_mb=64";
+
+#[tokio::test]
+/// The rebuild throttle of a nexus applies to rebuilds started after it was
+/// set as well as to a rebuild in progress.
+async fn nexus_rebuild_throttle() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        nexus_create(NEXUS_NAME, NEXUS_SIZE, None, &[CHILD_1.to_string()])
+            .await
+            .unwrap();
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        nexus.set_rebuild_throttle(1024 * 1024);
+
+        nexus.add_child(CHILD_2, false).await.unwrap();
+        wait_for_rebuild(
+            CHILD_2.to_string(),
+            RebuildState::Running,
+            Duration::from_secs(1),
+        );
+        let progress = nexus.get_rebuild_progress(CHILD_2).unwrap();
+        assert_eq!(progress.rate_limit_bytes_per_sec, 1024 * 1024);
+
+        // lifting the throttle lets the running rebuild complete
+        nexus.set_rebuild_throttle(0);
+        let progress = nexus.get_rebuild_progress(CHILD_2).unwrap();
+        assert_eq!(progress.rate_limit_bytes_per_sec, 0);
+        wait_for_rebuild(
+            CHILD_2.to_string(),
+            RebuildState::Completed,
+            Duration::from_secs(10),
+        );
+        assert!(RebuildJob::lookup(CHILD_2)
+            .map_or(true, |job| job.state() == RebuildState::Completed));
+
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,58 @@
+use mayastor::{
+    bdev::{device_create, device_destroy, nexus_create, nexus_lookup},
+    core::MayastorCliArgs,
+    rebuild::RebuildJob,
+};
+
+pub mod common;
+use common::MayastorTest;
+
+static NEXUS_NAME: &str = "rebuild_undersized_nexus";
+static NEXUS_SIZE: u64 = 8 * 1024 * 1024;
+static DISK_1: &str = "malloc:///d0?blk_size=512&size_mb=10";
+static DISK_2: &str = "malloc:///d1?blk_size=512&size_mb=10";
+static DISK_2_SMALL: &str = "malloc:///d1?blk_size=512&size_mb=8";
+static CHILD_1: &str = "bdev:///d0";
+static CHILD_2: &str = "bdev:///d1";
+
+#[tokio::test]
+/// A rebuild of a child which cannot hold all of the data of the nexus is
+/// rejected before it starts.
+async fn nexus_rebuild_undersized() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        device_create(DISK_1).await.unwrap();
+        device_create(DISK_2).await.unwrap();
+        nexus_create(
+            NEXUS_NAME,
+            NEXUS_SIZE,
+            None,
+            &[CHILD_1.to_string(), CHILD_2.to_string()],
+        )
+        .await
+        .unwrap();
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+
+        // replace the device of the second child with one which is as large
+        // as the nexus, but has no room for its metadata
+        nexus.offline_child(CHILD_2).await.unwrap();
+        device_destroy(DISK_2).await.unwrap();
+        device_create(DISK_2_SMALL).await.unwrap();
+
+        let error = nexus
+            .online_child(CHILD_2)
+            .await
+            .expect_err("rebuild of an undersized child must fail");
+        assert_eq!(
+            tonic::Status::from(error).code(),
+            tonic::Code::InvalidArgument
+        );
+        assert!(RebuildJob::lookup(CHILD_2).is_err());
+
+        nexus.destroy().await.unwrap();
+        device_destroy(DISK_1).await.unwrap();
+        device_destroy(DISK_2_SMALL).await.unwrap();
+    })
+    .await;
+}
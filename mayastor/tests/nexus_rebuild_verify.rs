@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup, ChildState},
+    core::{BlockDevice, BlockDeviceHandle, MayastorCliArgs},
+    rebuild::{RebuildJob, RebuildState},
+};
+
+pub mod common;
+use common::{wait_for_rebuild, MayastorTest};
+
+static NEXUS_NAME: &str = "rebuild_verify_nexus";
+static NEXUS_SIZE: u64 = 60 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///d0?blk_size=512&size_mb=64";
+static CHILD_2: &str = "malloc:///d1?blk_size=512&size_mb=64";
+
+static CORRUPT_NEXUS_NAME: &str = "rebuild_verify_corrupt_nexus";
+static CORRUPT_CHILD_1: &str = "malloc:///d2?blk_size=512&size_mb=64";
+static CORRUPT_CHILD_2: &str = "malloc:///d3?blk_size=512&size_mb=64";
+
+#[tokio::test]
+/// A rebuild verifying its destination completes when the rebuilt data
+/// matches the source, and the percentage verified must be at most 100.
+/// A destination which is corrupted after being copied fails the verify,
+/// leaving the child faulted.
+async fn nexus_rebuild_verify() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        nexus_create(NEXUS_NAME, NEXUS_SIZE, None, &[CHILD_1.to_string()])
+            .await
+            .unwrap();
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+
+        nexus.add_child(CHILD_2, true).await.unwrap();
+        assert!(nexus.start_rebuild_with_verify(CHILD_2, 101).await.is_err());
+        assert!(RebuildJob::lookup(CHILD_2).is_err());
+
+        let _ = nexus.start_rebuild_with_verify(CHILD_2, 100).await.unwrap();
+        assert_eq!(RebuildJob::lookup(CHILD_2).unwrap().verify_percent(), 100);
+        wait_for_rebuild(
+            CHILD_2.to_string(),
+            RebuildState::Completed,
+            Duration::from_secs(10),
+        );
+        assert!(RebuildJob::lookup(CHILD_2)
+            .map_or(true, |job| job.state() == RebuildState::Completed));
+
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+
+    // throttle the copy so the destination can be corrupted before the
+    // verify, which only starts once all of the data has been copied
+    let rebuilt = ms
+        .spawn(async {
+            nexus_create(
+                CORRUPT_NEXUS_NAME,
+                NEXUS_SIZE,
+                None,
+                &[CORRUPT_CHILD_1.to_string()],
+            )
+            .await
+            .unwrap();
+            let nexus = nexus_lookup(CORRUPT_NEXUS_NAME).unwrap();
+
+            nexus.add_child(CORRUPT_CHILD_2, true).await.unwrap();
+            nexus.set_rebuild_throttle(NEXUS_SIZE / 2);
+            nexus
+                .start_rebuild_with_verify(CORRUPT_CHILD_2, 100)
+                .await
+                .unwrap()
+        })
+        .await;
+
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    ms.spawn(async {
+        let nexus = nexus_lookup(CORRUPT_NEXUS_NAME).unwrap();
+        assert_eq!(
+            RebuildJob::lookup(CORRUPT_CHILD_2).unwrap().state(),
+            RebuildState::Running
+        );
+
+        // overwrite the first segment, which has been copied already
+        let handle = nexus.children[1].get_io_handle().unwrap();
+        let block_len = handle.get_device().block_len();
+        let mut buf = handle.dma_malloc(64 * block_len).unwrap();
+        buf.fill(0xff);
+        handle
+            .write_at(nexus.data_ent_offset * block_len, &buf)
+            .await
+            .unwrap();
+
+        nexus.set_rebuild_throttle(0);
+    })
+    .await;
+
+    let state = ms.spawn(async move { rebuilt.await.unwrap() }).await;
+    assert_eq!(state, RebuildState::Failed);
+
+    ms.spawn(async {
+        let nexus = nexus_lookup(CORRUPT_NEXUS_NAME).unwrap();
+        assert!(matches!(nexus.children[1].state(), ChildState::Faulted(_)));
+        assert_ne!(nexus.children[1].state(), ChildState::Open);
+
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,86 @@
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup},
+    core::MayastorCliArgs,
+};
+use rpc::mayastor::{BdevShareRequest, BdevUri, Null, NvmeControllerState};
+
+pub mod common;
+use common::{compose::Builder, MayastorTest};
+
+static NEXUS_NAME: &str = "topology_nexus";
+
+#[tokio::test]
+/// The topology of a nexus reaches from its children down to the NVMe
+/// controller and transport of a child exported over nvmf.
+async fn nexus_topology() {
+    let test = Builder::new()
+        .name("nexus_topology_test")
+        .network("10.1.0.0/16")
+        .add_container("ms1")
+        .with_clean(true)
+        .build()
+        .await
+        .unwrap();
+
+    let mut hdls = test.grpc_handles().await.unwrap();
+
+    // Create and share a bdev over nvmf
+    hdls[0].bdev.list(Null {}).await.unwrap();
+    hdls[0]
+        .bdev
+        .create(BdevUri {
+            uri: "malloc:///disk0?size_mb=100".into(),
+        })
+        .await
+        .unwrap();
+    hdls[0]
+        .bdev
+        .share(BdevShareRequest {
+            name: "disk0".into(),
+            proto: "nvmf".into(),
+        })
+        .await
+        .unwrap();
+
+    let ip = hdls[0].endpoint.ip().to_string();
+    let remote = format!("nvmf://{}:8420/nqn.2019-05.io.openebs:disk0", ip);
+
+    let mayastor = MayastorTest::new(MayastorCliArgs::default());
+    mayastor
+        .spawn(async move {
+            nexus_create(
+                NEXUS_NAME,
+                1024 * 1024 * 50,
+                None,
+                &[
+                    "malloc:///malloc0?blk_size=512&size_mb=100".into(),
+                    remote.clone(),
+                ],
+            )
+            .await
+            .unwrap();
+
+            let nexus = nexus_lookup(NEXUS_NAME).expect("Failed to find nexus");
+            let topology = nexus.to_grpc_topology();
+            assert_eq!(topology.nexus.unwrap().uuid, NEXUS_NAME);
+            assert_eq!(topology.children.len(), 2);
+
+            // a local child has no controller
+            let local = &topology.children[0];
+            assert_eq!(local.device_name, "malloc0");
+            assert!(local.controller.is_none());
+
+            let child = &topology.children[1];
+            assert_eq!(child.child.as_ref().unwrap().uri, remote);
+            assert_eq!(child.driver, "nvme");
+            let ctrlr = child.controller.as_ref().expect("no NVMe controller");
+            assert_eq!(ctrlr.name, child.device_name);
+            assert_eq!(ctrlr.state, NvmeControllerState::Running as i32);
+            assert_eq!(ctrlr.traddr, ip);
+            assert_eq!(ctrlr.trsvcid, "8420");
+            assert_eq!(ctrlr.subnqn, "nqn.2019-05.io.openebs:disk0");
+
+            nexus.destroy().await.unwrap();
+        })
+        .await;
+}
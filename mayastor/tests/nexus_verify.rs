@@ -0,0 +1,83 @@
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup},
+    core::MayastorCliArgs,
+};
+use rpc::mayastor::NexusRange;
+
+pub mod common;
+use common::MayastorTest;
+
+static NEXUS_NAME: &str = "verify_nexus";
+const MB: u64 = 1024 * 1024;
+
+#[tokio::test]
+/// Write to one child of a nexus behind its back and expect verifying the
+/// nexus to report the range written as differing.
+async fn nexus_verify() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        nexus_create(
+            NEXUS_NAME,
+            32 * MB,
+            None,
+            &[
+                "malloc:///verify0?blk_size=512&size_mb=64".into(),
+                "malloc:///verify1?blk_size=512&size_mb=64".into(),
+            ],
+        )
+        .await
+        .unwrap();
+
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        let reply = nexus.verify(Vec::new(), true).await.unwrap();
+        assert_eq!(reply.bytes_compared, 32 * MB);
+        assert!(reply.mismatches.is_empty());
+
+        // corrupt the second child within the data partition
+        let child = &nexus.children[1];
+        let hdl = child.get_io_handle().unwrap();
+        let mut buf = hdl.dma_malloc(4096).unwrap();
+        buf.fill(0xff);
+        let data_offset = nexus.data_ent_offset * 512;
+        hdl.write_at(data_offset + 5 * MB, &buf).await.unwrap();
+
+        let reply = nexus
+            .verify(
+                vec![NexusRange {
+                    offset: 4 * MB,
+                    length: 4 * MB,
+                }],
+                false,
+            )
+            .await
+            .unwrap();
+        assert_eq!(reply.bytes_compared, 4 * MB);
+        assert_eq!(reply.mismatches.len(), 1);
+        assert_eq!(reply.mismatches[0].uri, child.name);
+        assert_eq!(
+            reply.mismatches[0].ranges,
+            vec![NexusRange {
+                offset: 5 * MB,
+                length: MB,
+            }]
+        );
+
+        // ranges must be aligned and within the nexus
+        for (offset, length) in &[(1, 512), (0, 0), (31 * MB, 2 * MB)] {
+            assert!(nexus
+                .verify(
+                    vec![NexusRange {
+                        offset: *offset,
+                        length: *length,
+                    }],
+                    false,
+                )
+                .await
+                .is_err());
+        }
+
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
@@ -25,6 +25,7 @@ async fn create_replicas(h: &mut RpcHandle) {
         .create_pool(CreatePoolRequest {
             name: pool(),
             disks: vec!["malloc:///disk0?size_mb=64".into()],
+            cluster_size: 0,
         })
         .await
         .unwrap();
@@ -58,6 +59,8 @@ async fn create_nexus(h: &mut RpcHandle, children: Vec<String>) {
             uuid: uuid(),
             size: 4 * 1024 * 1024,
             children,
+            dry_run: false,
+            block_size: 0,
         })
         .await
         .unwrap();
@@ -0,0 +1,239 @@
+use std::time::{Duration, Instant};
+
+use common::{
+    bdev_io,
+    error_bdev::{
+        create_error_bdev,
+        inject_error,
+        SPDK_BDEV_IO_TYPE_WRITE,
+        VBDEV_IO_FAILURE,
+    },
+    MayastorTest,
+};
+use mayastor::{
+    bdev::{
+        nexus_create,
+        nexus_lookup,
+        ChildState,
+        NexusStatus,
+        NexusWritePolicy,
+        Reason,
+    },
+    core::{BdevHandle, MayastorCliArgs},
+    nexus_uri::bdev_create,
+    subsys::{Config, NexusOpts},
+};
+
+pub mod common;
+
+static NXNAME: &str = "write_policy_nexus";
+static FAST: &str = "malloc:///wp_fast?blk_size=512&size_mb=64";
+static SLOW_BASE: &str = "malloc:///wp_slow?blk_size=512&size_mb=64";
+static SLOW: &str = "delay:///wp_slow?avg_write_us=20000";
+
+static NXNAME_ERR: &str = "write_policy_error_nexus";
+static FAST_ERR: &str = "malloc:///wp_fast_err?blk_size=512&size_mb=64";
+static ERR_DISK: &str = "/tmp/wp_error.img";
+static ERR_DEVICE: &str = "wp_error";
+static ERR: &str = "bdev:///EE_wp_error";
+
+const WRITE_LATENCY: Duration = Duration::from_millis(20);
+const WRITES: u32 = 10;
+const NEXUS_SIZE: u64 = 32 * 1024 * 1024;
+/// data copied for the writes in the background of the slow child
+const BACKGROUND_MAX_MB: u64 = 1;
+
+/// write WRITES blocks of 4KiB to the nexus returning the time it took
+async fn timed_writes(fill: u8) -> Duration {
+    let h = BdevHandle::open(NXNAME, true, false).unwrap();
+    let mut buf = h.dma_malloc(4096).unwrap();
+    buf.fill(fill);
+
+    let start = Instant::now();
+    for i in 0 .. WRITES {
+        h.write_at(u64::from(i) * 4096, &buf).await.unwrap();
+    }
+    start.elapsed()
+}
+
+#[tokio::test]
+async fn nexus_write_policy() {
+    Config::get_or_init(|| Config {
+        nexus_opts: NexusOpts {
+            background_write_max_mb: BACKGROUND_MAX_MB,
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .apply();
+
+    common::truncate_file(ERR_DISK, 64 * 1024);
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        bdev_create(SLOW_BASE).await.unwrap();
+        nexus_create(NXNAME, NEXUS_SIZE, None, &[FAST.into(), SLOW.into()])
+            .await
+            .unwrap();
+    })
+    .await;
+
+    // all writes wait for the slow child by default
+    let sync = ms.spawn(async { timed_writes(0xaa).await }).await;
+    assert!(sync >= WRITE_LATENCY * WRITES, "{:?}", sync);
+
+    ms.spawn(async {
+        let nexus = nexus_lookup(NXNAME).unwrap();
+        assert_eq!(nexus.write_policy(), &NexusWritePolicy::WriteThroughAll);
+
+        // at least one child must be written synchronously
+        assert!(nexus
+            .set_write_policy(NexusWritePolicy::WriteBack {
+                slow_children: vec![FAST.into(), SLOW.into()],
+            })
+            .await
+            .is_err());
+
+        nexus
+            .set_write_policy(NexusWritePolicy::WriteBack {
+                slow_children: vec![SLOW.into()],
+            })
+            .await
+            .unwrap();
+    })
+    .await;
+
+    let write_back = ms.spawn(async { timed_writes(0xbb).await }).await;
+    assert!(write_back < WRITE_LATENCY * WRITES / 2, "{:?}", write_back);
+
+    // let the writes to the slow child complete in the background
+    tokio::time::sleep(WRITE_LATENCY * WRITES * 2).await;
+
+    // the slow child received the data all the same
+    ms.spawn(async {
+        let nexus = nexus_lookup(NXNAME).unwrap();
+        let offset = nexus.data_ent_offset * 512;
+        let h = BdevHandle::open("wp_slow", false, false).unwrap();
+        let mut buf = h.dma_malloc(4096 * u64::from(WRITES)).unwrap();
+        h.read_at(offset, &mut buf).await.unwrap();
+        assert!(buf.as_slice().iter().all(|b| *b == 0xbb));
+        assert_eq!(nexus.children[1].background_io(), 0);
+    })
+    .await;
+
+    // overlapping writes reach the slow child one after the other, and reads
+    // are not sent to it while any of them is pending
+    ms.spawn(async {
+        let nexus = nexus_lookup(NXNAME).unwrap();
+        let h = BdevHandle::open(NXNAME, true, false).unwrap();
+        let mut buf = h.dma_malloc(4096).unwrap();
+        for fill in 1 ..= WRITES as u8 {
+            buf.fill(fill);
+            h.write_at(0, &buf).await.unwrap();
+        }
+        assert!(nexus.children[1].background_io() > 0);
+
+        for _ in 0 .. WRITES {
+            h.read_at(0, &mut buf).await.unwrap();
+            assert!(buf.as_slice().iter().all(|b| *b == WRITES as u8));
+        }
+    })
+    .await;
+
+    tokio::time::sleep(WRITE_LATENCY * WRITES * 2).await;
+
+    ms.spawn(async {
+        let nexus = nexus_lookup(NXNAME).unwrap();
+        assert_eq!(nexus.children[1].background_io(), 0);
+        let offset = nexus.data_ent_offset * 512;
+        let h = BdevHandle::open("wp_slow", false, false).unwrap();
+        let mut buf = h.dma_malloc(4096).unwrap();
+        h.read_at(offset, &mut buf).await.unwrap();
+        assert!(buf.as_slice().iter().all(|b| *b == WRITES as u8));
+    })
+    .await;
+
+    // a flush waits for the writes in the background
+    ms.spawn(async {
+        let nexus = nexus_lookup(NXNAME).unwrap();
+        timed_writes(0xdd).await;
+        assert!(nexus.children[1].background_io() > 0);
+
+        let h = BdevHandle::open(NXNAME, true, false).unwrap();
+        h.flush().await.unwrap();
+        assert_eq!(nexus.children[1].background_io(), 0);
+    })
+    .await;
+
+    // a write whose data would exceed the limit of the data copied for the
+    // slow child waits for it
+    ms.spawn(async {
+        let nexus = nexus_lookup(NXNAME).unwrap();
+        let h = BdevHandle::open(NXNAME, true, false).unwrap();
+        let mut buf = h.dma_malloc((BACKGROUND_MAX_MB << 20) * 2).unwrap();
+        buf.fill(0xee);
+
+        let start = Instant::now();
+        h.write_at(0, &buf).await.unwrap();
+        assert!(start.elapsed() >= WRITE_LATENCY, "{:?}", start.elapsed());
+        assert_eq!(nexus.children[1].background_io(), 0);
+
+        let offset = nexus.data_ent_offset * 512;
+        let h = BdevHandle::open("wp_slow", false, false).unwrap();
+        let mut read = h.dma_malloc(buf.len()).unwrap();
+        h.read_at(offset, &mut read).await.unwrap();
+        assert!(read.as_slice().iter().all(|b| *b == 0xee));
+        drop(h);
+
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+
+    // a slow child failing a write it was not waited for is faulted, so that
+    // it gets rebuilt, while the write itself succeeds
+    ms.spawn(async {
+        create_error_bdev(ERR_DEVICE, ERR_DISK);
+        nexus_create(
+            NXNAME_ERR,
+            NEXUS_SIZE,
+            None,
+            &[FAST_ERR.into(), ERR.into()],
+        )
+        .await
+        .unwrap();
+
+        let nexus = nexus_lookup(NXNAME_ERR).unwrap();
+        nexus
+            .set_write_policy(NexusWritePolicy::WriteBack {
+                slow_children: vec![ERR.into()],
+            })
+            .await
+            .unwrap();
+
+        inject_error(
+            &format!("EE_{}", ERR_DEVICE),
+            SPDK_BDEV_IO_TYPE_WRITE,
+            VBDEV_IO_FAILURE,
+            1,
+        );
+        bdev_io::write_some(NXNAME_ERR, 0, 0xcc).await.unwrap();
+    })
+    .await;
+
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    ms.spawn(async {
+        let nexus = nexus_lookup(NXNAME_ERR).unwrap();
+        assert_eq!(
+            nexus.children[1].state(),
+            ChildState::Faulted(Reason::IoError)
+        );
+        assert_eq!(nexus.status(), NexusStatus::Degraded);
+        bdev_io::read_some(NXNAME_ERR, 0, 0xcc).await.unwrap();
+
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+
+    common::delete_file(&[ERR_DISK.into()]);
+}
@@ -0,0 +1,124 @@
+use std::time::Duration;
+
+use common::compose::{Builder, MayastorTest};
+use futures::channel::oneshot;
+use mayastor::{
+    bdev::{device_create, device_destroy, NVME_CONTROLLERS},
+    core::{MayastorCliArgs, OpCompletionCallbackArg},
+};
+use rpc::mayastor::{BdevShareRequest, BdevUri, Null};
+
+pub mod common;
+
+fn reset_done(success: bool, arg: OpCompletionCallbackArg) {
+    let sender = unsafe { Box::from_raw(arg as *mut oneshot::Sender<bool>) };
+    let _ = sender.send(success);
+}
+
+#[tokio::test]
+/// Destroying a controller while a reset is pending must not panic, and the
+/// controller must be gone from the list once it has been destroyed. Neither
+/// must dropping it while the reset is still in progress.
+async fn nvme_controller_drop() {
+    let test = Builder::new()
+        .name("cargo-test")
+        .network("10.1.0.0/16")
+        .add_container("ms1")
+        .with_clean(true)
+        .build()
+        .await
+        .unwrap();
+
+    let mut hdls = test.grpc_handles().await.unwrap();
+
+    hdls[0].bdev.list(Null {}).await.unwrap();
+    hdls[0]
+        .bdev
+        .create(BdevUri {
+            uri: "malloc:///disk0?size_mb=64".into(),
+        })
+        .await
+        .unwrap();
+    hdls[0]
+        .bdev
+        .share(BdevShareRequest {
+            name: "disk0".into(),
+            proto: "nvmf".into(),
+        })
+        .await
+        .unwrap();
+
+    let url = format!(
+        "nvmf://{}:8420/nqn.2019-05.io.openebs:disk0",
+        hdls[0].endpoint.ip()
+    );
+
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    let first_url = url.clone();
+    ms.spawn(async move {
+        let url = first_url;
+        let name = device_create(&url).await.unwrap();
+        let controller = NVME_CONTROLLERS.lookup_by_name(&name).unwrap();
+
+        let (s, r) = oneshot::channel::<bool>();
+        controller
+            .lock()
+            .reset(reset_done, Box::into_raw(Box::new(s)) as *mut _, false)
+            .unwrap();
+        drop(controller);
+
+        // the controller may refuse to be destroyed while resetting
+        if device_destroy(&url).await.is_err() {
+            r.await.unwrap();
+            device_destroy(&url).await.unwrap();
+        }
+
+        assert!(NVME_CONTROLLERS.lookup_by_name(&name).is_none());
+    })
+    .await;
+
+    // destroy the controller as soon as it lets go, without waiting for the
+    // reset, so it is dropped with the reset still in progress
+    let (name, r) = {
+        let url = url.clone();
+        ms.spawn(async move {
+            let name = device_create(&url).await.unwrap();
+            let controller = NVME_CONTROLLERS.lookup_by_name(&name).unwrap();
+
+            let (s, r) = oneshot::channel::<bool>();
+            controller
+                .lock()
+                .reset(reset_done, Box::into_raw(Box::new(s)) as *mut _, false)
+                .unwrap();
+            (name, r)
+        })
+        .await
+    };
+
+    let mut attempts = 0;
+    loop {
+        let url = url.clone();
+        if ms
+            .spawn(async move { device_destroy(&url).await.is_ok() })
+            .await
+        {
+            break;
+        }
+        attempts += 1;
+        assert!(attempts < 100, "controller never released");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    // the reset completes or is abandoned along with the controller
+    let _ = r.await;
+
+    // the target can be attached again once the detach went through
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    ms.spawn(async move {
+        assert_eq!(device_create(&url).await.unwrap(), name);
+        assert!(NVME_CONTROLLERS.lookup_by_name(&name).is_some());
+        device_destroy(&url).await.unwrap();
+    })
+    .await;
+}
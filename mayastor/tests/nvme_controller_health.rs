@@ -0,0 +1,53 @@
+use common::MayastorTest;
+use futures::channel::oneshot;
+use mayastor::{
+    bdev::{device_create, device_destroy, NvmeHealthLog, NVME_CONTROLLERS},
+    core::{Bdev, CoreError, MayastorCliArgs, Share},
+    ffihelper::{cb_arg, done_cb},
+    nexus_uri::bdev_create,
+    subsys::NvmfSubsystem,
+};
+
+pub mod common;
+
+#[tokio::test]
+/// The SMART / Health log page of an attached controller must be retrievable
+/// through the admin queue.
+async fn nvme_controller_health() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        bdev_create("malloc:///health0?size_mb=64").await.unwrap();
+        let bdev = Bdev::lookup_by_name("health0").unwrap();
+        bdev.share_nvmf(None).await.unwrap();
+
+        let uri = NvmfSubsystem::nqn_lookup("health0")
+            .unwrap()
+            .uri_endpoints()
+            .unwrap()
+            .remove(0);
+        let name = device_create(&uri).await.unwrap();
+
+        let (s, r) = oneshot::channel::<Result<NvmeHealthLog, CoreError>>();
+        NVME_CONTROLLERS
+            .lookup_by_name(&name)
+            .unwrap()
+            .lock()
+            .get_health_log(
+                |log, ch| {
+                    done_cb(ch, log);
+                },
+                cb_arg(s),
+            )
+            .unwrap();
+
+        let log = r.await.unwrap().unwrap();
+        assert_eq!(log.critical_warning, 0);
+        assert_eq!(log.media_errors, 0);
+
+        device_destroy(&uri).await.unwrap();
+
+        bdev.unshare().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,49 @@
+use common::MayastorTest;
+use mayastor::{
+    bdev::{device_create, device_destroy, NVME_CONTROLLERS},
+    core::{Bdev, MayastorCliArgs, Share},
+    nexus_uri::bdev_create,
+    subsys::NvmfSubsystem,
+};
+
+pub mod common;
+
+#[tokio::test]
+/// The identity of an attached controller and its namespace must be reported
+/// so that host devices can be correlated with replicas.
+async fn nvme_controller_info() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        bdev_create("malloc:///info0?size_mb=64").await.unwrap();
+        let bdev = Bdev::lookup_by_name("info0").unwrap();
+        bdev.share_nvmf(None).await.unwrap();
+
+        let uri = NvmfSubsystem::nqn_lookup("info0")
+            .unwrap()
+            .uri_endpoints()
+            .unwrap()
+            .remove(0);
+        let name = device_create(&uri).await.unwrap();
+
+        {
+            let ctrlr = NVME_CONTROLLERS.lookup_by_name(&name).unwrap();
+            let ctrlr = ctrlr.lock();
+
+            assert_eq!(ctrlr.model().unwrap(), "Mayastor NVMe controller");
+            assert!(!ctrlr.serial().unwrap().is_empty());
+
+            let trid = ctrlr.transport_id().unwrap();
+            assert_eq!(trid.subnqn(), "nqn.2019-05.io.openebs:info0");
+            assert!(uri.contains(&trid.traddr()));
+
+            let ns = ctrlr.namespace().unwrap();
+            assert_eq!(ns.uuid(), bdev.uuid());
+            assert_eq!(ns.nguid().len(), 32);
+        }
+
+        device_destroy(&uri).await.unwrap();
+        bdev.unshare().await.unwrap();
+    })
+    .await;
+}
@@ -1,8 +1,8 @@
-use std::{slice, str, sync::atomic::AtomicPtr};
+use std::sync::atomic::AtomicPtr;
 
 use crossbeam::atomic::AtomicCell;
 use libc::c_void;
-use once_cell::sync::{Lazy, OnceCell};
+use once_cell::sync::Lazy;
 
 use common::compose::{Builder, MayastorTest};
 use mayastor::{
@@ -12,8 +12,10 @@ use mayastor::{
         BlockDeviceHandle,
         DeviceTimeoutAction,
         DmaBuf,
+        GenericStatusCode,
         IoCompletionStatus,
         MayastorCliArgs,
+        NvmeCommandStatus,
     },
     subsys::{Config, NvmeBdevOpts},
 };
@@ -28,6 +30,8 @@ static MAYASTOR: Lazy<MayastorTest> =
     Lazy::new(|| MayastorTest::new(MayastorCliArgs::default()));
 
 static CALLBACK_FLAG: AtomicCell<bool> = AtomicCell::new(false);
+static CALLBACK_STATUS: AtomicCell<Option<IoCompletionStatus>> =
+    AtomicCell::new(None);
 
 const BUF_SIZE: u64 = 32768;
 
@@ -36,6 +40,43 @@ struct IoOpCtx {
     device_url: String,
     dma_buf: DmaBuf,
     handle: Box<dyn BlockDeviceHandle>,
+    cb_ctx: Box<CallbackCtx>,
+}
+
+/// Context passed to the I/O completion callback. The device name travels
+/// with the I/O rather than through a static, as the same test body runs
+/// more than once in this process.
+struct CallbackCtx {
+    device_name: String,
+    pattern: &'static str,
+}
+
+// Read completion callback.
+fn read_completion_callback(
+    device: &dyn BlockDevice,
+    status: IoCompletionStatus,
+    ctx: *mut c_void,
+) {
+    assert_ne!(
+        status,
+        IoCompletionStatus::Success,
+        "I/O operation completed successfully"
+    );
+    assert!(!CALLBACK_FLAG.load(), "Callback called multiple times");
+
+    let ctx = unsafe { &*(ctx as *const CallbackCtx) };
+
+    // Make sure we have the correct device.
+    assert_eq!(
+        device.device_name(),
+        ctx.device_name,
+        "Device name mismatch"
+    );
+
+    // Make sure we were passed the same pattern string as requested.
+    assert_eq!(ctx.pattern, TEST_CTX_STRING);
+    CALLBACK_STATUS.store(Some(status));
+    CALLBACK_FLAG.store(true);
 }
 
 fn get_config() -> &'static Config {
@@ -92,19 +133,15 @@ async fn test_io_timeout(action_on_timeout: DeviceTimeoutAction) {
     struct IoCtx {
         handle: Box<dyn BlockDeviceHandle>,
         device_url: String,
+        device_name: String,
     }
 
-    static DEVICE_NAME: OnceCell<String> = OnceCell::new();
-
     let cptr = MAYASTOR
         .spawn(async move {
             let device_name = device_create(&bdev_url).await.unwrap();
             let descr = device_open(&device_name, false).unwrap();
             let handle = descr.into_handle().unwrap();
 
-            // Store device name for further checking from I/O callback.
-            DEVICE_NAME.set(device_name.clone()).unwrap();
-
             // Set requested I/O timeout action.
             let device = handle.get_device();
             let mut io_controller = device.get_io_controller().unwrap();
@@ -118,6 +155,7 @@ async fn test_io_timeout(action_on_timeout: DeviceTimeoutAction) {
             AtomicPtr::new(Box::into_raw(Box::new(IoCtx {
                 handle,
                 device_url: bdev_url,
+                device_name,
             })))
         })
         .await;
@@ -128,37 +166,6 @@ async fn test_io_timeout(action_on_timeout: DeviceTimeoutAction) {
         println!("waiting for the container to be fully suspended... {}/5", i);
     }
 
-    // Read completion callback.
-    fn read_completion_callback(
-        device: &dyn BlockDevice,
-        status: IoCompletionStatus,
-        ctx: *mut c_void,
-    ) {
-        assert_ne!(
-            status,
-            IoCompletionStatus::Success,
-            "I/O operation completed successfully"
-        );
-        assert!(!CALLBACK_FLAG.load(), "Callback called multiple times");
-
-        // Make sure we have the correct device.
-        assert_eq!(
-            &device.device_name(),
-            DEVICE_NAME.get().unwrap(),
-            "Device name mismatch"
-        );
-
-        // Make sure we were passed the same pattern string as requested.
-        let s = unsafe {
-            let slice =
-                slice::from_raw_parts(ctx as *const u8, TEST_CTX_STRING.len());
-            str::from_utf8(slice).unwrap()
-        };
-
-        assert_eq!(s, TEST_CTX_STRING);
-        CALLBACK_FLAG.store(true);
-    }
-
     println!("Issuing I/O operation against disconnected device");
     let io_ctx = MAYASTOR
         .spawn(async move {
@@ -174,6 +181,10 @@ async fn test_io_timeout(action_on_timeout: DeviceTimeoutAction) {
                 device_url: ctx.device_url,
                 dma_buf: DmaBuf::new(BUF_SIZE, alignment).unwrap(),
                 handle: ctx.handle,
+                cb_ctx: Box::new(CallbackCtx {
+                    device_name: ctx.device_name,
+                    pattern: TEST_CTX_STRING,
+                }),
             };
 
             io_ctx.iov.iov_base = *io_ctx.dma_buf;
@@ -189,7 +200,7 @@ async fn test_io_timeout(action_on_timeout: DeviceTimeoutAction) {
                     (3 * 1024 * 1024) / block_len,
                     BUF_SIZE / block_len,
                     read_completion_callback,
-                    TEST_CTX_STRING.as_ptr() as *mut c_void,
+                    &*io_ctx.cb_ctx as *const CallbackCtx as *mut c_void,
                 )
                 .unwrap();
 
@@ -232,6 +243,138 @@ async fn io_timeout_reset() {
     test_io_timeout(DeviceTimeoutAction::Reset).await;
 }
 
+/// Delay the reads on the target rather than pausing it, so that it can
+/// process the abort command. The read must complete as aborted by request,
+/// whereas a controller reset would complete it as aborted due to the
+/// deletion of its submission queue.
+#[tokio::test]
+async fn io_timeout_abort() {
+    get_config().apply();
+
+    let test = Builder::new()
+        .name("cargo-test")
+        .network("10.1.0.0/16")
+        .add_container("ms1")
+        .with_clean(true)
+        .build()
+        .await
+        .unwrap();
+
+    let mut hdls = test.grpc_handles().await.unwrap();
+
+    // reads take far longer than the I/O timeout of 7 seconds
+    for h in &mut hdls {
+        h.bdev
+            .create(BdevUri {
+                uri: "malloc:///disk0?size_mb=128".into(),
+            })
+            .await
+            .unwrap();
+        h.bdev
+            .create(BdevUri {
+                uri: "delay:///disk0?avg_read_us=60000000".into(),
+            })
+            .await
+            .unwrap();
+
+        h.bdev
+            .share(BdevShareRequest {
+                name: "disk0_delay".into(),
+                proto: "nvmf".into(),
+            })
+            .await
+            .unwrap();
+    }
+
+    // request the I/O timeout action when creating the device
+    let bdev_url = format!(
+        "nvmf://{}:8420/nqn.2019-05.io.openebs:{}?timeout_action=abort",
+        hdls[0].endpoint.ip(),
+        "disk0_delay"
+    );
+
+    let io_ctx = MAYASTOR
+        .spawn(async move {
+            let device_name = device_create(&bdev_url).await.unwrap();
+            let descr = device_open(&device_name, false).unwrap();
+            let handle = descr.into_handle().unwrap();
+
+            // Check the requested I/O timeout action is in effect.
+            let (block_len, alignment) = {
+                let device = handle.get_device();
+                let io_controller = device.get_io_controller().unwrap();
+                assert_eq!(
+                    io_controller.get_timeout_action().unwrap(),
+                    DeviceTimeoutAction::Abort,
+                    "I/O timeout action mismatches"
+                );
+
+                (device.block_len(), device.alignment())
+            };
+
+            let mut io_ctx = IoOpCtx {
+                iov: iovec::default(),
+                device_url: bdev_url,
+                dma_buf: DmaBuf::new(BUF_SIZE, alignment).unwrap(),
+                handle,
+                cb_ctx: Box::new(CallbackCtx {
+                    device_name,
+                    pattern: TEST_CTX_STRING,
+                }),
+            };
+
+            io_ctx.iov.iov_base = *io_ctx.dma_buf;
+            io_ctx.iov.iov_len = BUF_SIZE;
+
+            CALLBACK_FLAG.store(false);
+            CALLBACK_STATUS.store(None);
+
+            io_ctx
+                .handle
+                .readv_blocks(
+                    &mut io_ctx.iov,
+                    1,
+                    (3 * 1024 * 1024) / block_len,
+                    BUF_SIZE / block_len,
+                    read_completion_callback,
+                    &*io_ctx.cb_ctx as *const CallbackCtx as *mut c_void,
+                )
+                .unwrap();
+
+            AtomicPtr::new(Box::into_raw(Box::new(io_ctx)))
+        })
+        .await;
+
+    // Wait up to 40 seconds till I/O is aborted, well before the delayed
+    // read would complete on its own.
+    for i in 1 .. 9 {
+        println!("waiting for I/O to be aborted... {}/8", i);
+        tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+        if CALLBACK_FLAG.load() {
+            break;
+        }
+    }
+
+    MAYASTOR
+        .spawn(async move {
+            let ctx = unsafe { Box::from_raw(io_ctx.into_inner()) };
+
+            device_destroy(&ctx.device_url).await.unwrap();
+        })
+        .await;
+
+    // Check test result after all the resources are freed.
+    assert_eq!(
+        CALLBACK_STATUS.load(),
+        Some(IoCompletionStatus::NvmeError(
+            NvmeCommandStatus::GenericCommandStatus(
+                GenericStatusCode::AbortedRequested
+            )
+        )),
+        "I/O was not aborted in response to timeout action"
+    );
+}
+
 #[tokio::test]
 async fn io_timeout_ignore() {
     get_config().apply();
@@ -275,10 +418,9 @@ async fn io_timeout_ignore() {
     struct IoCtx {
         handle: Box<dyn BlockDeviceHandle>,
         device_url: String,
+        device_name: String,
     }
 
-    static DEVICE_NAME: OnceCell<String> = OnceCell::new();
-
     let cptr = MAYASTOR
         .spawn(async move {
             let device_name = device_create(&bdev_url).await.unwrap();
@@ -299,12 +441,10 @@ async fn io_timeout_ignore() {
                 "I/O timeout action mismatches"
             );
 
-            // Store device name for further checking from I/O callback.
-            DEVICE_NAME.set(device_name.clone()).unwrap();
-
             AtomicPtr::new(Box::into_raw(Box::new(IoCtx {
                 handle,
                 device_url: bdev_url,
+                device_name,
             })))
         })
         .await;
@@ -315,37 +455,6 @@ async fn io_timeout_ignore() {
         println!("waiting for the container to be fully suspended... {}/5", i);
     }
 
-    // Read completion callback.
-    fn read_completion_callback(
-        device: &dyn BlockDevice,
-        status: IoCompletionStatus,
-        ctx: *mut c_void,
-    ) {
-        assert_ne!(
-            status,
-            IoCompletionStatus::Success,
-            "I/O operation completed successfully"
-        );
-        assert!(!CALLBACK_FLAG.load(), "Callback called multiple times");
-
-        // Make sure we have the correct device.
-        assert_eq!(
-            &device.device_name(),
-            DEVICE_NAME.get().unwrap(),
-            "Device name mismatch"
-        );
-
-        // Make sure we were passed the same pattern string as requested.
-        let s = unsafe {
-            let slice =
-                slice::from_raw_parts(ctx as *const u8, TEST_CTX_STRING.len());
-            str::from_utf8(slice).unwrap()
-        };
-
-        assert_eq!(s, TEST_CTX_STRING);
-        CALLBACK_FLAG.store(true);
-    }
-
     println!("Issuing I/O operation against disconnected device");
     // We can't use synchronous I/O operations because all I/O timeouts are
     // supposed to be ignored, so no possibility to interrupt active I/O
@@ -364,6 +473,10 @@ async fn io_timeout_ignore() {
                 device_url: ctx.device_url,
                 dma_buf: DmaBuf::new(BUF_SIZE, alignment).unwrap(),
                 handle: ctx.handle,
+                cb_ctx: Box::new(CallbackCtx {
+                    device_name: ctx.device_name,
+                    pattern: TEST_CTX_STRING,
+                }),
             };
 
             io_ctx.iov.iov_base = *io_ctx.dma_buf;
@@ -379,7 +492,7 @@ async fn io_timeout_ignore() {
                     (3 * 1024 * 1024) / block_len,
                     BUF_SIZE / block_len,
                     read_completion_callback,
-                    TEST_CTX_STRING.as_ptr() as *mut c_void,
+                    &*io_ctx.cb_ctx as *const CallbackCtx as *mut c_void,
                 )
                 .unwrap();
 
@@ -0,0 +1,107 @@
+use std::{sync::atomic::AtomicPtr, time::Duration};
+
+use common::MayastorTest;
+use mayastor::{
+    bdev::{device_create, device_open, NVME_CONTROLLERS},
+    core::{
+        Bdev,
+        BlockDeviceDescriptor,
+        BlockDeviceHandle,
+        MayastorCliArgs,
+        Share,
+    },
+    nexus_uri::bdev_create,
+    subsys::{Config, NvmeBdevOpts, NvmfSubsystem},
+};
+
+pub mod common;
+
+const IDLE_TIMEOUT_MS: u64 = 2_000;
+
+async fn controller_attached(ms: &MayastorTest<'_>, name: &str) -> bool {
+    let name = name.to_string();
+    ms.spawn(async move { NVME_CONTROLLERS.lookup_by_name(&name).is_some() })
+        .await
+}
+
+#[tokio::test]
+/// A controller without I/O channels or open descriptors is detached once the
+/// idle timeout expires, and can be attached again afterwards.
+async fn nvme_idle_detach() {
+    Config::get_or_init(|| Config {
+        nvme_bdev_opts: NvmeBdevOpts {
+            idle_detach_timeout_ms: IDLE_TIMEOUT_MS,
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    let uri = ms
+        .spawn(async {
+            bdev_create("malloc:///idle0?size_mb=64").await.unwrap();
+            Bdev::lookup_by_name("idle0")
+                .unwrap()
+                .share_nvmf(None)
+                .await
+                .unwrap();
+            NvmfSubsystem::nqn_lookup("idle0")
+                .unwrap()
+                .uri_endpoints()
+                .unwrap()
+                .remove(0)
+        })
+        .await;
+
+    // a controller with an I/O channel is never idle
+    let (name, handle) = {
+        let uri = uri.clone();
+        ms.spawn(async move {
+            let name = device_create(&uri).await.unwrap();
+            let handle =
+                device_open(&name, true).unwrap().into_handle().unwrap();
+            (name, AtomicPtr::new(Box::into_raw(Box::new(handle))))
+        })
+        .await
+    };
+
+    tokio::time::sleep(Duration::from_millis(2 * IDLE_TIMEOUT_MS)).await;
+    assert!(controller_attached(&ms, &name).await);
+
+    // it is detached once its last I/O channel is gone
+    ms.spawn(async move {
+        let handle: Box<Box<dyn BlockDeviceHandle>> =
+            unsafe { Box::from_raw(handle.into_inner()) };
+        drop(handle);
+    })
+    .await;
+
+    tokio::time::sleep(Duration::from_millis(IDLE_TIMEOUT_MS / 2)).await;
+    assert!(controller_attached(&ms, &name).await);
+
+    tokio::time::sleep(Duration::from_millis(2 * IDLE_TIMEOUT_MS)).await;
+    assert!(!controller_attached(&ms, &name).await);
+
+    // and attached again when needed, where an open descriptor without any
+    // I/O channel keeps it attached
+    let descr = ms
+        .spawn(async move {
+            let name = device_create(&uri).await.unwrap();
+            let descr = device_open(&name, true).unwrap();
+            AtomicPtr::new(Box::into_raw(Box::new(descr)))
+        })
+        .await;
+
+    tokio::time::sleep(Duration::from_millis(2 * IDLE_TIMEOUT_MS)).await;
+    assert!(controller_attached(&ms, &name).await);
+
+    ms.spawn(async move {
+        let descr: Box<Box<dyn BlockDeviceDescriptor>> =
+            unsafe { Box::from_raw(descr.into_inner()) };
+        drop(descr);
+    })
+    .await;
+
+    tokio::time::sleep(Duration::from_millis(3 * IDLE_TIMEOUT_MS)).await;
+    assert!(!controller_attached(&ms, &name).await);
+}
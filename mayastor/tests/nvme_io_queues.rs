@@ -0,0 +1,76 @@
+use common::compose::{Builder, MayastorTest};
+use mayastor::{
+    bdev::{device_create, device_destroy, NVME_CONTROLLERS},
+    core::MayastorCliArgs,
+};
+use rpc::mayastor::{BdevShareRequest, BdevUri, Null};
+
+pub mod common;
+
+fn num_io_queues(name: &str) -> u32 {
+    NVME_CONTROLLERS
+        .lookup_by_name(name)
+        .expect("controller not found")
+        .lock()
+        .num_io_queues()
+}
+
+#[tokio::test]
+async fn nvme_io_queues() {
+    let test = Builder::new()
+        .name("cargo-test")
+        .network("10.1.0.0/16")
+        .add_container("ms1")
+        .with_clean(true)
+        .build()
+        .await
+        .unwrap();
+
+    let mut hdls = test.grpc_handles().await.unwrap();
+
+    hdls[0].bdev.list(Null {}).await.unwrap();
+    hdls[0]
+        .bdev
+        .create(BdevUri {
+            uri: "malloc:///disk0?size_mb=64".into(),
+        })
+        .await
+        .unwrap();
+    hdls[0]
+        .bdev
+        .share(BdevShareRequest {
+            name: "disk0".into(),
+            proto: "nvmf".into(),
+        })
+        .await
+        .unwrap();
+
+    let url = format!(
+        "nvmf://{}:8420/nqn.2019-05.io.openebs:disk0",
+        hdls[0].endpoint.ip()
+    );
+
+    let ms = MayastorTest::new(MayastorCliArgs {
+        reactor_mask: "0x3".into(),
+        ..Default::default()
+    });
+
+    ms.spawn(async move {
+        // without a request the target grants at least one queue
+        let name = device_create(&url).await.unwrap();
+        assert!(num_io_queues(&name) > 0);
+        device_destroy(&url).await.unwrap();
+
+        // the requested number of queues is honoured by the target
+        let queues_url = format!("{}?io_queues=1", url);
+        let name = device_create(&queues_url).await.unwrap();
+        assert_eq!(num_io_queues(&name), 1);
+        device_destroy(&queues_url).await.unwrap();
+
+        // an invalid count is rejected
+        assert!(device_create(&format!("{}?io_queues=many", url))
+            .await
+            .is_err());
+    })
+    .await;
+}
@@ -0,0 +1,38 @@
+use common::MayastorTest;
+use mayastor::{
+    bdev::device_create,
+    core::MayastorCliArgs,
+    subsys::NvmfSubsystem,
+};
+
+pub mod common;
+
+#[tokio::test]
+/// Attaching to a controller which exposes no active namespace must fail
+/// cleanly instead of leaving a controller without a namespace behind.
+async fn nvme_no_namespace() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        let ss = NvmfSubsystem::new("no-namespace").unwrap();
+        ss.allow_any(true);
+        ss.start().await.unwrap();
+
+        let uri = NvmfSubsystem::nqn_lookup("no-namespace")
+            .unwrap()
+            .uri_endpoints()
+            .unwrap()
+            .remove(0);
+
+        assert!(device_create(&uri).await.is_err());
+
+        // the failed controller has been detached, so attaching again goes
+        // through the same checks rather than finding a stale controller
+        assert!(device_create(&uri).await.is_err());
+
+        let ss = NvmfSubsystem::nqn_lookup("no-namespace").unwrap();
+        ss.stop().await.unwrap();
+        ss.destroy();
+    })
+    .await;
+}
@@ -0,0 +1,80 @@
+use common::compose::{Builder, MayastorTest};
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup, NvmeControllerState, NVME_CONTROLLERS},
+    core::MayastorCliArgs,
+    subsys::{prewarm_status, Config},
+};
+use rpc::mayastor::{BdevShareRequest, BdevUri, Null};
+
+pub mod common;
+
+#[tokio::test]
+async fn nvme_prewarm_at_startup() {
+    let test = Builder::new()
+        .name("cargo-test")
+        .network("10.1.0.0/16")
+        .add_container("ms1")
+        .with_clean(true)
+        .build()
+        .await
+        .unwrap();
+
+    let mut hdls = test.grpc_handles().await.unwrap();
+
+    hdls[0].bdev.list(Null {}).await.unwrap();
+    hdls[0]
+        .bdev
+        .create(BdevUri {
+            uri: "malloc:///disk0?size_mb=64".into(),
+        })
+        .await
+        .unwrap();
+    hdls[0]
+        .bdev
+        .share(BdevShareRequest {
+            name: "disk0".into(),
+            proto: "nvmf".into(),
+        })
+        .await
+        .unwrap();
+
+    let uri = format!(
+        "nvmf://{}:8420/nqn.2019-05.io.openebs:disk0",
+        hdls[0].endpoint.ip()
+    );
+    // controllers are registered under the name of their first namespace
+    let ctrlr_name = format!("{}n1", &uri["nvmf://".len() ..]);
+
+    Config::get_or_init(|| Config {
+        nvme_prewarm_uris: vec![uri.clone()],
+        ..Default::default()
+    })
+    .apply();
+
+    // the controller must be connected by the time mayastor is up
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async move {
+        let controller = NVME_CONTROLLERS
+            .lookup_by_name(&ctrlr_name)
+            .expect("pre-warmed controller not found");
+        assert_eq!(controller.lock().get_state(), NvmeControllerState::Running);
+
+        let status = prewarm_status();
+        assert_eq!(status.len(), 1);
+        assert_eq!(status[0].uri, uri);
+        assert!(status[0].connected);
+
+        // a nexus on the target adopts the pre-warmed controller
+        nexus_create("prewarm_nexus", 32 * 1024 * 1024, None, &[uri.clone()])
+            .await
+            .expect("failed to create a nexus on the pre-warmed target");
+        assert_eq!(nexus_lookup("prewarm_nexus").unwrap().children.len(), 1);
+        nexus_lookup("prewarm_nexus")
+            .unwrap()
+            .destroy()
+            .await
+            .unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,64 @@
+use mayastor::{
+    bdev::{device_create, device_destroy, discover},
+    core::MayastorCliArgs,
+};
+use rpc::mayastor::{BdevShareRequest, BdevUri, Null};
+
+pub mod common;
+use common::{compose::Builder, MayastorTest};
+
+#[tokio::test]
+/// The subsystems advertised by the discovery controller of a target can be
+/// attached with the URI of their transport ID.
+async fn nvmf_discovery() {
+    let test = Builder::new()
+        .name("nvmf_discovery_test")
+        .network("10.1.0.0/16")
+        .add_container("ms1")
+        .with_clean(true)
+        .build()
+        .await
+        .unwrap();
+
+    let mut hdls = test.grpc_handles().await.unwrap();
+
+    // Create and share a bdev over nvmf
+    hdls[0].bdev.list(Null {}).await.unwrap();
+    hdls[0]
+        .bdev
+        .create(BdevUri {
+            uri: "malloc:///disk0?size_mb=100".into(),
+        })
+        .await
+        .unwrap();
+    hdls[0]
+        .bdev
+        .share(BdevShareRequest {
+            name: "disk0".into(),
+            proto: "nvmf".into(),
+        })
+        .await
+        .unwrap();
+
+    let ip = hdls[0].endpoint.ip().to_string();
+
+    let mayastor = MayastorTest::new(MayastorCliArgs::default());
+    mayastor
+        .spawn(async move {
+            let found = discover(&ip, "8420").await.unwrap();
+            let trid = found
+                .iter()
+                .find(|t| t.subnqn() == "nqn.2019-05.io.openebs:disk0")
+                .expect("shared bdev not discovered");
+            assert_eq!(trid.traddr(), ip);
+            assert_eq!(trid.svcid(), "8420");
+
+            let uri = trid.uri();
+            device_create(&uri).await.unwrap();
+            device_destroy(&uri).await.unwrap();
+
+            // nothing listens on this port
+            assert!(discover(&ip, "8440").await.is_err());
+        })
+        .await;
+}
@@ -10,6 +10,7 @@ use rpc::mayastor::{
     CreateNexusRequest,
     CreateReply,
     DestroyNexusRequest,
+    ListNexusRequest,
     Nexus,
     NexusState,
     Null,
@@ -304,6 +305,8 @@ async fn create_nexus(hdl: &mut RpcHandle, uuid: &str, children: Vec<String>) {
             uuid: uuid.to_string(),
             size: 20 * 1024 * 1024,
             children,
+            dry_run: false,
+            block_size: 0,
         })
         .await
         .expect("Failed to create nexus.");
@@ -316,6 +319,7 @@ async fn publish_nexus(hdl: &mut RpcHandle, uuid: &str) -> String {
             uuid: uuid.to_string(),
             key: "".to_string(),
             share: ShareProtocolNexus::NexusNvmf as i32,
+            min_healthy_children: 0,
         })
         .await
         .expect("Failed to publish nexus")
@@ -346,7 +350,7 @@ async fn create_and_share_bdevs(hdl: &mut RpcHandle, uuid: &str) -> String {
 async fn get_nexus(hdl: &mut RpcHandle, uuid: &str) -> Option<Nexus> {
     let nexus_list = hdl
         .mayastor
-        .list_nexus(Null {})
+        .list_nexus(ListNexusRequest::default())
         .await
         .unwrap()
         .into_inner()
@@ -365,7 +369,7 @@ async fn get_nexus(hdl: &mut RpcHandle, uuid: &str) -> Option<Nexus> {
 async fn get_nexus_state(hdl: &mut RpcHandle, uuid: &str) -> Option<i32> {
     let list = hdl
         .mayastor
-        .list_nexus(Null {})
+        .list_nexus(ListNexusRequest::default())
         .await
         .unwrap()
         .into_inner()
@@ -66,6 +66,25 @@ fn reactor_start_stop() {
 
         std::thread::sleep(Duration::from_secs(3));
 
+        // run an async closure on the last core and collect its result
+        let last = Cores::last().id();
+        let mut rx =
+            Reactors::spawn_on_core_async(last, || async { Cores::current() })
+                .unwrap();
+        let core = loop {
+            Reactors::master().poll_once();
+            if let Ok(Some(core)) = rx.try_recv() {
+                break core;
+            }
+        };
+        assert_eq!(core, last);
+
+        // the futures sent to the last core show up in its statistics
+        let stats = Reactors::get_by_core(last).unwrap().stats();
+        assert_eq!(stats.core, last);
+        assert!(stats.events >= 2);
+        assert!(stats.max_queue_time <= stats.queue_time);
+
         mayastor_env_stop(0);
 
         for thread in threads {
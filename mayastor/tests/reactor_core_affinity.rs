@@ -0,0 +1,80 @@
+use composer::Binary;
+use rpc::mayastor::{
+    CreateNexusRequest,
+    CreatePoolRequest,
+    CreateReplicaRequest,
+    ListNexusRequest,
+    Null,
+    ShareProtocolReplica,
+};
+
+pub mod common;
+use common::compose::Builder;
+
+const POOL_NAME: &str = "pool0";
+const UUID: &str = "cdc2a7db-3ac3-403a-af80-7fadc1581c47";
+const REACTOR_MASK: [u32; 2] = [1, 2];
+
+#[tokio::test]
+/// The core a nexus or replica is serviced on must be reported by the list
+/// calls, and it must be one of the cores of the configured reactor mask.
+async fn reactor_core_affinity() {
+    let test = Builder::new()
+        .name("core_affinity")
+        .network("10.1.0.0/16")
+        .add_container_bin(
+            "ms1",
+            Binary::from_dbg("mayastor").with_args(vec!["-l", "1,2"]),
+        )
+        .with_clean(true)
+        .build()
+        .await
+        .unwrap();
+
+    let mut hdls = test.grpc_handles().await.unwrap();
+    let ms = &mut hdls[0].mayastor;
+
+    ms.create_pool(CreatePoolRequest {
+        name: POOL_NAME.to_string(),
+        disks: vec!["malloc:///disk0?size_mb=64".into()],
+        cluster_size: 0,
+    })
+    .await
+    .unwrap();
+
+    ms.create_replica(CreateReplicaRequest {
+        uuid: UUID.to_string(),
+        pool: POOL_NAME.to_string(),
+        size: 32 * 1024 * 1024,
+        thin: false,
+        share: ShareProtocolReplica::ReplicaNone as i32,
+    })
+    .await
+    .unwrap();
+
+    ms.create_nexus(CreateNexusRequest {
+        uuid: UUID.to_string(),
+        size: 16 * 1024 * 1024,
+        children: vec![format!("loopback:///{}", UUID)],
+        dry_run: false,
+        block_size: 0,
+    })
+    .await
+    .unwrap();
+
+    let replicas = ms.list_replicas(Null {}).await.unwrap().into_inner();
+    assert_eq!(replicas.replicas.len(), 1);
+    assert!(REACTOR_MASK.contains(&replicas.replicas[0].core));
+
+    let nexus = ms
+        .list_nexus(ListNexusRequest::default())
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(nexus.nexus_list.len(), 1);
+    assert!(REACTOR_MASK.contains(&nexus.nexus_list[0].core));
+
+    let nexus = ms.list_nexus_v2(Null {}).await.unwrap().into_inner();
+    assert_eq!(nexus.nexus_list.len(), 1);
+    assert!(REACTOR_MASK.contains(&nexus.nexus_list[0].core));
+}
@@ -0,0 +1,161 @@
+use std::{
+    fs::OpenOptions,
+    io::{Read, Seek, SeekFrom, Write},
+};
+
+use common::MayastorTest;
+use mayastor::{
+    core::{BdevHandle, MayastorCliArgs},
+    lvs::{Error, Lvs},
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static POOL: &str = "tpool";
+static REPLICA: &str = "checksummed";
+
+static DISKNAME: &str = "/tmp/checksums.img";
+static BDEVNAME: &str = "aio:///tmp/checksums.img?blk_size=512";
+
+/// Flip one bit of the block holding the given pattern in the backing file
+/// of the pool, behind the back of mayastor.
+fn flip_bit_out_of_band(pattern: &[u8]) {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(DISKNAME)
+        .unwrap();
+    let mut block = vec![0u8; pattern.len()];
+    let mut offset = 0;
+
+    loop {
+        file.seek(SeekFrom::Start(offset)).unwrap();
+        file.read_exact(&mut block).expect("pattern not found");
+        if block == pattern {
+            break;
+        }
+        offset += block.len() as u64;
+    }
+
+    block[17] ^= 0x04;
+    file.seek(SeekFrom::Start(offset)).unwrap();
+    file.write_all(&block).unwrap();
+    file.sync_all().unwrap();
+}
+
+#[tokio::test]
+/// Flip a single bit of the replica data behind the back of the manifest and
+/// expect only the region holding it to be reported, while regions written
+/// through the replica are refreshed rather than reported as corrupted.
+async fn replica_checksums_detect_bit_flip() {
+    common::delete_file(&[DISKNAME.into()]);
+    common::truncate_file(DISKNAME, 64 * 1024);
+
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    let pattern = ms
+        .spawn(async {
+            let pool = Lvs::create_or_import(CreatePoolRequest {
+                name: POOL.into(),
+                disks: vec![BDEVNAME.into()],
+                cluster_size: 0,
+            })
+            .await
+            .unwrap();
+
+            let lvol = pool
+                .create_lvol(REPLICA, 16 * 1024 * 1024, false)
+                .await
+                .unwrap();
+
+            // checksums are opt-in
+            assert!(lvol.checksums().unwrap().is_none());
+            assert!(lvol.verify_checksums().await.is_err());
+
+            let handle = BdevHandle::open(REPLICA, true, false).unwrap();
+            let mut buf = handle.dma_malloc(64 * 1024).unwrap();
+            buf.fill(0x5a);
+            handle.write_at(0, &buf).await.unwrap();
+
+            // a block in the third region the bit flip is aimed at
+            let mut block = handle.dma_malloc(4096).unwrap();
+            block
+                .as_mut_slice()
+                .iter_mut()
+                .enumerate()
+                .for_each(|(i, b)| *b = (i % 251) as u8);
+            handle
+                .write_at(2 * 1024 * 1024 + 4096, &block)
+                .await
+                .unwrap();
+
+            let manifest = lvol.update_checksums().await.unwrap();
+            assert_eq!(manifest.region_size, 1024 * 1024);
+            assert_eq!(manifest.checksums.len(), 16);
+            assert_eq!(lvol.checksums().unwrap(), Some(manifest.clone()));
+
+            let (_, mismatches) = lvol.verify_checksums().await.unwrap();
+            assert!(mismatches.is_empty());
+
+            // a write is not reported as corruption, its region is refreshed
+            buf.fill(0xa5);
+            handle.write_at(0, &buf).await.unwrap();
+            let (refreshed, mismatches) =
+                lvol.verify_checksums().await.unwrap();
+            assert!(mismatches.is_empty());
+            assert_ne!(refreshed.checksums[0], manifest.checksums[0]);
+            assert_eq!(refreshed.checksums[1 ..], manifest.checksums[1 ..]);
+            assert_eq!(lvol.checksums().unwrap(), Some(refreshed));
+
+            // as is the region written since the last update
+            handle.write_at(1024 * 1024, &buf).await.unwrap();
+            let updated = lvol.update_checksums().await.unwrap();
+            assert_ne!(updated.checksums[1], manifest.checksums[1]);
+            let (_, mismatches) = lvol.verify_checksums().await.unwrap();
+            assert!(mismatches.is_empty());
+
+            // disabling checksums stops tracking, enabling them again
+            // computes the whole manifest
+            lvol.disable_checksums().await.unwrap();
+            assert_eq!(lvol.update_checksums().await.unwrap(), updated);
+
+            block.as_slice().to_vec()
+        })
+        .await;
+
+    flip_bit_out_of_band(&pattern);
+
+    ms.spawn(async {
+        let pool = Lvs::lookup(POOL).unwrap();
+        let lvol = pool.lvols().unwrap().find(|l| l.name() == REPLICA).unwrap();
+
+        let (_, mismatches) = lvol.verify_checksums().await.unwrap();
+        assert_eq!(mismatches, vec![2]);
+
+        lvol.disable_checksums().await.unwrap();
+        assert!(lvol.checksums().unwrap().is_none());
+
+        // writes made while a replica is not tracked cannot be told apart
+        // from corruption
+        lvol.update_checksums().await.unwrap();
+        pool.export().await.unwrap();
+        let pool = Lvs::create_or_import(CreatePoolRequest {
+            name: POOL.into(),
+            disks: vec![BDEVNAME.into()],
+            cluster_size: 0,
+        })
+        .await
+        .unwrap();
+        let lvol = pool.lvols().unwrap().find(|l| l.name() == REPLICA).unwrap();
+        assert!(matches!(
+            lvol.verify_checksums().await,
+            Err(Error::ChecksumsStale { .. })
+        ));
+
+        pool.destroy().await.unwrap();
+    })
+    .await;
+
+    common::delete_file(&[DISKNAME.into()]);
+}
@@ -0,0 +1,113 @@
+use common::MayastorTest;
+use mayastor::{
+    bdev::{device_create, device_open},
+    core::{
+        Bdev,
+        BdevHandle,
+        BlockDeviceHandle,
+        MayastorCliArgs,
+        Protocol,
+        Share,
+    },
+    lvs::{Lvol, Lvs},
+    subsys::NvmfSubsystem,
+};
+use rpc::mayastor::CreatePoolRequest;
+use std::convert::TryFrom;
+
+pub mod common;
+
+static REPLICA: &str = "migrated";
+const REPLICA_SIZE: u64 = 16 * 1024 * 1024;
+const IO_SIZE: u64 = 128 * 1024;
+
+async fn create_pool(name: &str, disk: &str) -> Lvs {
+    Lvs::create_or_import(CreatePoolRequest {
+        name: name.into(),
+        disks: vec![disk.into()],
+        cluster_size: 0,
+    })
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+/// Migrate a shared replica a host is connected to, and expect the data and
+/// the share to follow the replica to the other pool.
+async fn replica_migrate() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        let source = create_pool("pool0", "malloc:///disk0?size_mb=64").await;
+        let target = create_pool("pool1", "malloc:///disk1?size_mb=64").await;
+
+        let lvol = source
+            .create_lvol(REPLICA, REPLICA_SIZE, false)
+            .await
+            .unwrap();
+        lvol.share_nvmf(None).await.unwrap();
+        let uri = NvmfSubsystem::nqn_lookup(REPLICA)
+            .unwrap()
+            .uri_endpoints()
+            .unwrap()
+            .remove(0);
+
+        // a host connected to the share of the replica
+        let device = device_create(&uri).await.unwrap();
+        let host = device_open(&device, true).unwrap().into_handle().unwrap();
+
+        let mut buf = host.dma_malloc(IO_SIZE).unwrap();
+        for (i, offset) in (0 .. REPLICA_SIZE)
+            .step_by(IO_SIZE as usize * 8)
+            .enumerate()
+        {
+            buf.fill(i as u8 + 1);
+            host.write_at(offset, &buf).await.unwrap();
+        }
+
+        // a replica cannot be migrated to its own pool
+        assert!(lvol.migrate(&source, 0).await.is_err());
+
+        let lvol = Lvol::try_from(Bdev::lookup_by_name(REPLICA).unwrap())
+            .unwrap()
+            .migrate(&target, 0)
+            .await
+            .unwrap();
+        assert_eq!(lvol.name(), REPLICA);
+        assert_eq!(lvol.pool(), target.name());
+        assert_eq!(lvol.size(), REPLICA_SIZE);
+        assert_eq!(lvol.shared(), Some(Protocol::Nvmf));
+        assert_eq!(source.lvols().unwrap().count(), 0);
+        assert_eq!(target.lvols().unwrap().count(), 1);
+
+        // the share now serves the migrated replica
+        let subsystem = NvmfSubsystem::nqn_lookup(REPLICA).unwrap();
+        assert_eq!(subsystem.bdev().unwrap().uuid_as_string(), lvol.uuid());
+        assert_eq!(subsystem.uri_endpoints().unwrap().remove(0), uri);
+
+        // and the host still reads back what it wrote before
+        let replica = BdevHandle::open(REPLICA, true, false).unwrap();
+        let mut copy = replica.dma_malloc(IO_SIZE).unwrap();
+        for (i, offset) in (0 .. REPLICA_SIZE)
+            .step_by(IO_SIZE as usize * 8)
+            .enumerate()
+        {
+            host.read_at(offset, &mut buf).await.unwrap();
+            assert!(buf.as_slice().iter().all(|b| *b == i as u8 + 1));
+            replica.read_at(offset, &mut copy).await.unwrap();
+            assert_eq!(buf.as_slice(), copy.as_slice());
+        }
+
+        // its writes land on the migrated replica
+        buf.fill(0xa5);
+        host.write_at(IO_SIZE, &buf).await.unwrap();
+        replica.read_at(IO_SIZE, &mut copy).await.unwrap();
+        assert!(copy.as_slice().iter().all(|b| *b == 0xa5));
+
+        drop(host);
+        drop(replica);
+        target.destroy().await.unwrap();
+        source.destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,107 @@
+use std::{
+    convert::TryFrom,
+    time::{Duration, Instant},
+};
+
+use common::MayastorTest;
+use mayastor::{
+    core::{Bdev, BdevHandle, MayastorCliArgs},
+    lvs::{Error, Lvol, Lvs},
+    rebuild::{ClientOperations, RebuildJob},
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static REPLICA: &str = "resumed";
+static STAGING: &str = "bdev:///resumed-migrate";
+const REPLICA_SIZE: u64 = 8 * 1024 * 1024;
+const RATE_LIMIT: u64 = 2 * 1024 * 1024;
+
+async fn create_pool(name: &str, disk: &str) {
+    Lvs::create_or_import(CreatePoolRequest {
+        name: name.into(),
+        disks: vec![disk.into()],
+        cluster_size: 0,
+    })
+    .await
+    .unwrap();
+}
+
+fn replica() -> Lvol {
+    Lvol::try_from(Bdev::lookup_by_name(REPLICA).unwrap()).unwrap()
+}
+
+#[tokio::test]
+/// A throttled migration takes as long as its rate limit implies, and once
+/// interrupted it is resumed by the next migration of the replica.
+async fn replica_migrate_resume() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        create_pool("pool0", "malloc:///disk0?size_mb=64").await;
+        create_pool("pool1", "malloc:///disk1?size_mb=64").await;
+        Lvs::lookup("pool0")
+            .unwrap()
+            .create_lvol(REPLICA, REPLICA_SIZE, false)
+            .await
+            .unwrap();
+        let h = BdevHandle::open(REPLICA, true, false).unwrap();
+        let mut buf = h.dma_malloc(REPLICA_SIZE).unwrap();
+        buf.fill(0x5a);
+        h.write_at(0, &buf).await.unwrap();
+    })
+    .await;
+
+    // interrupt a throttled migration half way
+    let start = Instant::now();
+    let (interrupted, _) = tokio::join!(
+        ms.spawn(async {
+            let pool = Lvs::lookup("pool1").unwrap();
+            match replica().migrate(&pool, RATE_LIMIT).await {
+                Err(Error::MigrateInterrupted {
+                    blk, ..
+                }) => Some(blk),
+                _ => None,
+            }
+        }),
+        async {
+            tokio::time::sleep(Duration::from_secs(2)).await;
+            ms.spawn(async {
+                RebuildJob::lookup(STAGING)
+                    .unwrap()
+                    .as_client()
+                    .stop()
+                    .unwrap();
+            })
+            .await;
+        }
+    );
+    let blk = interrupted.expect("the migration was not interrupted");
+    assert!(blk > 0 && blk < REPLICA_SIZE / 512, "{}", blk);
+    // the copy was throttled, it would be done already otherwise
+    assert!(start.elapsed() >= Duration::from_secs(2));
+
+    // the next migration resumes the copy where it stopped and completes it
+    let start = Instant::now();
+    ms.spawn(async {
+        let pool = Lvs::lookup("pool1").unwrap();
+        let lvol = replica().migrate(&pool, RATE_LIMIT).await.unwrap();
+        assert_eq!(lvol.pool(), "pool1");
+        assert!(Bdev::lookup_by_name("resumed-migrate").is_none());
+
+        let h = BdevHandle::open(REPLICA, false, false).unwrap();
+        let mut buf = h.dma_malloc(REPLICA_SIZE).unwrap();
+        h.read_at(0, &mut buf).await.unwrap();
+        assert!(buf.as_slice().iter().all(|b| *b == 0x5a));
+    })
+    .await;
+    // a full copy at the rate limit would take 4 seconds
+    assert!(start.elapsed() < Duration::from_secs(REPLICA_SIZE / RATE_LIMIT));
+
+    ms.spawn(async {
+        Lvs::lookup("pool1").unwrap().destroy().await.unwrap();
+        Lvs::lookup("pool0").unwrap().destroy().await.unwrap();
+    })
+    .await;
+}
@@ -50,6 +50,7 @@ async fn replica_snapshot() {
         .create_pool(CreatePoolRequest {
             name: POOL2_NAME.to_string(),
             disks: vec!["malloc:///disk0?size_mb=96".into()],
+            cluster_size: 0,
         })
         .await
         .unwrap();
@@ -75,6 +76,7 @@ async fn replica_snapshot() {
             Lvs::create_or_import(CreatePoolRequest {
                 name: POOL1_NAME.to_string(),
                 disks: vec![format!("aio://{}", DISKNAME1)],
+                cluster_size: 0,
             })
             .await
             .unwrap();
@@ -1,17 +1,21 @@
 use common::{compose::Builder, MayastorTest};
 use mayastor::{
-    bdev::{nexus_create, nexus_lookup, NexusStatus},
+    bdev::{device_lookup, nexus_create, nexus_lookup, NexusStatus},
     core::{Bdev, MayastorCliArgs},
     nexus_uri::bdev_get_name,
     subsys::{Config, NvmeBdevOpts},
 };
 use rpc::mayastor::{BdevShareRequest, BdevUri, Null, ShareProtocolNexus};
 use std::process::{Command, Stdio};
-use tokio::time::Duration;
+use tokio::time::{Duration, Instant};
 
 pub mod common;
 static NXNAME: &str = "nexus";
 
+/// I/O timeout applied to the child controller, deliberately much shorter
+/// than the keep-alive timeout so the I/O timeout handler fires first.
+const CHILD_IO_TIMEOUT_US: u64 = 1_000_000;
+
 #[tokio::test]
 #[ignore]
 async fn replica_stop_cont() {
@@ -78,6 +82,16 @@ async fn replica_stop_cont() {
                 Bdev::lookup_by_name(&bdev_get_name(&c).unwrap()).is_some(),
                 "child bdev must exist"
             );
+
+            let device = device_lookup(&bdev_get_name(&c).unwrap())
+                .expect("child device must exist");
+            let mut io_controller = device.get_io_controller().unwrap();
+            io_controller.set_timeout_us(CHILD_IO_TIMEOUT_US).unwrap();
+            assert_eq!(
+                io_controller.get_timeout_us().unwrap(),
+                CHILD_IO_TIMEOUT_US,
+                "I/O timeout mismatches"
+            );
         })
         .await;
 
@@ -97,20 +111,33 @@ async fn replica_stop_cont() {
         .spawn()
         .expect("should send read from initiator");
 
-    println!("IO submitted unfreezing container...");
+    println!("IO submitted, waiting for the I/O timeout to fire...");
 
-    // KATO is 5s, wait at least that long
-    let n = 10;
-    for i in 1 ..= n {
-        ticker.tick().await;
-        println!("unfreeze delay... {}/{}", i, n);
+    // The timeout handler must fault the nexus within a bounded window:
+    // the I/O timeout itself plus some slack for the timeout poller and
+    // the removal of the child.
+    let deadline = Instant::now()
+        + Duration::from_micros(CHILD_IO_TIMEOUT_US)
+        + Duration::from_secs(5);
+    loop {
+        let status = mayastor
+            .spawn(async { nexus_lookup(NXNAME).unwrap().status() })
+            .await;
+        if status == NexusStatus::Faulted {
+            break;
+        }
+        assert!(
+            Instant::now() < deadline,
+            "I/O timeout did not fault the nexus in time (status {:?})",
+            status
+        );
+        tokio::time::sleep(Duration::from_millis(100)).await;
     }
+    println!("I/O timeout handled, nexus faulted");
+
     test.thaw("ms1").await.unwrap();
     println!("container thawed");
 
-    // Wait for faulting to complete first
-    ticker.tick().await;
-
     // with no child to send read to, io should still complete as failed
     let status = Command::new("../target/debug/initiator")
         .args(&[&nxuri, "read", "/tmp/tmpread"])
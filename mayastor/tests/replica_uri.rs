@@ -53,6 +53,7 @@ async fn replica_uri() {
                     "malloc:///disk0?size_mb={}",
                     DISKSIZE_KB / 1024
                 )],
+                cluster_size: 0,
             })
             .await
             .unwrap();
@@ -120,6 +121,8 @@ async fn replica_uri() {
             uuid: VOLUME_UUID.to_string(),
             size: VOLUME_SIZE_B,
             children: [replica_loopback.uri, replica_nvmf.uri].to_vec(),
+            dry_run: false,
+            block_size: 0,
         })
         .await
         .unwrap();
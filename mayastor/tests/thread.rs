@@ -60,6 +60,36 @@ async fn thread_tokio() {
     ms.send(mayastor_to_runtime());
 
     rx.unwrap().await.unwrap().unwrap();
+
+    // the value computed on a new thread is handed back with the thread
+    ms.spawn(async {
+        let (thread, (name, value)) =
+            Mthread::spawn_thread("setup".into(), Cores::first(), || {
+                (Mthread::current().unwrap().name().to_string(), 42)
+            })
+            .unwrap();
+        assert_eq!(name, "setup");
+        assert_eq!(value, 42);
+        assert_eq!(thread.name(), "setup");
+    })
+    .await;
+
+    // a panic within the closure is returned as an error and the previous
+    // thread is restored, leaving the reactor operational
+    ms.spawn(async {
+        let current = Mthread::current();
+        let (thread, _) =
+            Mthread::spawn_thread("panic".into(), Cores::first(), || {})
+                .unwrap();
+        let result = thread.with(|| panic!("boom"));
+        assert!(result.unwrap_err().to_string().contains("boom"));
+        assert_eq!(Mthread::current(), current);
+    })
+    .await;
+
+    let value = ms.spawn(async { Mthread::get_init().with(|| 42) }).await;
+    assert_eq!(value.unwrap(), 42);
+
     let th = mayastor::core::runtime::spawn_blocking(running_on_thread);
     tokio::time::sleep(Duration::from_secs(1)).await;
     th.await.unwrap();
@@ -41,6 +41,12 @@ static HOST_ID: once_cell::sync::Lazy<String> =
         host_id
     });
 
+/// Host ID presented to NVMe-oF targets unless a different one is given: the
+/// machine UUID if available, otherwise a random UUID.
+pub fn default_host_id() -> &'static str {
+    HOST_ID.as_str()
+}
+
 /// The TrType struct for all known transports types note: we are missing loop
 #[derive(Clone, Debug, Primitive)]
 #[allow(non_camel_case_types)]
@@ -468,6 +474,12 @@ pub struct ConnectArgs {
     /// keep alive timeout period in seconds
     #[builder(default = "None")]
     keep_alive_tmo: Option<u32>,
+    /// NQN of the host, derived from the host ID if not given
+    #[builder(default = "None")]
+    hostnqn: Option<String>,
+    /// ID of the host, see default_host_id() if not given
+    #[builder(default = "None")]
+    hostid: Option<String>,
 }
 
 impl ConnectArgsBuilder {
@@ -494,9 +506,16 @@ impl fmt::Display for ConnectArgs {
     /// The output is used for writing to nvme-fabrics file so be careful
     /// when making changes.
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let host_id = HOST_ID.as_str();
+        let host_id = self.hostid.as_deref().unwrap_or_else(default_host_id);
         write!(f, "nqn={},", self.nqn)?;
-        write!(f, "hostnqn=nqn.2019-05.io.openebs.mayastor:{},", host_id)?;
+        match &self.hostnqn {
+            Some(hostnqn) => write!(f, "hostnqn={},", hostnqn)?,
+            None => write!(
+                f,
+                "hostnqn=nqn.2019-05.io.openebs.mayastor:{},",
+                host_id
+            )?,
+        }
         write!(f, "hostid={},", host_id)?;
         write!(f, "transport={},", self.transport)?;
         write!(f, "traddr={},", self.traddr)?;
@@ -0,0 +1,30 @@
+use nvmeadm::nvmf_discovery::{default_host_id, ConnectArgsBuilder};
+
+#[test]
+fn connect_args_host() {
+    let args = |builder: &mut ConnectArgsBuilder| {
+        builder
+            .traddr("127.0.0.1")
+            .trsvcid("4420")
+            .nqn("nqn.2019-05.io.openebs:target")
+            .build()
+            .unwrap()
+            .to_string()
+    };
+
+    // the host identity defaults to the machine UUID
+    let text = args(&mut ConnectArgsBuilder::default());
+    assert!(text.contains(&format!(
+        "hostnqn=nqn.2019-05.io.openebs.mayastor:{},",
+        default_host_id()
+    )));
+    assert!(text.contains(&format!("hostid={},", default_host_id())));
+
+    let text = args(
+        ConnectArgsBuilder::default()
+            .hostnqn(Some("nqn.2019-05.io.openebs.mayastor:node-1".into()))
+            .hostid(Some("11111111-0000-0000-0000-000000000001".into())),
+    );
+    assert!(text.contains("hostnqn=nqn.2019-05.io.openebs.mayastor:node-1,"));
+    assert!(text.contains("hostid=11111111-0000-0000-0000-000000000001,"));
+}
@@ -5,6 +5,9 @@ extern crate serde;
 extern crate serde_derive;
 extern crate serde_json;
 extern crate tonic;
+
+pub mod validate;
+
 #[allow(dead_code)]
 #[allow(clippy::type_complexity)]
 #[allow(clippy::unit_arg)]
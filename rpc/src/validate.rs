@@ -0,0 +1,69 @@
+//! Validation of the arguments shared by the mayastor and CSI services and
+//! their clients, so that all of them accept and reject the same values.
+
+use std::fmt;
+
+use uuid::Uuid;
+
+/// A string which is not acceptable as a nexus or volume UUID
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidUuid {
+    /// the string which failed to parse
+    pub uuid: String,
+    /// why the string was rejected
+    pub reason: String,
+}
+
+impl fmt::Display for InvalidUuid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid UUID '{}': {}", self.uuid, self.reason)
+    }
+}
+
+impl std::error::Error for InvalidUuid {}
+
+/// Parses `uuid` as the UUID of a nexus or volume, the nil UUID is rejected
+/// as it cannot identify either.
+pub fn parse_uuid(uuid: &str) -> Result<Uuid, InvalidUuid> {
+    match Uuid::parse_str(uuid) {
+        Ok(parsed) if parsed.is_nil() => Err(InvalidUuid {
+            uuid: uuid.to_string(),
+            reason: "the nil UUID is not allowed".to_string(),
+        }),
+        Ok(parsed) => Ok(parsed),
+        Err(error) => Err(InvalidUuid {
+            uuid: uuid.to_string(),
+            reason: error.to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_valid_uuid() {
+        let uuid = "a4fb1316-8ab3-4c8b-a4b6-36e5dc7d4d46";
+        assert_eq!(parse_uuid(uuid).unwrap().to_hyphenated().to_string(), uuid);
+        // the other forms of the same UUID are accepted as well
+        assert!(parse_uuid("a4fb13168ab34c8ba4b636e5dc7d4d46").is_ok());
+    }
+
+    #[test]
+    fn parse_nil_uuid() {
+        let error = parse_uuid("00000000-0000-0000-0000-000000000000")
+            .expect_err("the nil UUID must be rejected");
+        assert_eq!(error.uuid, "00000000-0000-0000-0000-000000000000");
+    }
+
+    #[test]
+    fn parse_wrong_length_uuid() {
+        assert!(parse_uuid("").is_err());
+        assert!(parse_uuid("a4fb1316-8ab3-4c8b-a4b6-36e5dc7d4d4").is_err());
+        assert!(parse_uuid("a4fb1316-8ab3-4c8b-a4b6-36e5dc7d4d466").is_err());
+        assert!(
+            parse_uuid("nexus-a4fb1316-8ab3-4c8b-a4b6-36e5dc7d4d46").is_err()
+        );
+    }
+}
@@ -36,6 +36,11 @@ fn build_wrapper() {
         .include(".")
         .file("nvme_helper.c")
         .compile("nvme_helper");
+    cc::Build::new()
+        .include("spdk/include")
+        .include(".")
+        .file("lvol_helper.c")
+        .compile("lvol_helper");
 }
 
 fn main() {
@@ -77,6 +82,8 @@ fn main() {
         .allowlist_function("*.uring.*")
         .allowlist_function("^iscsi.*")
         .allowlist_function("^spdk.*")
+        .allowlist_function("create_delay_disk")
+        .allowlist_function("delete_delay_disk")
         .allowlist_function("create_malloc_disk")
         .allowlist_function("delete_malloc_disk")
         .allowlist_function("^bdev.*")
@@ -135,4 +142,5 @@ fn main() {
     println!("cargo:rerun-if-changed=wrapper.h");
     println!("cargo:rerun-if-changed=logwrapper.c");
     println!("cargo:rerun-if-changed=nvme_helper.c");
+    println!("cargo:rerun-if-changed=lvol_helper.c");
 }
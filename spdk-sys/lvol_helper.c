@@ -0,0 +1,25 @@
+#include "lvol_helper.h"
+
+#include <spdk/lib/blob/blobstore.h>
+#include <spdk_internal/lvolstore.h>
+
+uint64_t
+lvol_allocated_clusters(struct spdk_lvol *lvol) {
+	struct spdk_blob *blob = lvol->blob;
+	uint64_t allocated = 0;
+	uint64_t i;
+
+	/* unallocated clusters of a thin provisioned blob have no LBA */
+	for (i = 0; i < blob->active.num_clusters; i++) {
+		if (blob->active.clusters[i] != 0) {
+			allocated++;
+		}
+	}
+
+	return allocated;
+}
+
+struct spdk_thread *
+lvol_md_thread(struct spdk_lvol *lvol) {
+	return lvol->lvol_store->blobstore->md_thread;
+}
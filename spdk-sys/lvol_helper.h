@@ -0,0 +1,7 @@
+#include <stdint.h>
+
+struct spdk_lvol;
+struct spdk_thread;
+
+uint64_t lvol_allocated_clusters(struct spdk_lvol *lvol);
+struct spdk_thread *lvol_md_thread(struct spdk_lvol *lvol);
@@ -1,5 +1,6 @@
 #include <bdev/aio/bdev_aio.h>
 #include <bdev/crypto/vbdev_crypto.h>
+#include <bdev/delay/vbdev_delay.h>
 #include <bdev/error/vbdev_error.h>
 #include <bdev/lvol/vbdev_lvol.h>
 #include <bdev/nvme/bdev_nvme.h>
@@ -37,3 +38,4 @@
 
 #include "logwrapper.h"
 #include "nvme_helper.h"
+#include "lvol_helper.h"
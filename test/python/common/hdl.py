@@ -150,7 +150,7 @@ class MayastorHandle(object):
 
     def nexus_list(self):
         """List all the nexus devices."""
-        return self.ms.ListNexus(pb.Null()).nexus_list
+        return self.ms.ListNexus(pb.ListNexusRequest()).nexus_list
 
     def nexus_list_v2(self):
         """List all the nexus devices, with separate name and uuid."""